@@ -0,0 +1,102 @@
+//! Throughput benchmarks for reading pcaps
+//!
+//! The corpora are either taken from the integration test suite or
+//! generated with `testgen`, and are held in memory so that we measure
+//! parsing rather than IO.  For a comparison against libpcap, see
+//! `bench.sh`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+use std::{fs::File, io::Read};
+
+fn integration_test(name: &str) -> Vec<u8> {
+    let path = format!("{}/integration_tests/{name}", env!("CARGO_MANIFEST_DIR"));
+    let mut buf = vec![];
+    xz2::read::XzDecoder::new(File::open(path).unwrap())
+        .read_to_end(&mut buf)
+        .unwrap();
+    buf
+}
+
+fn spb_heavy() -> Vec<u8> {
+    let mut pcap = PcapngBuilder::new();
+    pcap.section(Endianness::Little)
+        .interface(LinkType::ETHERNET, 0, &[]);
+    for i in 0..10_000 {
+        pcap.simple_packet(&[i as u8; 128]);
+    }
+    pcap.as_bytes().to_vec()
+}
+
+fn option_heavy() -> Vec<u8> {
+    let mut pcap = PcapngBuilder::new();
+    pcap.section(Endianness::Little)
+        .interface(LinkType::ETHERNET, 0, &[(2, b"eth0"), (9, &[9])]);
+    for i in 0..10_000_u64 {
+        let flags = 1_u32.to_le_bytes();
+        let dropcount = i.to_le_bytes();
+        let options: &[(u16, &[u8])] = &[
+            (1, b"a comment which is reasonably long"),
+            (2, &flags),
+            (3, &[2, 0xde, 0xad, 0xbe, 0xef]),
+            (4, &dropcount),
+            (5, &dropcount),
+            (0xBEEF, b"unknown"),
+        ];
+        pcap.enhanced_packet(0, i * 1000, &[i as u8; 128], options);
+    }
+    pcap.as_bytes().to_vec()
+}
+
+fn multi_section() -> Vec<u8> {
+    let mut pcap = PcapngBuilder::new();
+    for section in 0..1000_u64 {
+        let endianness = match section % 2 {
+            0 => Endianness::Little,
+            _ => Endianness::Big,
+        };
+        pcap.section(endianness)
+            .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+            .interface(LinkType::RAW, 0, &[(2, b"tun0")]);
+        for i in 0..10 {
+            pcap.enhanced_packet((i % 2) as u32, section * 10 + i, &[0; 128], &[]);
+        }
+    }
+    pcap.as_bytes().to_vec()
+}
+
+fn read_all(pcap: &[u8]) -> (u64, u64) {
+    let mut n_pkts = 0;
+    let mut n_bytes = 0;
+    for pkt in Capture::new(pcap) {
+        let pkt = pkt.unwrap();
+        n_pkts += 1;
+        n_bytes += pkt.data.len() as u64;
+    }
+    (n_pkts, n_bytes)
+}
+
+fn bench_read(c: &mut Criterion) {
+    let corpora = [
+        ("epb_heavy", integration_test("1920x1080_H.265.pcapng.xz")),
+        ("spb_heavy", spb_heavy()),
+        ("option_heavy", option_heavy()),
+        ("multi_section", multi_section()),
+    ];
+    let mut group = c.benchmark_group("read");
+    for (name, pcap) in &corpora {
+        let (n_pkts, _) = read_all(pcap);
+        group.throughput(Throughput::Elements(n_pkts));
+        group.bench_with_input(BenchmarkId::new("packets", name), pcap, |b, pcap| {
+            b.iter(|| read_all(pcap))
+        });
+        group.throughput(Throughput::Bytes(pcap.len() as u64));
+        group.bench_with_input(BenchmarkId::new("bytes", name), pcap, |b, pcap| {
+            b.iter(|| read_all(pcap))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_read);
+criterion_main!(benches);
@@ -1,11 +1,6 @@
 use bpaf::Bpaf;
 use pcarp::Capture;
-use std::{
-    fs::File,
-    io::Read,
-    path::PathBuf,
-    time::{Instant, SystemTime},
-};
+use std::{fs::File, io::Read, path::PathBuf, time::Instant};
 use tracing::{info, warn};
 
 /// Dumps the packets from a pcapng file
@@ -23,7 +18,7 @@ fn main() {
     env_logger::init();
 
     let file = File::open(&opts.pcap).unwrap();
-    let reader: Box<dyn Read> = match opts.pcap.extension().and_then(|x| x.to_str()) {
+    let reader: Box<dyn Read + Send> = match opts.pcap.extension().and_then(|x| x.to_str()) {
         Some("pcapng") => Box::new(file),
         Some("gz") => Box::new(flate2::read::GzDecoder::new(file)),
         Some("xz") => Box::new(xz2::read::XzDecoder::new(file)),
@@ -36,9 +31,10 @@ fn main() {
             Box::new(file)
         }
     };
-    let pcap = Capture::new(reader);
+    let mut pcap = Capture::new_boxed(reader);
     let start = Instant::now();
-    for (n, pkt) in pcap.enumerate() {
+    let mut n = 0;
+    while let Some(pkt) = pcap.next() {
         let pkt = match pkt {
             Ok(pkt) => pkt,
             Err(e) => {
@@ -46,13 +42,9 @@ fn main() {
                 continue;
             }
         };
-        let ts = pkt.timestamp.unwrap_or(SystemTime::UNIX_EPOCH);
-        println!(
-            "[{}] {:>5}  {}",
-            humantime::format_rfc3339_nanos(ts),
-            pkt.data.len(),
-            sanitize(&pkt.data)
-        );
+        let iface = pkt.interface.and_then(|x| pcap.lookup_interface(x));
+        println!("{}", pkt.display_with(iface));
+        n += 1;
         if n % 1000 == 0 {
             let nanos = start.elapsed().subsec_nanos();
             let bps = n as f64 * 1_000_000_000.0 / f64::from(nanos);
@@ -60,7 +52,3 @@ fn main() {
         }
     }
 }
-
-fn sanitize(data: &[u8]) -> String {
-    String::from_utf8_lossy(data).replace(|x: char| !x.is_ascii() || x.is_control(), ".")
-}
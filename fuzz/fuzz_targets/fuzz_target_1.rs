@@ -2,6 +2,10 @@
 use libfuzzer_sys::fuzz_target;
 
 fuzz_target!(|data: &[u8]| {
-    let cap = pcarp::Capture::new(std::io::Cursor::new(data));
-    for _ in cap {}
+    let mut cap = pcarp::CaptureBuilder::new()
+        .collect_malformations(true)
+        .build(std::io::Cursor::new(data));
+    while cap.next().is_some() {
+        cap.take_malformations();
+    }
 });
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+/// Notes attached to a packet
+///
+/// pcarp never adds annotations itself: they're for your own code (eg.
+/// classifiers, or filters which flag the packets they let through) to
+/// record what it found.  They stay with the [`Packet`](crate::Packet) when
+/// it's cloned or passed through a transform, and a
+/// [`PcapngWriter`](crate::writer::PcapngWriter) writes them out with the
+/// packet (see
+/// [`AnnotationFormat`](crate::writer::AnnotationFormat)), so findings can
+/// be saved in the capture itself.
+///
+/// Each annotation is a key and a value.  They're kept sorted by key, so
+/// they're always written out in the same order.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::writer::PcapngWriter;
+/// use pcarp::Capture;
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .enhanced_packet(0, 0, b"hello", &[]);
+/// let mut capture = Capture::new(pcap.build());
+/// let mut wtr = PcapngWriter::new(vec![]);
+/// while let Some(x) = capture.next_with_iface() {
+///     let (mut pkt, iface) = x.unwrap();
+///     pkt.annotations.insert("verdict", "suspicious");
+///     assert_eq!(pkt.annotations.get("verdict"), Some("suspicious"));
+///     wtr.write(&pkt, iface.as_deref()).unwrap();
+/// }
+///
+/// let out = wtr.into_inner();
+/// let contains = |s: &[u8]| out.windows(s.len()).any(|x| x == s);
+/// assert!(contains(b"verdict: suspicious"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Annotations(BTreeMap<String, String>);
+
+impl Annotations {
+    /// Create an empty set of annotations
+    pub fn new() -> Annotations {
+        Annotations::default()
+    }
+
+    /// Add an annotation, returning the previous value for that key (if
+    /// there was one)
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        self.0.insert(key.into(), value.into())
+    }
+
+    /// The value of an annotation
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(|x| x.as_str())
+    }
+
+    /// Remove an annotation, returning its value
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.0.remove(key)
+    }
+
+    /// The annotations, in order of their keys
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// The number of annotations
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no annotations
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
@@ -0,0 +1,55 @@
+use crate::block::option_type::*;
+use bytes::Bytes;
+
+/// An option whose meaning is defined by a vendor
+///
+/// Custom options are scoped by the vendor's IANA Private Enterprise
+/// Number, so options from different vendors can't clash.  They may appear
+/// in any type of block.  pcarp doesn't know what they mean, so they're
+/// also kept in the block's `options`, in raw form.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::block::{Block, BlockReader, OPT_CUSTOM_STR_COPY};
+///
+/// # let mut value = 32473_u32.to_le_bytes().to_vec();
+/// # value.extend_from_slice(b"appliance-7");
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[(OPT_CUSTOM_STR_COPY, &value)]);
+/// let mut blocks = BlockReader::new(pcap.build());
+/// blocks.next().unwrap().unwrap();
+/// let descr = match blocks.next().unwrap().unwrap() {
+///     Block::InterfaceDescription(x) => x,
+///     x => panic!("{x:?}"),
+/// };
+/// let opt = &descr.custom_options[0];
+/// assert_eq!(opt.pen, 32473);
+/// assert_eq!(&opt.payload[..], b"appliance-7");
+/// assert!(opt.is_string() && opt.may_copy());
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CustomOption {
+    /// The option code: one of `OPT_CUSTOM_STR_COPY`, `OPT_CUSTOM_BIN_COPY`,
+    /// `OPT_CUSTOM_STR_NOCOPY`, or `OPT_CUSTOM_BIN_NOCOPY`
+    pub code: u16,
+    /// The vendor's Private Enterprise Number
+    pub pen: u32,
+    /// The rest of the option's value
+    pub payload: Bytes,
+}
+
+impl CustomOption {
+    /// Whether the payload is meant to be a UTF-8 string, rather than
+    /// binary data
+    pub fn is_string(&self) -> bool {
+        matches!(self.code, OPT_CUSTOM_STR_COPY | OPT_CUSTOM_STR_NOCOPY)
+    }
+
+    /// Whether the option may be copied when the block is written to a new
+    /// file
+    pub fn may_copy(&self) -> bool {
+        matches!(self.code, OPT_CUSTOM_STR_COPY | OPT_CUSTOM_BIN_COPY)
+    }
+}
@@ -0,0 +1,117 @@
+use crate::block::{BlockType, Malformation};
+use std::fmt;
+use std::sync::Arc;
+use tracing::*;
+
+/// Something which pcarp worked around while reading a capture
+///
+/// These are normally just logged (with `tracing`), so they're lost unless
+/// a subscriber is installed.  To collect them, count them, or show them
+/// in your own UI, use
+/// [`CaptureBuilder::diagnostic_sink()`](crate::CaptureBuilder::diagnostic_sink).
+///
+/// The sink is the one place where everything is reported.  The other
+/// ways of finding out about problems each see only part of the picture:
+/// [`collect_malformations()`](crate::CaptureBuilder::collect_malformations)
+/// keeps just the [`Malformation`]s, a
+/// [`MalformationPolicy::Custom`](crate::block::MalformationPolicy::Custom)
+/// decides whether to reject them but doesn't see anything else, and
+/// [`recovery_stats()`](crate::Capture::recovery_stats) and
+/// [`counters()`](crate::Capture::counters) are running totals.
+///
+/// Problems which pcarp couldn't work around are returned as errors.  If
+/// the error only affects one block, it's reported here too, as a
+/// `CorruptBlock`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Diagnostic {
+    /// A problem was found in a block, and worked around.  These are the
+    /// same problems which
+    /// [`collect_malformations()`](crate::CaptureBuilder::collect_malformations)
+    /// collects.
+    Malformation(BlockType, Malformation),
+    /// A section declared a version which pcarp doesn't know about, and
+    /// was parsed anyway.  See [`VersionPolicy`](crate::block::VersionPolicy).
+    UnknownVersion { major: u16, minor: u16 },
+    /// A block was skipped, because pcarp doesn't know how to parse it
+    IgnoredBlock(BlockType),
+    /// A block was skipped, because it's corrupt.  The error was returned
+    /// too.
+    CorruptBlock {
+        block_type: BlockType,
+        /// The length of the block, in bytes
+        len: u64,
+        /// The error which was returned, as a string
+        reason: String,
+    },
+    /// An interface's snap length is longer than pcarp's read buffer, so
+    /// the buffer may have to grow to fit its packets
+    LargeSnapLen { snap_len: u32 },
+    /// The file doesn't start with a section header.  The blocks before the
+    /// first one are read as if there were a section header in front of
+    /// them.
+    NoSectionHeader,
+    /// The stream ended part-way through a block, and the partial block
+    /// was discarded
+    IncompleteBlock { len: usize },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Diagnostic::Malformation(block_type, x) => write!(f, "{block_type:?}: {x}"),
+            Diagnostic::UnknownVersion { major, minor } => write!(
+                f,
+                "Saw a section with unknown version {major}.{minor}; parsing it anyway"
+            ),
+            Diagnostic::IgnoredBlock(block_type) => write!(f, "{block_type:?} blocks are ignored"),
+            Diagnostic::CorruptBlock {
+                block_type,
+                len,
+                reason,
+            } => write!(
+                f,
+                "Skipping a corrupt {block_type:?} block ({len} bytes): {reason}"
+            ),
+            Diagnostic::LargeSnapLen { snap_len } => write!(
+                f,
+                "The max packet length for this interface ({snap_len}) is greater \
+                than the length of our buffer"
+            ),
+            Diagnostic::NoSectionHeader => {
+                write!(f, "The file doesn't start with a section header")
+            }
+            Diagnostic::IncompleteBlock { len } => {
+                write!(f, "The stream ended part-way through a block ({len} bytes)")
+            }
+        }
+    }
+}
+
+/// A function which is called with each [`Diagnostic`]
+#[derive(Clone)]
+pub(crate) struct DiagnosticSink(Arc<dyn Fn(Diagnostic) + Send + Sync>);
+
+impl DiagnosticSink {
+    pub(crate) fn new(f: impl Fn(Diagnostic) + Send + Sync + 'static) -> DiagnosticSink {
+        DiagnosticSink(Arc::new(f))
+    }
+
+    pub(crate) fn send(&self, x: Diagnostic) {
+        (self.0)(x)
+    }
+}
+
+/// Log a diagnostic, and pass it on to the sink if there is one
+pub(crate) fn diagnose(sink: Option<&DiagnosticSink>, x: Diagnostic) {
+    warn!("{x}");
+    if let Some(sink) = sink {
+        sink.send(x);
+    }
+}
+
+impl fmt::Debug for DiagnosticSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DiagnosticSink(..)")
+    }
+}
@@ -1,5 +1,7 @@
 use crate::block::opts::*;
 use crate::block::util::*;
+use crate::block::CustomOption;
+use crate::iface::RawInterfaceId;
 use bytes::{Buf, Bytes};
 
 /// Contains a single captured packet, or a portion of it. It represents an evolution of the
@@ -25,12 +27,14 @@ use bytes::{Buf, Bytes};
 ///
 /// [1]: https://github.com/pcapng/pcapng
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct EnhancedPacket {
     /// Specifies the interface this packet comes from; the correct interface will be the one whose
     /// Interface Description Block (within the current Section of the file) is identified by the
     /// same number (see Section 4.2) of this field. The interface ID MUST be valid, which means
     /// that an matching interface description block MUST exist.
-    pub interface_id: u32,
+    pub interface_id: RawInterfaceId,
     /// Upper 32 bits and lower 32 bits of a 64-bit timestamp. The timestamp is a single 64-bit
     /// unsigned integer that represents the number of units of time that have elapsed since
     /// 1970-01-01 00:00:00 UTC. The length of a unit of time is specified by the 'if_tsresol'
@@ -94,16 +98,43 @@ pub struct EnhancedPacket {
     /// on which queue of the interface the specific packet was received.
     pub epb_queue: Option<u32>,
     pub epb_verdict: Vec<Bytes>,
+    /// Any options which pcarp doesn't understand, as `(option_type, value)`
+    /// pairs.  These may be vendor extensions, or simply options which were
+    /// defined after this version of pcarp was written.
+    pub options: Vec<(u16, Bytes)>,
+    /// The custom options, decoded.  These also appear in `options`.
+    pub custom_options: Vec<CustomOption>,
 }
 
 impl FromBytes for EnhancedPacket {
-    fn parse<T: Buf>(mut buf: T, endianness: Endianness) -> Result<EnhancedPacket, BlockError> {
+    fn parse<T: Buf>(mut buf: T, ctx: &ParseContext) -> Result<EnhancedPacket, BlockError> {
+        ctx.begin_body(buf.remaining());
+        let endianness = ctx.endianness;
         ensure_remaining!(buf, 20);
-        let interface_id = read_u32(&mut buf, endianness);
+        let interface_id = RawInterfaceId::from(read_u32(&mut buf, endianness));
         let timestamp = read_ts(&mut buf, endianness);
         let captured_len = read_u32(&mut buf, endianness);
         let packet_len = read_u32(&mut buf, endianness);
-        let packet_data = read_bytes(&mut buf, captured_len)?;
+        if ctx.skip_data {
+            // The data isn't read, but it still has to fit in the block
+            check_bytes(&buf, captured_len, ctx)?;
+            return Ok(EnhancedPacket {
+                interface_id,
+                timestamp,
+                captured_len,
+                packet_len,
+                packet_data: Bytes::new(),
+                epb_flags: 0,
+                epb_hash: vec![],
+                epb_dropcount: None,
+                epb_packetid: None,
+                epb_queue: None,
+                epb_verdict: vec![],
+                options: vec![],
+                custom_options: vec![],
+            });
+        }
+        let packet_data = read_bytes(&mut buf, captured_len, ctx)?;
 
         let mut epb_flags = 0;
         let mut epb_hash = vec![];
@@ -111,19 +142,22 @@ impl FromBytes for EnhancedPacket {
         let mut epb_packetid = None;
         let mut epb_queue = None;
         let mut epb_verdict = vec![];
-        parse_options(buf, endianness, |ty, bytes| {
+        let mut options = vec![];
+        let mut custom_options = vec![];
+        parse_options(buf, ctx, |ty, bytes| {
             match ty {
                 2 => {
-                    if let Some(x) = bytes_to_u32(bytes, endianness) {
+                    if let Some(x) = bytes_to_u32(bytes, ctx) {
                         epb_flags = x;
                     }
                 }
                 3 => epb_hash.push(bytes),
-                4 => epb_dropcount = bytes_to_u64(bytes, endianness),
-                5 => epb_packetid = bytes_to_u64(bytes, endianness),
-                6 => epb_queue = bytes_to_u32(bytes, endianness),
+                4 => epb_dropcount = bytes_to_u64(bytes, ctx),
+                5 => epb_packetid = bytes_to_u64(bytes, ctx),
+                6 => epb_queue = bytes_to_u32(bytes, ctx),
                 7 => epb_verdict.push(bytes),
-                _ => (), // Ignore unknown
+                // Keep unknown options
+                _ => keep_option(&mut options, &mut custom_options, ty, bytes, ctx),
             }
         });
 
@@ -139,6 +173,8 @@ impl FromBytes for EnhancedPacket {
             epb_packetid,
             epb_queue,
             epb_verdict,
+            options,
+            custom_options,
         })
     }
 }
@@ -1,4 +1,5 @@
-use crate::block::{trace, BlockType, Endianness};
+use crate::block::{debug, trace, BlockType, Endianness};
+use crate::sniff::Format;
 use bytes::Buf;
 use thiserror::Error;
 
@@ -45,9 +46,12 @@ pub(crate) fn parse_frame(
         return Err(FrameError::LegacyPcap);
     }
 
-    let block_type = BlockType::from(block_type);
+    // Only switch once the whole block is available, so that the caller can
+    // tell that it happened
+    let block_order = block_endianness(buf, *endianness);
+    let block_type = BlockType::from(read_u32(0, block_order));
 
-    let block_len = read_u32(4, *endianness) as usize;
+    let block_len = read_u32(4, block_order) as usize;
     if block_len < 12 {
         return Err(FrameError::BlockLengthTooSmall(block_len));
     }
@@ -55,24 +59,179 @@ pub(crate) fn parse_frame(
         return Ok(None);
     }
 
-    let block_len_2 = read_u32(block_len - 4, *endianness) as usize;
+    let block_len_2 = read_u32(block_len - 4, block_order) as usize;
     if block_len != block_len_2 {
         return Err(FrameError::BlockLengthMismatch(block_len, block_len_2));
     }
 
     let data_len = block_len - 12;
+    if block_order != *endianness {
+        debug!(
+            "The block is in the opposite byte order to its section; switching to {block_order:?}"
+        );
+        *endianness = block_order;
+    }
     Ok(Some((block_type, data_len)))
 }
 
+/// Blocks longer than this are assumed to be mistakes, for the purpose of
+/// detecting blocks in the wrong byte order
+const MAX_PLAUSIBLE_LEN: u32 = 16 << 20;
+
+/// The byte order of the block at the front of the buffer, given the byte
+/// order of the section it's in
+///
+/// Some tools write a section header in one byte order, and the rest of the
+/// section in the other.  If the block's type and length make no sense in
+/// the section's byte order, but do in the opposite one, then it's assumed
+/// that the block is swapped.  (A genuine block length is small, so when
+/// it's swapped it becomes either enormous or less than 12.)
+pub(crate) fn block_endianness(buf: &[u8], endianness: Endianness) -> Endianness {
+    let other = match endianness {
+        Endianness::Big => Endianness::Little,
+        Endianness::Little => Endianness::Big,
+    };
+    let header = |endianness: Endianness| {
+        let (ty, len) = match endianness {
+            Endianness::Big => ((&buf[0..4]).get_u32(), (&buf[4..8]).get_u32()),
+            Endianness::Little => ((&buf[0..4]).get_u32_le(), (&buf[4..8]).get_u32_le()),
+        };
+        let known = !matches!(BlockType::from(ty), BlockType::Unknown(_));
+        let len_ok = len >= 12 && len % 4 == 0 && len <= MAX_PLAUSIBLE_LEN;
+        (known, len_ok)
+    };
+    if buf.len() < 8 {
+        return endianness;
+    }
+    match (header(endianness), header(other)) {
+        ((false, false), (true, true)) => other,
+        _ => endianness,
+    }
+}
+
+/// The numeric block type at the front of a complete frame
+pub(crate) fn type_code(frame: &[u8], endianness: Endianness) -> u32 {
+    match endianness {
+        Endianness::Big => (&frame[..4]).get_u32(),
+        Endianness::Little => (&frame[..4]).get_u32_le(),
+    }
+}
+
+/// The length of the block, according to the frame's header
+///
+/// For frames which were skimmed in metadata-only mode, this is longer
+/// than the frame itself.
+pub(crate) fn block_len(frame: &[u8], endianness: Endianness) -> u32 {
+    type_code(&frame[4..], endianness)
+}
+
+/// How many more bytes are needed to complete the frame at the front of the
+/// given buffer
+///
+/// This should only be called when `parse_frame()` has returned `None`, with
+/// the endianness which it left behind.  It never returns zero.
+pub(crate) fn bytes_needed(buf: &[u8], endianness: Endianness) -> usize {
+    if buf.len() < 12 {
+        return 12 - buf.len();
+    }
+    let len = match block_endianness(buf, endianness) {
+        Endianness::Big => (&buf[4..8]).get_u32(),
+        Endianness::Little => (&buf[4..8]).get_u32_le(),
+    };
+    (len as usize).saturating_sub(buf.len()).max(1)
+}
+
 /// The pcap's superstructure is corrupt; further parsing is impossible
+///
+/// A file which ends part-way through a block isn't considered corrupt,
+/// since it may just be a capture which is still being written.  The
+/// blocks before it are read as normal, and then the capture ends:
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::Capture;
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .enhanced_packet(0, 0, b"hello", &[])
+/// #     .enhanced_packet(0, 0, b"world", &[]);
+/// let pcap = pcap.as_bytes();
+/// let mut capture = Capture::new(&pcap[..pcap.len() - 1]);
+/// let pkt = capture.next().unwrap().unwrap();
+/// assert_eq!(&pkt.data[..], b"hello");
+/// assert!(capture.next().is_none());
+///
+/// // Even if the block's length is absurd
+/// let mut pcap = pcap[..pcap.len() - 40].to_vec();
+/// pcap.extend_from_slice(&[6, 0, 0, 0, 0xfc, 0xff, 0xff, 0xff, 0, 0, 0, 0]);
+/// let mut capture = Capture::new(&pcap[..]);
+/// capture.next().unwrap().unwrap();
+/// assert!(capture.next().is_none());
+/// ```
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum FrameError {
     #[error("Didn't understand magic bytes {0:?}")]
     DidntUnderstandMagicBytes([u8; 4]),
+    /// The length at the end of a block doesn't match the one at the start
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::FrameError;
+    /// use pcarp::{Capture, Error};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[]);
+    /// let mut pcap = pcap.as_bytes().to_vec();
+    /// let n = pcap.len();
+    /// pcap[n - 4] += 4; // the packet block's trailing length
+    /// let mut capture = Capture::new(&pcap[..]);
+    /// match capture.next().unwrap() {
+    ///     Err(Error::Frame(FrameError::BlockLengthMismatch(40, 44))) => (),
+    ///     x => panic!("{x:?}"),
+    /// }
+    /// assert!(capture.next().is_none());
+    /// ```
     #[error("Block's start length was {0} but its end length was {1}")]
     BlockLengthMismatch(usize, usize),
+    /// A block's length is too small to hold its own header and trailer
+    ///
+    /// A block of exactly 12 bytes has an empty body.  That's fine as far as
+    /// the framing is concerned, even if it's too short for its block type.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::{BlockType, FrameError};
+    /// use pcarp::{Capture, Error};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .block(6, &[]) // an enhanced packet block of 12 bytes
+    /// #     .enhanced_packet(0, 0, b"hello", &[])
+    /// #     .raw(&[6, 0, 0, 0, 8, 0, 0, 0, 8, 0, 0, 0]); // and one of 8
+    /// let mut capture = Capture::new(pcap.build());
+    /// assert!(matches!(
+    ///     capture.next().unwrap(),
+    ///     Err(Error::Block(BlockType::EnhancedPacket, _)),
+    /// ));
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(&pkt.data[..], b"hello");
+    /// assert!(matches!(
+    ///     capture.next().unwrap(),
+    ///     Err(Error::Frame(FrameError::BlockLengthTooSmall(8))),
+    /// ));
+    /// ```
     #[error("Block's length is {0} bytes, but the minimum length is 12")]
     BlockLengthTooSmall(usize),
     #[error("Detected legacy pcap format")]
     LegacyPcap,
+    #[error("Section has unknown version {0}.{1}")]
+    UnknownVersion(u16, u16),
+    /// The file is in some other format.  See [`crate::sniff`].
+    #[error("This looks like a {0} file, not a pcapng")]
+    UnsupportedFormat(Format),
 }
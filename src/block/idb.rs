@@ -1,8 +1,8 @@
 use crate::block::opts::*;
 use crate::block::util::*;
+use crate::block::{CustomOption, Malformation, StrBytes};
 use crate::iface::LinkType;
-use bytes::Buf;
-use tracing::*;
+use bytes::{Buf, Bytes};
 
 /// Defines the most important characteristics of the interface(s) used for capturing traffic. This
 /// block is required in certain cases, as described later.
@@ -34,6 +34,8 @@ use tracing::*;
 ///
 /// [1]: https://github.com/pcapng/pcapng
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct InterfaceDescription {
     /// A value that defines the link layer type of this interface. The list of Standardized Link
     /// Layer Type codes is available in the tcpdump.org link-layer header types registry.
@@ -43,10 +45,10 @@ pub struct InterfaceDescription {
     pub snap_len: Option<u32>,
     /// The if_name option is a UTF-8 string containing the name of the
     /// device used to capture data. The string is not zero-terminated.
-    pub if_name: String,
+    pub if_name: StrBytes,
     /// The if_description option is a UTF-8 string containing the description
     /// of the device used to capture data. The string is not zero-terminated.
-    pub if_description: String,
+    pub if_description: StrBytes,
     /// The if_IPv4addr option is an IPv4 network address and corresponding
     /// netmask for the interface. The first four octets are the IP address,
     /// and the next four octets are the netmask. This option can be repeated
@@ -80,20 +82,28 @@ pub struct InterfaceDescription {
     /// negative power of 2 (e.g. 10 means 1/1024 of second). If this option
     /// is not present, a resolution of 10^-6 is assumed (i.e. timestamps
     /// have the same resolution of the standard 'libpcap' timestamps).
-    pub if_tsresol: u32,
+    ///
+    /// pcarp stores the number of units per second (eg. 1,000,000 for
+    /// microsecond resolution).
+    pub if_tsresol: u64,
     /// The if_tzone option identifies the time zone for GMT support.
-    pub if_tzone: Option<[u8; 4]>,
+    ///
+    /// The spec doesn't say how it's encoded.  pcarp reads it as a 32-bit
+    /// signed number of seconds which must be added to the timestamps to
+    /// get UTC, like the `thiszone` field of a legacy pcap header (so
+    /// -3600 means the timestamps are in UTC+1).
+    pub if_tzone: Option<i32>,
     /// The if_filter option identifies the filter (e.g. "capture only TCP
     /// traffic") used to capture traffic. The first octet of the Option Data
     /// keeps a code of the filter used (e.g. if this is a libpcap string,
     /// or BPF bytecode, and more).
-    pub if_filter: String,
+    pub if_filter: StrBytes,
     /// The if_os option is a UTF-8 string containing the name of the operating
     /// system of the machine in which this interface is installed. This can
     /// be different from the same information that can be contained by the
     /// Section Header Block (Section 4.1) because the capture can have been
     /// done on a remote machine. The string is not zero-terminated.
-    pub if_os: String,
+    pub if_os: StrBytes,
     /// The if_fcslen option is an 8-bit unsigned integer value that
     /// specifies the length of the Frame Check Sequence (in bits) for this
     /// interface. For link layers whose FCS length can change during time,
@@ -106,23 +116,58 @@ pub struct InterfaceDescription {
     /// is missing, the timestamps stored in the packet MUST be considered
     /// absolute timestamps. The time zone of the offset can be specified
     /// with the option if_tzone.
-    pub if_tsoffset: Option<[u8; 8]>,
+    pub if_tsoffset: Option<i64>,
     /// The if_hardware option is a UTF-8 string containing the description
     /// of the interface hardware. The string is not zero-terminated.
-    pub if_hardware: String,
-    /// The if_txrxspeeds option is a 64-bit unsigned value indicating the
+    pub if_hardware: StrBytes,
+    /// The if_txspeed option is a 64-bit unsigned value indicating the
     /// interface transmit speed in bits per second.
-    pub if_txspeed: Option<[u8; 8]>,
+    pub if_txspeed: Option<u64>,
     /// The if_rxspeed option is a 64-bit unsigned value indicating the
     /// interface receive speed, in bits per second.
-    pub if_rxspeed: Option<[u8; 8]>,
+    pub if_rxspeed: Option<u64>,
+    /// Any options which pcarp doesn't understand, as `(option_type, value)`
+    /// pairs.  These may be vendor extensions, or simply options which were
+    /// defined after this version of pcarp was written.
+    pub options: Vec<(u16, Bytes)>,
+    /// The custom options, decoded.  These also appear in `options`.
+    pub custom_options: Vec<CustomOption>,
+}
+
+impl InterfaceDescription {
+    /// An interface description with the given link type and no options
+    ///
+    /// The timestamp resolution is set to the default of microseconds.
+    pub fn new(link_type: LinkType) -> InterfaceDescription {
+        InterfaceDescription {
+            link_type,
+            snap_len: None,
+            if_name: StrBytes::default(),
+            if_description: StrBytes::default(),
+            if_ipv4_addr: vec![],
+            if_ipv6_addr: vec![],
+            if_mac_addr: None,
+            if_eui_addr: None,
+            if_speed: None,
+            if_tsresol: 1_000_000,
+            if_tzone: None,
+            if_filter: StrBytes::default(),
+            if_os: StrBytes::default(),
+            if_fcslen: None,
+            if_tsoffset: None,
+            if_hardware: StrBytes::default(),
+            if_txspeed: None,
+            if_rxspeed: None,
+            options: vec![],
+            custom_options: vec![],
+        }
+    }
 }
 
 impl FromBytes for InterfaceDescription {
-    fn parse<T: Buf>(
-        mut buf: T,
-        endianness: Endianness,
-    ) -> Result<InterfaceDescription, BlockError> {
+    fn parse<T: Buf>(mut buf: T, ctx: &ParseContext) -> Result<InterfaceDescription, BlockError> {
+        ctx.begin_body(buf.remaining());
+        let endianness = ctx.endianness;
         ensure_remaining!(buf, 8);
         let link_type = {
             let code = read_u16(&mut buf, endianness);
@@ -134,8 +179,8 @@ impl FromBytes for InterfaceDescription {
             x => Some(x),
         };
 
-        let mut if_name = String::new();
-        let mut if_description = String::new();
+        let mut if_name = StrBytes::default();
+        let mut if_description = StrBytes::default();
         let mut if_ipv4_addr = vec![];
         let mut if_ipv6_addr = vec![];
         let mut if_mac_addr = None;
@@ -143,59 +188,61 @@ impl FromBytes for InterfaceDescription {
         let mut if_speed = None;
         let mut if_tsresol = 1_000_000;
         let mut if_tzone = None;
-        let mut if_filter = String::new();
-        let mut if_os = String::new();
+        let mut if_filter = StrBytes::default();
+        let mut if_os = StrBytes::default();
         let mut if_fcslen = None;
         let mut if_tsoffset = None;
-        let mut if_hardware = String::new();
+        let mut if_hardware = StrBytes::default();
         let mut if_txspeed = None;
         let mut if_rxspeed = None;
-        parse_options(buf, endianness, |ty, bytes| {
+        let mut options = vec![];
+        let mut custom_options = vec![];
+        parse_options(buf, ctx, |ty, bytes| {
             match ty {
                 2 => if_name = bytes_to_string(bytes),
                 3 => if_description = bytes_to_string(bytes),
                 4 => {
-                    if let Some(x) = bytes_to_array(bytes) {
+                    if let Some(x) = bytes_to_array(bytes, ctx) {
                         if_ipv4_addr.push(x)
                     }
                 }
                 5 => {
-                    if let Some(x) = bytes_to_array(bytes) {
+                    if let Some(x) = bytes_to_array(bytes, ctx) {
                         if_ipv6_addr.push(x)
                     }
                 }
-                6 => if_mac_addr = bytes_to_array(bytes),
-                7 => if_eui_addr = bytes_to_array(bytes),
-                8 => if_speed = bytes_to_u64(bytes, endianness),
+                6 => if_mac_addr = bytes_to_array(bytes, ctx),
+                7 => if_eui_addr = bytes_to_array(bytes, ctx),
+                8 => if_speed = bytes_to_u64(bytes, ctx),
                 9 => {
-                    if let Some([v]) = bytes_to_array(bytes) {
+                    if let Some([v]) = bytes_to_array(bytes, ctx) {
                         let exp = u32::from(v & 0b0111_1111);
                         let base = match v >> 7 {
-                            0 => 10_u32,
-                            1 => 2_u32,
+                            0 => 10_u64,
+                            1 => 2_u64,
                             _ => unreachable!(),
                         };
                         if let Some(x) = base.checked_pow(exp) {
                             if_tsresol = x;
                         } else {
-                            warn!(
-                                "Saw an interface with a timestamp resolution \
-                                of {base}^{exp}.  The timestamps of packets \
-                                captured from this interface won't fit into  \
-                                a u32."
-                            )
+                            ctx.report(Malformation::TsresolOverflow {
+                                offset: ctx.current_option.get().0,
+                                base,
+                                exp,
+                            })
                         }
                     }
                 }
-                10 => if_tzone = bytes_to_array(bytes),
+                10 => if_tzone = bytes_to_u32(bytes, ctx).map(|x| x as i32),
                 11 => if_filter = bytes_to_string(bytes),
                 12 => if_os = bytes_to_string(bytes),
-                13 => if_fcslen = bytes_to_array(bytes),
-                14 => if_tsoffset = bytes_to_array(bytes),
+                13 => if_fcslen = bytes_to_array(bytes, ctx),
+                14 => if_tsoffset = bytes_to_u64(bytes, ctx).map(|x| x as i64),
                 15 => if_hardware = bytes_to_string(bytes),
-                16 => if_txspeed = bytes_to_array(bytes),
-                17 => if_rxspeed = bytes_to_array(bytes),
-                _ => (), // Ignore unknown
+                16 => if_txspeed = bytes_to_u64(bytes, ctx),
+                17 => if_rxspeed = bytes_to_u64(bytes, ctx),
+                // Keep unknown options
+                _ => keep_option(&mut options, &mut custom_options, ty, bytes, ctx),
             }
         });
 
@@ -218,6 +265,8 @@ impl FromBytes for InterfaceDescription {
             if_hardware,
             if_txspeed,
             if_rxspeed,
+            options,
+            custom_options,
         })
     }
 }
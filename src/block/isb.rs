@@ -1,6 +1,8 @@
 use crate::block::opts::*;
 use crate::block::util::*;
-use bytes::Buf;
+use crate::block::CustomOption;
+use crate::iface::RawInterfaceId;
+use bytes::{Buf, Bytes};
 
 /// Defines how to store some statistical data (e.g. packet dropped, etc) which can be useful to
 /// understand the conditions in which the capture has been made. If this appears in a file, an
@@ -18,11 +20,13 @@ use bytes::Buf;
 ///
 /// [1]: https://github.com/pcapng/pcapng
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct InterfaceStatistics {
     /// Specifies the interface these statistics refers to; the correct interface will be the one
     /// whose Interface Description Block (within the current Section of the file) is identified by
     /// same number (see Section 4.2) of this field.
-    pub interface_id: u32,
+    pub interface_id: RawInterfaceId,
     /// Time this statistics refers to. The format of the timestamp is the same already defined in
     /// the Enhanced Packet Block (Section 4.3).
     pub timestamp: Timestamp,
@@ -62,15 +66,20 @@ pub struct InterfaceStatistics {
     /// the value 'isb_filteraccept - isb_osdrop' because some packets could
     /// still be in the OS buffers when the capture ended.
     pub isb_usrdeliv: Option<u64>,
+    /// Any options which pcarp doesn't understand, as `(option_type, value)`
+    /// pairs.  These may be vendor extensions, or simply options which were
+    /// defined after this version of pcarp was written.
+    pub options: Vec<(u16, Bytes)>,
+    /// The custom options, decoded.  These also appear in `options`.
+    pub custom_options: Vec<CustomOption>,
 }
 
 impl FromBytes for InterfaceStatistics {
-    fn parse<T: Buf>(
-        mut buf: T,
-        endianness: Endianness,
-    ) -> Result<InterfaceStatistics, BlockError> {
+    fn parse<T: Buf>(mut buf: T, ctx: &ParseContext) -> Result<InterfaceStatistics, BlockError> {
+        ctx.begin_body(buf.remaining());
+        let endianness = ctx.endianness;
         ensure_remaining!(buf, 12);
-        let interface_id = read_u32(&mut buf, endianness);
+        let interface_id = RawInterfaceId::from(read_u32(&mut buf, endianness));
         let timestamp = read_ts(&mut buf, endianness);
 
         let mut isb_starttime = None;
@@ -80,16 +89,19 @@ impl FromBytes for InterfaceStatistics {
         let mut isb_filter_accept = None;
         let mut isb_osdrop = None;
         let mut isb_usrdeliv = None;
-        parse_options(buf, endianness, |ty, bytes| {
+        let mut options = vec![];
+        let mut custom_options = vec![];
+        parse_options(buf, ctx, |ty, bytes| {
             match ty {
-                2 => isb_starttime = bytes_to_ts(bytes, endianness),
-                3 => isb_endtime = bytes_to_ts(bytes, endianness),
-                4 => isb_ifrecv = bytes_to_u64(bytes, endianness),
-                5 => isb_ifdrop = bytes_to_u64(bytes, endianness),
-                6 => isb_filter_accept = bytes_to_u64(bytes, endianness),
-                7 => isb_osdrop = bytes_to_u64(bytes, endianness),
-                8 => isb_usrdeliv = bytes_to_u64(bytes, endianness),
-                _ => (), // Ignore unknown
+                2 => isb_starttime = bytes_to_ts(bytes, ctx),
+                3 => isb_endtime = bytes_to_ts(bytes, ctx),
+                4 => isb_ifrecv = bytes_to_u64(bytes, ctx),
+                5 => isb_ifdrop = bytes_to_u64(bytes, ctx),
+                6 => isb_filter_accept = bytes_to_u64(bytes, ctx),
+                7 => isb_osdrop = bytes_to_u64(bytes, ctx),
+                8 => isb_usrdeliv = bytes_to_u64(bytes, ctx),
+                // Keep unknown options
+                _ => keep_option(&mut options, &mut custom_options, ty, bytes, ctx),
             }
         });
 
@@ -103,6 +115,8 @@ impl FromBytes for InterfaceStatistics {
             isb_filter_accept,
             isb_osdrop,
             isb_usrdeliv,
+            options,
+            custom_options,
         })
     }
 }
@@ -0,0 +1,270 @@
+use crate::block::BlockType;
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::*;
+
+/// Something wrong with the contents of a block
+///
+/// Most of these can be worked around: by default, pcarp logs a warning
+/// and carries on (see [`MalformationPolicy`]).  If you'd like to know
+/// about them, use
+/// [`CaptureBuilder::collect_malformations()`](crate::CaptureBuilder::collect_malformations).
+/// The ones which can't be worked around are returned as a
+/// [`BlockError::Malformed`](crate::block::BlockError::Malformed).
+///
+/// Offsets are in bytes, counting from the start of the block's body (ie.
+/// from just after the block's length field).
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+#[non_exhaustive]
+pub enum Malformation {
+    /// A field (eg. the packet data) is longer than the rest of the block
+    ///
+    /// Lengths are never trusted, so even absurd ones (which would
+    /// overflow if the padding were added to them on a 32-bit target) are
+    /// reported this way.
+    ///
+    /// This goes for all of the packet blocks, whether or not the packet
+    /// data is being read:
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::{BlockError, BlockType, Malformation};
+    /// use pcarp::{CaptureBuilder, Error};
+    ///
+    /// // Packets which claim to hold 4 GiB of data
+    /// # let huge = u32::MAX.to_le_bytes();
+    /// # let epb = [&[0; 12][..], &huge, &huge, b"data"].concat();
+    /// # let opb = epb.clone(); // the same layout, as far as the lengths go
+    /// # let spb = [&huge[..], b"data"].concat();
+    /// let blocks = [
+    ///     (BlockType::EnhancedPacket, 6, epb),
+    ///     (BlockType::ObsoletePacket, 2, opb),
+    ///     (BlockType::SimplePacket, 3, spb),
+    /// ];
+    /// for (block_type, code, body) in blocks {
+    /// #   let mut pcap = PcapngBuilder::new();
+    /// #   pcap.section(Endianness::Little)
+    /// #       .interface(LinkType::ETHERNET, 0, &[])
+    /// #       .block(code, &body)
+    /// #       .enhanced_packet(0, 0, b"fine", &[]);
+    ///     for metadata_only in [false, true] {
+    ///         let mut capture = CaptureBuilder::new()
+    ///             .metadata_only(metadata_only)
+    ///             .build(pcap.build());
+    ///         match capture.next().unwrap() {
+    ///             Err(Error::Block(ty, BlockError::Malformed(x))) if ty == block_type => {
+    ///                 assert!(matches!(x, Malformation::FieldOverrun { available: 4, .. }));
+    ///             }
+    ///             x => panic!("{x:?}"),
+    ///         }
+    ///         let pkt = capture.next().unwrap().unwrap();
+    ///         assert_eq!(pkt.orig_len, 4);
+    ///     }
+    /// }
+    /// ```
+    #[error("At offset {offset}: a {len}-byte field overruns the block ({available} bytes left)")]
+    FieldOverrun {
+        offset: usize,
+        len: usize,
+        available: usize,
+    },
+    /// An option is longer than the rest of the block.  The options after
+    /// it are lost.
+    #[error(
+        "At offset {offset}: option {option_type} is {len} bytes long, but only \
+        {available} bytes are left.  Not going to try to parse any more options"
+    )]
+    TruncatedOption {
+        offset: usize,
+        option_type: u16,
+        len: usize,
+        available: usize,
+    },
+    /// A field isn't followed by the padding which should align the next
+    /// field to 32 bits (with [`PaddingPolicy::Tolerant`](crate::block::PaddingPolicy::Tolerant))
+    #[error(
+        "At offset {offset}: a {len}-byte field isn't followed by padding; carrying on without it"
+    )]
+    MissingPadding { offset: usize, len: usize },
+    /// The end-of-options option has a payload
+    #[error("At offset {offset}: the end-of-options option has a {len}-byte payload")]
+    EndOfOptPayload { offset: usize, len: usize },
+    /// There are bytes after the end of the options
+    #[error("At offset {offset}: the block contains {len} extra bytes after the options")]
+    TrailingBytes { offset: usize, len: usize },
+    /// An option doesn't have the length its type requires.  It's ignored.
+    #[error(
+        "At offset {offset}: option {option_type} has the wrong length: expected \
+        {expected} bytes but saw {actual}"
+    )]
+    WrongOptionLength {
+        offset: usize,
+        option_type: u16,
+        expected: usize,
+        actual: usize,
+    },
+    /// The section header gives a negative length (other than -1)
+    #[error("At offset {offset}: the section length is {value}, which is invalid")]
+    InvalidSectionLength { offset: usize, value: i64 },
+    /// The name resolution records aren't terminated by an `nrb_record_end`
+    #[error("At offset {offset}: the name resolution records have no nrb_record_end")]
+    MissingRecordEnd { offset: usize },
+    /// An interface's timestamp resolution is too fine for its timestamps
+    /// to fit into a u64.  It's ignored.
+    #[error("At offset {offset}: the timestamp resolution of {base}^{exp} is too fine to use")]
+    TsresolOverflow { offset: usize, base: u64, exp: u32 },
+    /// A block is in the opposite byte order to its section header.  The
+    /// rest of the section is assumed to be swapped too.
+    ///
+    /// This is detected when a block's type and length make no sense as
+    /// they are, but do when byte-swapped.
+    ///
+    /// ```
+    /// # use pcarp::{iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::{Endianness, Malformation};
+    /// use pcarp::CaptureBuilder;
+    ///
+    /// // A little-endian section header, followed by big-endian blocks
+    /// # let mut shb = PcapngBuilder::new();
+    /// # shb.section(Endianness::Little);
+    /// # let mut blocks = PcapngBuilder::new();
+    /// # blocks.section(Endianness::Big)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[]);
+    /// # let (shb, blocks) = (shb.as_bytes(), blocks.as_bytes());
+    /// # let pcap = [&shb[..], &blocks[shb.len()..]].concat();
+    /// let mut capture = CaptureBuilder::new()
+    ///     .collect_malformations(true)
+    ///     .build(&pcap[..]);
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(&pkt.data[..], b"hello");
+    /// let (_, x) = &capture.take_malformations()[0];
+    /// assert_eq!(x, &Malformation::SwappedByteOrder);
+    /// ```
+    ///
+    /// Lengths of up to 16 MiB are considered plausible.  A genuine block
+    /// which is longer than that is read as normal, but if it's in the
+    /// wrong byte order then it isn't recognised as being swapped:
+    ///
+    /// ```
+    /// # use pcarp::{iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::Endianness;
+    /// use pcarp::Capture;
+    ///
+    /// const MIB_16: usize = 16 << 20;
+    /// for (block_len, detected) in [(MIB_16 - 4, true), (MIB_16, true), (MIB_16 + 4, false)] {
+    ///     // The rest of an enhanced packet block takes 32 bytes
+    ///     let data = vec![0; block_len - 32];
+    /// #   let mut shb = PcapngBuilder::new();
+    /// #   shb.section(Endianness::Little);
+    /// #   let mut pcap = PcapngBuilder::new();
+    /// #   pcap.section(Endianness::Little)
+    /// #       .interface(LinkType::ETHERNET, 0, &[])
+    /// #       .enhanced_packet(0, 0, &data, &[]);
+    /// #   let mut swapped = PcapngBuilder::new();
+    /// #   // Only the first swapped block is checked, so it's the packet
+    /// #   swapped.section(Endianness::Big).enhanced_packet(0, 0, &data, &[]);
+    /// #   let (shb, swapped) = (shb.as_bytes(), swapped.as_bytes());
+    /// #   let swapped = [&shb[..], &swapped[shb.len()..]].concat();
+    ///     let pkt = Capture::new(pcap.build()).next().unwrap().unwrap();
+    ///     assert_eq!(pkt.data.len(), data.len());
+    ///
+    ///     // Otherwise, the file appears to end part-way through the block
+    ///     let pkt = Capture::new(&swapped[..]).next();
+    ///     assert_eq!(pkt.is_some(), detected);
+    /// }
+    /// ```
+    #[error(
+        "The block is in the opposite byte order to its section header; \
+        reading the rest of the section in that order"
+    )]
+    SwappedByteOrder,
+    /// A block refers to an interface which hasn't been defined
+    #[error("At offset {offset}: interface {interface_id} hasn't been defined")]
+    UndefinedInterface { offset: usize, interface_id: u32 },
+}
+
+/// What to do about the [`Malformation`]s which can be worked around
+///
+/// Every such problem goes through the policy, whichever block it's found
+/// in.  Problems which aren't rejected are still collected by
+/// [`CaptureBuilder::collect_malformations()`](crate::CaptureBuilder::collect_malformations).
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::block::{BlockError, BlockType, Malformation, MalformationPolicy};
+/// use pcarp::{CaptureBuilder, Error};
+/// use std::sync::Arc;
+///
+/// // A packet with some junk after the end-of-options option
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .enhanced_packet(0, 0, b"hello", &[(0, b""), (1, b"junk")]);
+/// let mut capture = CaptureBuilder::new().build(pcap.build());
+/// assert!(capture.next().unwrap().is_ok());
+///
+/// let mut capture = CaptureBuilder::new()
+///     .malformation_policy(MalformationPolicy::Reject)
+///     .build(pcap.build());
+/// match capture.next().unwrap() {
+///     Err(Error::Block(BlockType::EnhancedPacket, BlockError::Malformed(x))) => {
+///         assert!(matches!(x, Malformation::TrailingBytes { .. }));
+///     }
+///     x => panic!("{x:?}"),
+/// }
+///
+/// // Only reject packets which have junk after their options
+/// let policy = MalformationPolicy::Custom(Arc::new(|block_type, x| {
+///     block_type == BlockType::EnhancedPacket && matches!(x, Malformation::TrailingBytes { .. })
+/// }));
+/// let mut capture = CaptureBuilder::new()
+///     .malformation_policy(policy)
+///     .build(pcap.build());
+/// assert!(capture.next().unwrap().is_err());
+/// ```
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub enum MalformationPolicy {
+    /// Log a warning and carry on.  This is the default.
+    #[default]
+    Warn,
+    /// Carry on without logging anything
+    Ignore,
+    /// Treat the block as corrupt: it's returned as a
+    /// [`BlockError::Malformed`](crate::block::BlockError::Malformed), just
+    /// like a problem which can't be worked around
+    Reject,
+    /// Call a function, which decides whether to reject the block.
+    /// Nothing is logged.
+    #[allow(clippy::type_complexity)]
+    Custom(Arc<dyn Fn(BlockType, &Malformation) -> bool + Send + Sync>),
+}
+
+impl fmt::Debug for MalformationPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MalformationPolicy::Warn => f.write_str("Warn"),
+            MalformationPolicy::Ignore => f.write_str("Ignore"),
+            MalformationPolicy::Reject => f.write_str("Reject"),
+            MalformationPolicy::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+impl MalformationPolicy {
+    /// Whether a block in which `x` was found should be rejected.  Any
+    /// warning is logged here.
+    pub(crate) fn rejects(&self, block_type: BlockType, x: &Malformation) -> bool {
+        match self {
+            MalformationPolicy::Warn => {
+                warn!("{block_type:?}: {x}");
+                false
+            }
+            MalformationPolicy::Ignore => false,
+            MalformationPolicy::Reject => true,
+            MalformationPolicy::Custom(f) => f(block_type, x),
+        }
+    }
+}
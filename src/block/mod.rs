@@ -1,46 +1,81 @@
-/*! **\[Internal\]** Block definitions.
+/*! Block definitions.
 
-> Caveat: This is an internal module, and is exposed for the sake of
-> interest only.  The API may change in a patch bump.  The user may need to
-> enforce invariants.  The documentation may be inaccurate.
+This is the low-level API: if you need something which [`Capture`]
+doesn't provide, you can read the blocks of a pcapng yourself with a
+[`BlockReader`].  To get an idea of how the pcap-ng format works, take a
+look at [`Block`].
 
-That said, if you want to get an idea of how the pcap-ng format works,
-take a look at [`Block`](enum.Block.html).
+## Stability
 
-All documentation in this module is taken from [the pcap-ng spec][].  It is
-copyright (c) 2018 IETF Trust and the persons identified as the authors of
-the linked document. All rights reserved.
+Everything exported from this module follows semver, just like the rest
+of pcarp.  That covers the parsed block structs, [`Block`], [`BlockType`],
+[`Timestamp`], the option codes, and [`BlockReader`].  So that new blocks,
+fields, and errors can be added in a minor release, the enums and the
+block structs are `#[non_exhaustive]`: match them with a wildcard arm,
+and build blocks with [`FromBytes`] (or `InterfaceDescription::new()`)
+rather than with struct literals.
 
+The framing layer - finding block boundaries, byte-order detection, and
+the option-parsing helpers - is private, and may change at any time.
+
+All documentation of the blocks themselves is taken from [the pcap-ng
+spec][].  It is copyright (c) 2018 IETF Trust and the persons identified
+as the authors of the linked document. All rights reserved.
+
+[`Capture`]: crate::Capture
 [the pcap-ng spec]: https://github.com/pcapng/pcapng
 */
 
+mod custom;
+mod diagnostic;
 mod epb;
 mod frame;
 mod idb;
 mod isb;
+mod malformed;
 mod nrb;
 mod opb;
+mod option_type;
 mod opts;
+mod quirks;
 mod rdr;
+mod registry;
 mod shb;
 mod spb;
+mod str_bytes;
 mod util;
 
-pub use self::epb::*;
-pub use self::frame::*;
-pub use self::idb::*;
-pub use self::isb::*;
-pub use self::nrb::*;
-pub use self::opb::*;
-pub use self::rdr::*;
-pub use self::shb::*;
-pub use self::spb::*;
-pub use self::util::*;
+pub use self::custom::CustomOption;
+pub use self::diagnostic::Diagnostic;
+pub use self::epb::EnhancedPacket;
+pub use self::frame::FrameError;
+pub use self::idb::InterfaceDescription;
+pub use self::isb::InterfaceStatistics;
+pub use self::malformed::{Malformation, MalformationPolicy};
+pub use self::nrb::NameResolution;
+pub use self::opb::ObsoletePacket;
+pub use self::option_type::*;
+pub use self::quirks::Quirks;
+pub use self::rdr::{BlockReader, VersionPolicy};
+pub use self::registry::{BlockParser, Extension};
+pub use self::shb::SectionHeader;
+pub use self::spb::SimplePacket;
+pub use self::str_bytes::StrBytes;
+pub use self::util::{BlockError, Endianness, FromBytes, PaddingPolicy, ParseContext, Timestamp};
+
+// The framing layer is shared with the section scanner, but nothing else
+pub(crate) use self::diagnostic::{diagnose, DiagnosticSink};
+pub(crate) use self::frame::block_endianness;
+pub(crate) use self::registry::ParserRegistry;
+pub(crate) use self::util::read_vec;
 
+use crate::iface::RawInterfaceId;
 use bytes::{Buf, Bytes};
 use tracing::*;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum BlockType {
     SectionHeader,
     InterfaceDescription,
@@ -74,7 +109,7 @@ impl From<u32> for BlockType {
             0x0000_0009 => BlockType::SystemdJournalExport,
             0x0000_000A => BlockType::DecryptionSecrets,
             0x0000_0101 | 0x40000102 => BlockType::Hone,
-            0x0000_0201..=0x0000_0213 => BlockType::Sysdig,
+            0x0000_0201..=0x0000_0219 | 0x0000_0220..=0x0000_0222 => BlockType::Sysdig,
             0x0000_0BAD | 0x40000BAD => BlockType::Custom,
             n => BlockType::Unknown(n),
         }
@@ -82,6 +117,8 @@ impl From<u32> for BlockType {
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Block {
     SectionHeader(SectionHeader),
     InterfaceDescription(InterfaceDescription),
@@ -90,37 +127,79 @@ pub enum Block {
     NameResolution(NameResolution),
     InterfaceStatistics(InterfaceStatistics),
     EnhancedPacket(EnhancedPacket),
-    Unparsed(BlockType),
+    /// A block which pcarp doesn't know how to parse.  `code` is the
+    /// block's numeric type, as it appears in the file; `body` is
+    /// everything between the block's length fields, in the section's byte
+    /// order.
+    Unparsed {
+        ty: BlockType,
+        code: u32,
+        body: Bytes,
+    },
+    /// A block parsed by a user-supplied [`BlockParser`].  These can't be
+    /// serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Extension(BlockType, Extension),
 }
 
 impl Block {
+    /// The type of this block
+    pub fn block_type(&self) -> BlockType {
+        match self {
+            Block::SectionHeader(_) => BlockType::SectionHeader,
+            Block::InterfaceDescription(_) => BlockType::InterfaceDescription,
+            Block::ObsoletePacket(_) => BlockType::ObsoletePacket,
+            Block::SimplePacket(_) => BlockType::SimplePacket,
+            Block::NameResolution(_) => BlockType::NameResolution,
+            Block::InterfaceStatistics(_) => BlockType::InterfaceStatistics,
+            Block::EnhancedPacket(_) => BlockType::EnhancedPacket,
+            Block::Unparsed { ty, .. } => *ty,
+            Block::Extension(x, _) => *x,
+        }
+    }
+
     pub(crate) fn parse(
         block_type: BlockType,
-        block_data: impl Buf,
-        endianness: Endianness,
+        mut block_data: impl Buf,
+        ctx: &ParseContext,
     ) -> Result<Block, BlockError> {
         use BlockType as BT;
         Ok(match block_type {
-            BT::SectionHeader => SectionHeader::parse(block_data, endianness)?.into(),
-            BT::InterfaceDescription => InterfaceDescription::parse(block_data, endianness)?.into(),
-            BT::ObsoletePacket => ObsoletePacket::parse(block_data, endianness)?.into(),
-            BT::SimplePacket => SimplePacket::parse(block_data, endianness)?.into(),
-            BT::NameResolution => NameResolution::parse(block_data, endianness)?.into(),
-            BT::InterfaceStatistics => InterfaceStatistics::parse(block_data, endianness)?.into(),
-            BT::EnhancedPacket => EnhancedPacket::parse(block_data, endianness)?.into(),
-            _ => Block::Unparsed(block_type),
+            BT::SectionHeader => SectionHeader::parse(block_data, ctx)?.into(),
+            BT::InterfaceDescription => InterfaceDescription::parse(block_data, ctx)?.into(),
+            BT::ObsoletePacket => ObsoletePacket::parse(block_data, ctx)?.into(),
+            BT::SimplePacket => SimplePacket::parse(block_data, ctx)?.into(),
+            BT::NameResolution => NameResolution::parse(block_data, ctx)?.into(),
+            BT::InterfaceStatistics => InterfaceStatistics::parse(block_data, ctx)?.into(),
+            BT::EnhancedPacket => EnhancedPacket::parse(block_data, ctx)?.into(),
+            _ => Block::Unparsed {
+                ty: block_type,
+                code: ctx.type_code(),
+                body: block_data.copy_to_bytes(block_data.remaining()),
+            },
         })
     }
 
-    pub(crate) fn into_pkt(self) -> Option<(Option<(Timestamp, u32)>, Bytes)> {
+    /// The interface ID, timestamp, and data of a packet block
+    ///
+    /// Simple packets don't have a timestamp, and implicitly belong to
+    /// interface 0.
+    pub(crate) fn into_pkt(self) -> Option<(RawInterfaceId, Option<Timestamp>, Bytes, u32)> {
         match self {
-            Block::EnhancedPacket(pkt) => {
-                Some((Some((pkt.timestamp, pkt.interface_id)), pkt.packet_data))
+            Block::EnhancedPacket(pkt) => Some((
+                pkt.interface_id,
+                Some(pkt.timestamp),
+                pkt.packet_data,
+                pkt.packet_len,
+            )),
+            Block::SimplePacket(pkt) => {
+                Some((RawInterfaceId(0), None, pkt.packet_data, pkt.packet_len))
             }
-            Block::SimplePacket(pkt) => Some((None, pkt.packet_data)),
             Block::ObsoletePacket(pkt) => Some((
-                Some((pkt.timestamp, u32::from(pkt.interface_id))),
+                pkt.interface_id,
+                Some(pkt.timestamp),
                 pkt.packet_data,
+                pkt.packet_len,
             )),
             _ => None,
         }
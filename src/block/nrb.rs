@@ -1,5 +1,10 @@
+use crate::block::opts::*;
 use crate::block::util::*;
+use crate::block::{
+    CustomOption, Malformation, StrBytes, NS_DNSIP4ADDR, NS_DNSIP6ADDR, NS_DNSNAME,
+};
 use bytes::{Buf, Bytes};
+use std::net::{Ipv4Addr, Ipv6Addr};
 
 /// Defines the mapping from numeric addresses present in the packet capture and the canonical name
 /// counterpart.
@@ -29,17 +34,77 @@ use bytes::{Buf, Bytes};
 ///
 /// [1]: https://github.com/pcapng/pcapng
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct NameResolution {
     /// Zero or more Name Resolution Records (in the TLV format), each of which contains an
     /// association between a network address and a name. An nrb_record_end MUST be added after the
     /// last Record, and MUST exist even if there are no other Records in the NRB.
     pub record_values: Bytes, // TODO
+    /// The ns_dnsname option is a UTF-8 string containing the name of the machine (DNS server)
+    /// used to perform the name resolution. The string is not zero-terminated.
+    pub ns_dnsname: StrBytes,
+    /// The ns_dnsIP4addr option specifies the IPv4 address of the DNS server.
+    pub ns_dns_ip4_addr: Option<Ipv4Addr>,
+    /// The ns_dnsIP6addr option specifies the IPv6 address of the DNS server.
+    pub ns_dns_ip6_addr: Option<Ipv6Addr>,
+    /// Any options which pcarp doesn't understand, as `(option_type, value)`
+    /// pairs.  These may be vendor extensions, or simply options which were
+    /// defined after this version of pcarp was written.
+    pub options: Vec<(u16, Bytes)>,
+    /// The custom options, decoded.  These also appear in `options`.
+    pub custom_options: Vec<CustomOption>,
 }
 
 impl FromBytes for NameResolution {
-    fn parse<T: Buf>(mut buf: T, _endianness: Endianness) -> Result<NameResolution, BlockError> {
+    fn parse<T: Buf>(mut buf: T, ctx: &ParseContext) -> Result<NameResolution, BlockError> {
+        ctx.begin_body(buf.remaining());
+        let all = buf.copy_to_bytes(buf.remaining());
+        let records_len = match records_len(all.clone(), ctx) {
+            Some(x) => x,
+            None => {
+                if !ctx.quirks.missing_record_end {
+                    ctx.report(Malformation::MissingRecordEnd { offset: 0 });
+                }
+                all.len()
+            }
+        };
+        let record_values = all.slice(..records_len);
+
+        let mut ns_dnsname = StrBytes::default();
+        let mut ns_dns_ip4_addr = None;
+        let mut ns_dns_ip6_addr = None;
+        let mut options = vec![];
+        let mut custom_options = vec![];
+        parse_options(all.slice(records_len..), ctx, |ty, bytes| match ty {
+            NS_DNSNAME => ns_dnsname = bytes_to_string(bytes),
+            NS_DNSIP4ADDR => ns_dns_ip4_addr = bytes_to_array::<4>(bytes, ctx).map(Ipv4Addr::from),
+            NS_DNSIP6ADDR => ns_dns_ip6_addr = bytes_to_array::<16>(bytes, ctx).map(Ipv6Addr::from),
+            _ => keep_option(&mut options, &mut custom_options, ty, bytes, ctx),
+        });
+
         Ok(NameResolution {
-            record_values: buf.copy_to_bytes(buf.remaining()),
+            record_values,
+            ns_dnsname,
+            ns_dns_ip4_addr,
+            ns_dns_ip6_addr,
+            options,
+            custom_options,
         })
     }
 }
+
+/// The length of the records, including the nrb_record_end.  Returns `None`
+/// if there's no nrb_record_end.
+fn records_len(mut buf: Bytes, ctx: &ParseContext) -> Option<usize> {
+    let total = buf.len();
+    while buf.remaining() >= 4 {
+        let record_type = read_u16(&mut buf, ctx.endianness);
+        let record_len = read_u16(&mut buf, ctx.endianness);
+        if record_type == 0 {
+            return Some(total - buf.remaining());
+        }
+        read_bytes(&mut buf, u32::from(record_len), ctx).ok()?;
+    }
+    None
+}
@@ -1,5 +1,7 @@
 use crate::block::opts::*;
 use crate::block::util::*;
+use crate::block::CustomOption;
+use crate::iface::RawInterfaceId;
 use bytes::{Buf, Bytes};
 
 /// Contains a single captured packet, or a portion of it. It is OBSOLETE, and superseded by the
@@ -16,12 +18,16 @@ use bytes::{Buf, Bytes};
 ///
 /// [1]: https://github.com/pcapng/pcapng
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct ObsoletePacket {
     /// Specifies the interface this packet comes from; the correct interface will be the one whose
     /// Interface Description Block (within the current Section of the file) is identified by the
     /// same number (see Section 4.2) of this field. The interface ID MUST be valid, which means
     /// that an matching interface description block MUST exist.
-    pub interface_id: u16,
+    ///
+    /// In this block the field is only 16 bits wide.
+    pub interface_id: RawInterfaceId,
     /// A local drop counter. It specifies the number of packets lost (by the interface and the
     /// operating system) between this packet and the preceding one. The value xFFFF (in
     /// hexadecimal) is reserved for those systems in which this information is not available.
@@ -47,12 +53,16 @@ pub struct ObsoletePacket {
     /// Optionally, a list of options (formatted according to the rules defined in Section 3.5) can
     /// be present.
     pub options: Vec<(u16, Bytes)>,
+    /// The custom options, decoded.  These also appear in `options`.
+    pub custom_options: Vec<CustomOption>,
 }
 
 impl FromBytes for ObsoletePacket {
-    fn parse<T: Buf>(mut buf: T, endianness: Endianness) -> Result<ObsoletePacket, BlockError> {
+    fn parse<T: Buf>(mut buf: T, ctx: &ParseContext) -> Result<ObsoletePacket, BlockError> {
+        ctx.begin_body(buf.remaining());
+        let endianness = ctx.endianness;
         ensure_remaining!(buf, 20);
-        let interface_id = read_u16(&mut buf, endianness);
+        let interface_id = RawInterfaceId::from(read_u16(&mut buf, endianness));
         let drops_count = match read_u16(&mut buf, endianness) {
             0xFFFF => None,
             x => Some(x),
@@ -60,11 +70,25 @@ impl FromBytes for ObsoletePacket {
         let timestamp = read_ts(&mut buf, endianness);
         let captured_len = read_u32(&mut buf, endianness);
         let packet_len = read_u32(&mut buf, endianness);
-        let packet_data = read_bytes(&mut buf, captured_len)?;
         let mut options = vec![];
-        parse_options(buf, endianness, |option_type, option_bytes| {
-            options.push((option_type, option_bytes));
-        });
+        let mut custom_options = vec![];
+        let packet_data = if ctx.skip_data {
+            // The data isn't read, but it still has to fit in the block
+            check_bytes(&buf, captured_len, ctx)?;
+            Bytes::new()
+        } else {
+            let data = read_bytes(&mut buf, captured_len, ctx)?;
+            parse_options(buf, ctx, |option_type, option_bytes| {
+                keep_option(
+                    &mut options,
+                    &mut custom_options,
+                    option_type,
+                    option_bytes,
+                    ctx,
+                );
+            });
+            data
+        };
         Ok(ObsoletePacket {
             interface_id,
             drops_count,
@@ -73,6 +97,7 @@ impl FromBytes for ObsoletePacket {
             packet_len,
             packet_data,
             options,
+            custom_options,
         })
     }
 }
@@ -0,0 +1,234 @@
+use crate::block::BlockType;
+
+// Options which may appear in any block
+pub const OPT_ENDOFOPT: u16 = 0;
+pub const OPT_COMMENT: u16 = 1;
+pub const OPT_CUSTOM_STR_COPY: u16 = 2988;
+pub const OPT_CUSTOM_BIN_COPY: u16 = 2989;
+pub const OPT_CUSTOM_STR_NOCOPY: u16 = 19372;
+pub const OPT_CUSTOM_BIN_NOCOPY: u16 = 19373;
+
+// Section header block options
+pub const SHB_HARDWARE: u16 = 2;
+pub const SHB_OS: u16 = 3;
+pub const SHB_USERAPPL: u16 = 4;
+
+// Interface description block options
+pub const IF_NAME: u16 = 2;
+pub const IF_DESCRIPTION: u16 = 3;
+pub const IF_IPV4ADDR: u16 = 4;
+pub const IF_IPV6ADDR: u16 = 5;
+pub const IF_MACADDR: u16 = 6;
+pub const IF_EUIADDR: u16 = 7;
+pub const IF_SPEED: u16 = 8;
+pub const IF_TSRESOL: u16 = 9;
+pub const IF_TZONE: u16 = 10;
+pub const IF_FILTER: u16 = 11;
+pub const IF_OS: u16 = 12;
+pub const IF_FCSLEN: u16 = 13;
+pub const IF_TSOFFSET: u16 = 14;
+pub const IF_HARDWARE: u16 = 15;
+pub const IF_TXSPEED: u16 = 16;
+pub const IF_RXSPEED: u16 = 17;
+pub const IF_IANA_TZNAME: u16 = 18;
+
+// Enhanced packet block options
+pub const EPB_FLAGS: u16 = 2;
+pub const EPB_HASH: u16 = 3;
+pub const EPB_DROPCOUNT: u16 = 4;
+pub const EPB_PACKETID: u16 = 5;
+pub const EPB_QUEUE: u16 = 6;
+pub const EPB_VERDICT: u16 = 7;
+pub const EPB_PROCESSID_THREADID: u16 = 8;
+
+// (Obsolete) packet block options
+pub const PACK_FLAGS: u16 = 2;
+pub const PACK_HASH: u16 = 3;
+
+// Name resolution block options
+pub const NS_DNSNAME: u16 = 2;
+pub const NS_DNSIP4ADDR: u16 = 3;
+pub const NS_DNSIP6ADDR: u16 = 4;
+
+// Interface statistics block options
+pub const ISB_STARTTIME: u16 = 2;
+pub const ISB_ENDTIME: u16 = 3;
+pub const ISB_IFRECV: u16 = 4;
+pub const ISB_IFDROP: u16 = 5;
+pub const ISB_FILTERACCEPT: u16 = 6;
+pub const ISB_OSDROP: u16 = 7;
+pub const ISB_USRDELIV: u16 = 8;
+
+/// The meaning of an option code
+///
+/// Most option codes mean different things in different types of block, so
+/// an option code can only be interpreted in the context of its block.
+///
+/// ```
+/// # use pcarp::block::{BlockType, OptionType, IF_NAME};
+/// let x = OptionType::new(BlockType::InterfaceDescription, IF_NAME);
+/// assert_eq!(x, OptionType::IfName);
+/// assert_eq!(x.code(), 2);
+/// let x = OptionType::new(BlockType::EnhancedPacket, 0xBEEF);
+/// assert_eq!(x, OptionType::Unknown(0xBEEF));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum OptionType {
+    EndOfOpt,
+    Comment,
+    CustomStrCopy,
+    CustomBinCopy,
+    CustomStrNoCopy,
+    CustomBinNoCopy,
+    ShbHardware,
+    ShbOs,
+    ShbUserAppl,
+    IfName,
+    IfDescription,
+    IfIpv4Addr,
+    IfIpv6Addr,
+    IfMacAddr,
+    IfEuiAddr,
+    IfSpeed,
+    IfTsresol,
+    IfTzone,
+    IfFilter,
+    IfOs,
+    IfFcslen,
+    IfTsoffset,
+    IfHardware,
+    IfTxspeed,
+    IfRxspeed,
+    IfIanaTzname,
+    EpbFlags,
+    EpbHash,
+    EpbDropcount,
+    EpbPacketid,
+    EpbQueue,
+    EpbVerdict,
+    EpbProcessidThreadid,
+    PackFlags,
+    PackHash,
+    NsDnsName,
+    NsDnsIp4Addr,
+    NsDnsIp6Addr,
+    IsbStarttime,
+    IsbEndtime,
+    IsbIfrecv,
+    IsbIfdrop,
+    IsbFilterAccept,
+    IsbOsdrop,
+    IsbUsrdeliv,
+    /// An option which isn't defined for this type of block
+    Unknown(u16),
+}
+
+impl OptionType {
+    /// Interpret an option code which appeared in a block of the given type
+    pub fn new(block_type: BlockType, code: u16) -> OptionType {
+        use BlockType as BT;
+        use OptionType::*;
+        match (block_type, code) {
+            (_, OPT_ENDOFOPT) => EndOfOpt,
+            (_, OPT_COMMENT) => Comment,
+            (_, OPT_CUSTOM_STR_COPY) => CustomStrCopy,
+            (_, OPT_CUSTOM_BIN_COPY) => CustomBinCopy,
+            (_, OPT_CUSTOM_STR_NOCOPY) => CustomStrNoCopy,
+            (_, OPT_CUSTOM_BIN_NOCOPY) => CustomBinNoCopy,
+            (BT::SectionHeader, SHB_HARDWARE) => ShbHardware,
+            (BT::SectionHeader, SHB_OS) => ShbOs,
+            (BT::SectionHeader, SHB_USERAPPL) => ShbUserAppl,
+            (BT::InterfaceDescription, IF_NAME) => IfName,
+            (BT::InterfaceDescription, IF_DESCRIPTION) => IfDescription,
+            (BT::InterfaceDescription, IF_IPV4ADDR) => IfIpv4Addr,
+            (BT::InterfaceDescription, IF_IPV6ADDR) => IfIpv6Addr,
+            (BT::InterfaceDescription, IF_MACADDR) => IfMacAddr,
+            (BT::InterfaceDescription, IF_EUIADDR) => IfEuiAddr,
+            (BT::InterfaceDescription, IF_SPEED) => IfSpeed,
+            (BT::InterfaceDescription, IF_TSRESOL) => IfTsresol,
+            (BT::InterfaceDescription, IF_TZONE) => IfTzone,
+            (BT::InterfaceDescription, IF_FILTER) => IfFilter,
+            (BT::InterfaceDescription, IF_OS) => IfOs,
+            (BT::InterfaceDescription, IF_FCSLEN) => IfFcslen,
+            (BT::InterfaceDescription, IF_TSOFFSET) => IfTsoffset,
+            (BT::InterfaceDescription, IF_HARDWARE) => IfHardware,
+            (BT::InterfaceDescription, IF_TXSPEED) => IfTxspeed,
+            (BT::InterfaceDescription, IF_RXSPEED) => IfRxspeed,
+            (BT::InterfaceDescription, IF_IANA_TZNAME) => IfIanaTzname,
+            (BT::EnhancedPacket, EPB_FLAGS) => EpbFlags,
+            (BT::EnhancedPacket, EPB_HASH) => EpbHash,
+            (BT::EnhancedPacket, EPB_DROPCOUNT) => EpbDropcount,
+            (BT::EnhancedPacket, EPB_PACKETID) => EpbPacketid,
+            (BT::EnhancedPacket, EPB_QUEUE) => EpbQueue,
+            (BT::EnhancedPacket, EPB_VERDICT) => EpbVerdict,
+            (BT::EnhancedPacket, EPB_PROCESSID_THREADID) => EpbProcessidThreadid,
+            (BT::ObsoletePacket, PACK_FLAGS) => PackFlags,
+            (BT::ObsoletePacket, PACK_HASH) => PackHash,
+            (BT::NameResolution, NS_DNSNAME) => NsDnsName,
+            (BT::NameResolution, NS_DNSIP4ADDR) => NsDnsIp4Addr,
+            (BT::NameResolution, NS_DNSIP6ADDR) => NsDnsIp6Addr,
+            (BT::InterfaceStatistics, ISB_STARTTIME) => IsbStarttime,
+            (BT::InterfaceStatistics, ISB_ENDTIME) => IsbEndtime,
+            (BT::InterfaceStatistics, ISB_IFRECV) => IsbIfrecv,
+            (BT::InterfaceStatistics, ISB_IFDROP) => IsbIfdrop,
+            (BT::InterfaceStatistics, ISB_FILTERACCEPT) => IsbFilterAccept,
+            (BT::InterfaceStatistics, ISB_OSDROP) => IsbOsdrop,
+            (BT::InterfaceStatistics, ISB_USRDELIV) => IsbUsrdeliv,
+            (_, code) => Unknown(code),
+        }
+    }
+
+    /// The option code
+    pub fn code(self) -> u16 {
+        use OptionType::*;
+        match self {
+            EndOfOpt => OPT_ENDOFOPT,
+            Comment => OPT_COMMENT,
+            CustomStrCopy => OPT_CUSTOM_STR_COPY,
+            CustomBinCopy => OPT_CUSTOM_BIN_COPY,
+            CustomStrNoCopy => OPT_CUSTOM_STR_NOCOPY,
+            CustomBinNoCopy => OPT_CUSTOM_BIN_NOCOPY,
+            ShbHardware => SHB_HARDWARE,
+            ShbOs => SHB_OS,
+            ShbUserAppl => SHB_USERAPPL,
+            IfName => IF_NAME,
+            IfDescription => IF_DESCRIPTION,
+            IfIpv4Addr => IF_IPV4ADDR,
+            IfIpv6Addr => IF_IPV6ADDR,
+            IfMacAddr => IF_MACADDR,
+            IfEuiAddr => IF_EUIADDR,
+            IfSpeed => IF_SPEED,
+            IfTsresol => IF_TSRESOL,
+            IfTzone => IF_TZONE,
+            IfFilter => IF_FILTER,
+            IfOs => IF_OS,
+            IfFcslen => IF_FCSLEN,
+            IfTsoffset => IF_TSOFFSET,
+            IfHardware => IF_HARDWARE,
+            IfTxspeed => IF_TXSPEED,
+            IfRxspeed => IF_RXSPEED,
+            IfIanaTzname => IF_IANA_TZNAME,
+            EpbFlags => EPB_FLAGS,
+            EpbHash => EPB_HASH,
+            EpbDropcount => EPB_DROPCOUNT,
+            EpbPacketid => EPB_PACKETID,
+            EpbQueue => EPB_QUEUE,
+            EpbVerdict => EPB_VERDICT,
+            EpbProcessidThreadid => EPB_PROCESSID_THREADID,
+            PackFlags => PACK_FLAGS,
+            PackHash => PACK_HASH,
+            NsDnsName => NS_DNSNAME,
+            NsDnsIp4Addr => NS_DNSIP4ADDR,
+            NsDnsIp6Addr => NS_DNSIP6ADDR,
+            IsbStarttime => ISB_STARTTIME,
+            IsbEndtime => ISB_ENDTIME,
+            IsbIfrecv => ISB_IFRECV,
+            IsbIfdrop => ISB_IFDROP,
+            IsbFilterAccept => ISB_FILTERACCEPT,
+            IsbOsdrop => ISB_OSDROP,
+            IsbUsrdeliv => ISB_USRDELIV,
+            Unknown(code) => code,
+        }
+    }
+}
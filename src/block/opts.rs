@@ -1,32 +1,42 @@
+use crate::block::option_type::*;
 use crate::block::util::*;
+use crate::block::{CustomOption, Malformation, StrBytes};
 use bytes::{Buf, Bytes};
-use tracing::*;
 
 pub(crate) fn parse_options<T: Buf>(
     mut buf: T,
-    endianness: Endianness,
+    ctx: &ParseContext,
     mut handle: impl FnMut(u16, Bytes),
 ) {
+    let endianness = ctx.endianness;
     while buf.remaining() > 3 {
+        let offset = ctx.offset(buf.remaining());
         let option_type = read_u16(&mut buf, endianness);
         let option_len = read_u16(&mut buf, endianness);
-        let option_bytes = match read_bytes(&mut buf, option_len as u32) {
+        let available = buf.remaining();
+        let option_bytes = match read_bytes(&mut buf, option_len as u32, ctx) {
             Ok(x) => x,
             Err(_) => {
-                warn!(
-                    "Saw a truncated option.  Not going to try to parse any \
-                    more options"
-                );
+                ctx.report(Malformation::TruncatedOption {
+                    offset,
+                    option_type,
+                    len: option_len as usize,
+                    available,
+                });
                 break;
             }
         };
+        ctx.current_option.set((offset, option_type));
         match option_type {
             // The opt_endofopt option delimits the end of the optional
             // fields. This option MUST NOT be repeated within a given
             // list of options.
-            0 => {
+            OPT_ENDOFOPT => {
                 if option_len != 0 {
-                    warn!("The end-of-opt option contained a payload: {option_bytes:?}");
+                    ctx.report(Malformation::EndOfOptPayload {
+                        offset,
+                        len: option_bytes.len(),
+                    });
                 }
                 break;
             }
@@ -36,55 +46,90 @@ pub(crate) fn parse_options<T: Buf>(
             // + linefeed ('\r\n') or just linefeed ('\n'); either form
             // may appear and be considered a line separator. The string
             // is not zero-terminated.
-            1 => (), // We don't do anything with comments; discard
-            // References to the "custom data" section of the pcap.
-            // We don't handle any of this stuff.
-            2988 | 2989 | 19372 | 19373 => (),
-            // Block-specific or custom
+            OPT_COMMENT => (), // We don't do anything with comments; discard
+            // Block-specific or custom.  Custom options are passed on as-is,
+            // PEN and all, since pcarp doesn't know what they mean.
             _ => handle(option_type, option_bytes),
         }
     }
     if buf.remaining() != 0 {
-        warn!(
-            "The block contained extra bytes after the options: {:?}",
-            buf.copy_to_bytes(buf.remaining()),
-        );
+        ctx.report(Malformation::TrailingBytes {
+            offset: ctx.offset(buf.remaining()),
+            len: buf.remaining(),
+        });
     }
 }
 
-pub(crate) fn bytes_to_string(bytes: Bytes) -> String {
-    String::from_utf8_lossy(&bytes).to_string()
+/// Keep an option which the block's parser doesn't know about.  Custom
+/// options are decoded as well.
+pub(crate) fn keep_option(
+    options: &mut Vec<(u16, Bytes)>,
+    custom_options: &mut Vec<CustomOption>,
+    code: u16,
+    bytes: Bytes,
+    ctx: &ParseContext,
+) {
+    if let OPT_CUSTOM_STR_COPY
+    | OPT_CUSTOM_BIN_COPY
+    | OPT_CUSTOM_STR_NOCOPY
+    | OPT_CUSTOM_BIN_NOCOPY = code
+    {
+        if bytes.len() >= 4 {
+            custom_options.push(CustomOption {
+                code,
+                pen: read_u32(&mut bytes.slice(..4), ctx.endianness),
+                payload: bytes.slice(4..),
+            });
+        } else if !ctx.quirks.empty_custom_options {
+            let (offset, option_type) = ctx.current_option.get();
+            ctx.report(Malformation::WrongOptionLength {
+                offset,
+                option_type,
+                expected: 4,
+                actual: bytes.len(),
+            });
+        }
+    }
+    options.push((code, bytes));
+}
+
+pub(crate) fn bytes_to_string(bytes: Bytes) -> StrBytes {
+    StrBytes::from_utf8_lossy(bytes)
 }
 
-pub(crate) fn ensure_len(bytes: &Bytes, expected: usize) -> Option<()> {
+/// Check the length of the option which is currently being parsed
+pub(crate) fn ensure_len(bytes: &Bytes, expected: usize, ctx: &ParseContext) -> Option<()> {
     let actual = bytes.len();
     if expected == actual {
         Some(())
     } else {
-        warn!(
-            "Option has the wrong length: expected {expected} bytes but \
-            saw {actual}"
-        );
+        let (offset, option_type) = ctx.current_option.get();
+        ctx.report(Malformation::WrongOptionLength {
+            offset,
+            option_type,
+            expected,
+            actual,
+        });
         None
     }
 }
 
-pub(crate) fn bytes_to_array<const N: usize>(bytes: Bytes) -> Option<[u8; N]> {
-    ensure_len(&bytes, N)?;
+pub(crate) fn bytes_to_array<const N: usize>(bytes: Bytes, ctx: &ParseContext) -> Option<[u8; N]> {
+    ensure_len(&bytes, N, ctx)?;
     bytes.as_ref().try_into().ok()
 }
 
-pub(crate) fn bytes_to_u64(mut bytes: Bytes, endianness: Endianness) -> Option<u64> {
-    ensure_len(&bytes, 8)?;
-    Some(read_u64(&mut bytes, endianness))
+pub(crate) fn bytes_to_u64(mut bytes: Bytes, ctx: &ParseContext) -> Option<u64> {
+    ensure_len(&bytes, 8, ctx)?;
+    Some(read_u64(&mut bytes, ctx.endianness))
 }
 
-pub(crate) fn bytes_to_u32(mut bytes: Bytes, endianness: Endianness) -> Option<u32> {
-    ensure_len(&bytes, 4)?;
-    Some(read_u32(&mut bytes, endianness))
+pub(crate) fn bytes_to_u32(mut bytes: Bytes, ctx: &ParseContext) -> Option<u32> {
+    ensure_len(&bytes, 4, ctx)?;
+    Some(read_u32(&mut bytes, ctx.endianness))
 }
 
-pub(crate) fn bytes_to_ts(mut bytes: Bytes, endianness: Endianness) -> Option<Timestamp> {
-    ensure_len(&bytes, 8)?;
-    Some(read_ts(&mut bytes, endianness))
+pub(crate) fn bytes_to_ts(mut bytes: Bytes, ctx: &ParseContext) -> Option<Timestamp> {
+    ensure_len(&bytes, 8, ctx)?;
+    Some(read_ts(&mut bytes, ctx.endianness))
 }
@@ -0,0 +1,82 @@
+/// Departures from the spec which Wireshark and dumpcap are known to make
+///
+/// Files written by Wireshark are usually well-formed, but a few of the
+/// things it writes (or passes through from other tools when merging
+/// captures) aren't quite what the spec says.  Wireshark reads them without
+/// complaint, so if you'd like pcarp to do the same, turn on the quirks
+/// you're expecting.  Each one stops pcarp reporting a particular
+/// [`Malformation`](crate::block::Malformation), or logging a particular
+/// warning; the contents of the blocks are the same either way.
+///
+/// By default no quirks are enabled.  [`Quirks::wireshark()`] enables all
+/// of them.
+///
+/// ```
+/// # use pcarp::{iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::block::{BlockType, Endianness, Malformation, Quirks, OPT_CUSTOM_BIN_COPY};
+/// use pcarp::CaptureBuilder;
+///
+/// // An empty custom option, and a name resolution block with no
+/// // nrb_record_end
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .enhanced_packet(0, 0, b"hello", &[(OPT_CUSTOM_BIN_COPY, b"")])
+/// #     .block(4, &[]);
+/// let mut capture = CaptureBuilder::new()
+///     .collect_malformations(true)
+///     .build(pcap.build());
+/// assert_eq!(capture.by_ref().filter(|x| x.is_ok()).count(), 1);
+/// let found = capture.take_malformations();
+/// assert!(matches!(found[0], (BlockType::EnhancedPacket, Malformation::WrongOptionLength { .. })));
+/// assert!(matches!(found[1], (BlockType::NameResolution, Malformation::MissingRecordEnd { .. })));
+///
+/// let mut capture = CaptureBuilder::new()
+///     .collect_malformations(true)
+///     .quirks(Quirks::wireshark())
+///     .build(pcap.build());
+/// let pkt = capture.next().unwrap().unwrap();
+/// assert_eq!(&pkt.data[..], b"hello");
+/// assert!(capture.next().is_none());
+/// assert_eq!(capture.take_malformations(), []);
+///
+/// // Or just the one
+/// let mut quirks = Quirks::default();
+/// quirks.empty_custom_options = true;
+/// let mut capture = CaptureBuilder::new()
+///     .collect_malformations(true)
+///     .quirks(quirks)
+///     .build(pcap.build());
+/// assert_eq!(capture.by_ref().count(), 1);
+/// assert_eq!(capture.take_malformations().len(), 1);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Quirks {
+    /// Accept custom options which are too short to hold a Private
+    /// Enterprise Number, such as the zero-length ones Wireshark can write.
+    /// They're kept in the block's `options`, but not in its
+    /// `custom_options`, since there's nothing to decode.
+    pub empty_custom_options: bool,
+    /// Accept name resolution blocks whose records aren't terminated by an
+    /// `nrb_record_end`.  The records are taken to run to the end of the
+    /// block, with no options after them.
+    pub missing_record_end: bool,
+    /// Skip sysdig blocks without logging a warning.  These are written by
+    /// sysdig and falco, and Wireshark keeps them when merging captures.
+    /// They're still counted in
+    /// [`RecoveryStats::blocks_ignored`](crate::RecoveryStats::blocks_ignored).
+    pub sysdig_blocks: bool,
+}
+
+impl Quirks {
+    /// Accept everything Wireshark is known to write
+    pub fn wireshark() -> Quirks {
+        Quirks {
+            empty_custom_options: true,
+            missing_record_end: true,
+            sysdig_blocks: true,
+        }
+    }
+}
@@ -1,18 +1,108 @@
 use crate::block::frame::*;
 use crate::block::*;
+use crate::sniff::{self, Format};
 use crate::{Error, Result};
 use bytes::{Buf, Bytes, BytesMut};
 use std::io::Read;
 use std::io::{Seek, SeekFrom};
+use std::sync::Arc;
 
 /// An iterator that reads blocks from a pcap
 pub struct BlockReader<R> {
     rdr: R,
     buf: Bytes,
+    /// The size of the allocation behind `buf`, if we made it
+    buf_capacity: usize,
+    /// If the whole pcap is in memory, this is it
+    source: Option<Bytes>,
+    /// The offset of the start of `buf` within the pcap
+    buf_offset: u64,
     /// Whether an unrecoverable error has occurred
     dead: bool,
+    /// Whether we've checked that the file is actually a pcapng
+    format_checked: bool,
     /// Endianness of the current section
     endianness: Endianness,
+    /// Endianness of any blocks before the first section header
+    initial_endianness: Endianness,
+    /// What to do with sections of an unknown version
+    version_policy: VersionPolicy,
+    /// What to do about missing padding
+    padding_policy: PaddingPolicy,
+    /// What to do about problems which can be worked around
+    malformation_policy: MalformationPolicy,
+    /// Which of Wireshark's departures from the spec to accept
+    quirks: Quirks,
+    /// User-supplied block parsers
+    parsers: ParserRegistry,
+    /// Whether to avoid reading beyond the end of the current block
+    low_latency: bool,
+    metadata_only: bool,
+    /// How to skip the rest of a skimmed packet block, if not by reading it
+    skip: Option<fn(&mut R, u64) -> std::io::Result<u64>>,
+    /// Problems found in the blocks read so far, if we're collecting them
+    malformations: Option<Vec<(BlockType, Malformation)>>,
+    /// Where to send the problems as they're found
+    diagnostics: Option<DiagnosticSink>,
+}
+
+/// What to do when a section declares a format version which pcarp doesn't
+/// know about
+///
+/// pcarp understands version 1.0 of the format (and 1.2, which libpcap also
+/// accepts).  This policy decides what to do with other 1.x versions.  A
+/// change to the major version means that the framing has changed (the
+/// drafts for 2.0 propose 64-bit block lengths, for instance), which pcarp
+/// doesn't support yet, so sections with any other major version are always
+/// rejected.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::block::FrameError;
+/// use pcarp::{Capture, Error};
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .enhanced_packet(0, 0, b"hello", &[]);
+/// let mut pcap = pcap.as_bytes().to_vec();
+/// pcap[14] = 5; // the minor version
+/// let pkt = Capture::new(&pcap[..]).next().unwrap().unwrap();
+/// assert_eq!(&pkt.data[..], b"hello");
+///
+/// pcap[12] = 2; // the major version
+/// match Capture::new(&pcap[..]).next().unwrap() {
+///     Err(Error::Frame(FrameError::UnknownVersion(2, 5))) => (),
+///     x => panic!("{x:?}"),
+/// }
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[non_exhaustive]
+pub enum VersionPolicy {
+    /// Log a warning and parse the section as if it were version 1.0.  This
+    /// is the default.
+    #[default]
+    BestEffort,
+    /// Refuse to parse the section.  This is a fatal error.
+    Reject,
+}
+
+/// Whether pcarp knows how to parse a section with the given version
+pub(crate) fn is_known_version(major: u16, minor: u16) -> bool {
+    matches!((major, minor), (1, 0) | (1, 2))
+}
+
+/// Check that we can parse a section with the given header
+pub(crate) fn check_version(shb: &SectionHeader, policy: VersionPolicy) -> Result<(), FrameError> {
+    let (major, minor) = (shb.major_version, shb.minor_version);
+    if is_known_version(major, minor) {
+        return Ok(());
+    }
+    match policy {
+        // The reader reports it as a diagnostic
+        VersionPolicy::BestEffort if major == 1 => Ok(()),
+        _ => Err(FrameError::UnknownVersion(major, minor)),
+    }
 }
 
 impl<R> BlockReader<R> {
@@ -23,22 +113,264 @@ impl<R> BlockReader<R> {
         BlockReader {
             rdr,
             buf: Bytes::new(),
+            buf_capacity: 0,
+            source: None,
+            buf_offset: 0,
             dead: false,
+            format_checked: false,
             endianness: Endianness::Little, // arbitrary
+            initial_endianness: Endianness::Little,
+            version_policy: VersionPolicy::default(),
+            padding_policy: PaddingPolicy::default(),
+            malformation_policy: MalformationPolicy::default(),
+            quirks: Quirks::default(),
+            parsers: ParserRegistry::default(),
+            low_latency: false,
+            metadata_only: false,
+            skip: None,
+            malformations: None,
+            diagnostics: None,
         }
     }
 
+    /// Create a `BlockReader` which reads from an in-memory pcap
+    ///
+    /// The blocks are sliced out of `data` without copying.  The reader is
+    /// only used if `data` ends with an incomplete block.
+    pub fn from_bytes(rdr: R, data: Bytes) -> BlockReader<R> {
+        let mut x = BlockReader::new(rdr);
+        x.buf = data.clone();
+        x.source = Some(data);
+        x
+    }
+
+    /// Set the byte order of any blocks which appear before the first
+    /// section header
+    ///
+    /// This only has an effect if called before reading any blocks.
+    pub fn set_initial_endianness(&mut self, endianness: Endianness) {
+        self.initial_endianness = endianness;
+        if self.buf_offset == 0 {
+            self.endianness = endianness;
+        }
+    }
+
+    /// Set what to do with sections of an unknown version
+    pub fn set_version_policy(&mut self, policy: VersionPolicy) {
+        self.version_policy = policy;
+    }
+
+    /// Set what to do about missing padding
+    pub fn set_padding_policy(&mut self, policy: PaddingPolicy) {
+        self.padding_policy = policy;
+    }
+
+    /// Set what to do about problems which can be worked around
+    pub fn set_malformation_policy(&mut self, policy: MalformationPolicy) {
+        self.malformation_policy = policy;
+    }
+
+    /// Set which of Wireshark's departures from the spec to accept
+    ///
+    /// See [`Quirks`] for details.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Only ask the underlying reader for the bytes needed to complete the
+    /// current block
+    ///
+    /// See [`CaptureBuilder::low_latency()`](crate::CaptureBuilder::low_latency).
+    pub fn set_low_latency(&mut self, low_latency: bool) {
+        self.low_latency = low_latency;
+    }
+
+    /// Don't read the data or options of packet blocks
+    ///
+    /// See [`CaptureBuilder::metadata_only()`](crate::CaptureBuilder::metadata_only).
+    pub fn set_metadata_only(&mut self, metadata_only: bool) {
+        self.metadata_only = metadata_only;
+    }
+
+    /// Keep a list of the problems found in the blocks, for
+    /// [`take_malformations()`](BlockReader::take_malformations)
+    ///
+    /// See [`CaptureBuilder::collect_malformations()`](crate::CaptureBuilder::collect_malformations).
+    pub fn set_collect_malformations(&mut self, collect: bool) {
+        if !collect {
+            self.malformations = None;
+        } else if self.malformations.is_none() {
+            self.malformations = Some(vec![]);
+        }
+    }
+
+    /// Take the problems found in the blocks read since the last call,
+    /// along with the types of the blocks they were found in
+    ///
+    /// This is always empty unless `set_collect_malformations()` was
+    /// called.
+    pub fn take_malformations(&mut self) -> Vec<(BlockType, Malformation)> {
+        self.malformations
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Call a function with each problem found in the blocks, as it's
+    /// found
+    ///
+    /// A `BlockReader` only reports [`Diagnostic::Malformation`]s and
+    /// [`Diagnostic::UnknownVersion`]s.
+    /// See [`CaptureBuilder::diagnostic_sink()`](crate::CaptureBuilder::diagnostic_sink).
+    pub fn set_diagnostic_sink(&mut self, f: impl Fn(Diagnostic) + Send + Sync + 'static) {
+        self.diagnostics = Some(DiagnosticSink::new(f));
+    }
+
+    pub(crate) fn set_diagnostics(&mut self, sink: Option<DiagnosticSink>) {
+        self.diagnostics = sink;
+    }
+
+    /// Log a diagnostic, and pass it on to the sink
+    pub(crate) fn diagnose(&self, x: Diagnostic) {
+        diagnose(self.diagnostics.as_ref(), x);
+    }
+
+    pub(crate) fn record_malformation(&mut self, block_type: BlockType, x: Malformation) {
+        if let Some(sink) = &self.diagnostics {
+            sink.send(Diagnostic::Malformation(block_type, x.clone()));
+        }
+        if let Some(xs) = &mut self.malformations {
+            xs.push((block_type, x));
+        }
+    }
+
+    /// Split the next block off the front of `buf`, if it's all there
+    ///
+    /// This is the framing shared by `try_next_raw()`, which takes blocks
+    /// from our own buffer, and `decode_raw()`, which takes them from the
+    /// caller's.
+    fn take_frame(&mut self, buf: &mut impl Buf) -> Result<Option<(BlockType, Bytes)>> {
+        let section_endianness = self.endianness;
+        match parse_frame(buf.chunk(), &mut self.endianness) {
+            Ok(Some((block_type, data_len))) => {
+                let frame = buf.copy_to_bytes(data_len + 12);
+                self.buf_offset += data_len as u64 + 12;
+                trace!("Saw a complete {block_type:?} block, len {data_len}");
+                if block_type != BlockType::SectionHeader && self.endianness != section_endianness {
+                    self.report_malformation(block_type, Malformation::SwappedByteOrder)
+                        .map_err(|e| Error::Block(block_type, e))?;
+                }
+                Ok(Some((block_type, frame)))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                // Framing errors are unrecoverable
+                self.dead = true;
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Take the next block from the front of `src`, rather than reading it
+    ///
+    /// This is for data which is pushed to us, eg. by a tokio codec.  The
+    /// bytes of an incomplete block are left in `src`.  Pass the returned
+    /// frame to `parse_raw()` to parse it.
+    #[cfg(feature = "tokio-codec")]
+    pub(crate) fn decode_raw(&mut self, src: &mut BytesMut) -> Result<Option<(BlockType, Bytes)>> {
+        if self.dead {
+            src.clear();
+            return Ok(None);
+        }
+        self.take_frame(src)
+    }
+
+    /// Apply the malformation policy to a problem found outside the block
+    /// parsers, recording it unless the block should be rejected
+    pub(crate) fn report_malformation(
+        &mut self,
+        block_type: BlockType,
+        x: Malformation,
+    ) -> std::result::Result<(), BlockError> {
+        if self.malformation_policy.rejects(block_type, &x) {
+            return Err(BlockError::Malformed(x));
+        }
+        self.record_malformation(block_type, x);
+        Ok(())
+    }
+
+    /// Parse blocks of the given type using the given function, instead of
+    /// the built-in parser
+    pub fn register_parser(&mut self, block_type: BlockType, parser: Arc<BlockParser>) {
+        self.parsers.register(block_type, parser);
+    }
+
+    pub(crate) fn set_parsers(&mut self, parsers: ParserRegistry) {
+        self.parsers = parsers;
+    }
+
     /// Rewind to the beginning of the pcapng file
     pub fn rewind(&mut self) -> std::io::Result<()>
     where
         R: Seek,
     {
-        self.rdr.seek(SeekFrom::Start(0))?;
-        self.buf = Bytes::new();
+        self.seek_to(0, self.initial_endianness)
+    }
+
+    /// Seek to the start of a block, whose offset and endianness were
+    /// previously obtained from `position()` and `endianness()`
+    pub(crate) fn seek_to(&mut self, offset: u64, endianness: Endianness) -> std::io::Result<()>
+    where
+        R: Seek,
+    {
+        match &self.source {
+            Some(source) => {
+                let start = usize::try_from(offset).unwrap_or(usize::MAX);
+                self.buf = source.slice(start.min(source.len())..);
+            }
+            None => {
+                self.rdr.seek(SeekFrom::Start(offset))?;
+                self.buf = Bytes::new();
+                self.buf_capacity = 0;
+            }
+        }
+        self.buf_offset = offset;
         self.dead = false;
-        self.endianness = Endianness::Little;
+        self.endianness = endianness;
         Ok(())
     }
+
+    /// The offset within the pcap of the next block to be read
+    pub fn position(&self) -> u64 {
+        self.buf_offset
+    }
+
+    /// The endianness of the current section
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// The number of bytes allocated for the read buffer
+    ///
+    /// When reading from an in-memory pcap, the blocks are sliced out of
+    /// the caller's data, so this is zero.
+    pub fn buffer_capacity(&self) -> usize {
+        self.buf_capacity
+    }
+
+    /// The whole pcap, if it's in memory
+    pub(crate) fn source(&self) -> Option<&Bytes> {
+        self.source.as_ref()
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.rdr
+    }
+
+    #[cfg(feature = "zstd-seekable")]
+    pub(crate) fn into_inner(self) -> R {
+        self.rdr
+    }
 }
 
 impl<R: Read> Iterator for BlockReader<R> {
@@ -49,17 +381,30 @@ impl<R: Read> Iterator for BlockReader<R> {
 }
 
 impl<R: Read> BlockReader<R> {
+    /// Read more data.  `wanted` is the number of bytes needed to make
+    /// progress; in low-latency mode, no more than this is requested.
+    ///
     /// In the event of an IO error, no state is modified.  It should be
     /// safe to just try again.
-    fn fill_buf(&mut self) -> std::io::Result<usize> {
+    fn fill_buf(&mut self, wanted: usize) -> std::io::Result<usize> {
         // This is evil because it relies on R's read() being correctly
         // implemented for safety.
         let n_leftover = self.buf.len();
-        let mut new_buf = BytesMut::zeroed(Self::BUF_CAPACITY + n_leftover);
+        // The block length isn't trusted: the buffer grows as the data
+        // arrives, doubling each time so that big blocks aren't copied too
+        // often
+        let n_wanted = Self::BUF_CAPACITY.max(n_leftover);
+        let n_wanted = if self.low_latency {
+            wanted.min(n_wanted)
+        } else {
+            n_wanted
+        };
+        let mut new_buf = BytesMut::zeroed(n_wanted + n_leftover);
         new_buf[..n_leftover].copy_from_slice(&self.buf);
         let n_read = self.rdr.read(&mut new_buf[n_leftover..])?;
         new_buf.truncate(n_leftover + n_read);
         self.buf = new_buf.freeze();
+        self.buf_capacity = n_wanted + n_leftover;
         Ok(n_read)
     }
 
@@ -84,33 +429,70 @@ impl<R: Read> BlockReader<R> {
     //     Ok(n_read)
     // }
 
+    /// At the start of the file, check that it's a pcapng and not something
+    /// else.  Files which don't start with an SHB are allowed, unless they're
+    /// recognisably in some other format.
+    fn check_format(&mut self) -> Result<()> {
+        if self.format_checked || self.buf_offset != 0 {
+            return Ok(());
+        }
+        while self.buf.len() < sniff::SNIFF_LEN {
+            if self.fill_buf(sniff::SNIFF_LEN - self.buf.len())? == 0 {
+                break;
+            }
+        }
+        match Format::detect(&self.buf) {
+            Format::Pcapng | Format::Unknown => {
+                self.format_checked = true;
+                Ok(())
+            }
+            Format::Pcap => Err(FrameError::LegacyPcap.into()),
+            format => Err(FrameError::UnsupportedFormat(format).into()),
+        }
+    }
+
     /// Get the next block.
     pub(crate) fn try_next(&mut self) -> Result<Option<Block>> {
+        match self.try_next_raw()? {
+            Some((block_type, frame)) => self.parse_raw(block_type, frame).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the next block, without parsing it
+    ///
+    /// The returned bytes are the whole block, including the type and
+    /// length fields.  Pass them to `parse_raw()` to parse the block.
+    pub(crate) fn try_next_raw(&mut self) -> Result<Option<(BlockType, Bytes)>> {
         if self.dead {
             return Ok(None);
         }
+        if let Err(e) = self.check_format() {
+            // Being in the wrong format is unrecoverable
+            if let Error::Frame(_) = e {
+                self.dead = true;
+            }
+            return Err(e);
+        }
         loop {
-            match parse_frame(self.buf.chunk(), &mut self.endianness) {
-                Ok(Some((block_type, data_len))) => {
-                    self.buf.advance(8);
-                    let block_data = self.buf.copy_to_bytes(data_len);
-                    self.buf.advance(4);
-                    trace!("Saw a complete {block_type:?} block, len {data_len}");
-                    match Block::parse(block_type, block_data, self.endianness) {
-                        Ok(block) => {
-                            trace!("Parsed block as {block:?}");
-                            return Ok(Some(block));
+            let mut buf = std::mem::take(&mut self.buf);
+            let frame = self.take_frame(&mut buf);
+            self.buf = buf;
+            match frame? {
+                Some(x) => return Ok(Some(x)),
+                None if self.metadata_only => match self.skim_packet()? {
+                    Skimmed::Packet(block_type, frame) => return Ok(Some((block_type, frame))),
+                    Skimmed::Eof => return Ok(None),
+                    Skimmed::NotApplicable => {
+                        let n_read = self.fill_buf(bytes_needed(&self.buf, self.endianness))?;
+                        debug!("Read {n_read} bytes");
+                        if n_read == 0 {
+                            return Ok(None);
                         }
-                        Err(e) => return Err(Error::Block(block_type, e)),
                     }
-                }
-                Err(e) => {
-                    // Framing errors are unrecoverable
-                    self.dead = true;
-                    return Err(e.into());
-                }
-                Ok(None) => {
-                    let n_read = self.fill_buf()?;
+                },
+                None => {
+                    let n_read = self.fill_buf(bytes_needed(&self.buf, self.endianness))?;
                     debug!("Read {n_read} bytes");
                     if n_read == 0 {
                         return Ok(None);
@@ -121,4 +503,220 @@ impl<R: Read> BlockReader<R> {
             }
         }
     }
+
+    /// In metadata-only mode, get the header of the packet block at the
+    /// front of the buffer, and skip the rest of it without buffering it
+    ///
+    /// This is only called when the buffer doesn't contain the whole block.
+    /// The returned frame is cut short: it's just the header, followed by
+    /// the block's trailer.
+    fn skim_packet(&mut self) -> Result<Skimmed> {
+        let buf = self.buf.chunk();
+        if buf.len() < 12 {
+            return Ok(Skimmed::NotApplicable);
+        }
+        let endianness = block_endianness(buf, self.endianness);
+        let read_u32 = |i: usize| match endianness {
+            Endianness::Big => (&buf[i..i + 4]).get_u32(),
+            Endianness::Little => (&buf[i..i + 4]).get_u32_le(),
+        };
+        let block_type = BlockType::from(read_u32(0));
+        let block_len = read_u32(4) as usize;
+        let header_len = match block_type {
+            BlockType::EnhancedPacket | BlockType::ObsoletePacket => 28,
+            BlockType::SimplePacket => 12,
+            _ => return Ok(Skimmed::NotApplicable),
+        };
+        if buf.len() < header_len || block_len < header_len + 4 {
+            // Let the framing layer deal with it
+            return Ok(Skimmed::NotApplicable);
+        }
+        let mut frame = BytesMut::with_capacity(header_len + 4);
+        frame.extend_from_slice(&buf[..header_len]);
+        frame.extend_from_slice(&buf[4..8]); // the trailer repeats the length
+        let to_skip = (block_len - buf.len()) as u64;
+        let skipped = match self.skip {
+            Some(skip) => skip(&mut self.rdr, to_skip)?,
+            None => std::io::copy(&mut (&mut self.rdr).take(to_skip), &mut std::io::sink())?,
+        };
+        if skipped < to_skip {
+            debug!("The pcap ends part-way through a packet block");
+            self.dead = true;
+            return Ok(Skimmed::Eof);
+        }
+        trace!("Skimmed a {block_type:?} block, len {block_len}");
+        self.buf = Bytes::new();
+        self.buf_capacity = 0;
+        self.buf_offset += block_len as u64;
+        if block_type != BlockType::SectionHeader && endianness != self.endianness {
+            self.endianness = endianness;
+            self.report_malformation(block_type, Malformation::SwappedByteOrder)
+                .map_err(|e| Error::Block(block_type, e))?;
+        }
+        Ok(Skimmed::Packet(block_type, frame.freeze()))
+    }
+
+    /// Get the next block without parsing it, and without moving past it
+    ///
+    /// Framing errors are returned, but don't kill the reader: that
+    /// happens when `try_next()` hits the same error.
+    pub(crate) fn peek_raw(&mut self) -> Result<Option<(BlockType, Bytes)>> {
+        if self.dead {
+            return Ok(None);
+        }
+        self.check_format()?;
+        loop {
+            let mut endianness = self.endianness;
+            match parse_frame(self.buf.chunk(), &mut endianness)? {
+                Some((block_type, data_len)) => {
+                    return Ok(Some((block_type, self.buf.slice(..data_len + 12))));
+                }
+                None => {
+                    let n_read = self.fill_buf(bytes_needed(&self.buf, endianness))?;
+                    debug!("Read {n_read} bytes");
+                    if n_read == 0 {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parse a block which was returned by `try_next_raw()`
+    pub(crate) fn parse_raw(&mut self, block_type: BlockType, frame: Bytes) -> Result<Block> {
+        let (parsed, malformations) = self.parse_with(block_type, &frame, self.endianness);
+        for x in malformations {
+            self.record_malformation(block_type, x);
+        }
+        match parsed {
+            Ok(block) => {
+                trace!("Parsed block as {block:?}");
+                if let Block::SectionHeader(shb) = &block {
+                    if let Err(e) = check_version(shb, self.version_policy) {
+                        self.dead = true;
+                        return Err(e.into());
+                    }
+                    let (major, minor) = (shb.major_version, shb.minor_version);
+                    if !is_known_version(major, minor) {
+                        self.diagnose(Diagnostic::UnknownVersion { major, minor });
+                    }
+                }
+                Ok(block)
+            }
+            Err(e) => Err(Error::Block(block_type, e)),
+        }
+    }
+
+    /// Parse a complete frame in the given byte order, returning the
+    /// problems found along with the block
+    fn parse_with(
+        &self,
+        block_type: BlockType,
+        frame: &Bytes,
+        endianness: Endianness,
+    ) -> (std::result::Result<Block, BlockError>, Vec<Malformation>) {
+        let block_data = frame.slice(8..frame.len() - 4);
+        let code = type_code(frame, endianness);
+        let mut ctx = ParseContext::new(endianness).with_type_code(code);
+        ctx.padding_policy = self.padding_policy;
+        ctx.malformation_policy = self.malformation_policy.clone();
+        ctx.quirks = self.quirks;
+        ctx.skip_data = self.metadata_only;
+        if self.metadata_only {
+            let block_len = block_len(frame, endianness) as usize;
+            ctx.skimmed_len = block_len.saturating_sub(frame.len());
+        }
+        let parsed = self.parsers.parse(block_type, block_data, &ctx);
+        (parsed, ctx.take_malformations())
+    }
+
+    /// In metadata-only mode, skip over packet blocks by seeking past them,
+    /// rather than by reading them
+    ///
+    /// This saves reading the data of large packets, which makes a pass
+    /// over a capture on disk much cheaper when most of it is packet data.
+    /// Blocks which fit in the read buffer are still read.
+    pub fn set_skip_by_seeking(&mut self, seek: bool)
+    where
+        R: Seek,
+    {
+        self.skip = if seek { Some(seek_forward::<R>) } else { None };
+    }
+
+    /// Read and parse the block at the given offset, without moving the
+    /// reader's position
+    ///
+    /// The block is read in the byte order of the current section, unless
+    /// it's obviously in the other one (or it's a section header, which
+    /// says which order it's in).  Problems found in the block aren't
+    /// recorded, and framing errors don't kill the reader.
+    pub fn read_block_at(&mut self, offset: u64) -> Result<Block>
+    where
+        R: Seek,
+    {
+        let mut endianness = self.endianness;
+        let frame = match &self.source {
+            Some(source) => {
+                let start = usize::try_from(offset).unwrap_or(usize::MAX);
+                source.slice(start.min(source.len())..)
+            }
+            None => {
+                let pos = self.rdr.stream_position()?;
+                let frame = read_frame_at(&mut self.rdr, offset, &mut endianness);
+                self.rdr.seek(SeekFrom::Start(pos))?;
+                frame?
+            }
+        };
+        let (block_type, data_len) = match parse_frame(&frame, &mut endianness)? {
+            Some(x) => x,
+            None => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+        };
+        let frame = frame.slice(..data_len + 12);
+        trace!("Read a {block_type:?} block at offset {offset}");
+        let (parsed, _) = self.parse_with(block_type, &frame, endianness);
+        parsed.map_err(|e| Error::Block(block_type, e))
+    }
+}
+
+/// Read the frame which starts at `offset`
+///
+/// `endianness` is updated as `parse_frame()` would.  If the stream ends
+/// before the end of the frame, an `UnexpectedEof` error is returned.
+fn read_frame_at<R: Read + Seek>(
+    rdr: &mut R,
+    offset: u64,
+    endianness: &mut Endianness,
+) -> Result<Bytes> {
+    rdr.seek(SeekFrom::Start(offset))?;
+    let mut frame = vec![];
+    while parse_frame(&frame, endianness)?.is_none() {
+        let needed = bytes_needed(&frame, *endianness) as u64;
+        // The buffer grows as the data arrives, so a bogus length in a
+        // corrupt file can't make us allocate gigabytes up front
+        let n_read = rdr.by_ref().take(needed).read_to_end(&mut frame)?;
+        if (n_read as u64) < needed {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+    }
+    Ok(Bytes::from(frame))
+}
+
+/// The outcome of `BlockReader::skim_packet()`
+enum Skimmed {
+    /// A packet block's header (and trailer)
+    Packet(BlockType, Bytes),
+    /// The file ended part-way through the block
+    Eof,
+    /// The block at the front of the buffer can't be skimmed
+    NotApplicable,
+}
+
+/// Skip over `n` bytes by seeking, returning the number of bytes skipped.
+/// This is fewer than `n` if the end of the stream was reached.
+fn seek_forward<R: Seek>(rdr: &mut R, n: u64) -> std::io::Result<u64> {
+    let pos = rdr.stream_position()?;
+    let end = rdr.seek(SeekFrom::End(0))?.max(pos);
+    let target = pos.saturating_add(n).min(end);
+    rdr.seek(SeekFrom::Start(target))?;
+    Ok(target - pos)
 }
@@ -0,0 +1,81 @@
+use crate::block::*;
+use bytes::Bytes;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A function which parses the body of a block
+///
+/// It's given the block's body (without the type and length fields), and
+/// the context in which to parse it (eg. the endianness of the current
+/// section).
+pub type BlockParser = dyn Fn(Bytes, &ParseContext) -> Result<Block, BlockError> + Send + Sync;
+
+/// Parsers which override the built-in ones, keyed by block type
+#[derive(Clone, Default)]
+pub(crate) struct ParserRegistry {
+    parsers: HashMap<BlockType, Arc<BlockParser>>,
+}
+
+impl fmt::Debug for ParserRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.parsers.keys()).finish()
+    }
+}
+
+impl ParserRegistry {
+    pub(crate) fn register(&mut self, block_type: BlockType, parser: Arc<BlockParser>) {
+        self.parsers.insert(block_type, parser);
+    }
+
+    pub(crate) fn parse(
+        &self,
+        block_type: BlockType,
+        block_data: Bytes,
+        ctx: &ParseContext,
+    ) -> Result<Block, BlockError> {
+        let parsed = match self.parsers.get(&block_type) {
+            Some(parser) => parser(block_data, ctx),
+            None => Block::parse(block_type, block_data, ctx),
+        };
+        match ctx.take_rejection() {
+            Some(x) => parsed.and(Err(BlockError::Malformed(x))),
+            None => parsed,
+        }
+    }
+}
+
+/// A block parsed by a user-supplied [`BlockParser`]
+///
+/// This can hold any type.  Use [`downcast_ref()`](Self::downcast_ref) to
+/// get it back out.
+#[derive(Clone)]
+pub struct Extension(Arc<dyn Any + Send + Sync>);
+
+impl Extension {
+    /// Wrap a value of any type
+    pub fn new<T: Any + Send + Sync>(x: T) -> Extension {
+        Extension(Arc::new(x))
+    }
+
+    /// Get the contents, if they're of type `T`
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.downcast_ref()
+    }
+}
+
+impl fmt::Debug for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Extension(..)")
+    }
+}
+
+/// Extensions are only equal if they're clones of each other
+impl PartialEq for Extension {
+    fn eq(&self, other: &Extension) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Extension {}
@@ -1,7 +1,7 @@
 use crate::block::opts::*;
 use crate::block::util::*;
-use bytes::Buf;
-use tracing::*;
+use crate::block::{CustomOption, Malformation, StrBytes};
+use bytes::{Buf, Bytes};
 
 /// Defines the most important characteristics of the capture file.
 ///
@@ -15,6 +15,8 @@ use tracing::*;
 ///
 /// [1]: https://github.com/pcapng/pcapng
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct SectionHeader {
     /// Used to distinguish sections that have been saved on little-endian machines from the ones
     /// saved on big-endian machines.
@@ -43,19 +45,27 @@ pub struct SectionHeader {
     /// The shb_hardware option is a UTF-8 string containing the description
     /// of the hardware used to create this section. The string is not
     /// zero-terminated.
-    pub shb_hardware: String,
+    pub shb_hardware: StrBytes,
     /// The shb_os option is a UTF-8 string containing the name of the
     /// operating system used to create this section. The string is not
     /// zero-terminated.
-    pub shb_os: String,
+    pub shb_os: StrBytes,
     /// The shb_userappl option is a UTF-8 string containing the name of
     /// the application used to create this section. The string is not
     /// zero-terminated.
-    pub shb_userappl: String,
+    pub shb_userappl: StrBytes,
+    /// Any options which pcarp doesn't understand, as `(option_type, value)`
+    /// pairs.  These may be vendor extensions, or simply options which were
+    /// defined after this version of pcarp was written.
+    pub options: Vec<(u16, Bytes)>,
+    /// The custom options, decoded.  These also appear in `options`.
+    pub custom_options: Vec<CustomOption>,
 }
 
 impl FromBytes for SectionHeader {
-    fn parse<T: Buf>(mut buf: T, endianness: Endianness) -> Result<SectionHeader, BlockError> {
+    fn parse<T: Buf>(mut buf: T, ctx: &ParseContext) -> Result<SectionHeader, BlockError> {
+        ctx.begin_body(buf.remaining());
+        let endianness = ctx.endianness;
         ensure_remaining!(buf, 12);
         buf.advance(4); // the endianness - we've already parsed it
         let major_version = read_u16(&mut buf, endianness);
@@ -65,20 +75,32 @@ impl FromBytes for SectionHeader {
             x => match u64::try_from(x) {
                 Ok(x) => Some(x),
                 Err(_) => {
-                    warn!("SHB lists the length as {x}, but this is invalid");
+                    ctx.report(Malformation::InvalidSectionLength {
+                        offset: 8,
+                        value: x,
+                    });
                     None
                 }
             },
         };
-        let mut shb_hardware = String::new();
-        let mut shb_os = String::new();
-        let mut shb_userappl = String::new();
-        parse_options(buf, endianness, |option_type, option_bytes| {
+        let mut shb_hardware = StrBytes::default();
+        let mut shb_os = StrBytes::default();
+        let mut shb_userappl = StrBytes::default();
+        let mut options = vec![];
+        let mut custom_options = vec![];
+        parse_options(buf, ctx, |option_type, option_bytes| {
             match option_type {
-                2 => shb_hardware = String::from_utf8_lossy(&option_bytes).to_string(),
-                3 => shb_os = String::from_utf8_lossy(&option_bytes).to_string(),
-                4 => shb_userappl = String::from_utf8_lossy(&option_bytes).to_string(),
-                _ => (), // Ignore unknown
+                2 => shb_hardware = bytes_to_string(option_bytes),
+                3 => shb_os = bytes_to_string(option_bytes),
+                4 => shb_userappl = bytes_to_string(option_bytes),
+                // Keep unknown options
+                _ => keep_option(
+                    &mut options,
+                    &mut custom_options,
+                    option_type,
+                    option_bytes,
+                    ctx,
+                ),
             }
         });
         Ok(SectionHeader {
@@ -89,6 +111,8 @@ impl FromBytes for SectionHeader {
             shb_hardware,
             shb_os,
             shb_userappl,
+            options,
+            custom_options,
         })
     }
 }
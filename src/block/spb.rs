@@ -26,6 +26,8 @@ use bytes::{Buf, Bytes};
 ///
 /// [1]: https://github.com/pcapng/pcapng
 #[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct SimplePacket {
     /// Actual length of the packet when it was transmitted on the network. It can be different
     /// from length of the Packet Data field's length if the packet has been truncated by the
@@ -44,12 +46,20 @@ pub struct SimplePacket {
 }
 
 impl FromBytes for SimplePacket {
-    fn parse<T: Buf>(mut buf: T, endianness: Endianness) -> Result<SimplePacket, BlockError> {
+    fn parse<T: Buf>(mut buf: T, ctx: &ParseContext) -> Result<SimplePacket, BlockError> {
+        ctx.begin_body(buf.remaining());
+        let endianness = ctx.endianness;
         ensure_remaining!(buf, 4);
         let packet_len = read_u32(&mut buf, endianness);
+        let packet_data = if ctx.skip_data {
+            check_bytes(&buf, packet_len, ctx)?;
+            Bytes::new()
+        } else {
+            read_bytes(&mut buf, packet_len, ctx)?
+        };
         Ok(SimplePacket {
             packet_len,
-            packet_data: read_bytes(&mut buf, packet_len)?,
+            packet_data,
         })
     }
 }
@@ -0,0 +1,149 @@
+use bytes::Bytes;
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+/// A string which shares its storage with the block it was read from
+///
+/// String options (interface names, OS descriptions, etc.) are read without
+/// copying them out of the block.  A `StrBytes` derefs to `str`, so it can
+/// mostly be used like one; cloning it is cheap.
+///
+/// The spec says these options are UTF-8.  If one isn't, any invalid
+/// sequences are replaced with U+FFFD, as with `String::from_utf8_lossy()`;
+/// only in that case is the string copied.
+///
+/// ```
+/// # use pcarp::{iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::block::{Block, BlockReader, Endianness};
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0"), (12, b"Linux \xFF")]);
+/// # let pcap = pcap.as_bytes();
+/// let mut blocks = BlockReader::new(pcap);
+/// blocks.next(); // the section header
+/// let idb = match blocks.next().unwrap().unwrap() {
+///     Block::InterfaceDescription(x) => x,
+///     _ => unreachable!(),
+/// };
+/// assert_eq!(idb.if_name, "eth0");
+/// assert_eq!(idb.if_os, "Linux \u{FFFD}");
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StrBytes(Bytes);
+
+impl StrBytes {
+    /// Wrap some bytes, replacing any invalid UTF-8
+    pub(crate) fn from_utf8_lossy(bytes: Bytes) -> StrBytes {
+        match std::str::from_utf8(&bytes) {
+            Ok(_) => StrBytes(bytes),
+            Err(_) => StrBytes::from(String::from_utf8_lossy(&bytes).into_owned()),
+        }
+    }
+
+    /// A `StrBytes` which points to a static string
+    pub const fn from_static(x: &'static str) -> StrBytes {
+        StrBytes(Bytes::from_static(x.as_bytes()))
+    }
+
+    /// Borrow the string
+    pub fn as_str(&self) -> &str {
+        // SAFETY: Every constructor checks that the bytes are UTF-8
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+
+    /// The underlying bytes, which are guaranteed to be UTF-8
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+}
+
+impl Deref for StrBytes {
+    type Target = str;
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for StrBytes {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for StrBytes {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// Must agree with `str`'s impl, since we implement `Borrow<str>`
+impl Hash for StrBytes {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl From<String> for StrBytes {
+    fn from(x: String) -> StrBytes {
+        StrBytes(Bytes::from(x))
+    }
+}
+
+impl From<&str> for StrBytes {
+    fn from(x: &str) -> StrBytes {
+        StrBytes(Bytes::copy_from_slice(x.as_bytes()))
+    }
+}
+
+impl From<StrBytes> for String {
+    fn from(x: StrBytes) -> String {
+        x.as_str().to_owned()
+    }
+}
+
+impl PartialEq<str> for StrBytes {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for StrBytes {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for StrBytes {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl fmt::Display for StrBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for StrBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for StrBytes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for StrBytes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<StrBytes, D::Error> {
+        String::deserialize(deserializer).map(StrBytes::from)
+    }
+}
@@ -1,21 +1,208 @@
+use crate::block::{BlockType, Malformation, MalformationPolicy, Quirks};
 use bytes::*;
+use std::cell::{Cell, RefCell};
+use std::io::{self, Read};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Clone, PartialEq, Eq, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Endianness {
     Big,
     Little,
 }
 
-pub(crate) trait FromBytes: Sized {
-    fn parse<T: Buf>(buf: T, endianness: Endianness) -> Result<Self, BlockError>;
+impl Endianness {
+    /// The byte order of the machine pcarp is running on
+    pub fn native() -> Endianness {
+        if cfg!(target_endian = "big") {
+            Endianness::Big
+        } else {
+            Endianness::Little
+        }
+    }
+}
+
+/// What to do when a field isn't followed by the padding which should align
+/// the next field to 32 bits
+///
+/// Some buggy exporters forget to pad the packet data or option values.
+/// Since every field after the missing padding is misaligned, this
+/// normally leads to the block being reported as truncated, or to its
+/// options being garbled.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[non_exhaustive]
+pub enum PaddingPolicy {
+    /// Expect the padding required by the spec.  This is the default.
+    #[default]
+    Strict,
+    /// If the length of the rest of the block shows that the padding is
+    /// missing, log a warning and carry on without it.
+    ///
+    /// This relies on the fields after the missing padding being correctly
+    /// padded.  If several fields in a block lack padding, some of them
+    /// may still be misparsed.
+    Tolerant,
+}
+
+/// Everything a block parser needs to know, besides the block's contents
+///
+/// The built-in parsers also use it to report any [`Malformation`]s they
+/// find; see [`take_malformations()`](ParseContext::take_malformations).
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ParseContext {
+    /// The endianness of the current section
+    pub endianness: Endianness,
+    /// What to do about missing padding
+    pub padding_policy: PaddingPolicy,
+    /// What to do about the problems the parsers find
+    pub malformation_policy: MalformationPolicy,
+    /// Which of Wireshark's departures from the spec to accept
+    pub quirks: Quirks,
+    /// The length of the body of the block being parsed
+    body_len: Cell<usize>,
+    /// The offset and type of the option being parsed
+    pub(crate) current_option: Cell<(usize, u16)>,
+    malformations: RefCell<Vec<Malformation>>,
+    /// The first problem which the policy said to reject the block for
+    rejection: RefCell<Option<Malformation>>,
+    type_code: u32,
+    /// Don't read the data or options of packet blocks (only their
+    /// headers might be present)
+    pub(crate) skip_data: bool,
+    /// The number of bytes at the end of the body which were skipped over,
+    /// rather than read, when `skip_data` is set
+    pub(crate) skimmed_len: usize,
+}
+
+impl ParseContext {
+    /// A context with the given endianness and default settings
+    pub fn new(endianness: Endianness) -> ParseContext {
+        ParseContext {
+            endianness,
+            padding_policy: PaddingPolicy::default(),
+            malformation_policy: MalformationPolicy::default(),
+            quirks: Quirks::default(),
+            body_len: Cell::new(0),
+            current_option: Cell::new((0, 0)),
+            malformations: RefCell::new(vec![]),
+            rejection: RefCell::new(None),
+            type_code: 0,
+            skip_data: false,
+            skimmed_len: 0,
+        }
+    }
+
+    /// Set the code of the block being parsed
+    pub(crate) fn with_type_code(mut self, code: u32) -> ParseContext {
+        self.type_code = code;
+        self
+    }
+
+    /// The numeric type of the block being parsed, as it appears in the
+    /// file
+    ///
+    /// Several codes can map to the same [`BlockType`] (eg. the sysdig
+    /// blocks, 0x0201 to 0x0222), so this lets a
+    /// [`BlockParser`](crate::block::BlockParser) tell them apart.  It's
+    /// zero if the context wasn't created by pcarp.  Blocks which pcarp
+    /// doesn't parse carry their code with them:
+    ///
+    /// ```
+    /// # use pcarp::{iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::{Block, BlockReader, BlockType, Endianness};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Big).block(0x0205, &[0; 8]);
+    /// # let pcap = pcap.as_bytes();
+    /// let mut blocks = BlockReader::new(pcap);
+    /// blocks.next(); // the section header
+    /// match blocks.next().unwrap().unwrap() {
+    ///     Block::Unparsed { ty, code, .. } => {
+    ///         assert_eq!(ty, BlockType::Sysdig);
+    ///         assert_eq!(code, 0x0205);
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn type_code(&self) -> u32 {
+        self.type_code
+    }
+
+    /// The problems found by the parsers which have used this context
+    ///
+    /// Problems which prevented a block from being parsed at all aren't
+    /// included: they're returned as errors instead.  Neither are the ones
+    /// which the [`MalformationPolicy`] rejected.
+    pub fn take_malformations(&self) -> Vec<Malformation> {
+        self.malformations.take()
+    }
+
+    /// Called by each parser before it starts, so that offsets can be
+    /// worked out from the number of bytes remaining
+    pub(crate) fn begin_body(&self, len: usize) {
+        self.body_len.set(len);
+    }
+
+    /// The offset of the position with `remaining` bytes left
+    pub(crate) fn offset(&self, remaining: usize) -> usize {
+        self.body_len.get().saturating_sub(remaining)
+    }
+
+    pub(crate) fn report(&self, malformation: Malformation) {
+        let block_type = BlockType::from(self.type_code);
+        if self.malformation_policy.rejects(block_type, &malformation) {
+            self.rejection.borrow_mut().get_or_insert(malformation);
+        } else {
+            self.malformations.borrow_mut().push(malformation);
+        }
+    }
+
+    /// The problem which the policy said to reject the block for, if any
+    pub(crate) fn take_rejection(&self) -> Option<Malformation> {
+        self.rejection.take()
+    }
+}
+
+/// Types which can be parsed from the body of a block
+///
+/// This is implemented by all the built-in block types.  It's useful when
+/// writing a [`BlockParser`](crate::block::BlockParser) which wraps one of
+/// the built-in parsers.
+pub trait FromBytes: Sized {
+    fn parse<T: Buf>(buf: T, ctx: &ParseContext) -> Result<Self, BlockError>;
+
+    /// Parse using the default settings for the given endianness
+    ///
+    /// This is how `parse()` was called before it took a [`ParseContext`].
+    ///
+    /// ```
+    /// use pcarp::block::{Endianness, EnhancedPacket, FromBytes};
+    ///
+    /// let mut body = vec![];
+    /// for x in [0_u32, 0, 1_000, 4, 4] {
+    ///     body.extend_from_slice(&x.to_le_bytes());
+    /// }
+    /// body.extend_from_slice(b"abcd");
+    /// let epb = EnhancedPacket::parse_with_endianness(&body[..], Endianness::Little).unwrap();
+    /// assert_eq!(&epb.packet_data[..], b"abcd");
+    /// ```
+    fn parse_with_endianness<T: Buf>(buf: T, endianness: Endianness) -> Result<Self, BlockError> {
+        Self::parse(buf, &ParseContext::new(endianness))
+    }
 }
 
 /// A block is corrupt.  We can continue parsing further blocks
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum BlockError {
     #[error("Not enough bytes")]
     TruncatedBlock,
+    #[error("Timestamp is too large to represent")]
+    TimestampOverflow,
+    #[error("{0}")]
+    Malformed(Malformation),
 }
 
 macro_rules! ensure_remaining {
@@ -55,11 +242,93 @@ pub(crate) fn read_u64<T: Buf>(buf: &mut T, endianness: Endianness) -> u64 {
     }
 }
 
-pub(crate) fn read_bytes<T: Buf>(buf: &mut T, len: u32) -> Result<Bytes, BlockError> {
+/// Convert a length field to a `usize`, along with the length of the
+/// padding which follows it
+///
+/// Lengths taken from the file can be anything, so they mustn't be used in
+/// unchecked arithmetic: on 32-bit targets, adding the padding to a length
+/// near `u32::MAX` would overflow.  Returns `None` if the padded length
+/// doesn't fit in a `usize`.
+pub(crate) fn padded_len(len: u32) -> Option<(usize, usize)> {
+    let len = usize::try_from(len).ok()?;
     let padding = (4 - len % 4) % 4;
-    ensure_remaining!(buf, len as usize + padding as usize);
-    let bytes = buf.copy_to_bytes(len as usize);
-    buf.advance(padding as usize);
+    len.checked_add(padding)?;
+    Some((len, padding))
+}
+
+/// Read exactly `len` bytes
+///
+/// Unlike `read_exact()` into a zeroed buffer, the memory is only allocated
+/// as the data arrives, so a bogus length in a corrupt file can't make us
+/// allocate gigabytes up front.
+pub(crate) fn read_vec(rdr: &mut impl Read, len: u64) -> io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    rdr.take(len).read_to_end(&mut buf)?;
+    if (buf.len() as u64) < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+/// Check that a field of `len` bytes fits in the rest of the block,
+/// without reading it
+///
+/// In metadata-only mode, packet blocks may have been cut short; the bytes
+/// which were skipped over count as part of the block.
+pub(crate) fn check_bytes<T: Buf>(buf: &T, len: u32, ctx: &ParseContext) -> Result<(), BlockError> {
+    let offset = ctx.offset(buf.remaining());
+    check_field(len, buf.remaining() + ctx.skimmed_len, offset, ctx).map(|_| ())
+}
+
+/// The length and padding of a field of `len` bytes at `offset`, or an
+/// error if it doesn't fit in the `available` bytes
+fn check_field(
+    len: u32,
+    available: usize,
+    offset: usize,
+    ctx: &ParseContext,
+) -> Result<(usize, usize), BlockError> {
+    let overrun = |len| {
+        BlockError::Malformed(Malformation::FieldOverrun {
+            offset,
+            len,
+            available,
+        })
+    };
+    let (len, padding) = padded_len(len).ok_or_else(|| overrun(usize::MAX))?;
+    let needed = match ctx.padding_policy {
+        PaddingPolicy::Strict => len + padding,
+        PaddingPolicy::Tolerant => len,
+    };
+    if available < needed {
+        return Err(overrun(needed));
+    }
+    Ok((len, padding))
+}
+
+/// Read a field of the given length, followed by padding to 32 bits
+pub(crate) fn read_bytes<T: Buf>(
+    buf: &mut T,
+    len: u32,
+    ctx: &ParseContext,
+) -> Result<Bytes, BlockError> {
+    let (len, padding) = check_field(len, buf.remaining(), ctx.offset(buf.remaining()), ctx)?;
+    let bytes = buf.copy_to_bytes(len);
+    // If everything after this field is correctly padded, the rest of the
+    // block will be a multiple of 4 bytes long once we've skipped our own
+    // padding
+    let rest = buf.remaining();
+    if ctx.padding_policy == PaddingPolicy::Tolerant
+        && padding != 0
+        && (rest < padding || (rest - padding) % 4 != 0)
+    {
+        ctx.report(Malformation::MissingPadding {
+            offset: ctx.offset(rest),
+            len,
+        });
+    } else {
+        buf.advance(padding);
+    }
     Ok(bytes)
 }
 
@@ -68,7 +337,31 @@ pub(crate) fn read_bytes<T: Buf>(buf: &mut T, len: u32) -> Result<Bytes, BlockEr
 /// The meaning of "unit" is defined by the if_tsresol option in the relevant
 /// interface definition block.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Timestamp(pub u64);
+
+impl Timestamp {
+    /// The time since the epoch, given the number of units per second
+    ///
+    /// This is exact (to the nanosecond, rounding down), and doesn't involve
+    /// `SystemTime`, so it's suitable for targets which have no clock.
+    /// Panics if `units_per_sec` is zero.
+    ///
+    /// ```
+    /// # use pcarp::block::Timestamp;
+    /// # use std::time::Duration;
+    /// let ts = Timestamp(1_500_000_000_123_456);
+    /// let expected = Duration::new(1_500_000_000, 123_456_000);
+    /// assert_eq!(ts.to_duration(1_000_000), expected);
+    /// ```
+    pub fn to_duration(self, units_per_sec: u64) -> Duration {
+        let secs = self.0 / units_per_sec;
+        let units = self.0 % units_per_sec;
+        let nanos = u128::from(units) * 1_000_000_000 / u128::from(units_per_sec);
+        Duration::new(secs, nanos as u32)
+    }
+}
+
 pub(crate) fn read_ts<T: Buf>(buf: &mut T, endianness: Endianness) -> Timestamp {
     let hi = read_u32(buf, endianness);
     let lo = read_u32(buf, endianness);
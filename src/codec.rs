@@ -0,0 +1,280 @@
+/*! Decoding pcapng streams with tokio
+
+Some capture services stream pcapng over the network, rather than writing
+it to a file.  [`PcapNgDecoder`] and [`PacketDecoder`] implement
+[`tokio_util::codec::Decoder`], so they can be used with a
+[`FramedRead`](tokio_util::codec::FramedRead) to turn such a stream into a
+`Stream` of blocks or packets.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use bytes::BytesMut;
+use pcarp::codec::PacketDecoder;
+use tokio_util::codec::Decoder;
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 0, b"hello", &[]);
+# let pcap = pcap.as_bytes();
+let mut decoder = PacketDecoder::new();
+// The data arrives in dribs and drabs
+let mut buf = BytesMut::from(&pcap[..50]);
+assert!(decoder.decode(&mut buf).unwrap().is_none());
+buf.extend_from_slice(&pcap[50..]);
+let pkt = decoder.decode(&mut buf).unwrap().unwrap().unwrap();
+assert_eq!(&pkt.data[..], b"hello");
+```
+
+A `FramedRead` stops at the first error returned by its decoder, but most
+errors in a pcapng only affect a single block.  For this reason, the
+decoders' `Error` type is only used for fatal errors: a corrupt block is
+returned as an item, in the form of an `Err(Error::Block(..))`, and the
+stream carries on.
+*/
+
+use crate::block::{
+    Block, BlockParser, BlockReader, BlockType, Diagnostic, Malformation, MalformationPolicy,
+    PaddingPolicy, Quirks, VersionPolicy,
+};
+use crate::iface::{InterfaceId, InterfaceInfo};
+use crate::{Capture, CaptureBuilder, Counters, Error, InMemory, Packet, RecoveryStats, Result};
+use bytes::{Bytes, BytesMut};
+use std::sync::Arc;
+use tokio_util::codec::Decoder;
+
+/// Decodes a pcapng stream into blocks
+///
+/// The blocks are framed and parsed just as a [`BlockReader`] would do it,
+/// with the same policies.
+pub struct PcapNgDecoder {
+    /// Never reads anything itself: the data is passed to `decode()`
+    inner: BlockReader<InMemory>,
+}
+
+impl std::fmt::Debug for PcapNgDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("PcapNgDecoder")
+            .field("position", &self.inner.position())
+            .field("endianness", &self.inner.endianness())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for PcapNgDecoder {
+    fn default() -> Self {
+        PcapNgDecoder::new()
+    }
+}
+
+impl PcapNgDecoder {
+    /// Create a new `PcapNgDecoder`
+    pub fn new() -> PcapNgDecoder {
+        PcapNgDecoder {
+            inner: BlockReader::from_bytes(InMemory(()), Bytes::new()),
+        }
+    }
+
+    /// Set what to do with sections of an unknown version
+    pub fn set_version_policy(&mut self, policy: VersionPolicy) {
+        self.inner.set_version_policy(policy);
+    }
+
+    /// Set what to do about missing padding
+    pub fn set_padding_policy(&mut self, policy: PaddingPolicy) {
+        self.inner.set_padding_policy(policy);
+    }
+
+    /// Set what to do about problems which can be worked around
+    ///
+    /// See [`CaptureBuilder::malformation_policy()`].
+    pub fn set_malformation_policy(&mut self, policy: MalformationPolicy) {
+        self.inner.set_malformation_policy(policy);
+    }
+
+    /// Set which of Wireshark's departures from the spec to accept
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.inner.set_quirks(quirks);
+    }
+
+    /// Parse blocks of the given type using the given function, instead of
+    /// the built-in parser
+    pub fn register_parser(&mut self, block_type: BlockType, parser: Arc<BlockParser>) {
+        self.inner.register_parser(block_type, parser);
+    }
+
+    /// Keep a list of the problems found in the blocks
+    ///
+    /// See [`BlockReader::set_collect_malformations()`].
+    pub fn set_collect_malformations(&mut self, collect: bool) {
+        self.inner.set_collect_malformations(collect);
+    }
+
+    /// Take the problems found in the blocks decoded since the last call
+    pub fn take_malformations(&mut self) -> Vec<(BlockType, Malformation)> {
+        self.inner.take_malformations()
+    }
+
+    /// Call a function with each problem found in the blocks, as it's
+    /// found
+    ///
+    /// See [`BlockReader::set_diagnostic_sink()`].  The decoder also
+    /// reports a [`Diagnostic::IncompleteBlock`] if the stream ends
+    /// part-way through a block.
+    pub fn set_diagnostic_sink(&mut self, f: impl Fn(Diagnostic) + Send + Sync + 'static) {
+        self.inner.set_diagnostic_sink(f);
+    }
+}
+
+/// Decode the next block, returning its length along with it
+///
+/// Errors which only affect the block are returned alongside its length;
+/// the outer `Err` is for fatal errors.
+fn decode_block(
+    inner: &mut BlockReader<InMemory>,
+    src: &mut BytesMut,
+) -> Result<Option<(Result<Block>, u64)>> {
+    let start = BlockReader::position(inner);
+    let block = match inner.decode_raw(src) {
+        Ok(Some((block_type, frame))) => inner.parse_raw(block_type, frame),
+        Ok(None) => return Ok(None),
+        Err(e) => Err(e),
+    };
+    let len = BlockReader::position(inner) - start;
+    match block {
+        Ok(block) => Ok(Some((Ok(block), len))),
+        Err(e @ Error::Block(..)) => Ok(Some((Err(e), len))),
+        Err(e) => Err(e),
+    }
+}
+
+impl Decoder for PcapNgDecoder {
+    type Item = Result<Block>;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Result<Block>>> {
+        Ok(decode_block(&mut self.inner, src)?.map(|(block, _)| block))
+    }
+
+    /// Any incomplete block at the end of the stream is discarded
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Result<Block>>> {
+        let x = self.decode(src)?;
+        if x.is_none() {
+            discard_leftovers(&self.inner, src);
+        }
+        Ok(x)
+    }
+}
+
+fn discard_leftovers(inner: &BlockReader<InMemory>, src: &mut BytesMut) {
+    if !src.is_empty() {
+        inner.diagnose(Diagnostic::IncompleteBlock { len: src.len() });
+        src.clear();
+    }
+}
+
+/// Decodes a pcapng stream into packets
+///
+/// This keeps track of the interfaces defined in the stream, just like a
+/// [`Capture`] does.  Each packet owns its data, so it can be sent off
+/// elsewhere without copying.
+pub struct PacketDecoder {
+    /// Its reader is never used: the data is passed to `decode()`
+    capture: Capture<InMemory>,
+}
+
+impl Default for PacketDecoder {
+    fn default() -> Self {
+        PacketDecoder::new()
+    }
+}
+
+impl PacketDecoder {
+    /// Create a `PacketDecoder` with the default settings
+    ///
+    /// To change the settings, use [`CaptureBuilder::build_decoder()`].
+    pub fn new() -> PacketDecoder {
+        CaptureBuilder::new().build_decoder()
+    }
+
+    pub(crate) fn from_capture(capture: Capture<InMemory>) -> PacketDecoder {
+        PacketDecoder { capture }
+    }
+
+    /// Get some info about a certain network interface
+    ///
+    /// See [`Capture::lookup_interface()`].
+    pub fn lookup_interface(&self, interface_id: InterfaceId) -> Option<&InterfaceInfo> {
+        self.capture.lookup_interface(interface_id)
+    }
+
+    /// How much damage has been worked around so far
+    ///
+    /// See [`Capture::recovery_stats()`].
+    pub fn recovery_stats(&self) -> &RecoveryStats {
+        self.capture.recovery_stats()
+    }
+
+    /// Counts of the blocks, bytes, packets, and errors seen so far
+    ///
+    /// See [`Capture::counters()`].
+    pub fn counters(&self) -> &Counters {
+        self.capture.counters()
+    }
+
+    /// Take the problems found in the blocks decoded since the last call
+    ///
+    /// See [`Capture::take_malformations()`].
+    pub fn take_malformations(&mut self) -> Vec<(BlockType, Malformation)> {
+        self.capture.take_malformations()
+    }
+}
+
+impl Decoder for PacketDecoder {
+    type Item = Result<Packet>;
+    type Error = Error;
+
+    /// Once a limit set by [`CaptureBuilder::limit_packets()`] or
+    /// [`CaptureBuilder::limit_bytes()`] is reached, the rest of the stream
+    /// is discarded
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Result<Packet>>> {
+        loop {
+            if self.capture.limit_reached() {
+                src.clear();
+                return Ok(None);
+            }
+            let (block, len) = match decode_block(&mut self.capture.inner, src) {
+                Ok(Some(x)) => x,
+                Ok(None) => return Ok(None),
+                Err(e) => {
+                    self.capture.handle_error(&e, 0);
+                    return Err(e);
+                }
+            };
+            let x = match block {
+                Ok(block) => {
+                    let endianness = self.capture.inner.endianness();
+                    self.capture
+                        .handle_block(block, len, endianness)
+                        .transpose()
+                }
+                Err(e) => {
+                    self.capture.handle_error(&e, len);
+                    Some(Err(e))
+                }
+            };
+            if x.is_some() {
+                return Ok(x);
+            }
+        }
+    }
+
+    /// Any incomplete block at the end of the stream is discarded
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Result<Packet>>> {
+        let x = self.decode(src)?;
+        if x.is_none() {
+            discard_leftovers(&self.capture.inner, src);
+        }
+        Ok(x)
+    }
+}
@@ -0,0 +1,463 @@
+/*! Checking pcarp against a corpus of known-good captures
+
+This module is only available with the `corpus` feature.  pcarp's
+integration tests run against a directory of captures, each stored
+xz-compressed (`foo.pcapng.xz`) alongside a dump of its expected contents
+(`foo.pcapng.expected`).  The dump has one line per packet: the packet's
+timestamp in RFC 3339 format, a tab, and the MD5 of its data.  Packets
+without a timestamp, and blocks which pcarp can't read, are left out.
+
+[`Corpus`] lets you run the same checks on a directory laid out the same
+way, eg. a checkout of pcarp's `integration_tests`, or a corpus of your own.
+Captures are decompressed next to the `.xz` file the first time they're
+needed, and the decompressed copy is reused after that.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::corpus::{digests, Corpus};
+use pcarp::Capture;
+use std::io::Write;
+
+# let dir = std::env::temp_dir().join(format!("pcarp-corpus-doctest-{}", std::process::id()));
+# let _ = std::fs::remove_dir_all(&dir);
+# std::fs::create_dir_all(&dir).unwrap();
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 1_500_000_000_000_000, b"hello", &[]);
+# let mut xz = xz2::write::XzEncoder::new(vec![], 6);
+# xz.write_all(pcap.as_bytes()).unwrap();
+# std::fs::write(dir.join("hello.pcapng.xz"), xz.finish().unwrap()).unwrap();
+let (lines, _) = digests(Capture::new(pcap.build()));
+assert_eq!(lines, ["2017-07-14T02:40:00.000000000Z\t5d41402abc4b2a76b9719d911017c592"]);
+std::fs::write(dir.join("hello.pcapng.expected"), lines.join("\n") + "\n")?;
+
+let corpus = Corpus::open(&dir)?;
+for entry in corpus.entries() {
+    let report = entry.verify()?;
+    assert!(report.is_ok(), "{}: {report}", entry.name());
+}
+# std::fs::remove_dir_all(&dir)?;
+# Ok::<(), std::io::Error>(())
+```
+
+## Downloading a corpus
+
+A corpus can be shared by publishing its files, along with a [`Manifest`]
+which pins each of them to its SHA-256 digest.  [`Corpus::fetch()`]
+downloads whichever files are missing from a local directory (or don't
+match the manifest), and checks them against their digests before putting
+them in place.  The downloading itself is done by a function you supply;
+[`curl()`] is one which uses the `curl` command.
+
+```
+use pcarp::corpus::{Corpus, Manifest};
+use std::collections::HashMap;
+use std::io;
+
+# let dir = std::env::temp_dir().join(format!("pcarp-corpus-doctest-{}", std::process::id()));
+# let _ = std::fs::remove_dir_all(&dir);
+# std::fs::create_dir_all(&dir).unwrap();
+// These would normally be on a web server somewhere
+let server = HashMap::from([("https://example.com/empty.pcapng.expected", b"")]);
+let manifest = Manifest::parse(
+    "# name, sha256, url
+    empty.pcapng.expected e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 https://example.com/empty.pcapng.expected",
+)?;
+let download = |url: &str| match server.get(url) {
+    Some(x) => Ok(x.to_vec()),
+    None => Err(io::Error::from(io::ErrorKind::NotFound)),
+};
+Corpus::fetch(&dir, &manifest, download)?;
+assert_eq!(std::fs::read(dir.join("empty.pcapng.expected"))?, b"");
+# std::fs::remove_dir_all(&dir)?;
+# Ok::<(), std::io::Error>(())
+```
+*/
+
+use crate::human::Rfc3339;
+use crate::{Capture, Error, Packet};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::*;
+
+/// A directory of captures, each with a dump of its expected contents
+///
+/// See the [module-level documentation](self) for the layout, and an
+/// example.
+#[derive(Debug, Clone)]
+pub struct Corpus {
+    entries: Vec<CorpusEntry>,
+}
+
+impl Corpus {
+    /// Find the captures in a directory
+    ///
+    /// Files ending in `.pcapng.xz` or `.pcapng` are included (once each,
+    /// if both are present).  They're sorted by name.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Corpus> {
+        let mut entries: Vec<CorpusEntry> = vec![];
+        for x in fs::read_dir(dir)? {
+            let path = x?.path();
+            let file_name = match path.file_name().and_then(|x| x.to_str()) {
+                Some(x) => x,
+                None => continue,
+            };
+            let name = match file_name.strip_suffix(".xz") {
+                Some(x) => x,
+                None => file_name,
+            };
+            if !name.ends_with(".pcapng") || entries.iter().any(|x| x.name == name) {
+                continue;
+            }
+            entries.push(CorpusEntry {
+                name: name.to_owned(),
+                path: path.with_file_name(name),
+            });
+        }
+        entries.sort_by(|x, y| x.name.cmp(&y.name));
+        Ok(Corpus { entries })
+    }
+
+    /// Download the files in the manifest which are missing from `dir`,
+    /// and then open it
+    ///
+    /// Files which are already present are kept if they match the
+    /// manifest, and downloaded again if they don't.  `download` is given
+    /// a URL, and should return the body of the response; see [`curl()`].
+    /// Each file is checked against its digest before it's put in place,
+    /// so a failed or tampered download never ends up in the corpus.  If
+    /// a digest doesn't match, an `InvalidData` error is returned.
+    pub fn fetch(
+        dir: impl AsRef<Path>,
+        manifest: &Manifest,
+        mut download: impl FnMut(&str) -> io::Result<Vec<u8>>,
+    ) -> io::Result<Corpus> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        for x in &manifest.entries {
+            let path = dir.join(&x.name);
+            if path.exists() && sha256(&mut File::open(&path)?)? == x.sha256 {
+                continue;
+            }
+            debug!("Downloading {} from {}", x.name, x.url);
+            let data = download(&x.url)?;
+            if sha256(&mut &data[..])? != x.sha256 {
+                let msg = format!("{} doesn't match the digest in the manifest", x.url);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+            }
+            let tmp = dir.join(format!("{}.part", x.name));
+            fs::write(&tmp, data)?;
+            fs::rename(&tmp, &path)?;
+        }
+        Corpus::open(dir)
+    }
+
+    /// The captures in the corpus
+    pub fn entries(&self) -> &[CorpusEntry] {
+        &self.entries
+    }
+}
+
+/// The files which make up a corpus, and where to download them from
+///
+/// A manifest is a text file with one line per file: its name, the
+/// SHA-256 of its contents (in hex), and its URL, separated by whitespace.
+/// Blank lines, and lines starting with `#`, are ignored.  The files are
+/// the `.pcapng.xz` and `.pcapng.expected` files described in the
+/// [module-level documentation](self).  See [`Corpus::fetch()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    entries: Vec<ManifestEntry>,
+}
+
+/// A file listed in a [`Manifest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// The file's name within the corpus directory
+    pub name: String,
+    /// The SHA-256 of the file's contents
+    pub sha256: [u8; 32],
+    /// Where to download the file from
+    pub url: String,
+}
+
+impl Manifest {
+    /// Parse a manifest
+    ///
+    /// Returns an `InvalidData` error if a line is malformed, or if a name
+    /// isn't a plain file name (eg. it contains a `/`).
+    pub fn parse(text: &str) -> io::Result<Manifest> {
+        let mut entries = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |what: &str| {
+                let msg = format!("line {} of the manifest: {what}", i + 1);
+                io::Error::new(io::ErrorKind::InvalidData, msg)
+            };
+            let mut fields = line.split_whitespace();
+            let (name, sha256, url) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(x), Some(y), Some(z)) if fields.next().is_none() => (x, y, z),
+                _ => return Err(invalid("expected a name, a digest, and a URL")),
+            };
+            if Path::new(name).file_name().and_then(|x| x.to_str()) != Some(name) {
+                return Err(invalid("the name must be a plain file name"));
+            }
+            entries.push(ManifestEntry {
+                name: name.to_owned(),
+                sha256: parse_hex(sha256).ok_or_else(|| invalid("bad digest"))?,
+                url: url.to_owned(),
+            });
+        }
+        Ok(Manifest { entries })
+    }
+
+    /// Read and parse a manifest file
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Manifest> {
+        Manifest::parse(&fs::read_to_string(path)?)
+    }
+
+    /// The files in the manifest
+    pub fn entries(&self) -> &[ManifestEntry] {
+        &self.entries
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for x in &self.entries {
+            let digest: String = x.sha256.iter().map(|x| format!("{x:02x}")).collect();
+            writeln!(f, "{} {} {}", x.name, digest, x.url)?;
+        }
+        Ok(())
+    }
+}
+
+/// Download a file using the `curl` command, for use with
+/// [`Corpus::fetch()`]
+///
+/// Redirects are followed, and HTTP errors are reported as errors.  If
+/// `curl` isn't installed, a `NotFound` error is returned.
+pub fn curl(url: &str) -> io::Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--url",
+            url,
+        ])
+        .output()?;
+    if !output.status.success() {
+        let msg = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(io::Error::new(io::ErrorKind::Other, msg));
+    }
+    Ok(output.stdout)
+}
+
+fn sha256(rdr: &mut impl Read) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    io::copy(rdr, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+fn parse_hex(hex: &str) -> Option<[u8; 32]> {
+    let mut out = [0; 32];
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    for (x, chunk) in out.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *x = u8::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+    }
+    Some(out)
+}
+
+/// A capture in a [`Corpus`]
+#[derive(Debug, Clone)]
+pub struct CorpusEntry {
+    /// The file name of the decompressed capture
+    name: String,
+    /// Where the decompressed capture is (or will be) stored
+    path: PathBuf,
+}
+
+impl CorpusEntry {
+    /// The file name of the capture, without the `.xz`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The path of the decompressed capture, decompressing it first if
+    /// necessary
+    ///
+    /// The capture is decompressed to a temporary file, which is then
+    /// renamed, so an interrupted decompression won't leave a truncated
+    /// capture behind.
+    pub fn cached_path(&self) -> io::Result<&Path> {
+        if !self.path.exists() {
+            let mut xz = xz2::read::XzDecoder::new_multi_decoder(File::open(self.xz_path())?);
+            let tmp = self.path.with_extension("pcapng.part");
+            io::copy(&mut xz, &mut File::create(&tmp)?)?;
+            fs::rename(&tmp, &self.path)?;
+        }
+        Ok(&self.path)
+    }
+
+    /// Open the decompressed capture
+    pub fn open(&self) -> io::Result<File> {
+        File::open(self.cached_path()?)
+    }
+
+    /// The expected digests, one per line
+    pub fn expected(&self) -> io::Result<Vec<String>> {
+        let path = self.path.with_extension("pcapng.expected");
+        BufReader::new(File::open(path)?).lines().collect()
+    }
+
+    /// Read the capture with pcarp, and compare its digests with the
+    /// expected ones
+    ///
+    /// Errors reported by pcarp are counted in the report, rather than
+    /// being returned, except for IO errors.
+    pub fn verify(&self) -> io::Result<Report> {
+        let expected = self.expected()?;
+        let mut report = Report {
+            expected: expected.len() as u64,
+            ..Report::default()
+        };
+        let mut expected = expected.into_iter();
+        for pkt in Capture::new(self.open()?) {
+            let line = match pkt {
+                Ok(pkt) => match digest(&pkt) {
+                    Some(x) => x,
+                    None => continue,
+                },
+                Err(Error::IO(e)) => return Err(e),
+                Err(_) => {
+                    report.errors += 1;
+                    continue;
+                }
+            };
+            report.packets += 1;
+            let expected = expected.next();
+            if expected.as_ref() != Some(&line) {
+                report.mismatches.push(Mismatch {
+                    line: report.packets,
+                    actual: Some(line),
+                    expected,
+                });
+            }
+        }
+        for (i, x) in expected.enumerate() {
+            report.mismatches.push(Mismatch {
+                line: report.packets + i as u64 + 1,
+                actual: None,
+                expected: Some(x),
+            });
+        }
+        Ok(report)
+    }
+
+    fn xz_path(&self) -> PathBuf {
+        self.path.with_extension("pcapng.xz")
+    }
+}
+
+/// A line on which pcarp's dump differs from the expected one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The line number, counting from 1
+    pub line: u64,
+    /// The line produced by pcarp; `None` if pcarp produced fewer lines
+    pub actual: Option<String>,
+    /// The expected line; `None` if pcarp produced more lines
+    pub expected: Option<String>,
+}
+
+/// The result of verifying a capture
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The number of packets with a timestamp which pcarp read
+    pub packets: u64,
+    /// The number of lines in the expected dump
+    pub expected: u64,
+    /// The number of errors which pcarp reported.  These don't count as
+    /// failures, since some of the captures are corrupt.
+    pub errors: u64,
+    /// The lines on which pcarp's dump differs from the expected one
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl Report {
+    /// Whether pcarp's dump matched the expected one
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "pcarp read {} packets ({} errors), expected {}; {} mismatches",
+            self.packets,
+            self.errors,
+            self.expected,
+            self.mismatches.len(),
+        )?;
+        for x in &self.mismatches {
+            writeln!(
+                f,
+                "line {}: pcarp {:?}, expected {:?}",
+                x.line, x.actual, x.expected
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A packet's line in an `.expected` file, or `None` if it has no
+/// timestamp
+pub fn digest(pkt: &Packet) -> Option<String> {
+    let ts = pkt.timestamp?;
+    Some(format!("{}\t{:x}", Rfc3339(ts), md5::compute(&pkt.data)))
+}
+
+/// The lines of an `.expected` file for a capture, along with the number of
+/// errors which were skipped over
+pub fn digests<R: Read>(capture: Capture<R>) -> (Vec<String>, u64) {
+    let mut lines = vec![];
+    let mut errors = 0;
+    for pkt in capture {
+        match pkt {
+            Ok(pkt) => lines.extend(digest(&pkt)),
+            Err(_) => errors += 1,
+        }
+    }
+    (lines, errors)
+}
+
+/// Write the `.expected` file for a capture
+///
+/// Errors reported by pcarp are skipped over, except for IO errors.
+pub fn write_digests<R: Read>(capture: Capture<R>, mut out: impl Write) -> io::Result<()> {
+    for pkt in capture {
+        let pkt = match pkt {
+            Ok(x) => x,
+            Err(Error::IO(e)) => return Err(e),
+            Err(_) => continue,
+        };
+        if let Some(line) = digest(&pkt) {
+            writeln!(out, "{line}")?;
+        }
+    }
+    Ok(())
+}
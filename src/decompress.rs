@@ -0,0 +1,222 @@
+/*! Reading compressed pcaps
+
+Captures are often stored compressed.  Since a [`Capture`](crate::Capture)
+reads from anything which implements `Read`, you can always wrap the file
+in a decoder yourself; but if you don't know in advance how a file was
+compressed, [`Decompressors`] can work it out for you.  It looks at the
+start of the file, picks a matching [`Decompressor`], and returns a reader
+of the decompressed data.
+
+Decompressors for gzip, xz, and zstd are built in, behind the `gzip`, `xz`,
+and `zstd` features respectively.  Other formats can be supported by
+implementing [`Decompressor`] and registering it:
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::decompress::{Decompressor, Decompressors};
+use pcarp::Capture;
+use std::io::{self, Read};
+
+/// A very simple "compression" format: a magic number, followed by the
+/// uncompressed data
+struct Toy;
+
+impl Decompressor for Toy {
+    fn name(&self) -> &str {
+        "toy"
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        header.starts_with(b"TOY!")
+    }
+
+    fn decompress(&self, mut rdr: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+        rdr.read_exact(&mut [0; 4])?;
+        Ok(rdr)
+    }
+}
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 0, b"hello", &[]);
+let mut file = b"TOY!".to_vec();
+file.extend_from_slice(pcap.as_bytes());
+
+let mut decompressors = Decompressors::new();
+decompressors.register(Toy);
+let rdr = decompressors.open(io::Cursor::new(file))?;
+let mut capture = Capture::new_boxed(rdr);
+assert_eq!(&capture.next().unwrap().unwrap().data[..], b"hello");
+# Ok::<(), io::Error>(())
+```
+*/
+
+#[cfg(any(feature = "gzip", feature = "xz", feature = "zstd"))]
+use crate::sniff::Format;
+use crate::sniff::SNIFF_LEN;
+use std::fmt;
+use std::io::{self, Cursor, Read};
+use std::sync::Arc;
+use tracing::*;
+
+/// A compression format which pcarp can read
+pub trait Decompressor: Send + Sync {
+    /// A short name for the format (eg. "gzip"), for use in logs
+    fn name(&self) -> &str;
+
+    /// Whether a file which starts with `header` is in this format
+    ///
+    /// `header` holds the first [`SNIFF_LEN`] bytes of the file, or the
+    /// whole file if it's shorter than that.
+    fn detect(&self, header: &[u8]) -> bool;
+
+    /// Wrap `rdr`, which reads the compressed data from the start
+    /// (including the header), in a decoder
+    fn decompress(&self, rdr: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>>;
+}
+
+/// A set of [`Decompressor`]s, which are tried in turn
+///
+/// See the [module-level documentation](self) for an example.
+#[derive(Clone)]
+pub struct Decompressors {
+    decompressors: Vec<Arc<dyn Decompressor>>,
+}
+
+impl fmt::Debug for Decompressors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.decompressors.iter().map(|x| x.name()))
+            .finish()
+    }
+}
+
+impl Default for Decompressors {
+    fn default() -> Self {
+        Decompressors::new()
+    }
+}
+
+impl Decompressors {
+    /// The built-in decompressors which were enabled at compile time
+    pub fn new() -> Decompressors {
+        // Not mutated if none of the features are enabled
+        #[allow(unused_mut)]
+        let mut x = Decompressors::empty();
+        #[cfg(feature = "gzip")]
+        x.register(Gzip);
+        #[cfg(feature = "xz")]
+        x.register(Xz);
+        #[cfg(feature = "zstd")]
+        x.register(Zstd);
+        x
+    }
+
+    /// No decompressors at all
+    pub fn empty() -> Decompressors {
+        Decompressors {
+            decompressors: vec![],
+        }
+    }
+
+    /// Add a decompressor
+    ///
+    /// Decompressors are tried in the reverse of the order they were added
+    /// in, so this one takes precedence over the built-in ones.
+    pub fn register(&mut self, x: impl Decompressor + 'static) -> &mut Decompressors {
+        self.decompressors.push(Arc::new(x));
+        self
+    }
+
+    /// The decompressor for a file which starts with `header`, if any
+    pub fn detect(&self, header: &[u8]) -> Option<&dyn Decompressor> {
+        self.decompressors
+            .iter()
+            .rev()
+            .find(|x| x.detect(header))
+            .map(|x| &**x)
+    }
+
+    /// Read the start of `rdr`, and if it's in a known compression format,
+    /// wrap it in the appropriate decoder
+    ///
+    /// If the format isn't recognised, the returned reader reads `rdr` as it
+    /// is.
+    pub fn open(&self, mut rdr: impl Read + Send + 'static) -> io::Result<Box<dyn Read + Send>> {
+        let mut header = vec![];
+        (&mut rdr).take(SNIFF_LEN as u64).read_to_end(&mut header)?;
+        let decompressor = self.detect(&header);
+        let rdr = Box::new(Cursor::new(header).chain(rdr));
+        match decompressor {
+            Some(x) => {
+                debug!("Decompressing the pcap as {}", x.name());
+                x.decompress(rdr)
+            }
+            None => Ok(rdr),
+        }
+    }
+}
+
+/// gzip, including files made of several concatenated gzip members.  Only
+/// available with the `gzip` feature.
+#[cfg(feature = "gzip")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gzip;
+
+#[cfg(feature = "gzip")]
+impl Decompressor for Gzip {
+    fn name(&self) -> &str {
+        "gzip"
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        Format::detect(header) == Format::Gzip
+    }
+
+    fn decompress(&self, rdr: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(flate2::read::MultiGzDecoder::new(rdr)))
+    }
+}
+
+/// xz, including files made of several concatenated xz streams.  Only
+/// available with the `xz` feature.
+#[cfg(feature = "xz")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Xz;
+
+#[cfg(feature = "xz")]
+impl Decompressor for Xz {
+    fn name(&self) -> &str {
+        "xz"
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        Format::detect(header) == Format::Xz
+    }
+
+    fn decompress(&self, rdr: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(xz2::read::XzDecoder::new_multi_decoder(rdr)))
+    }
+}
+
+/// zstd.  This also reads files written in zstd's seekable format, from
+/// start to finish.  Only available with the `zstd` feature.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Zstd;
+
+#[cfg(feature = "zstd")]
+impl Decompressor for Zstd {
+    fn name(&self) -> &str {
+        "zstd"
+    }
+
+    fn detect(&self, header: &[u8]) -> bool {
+        Format::detect(header) == Format::Zstd
+    }
+
+    fn decompress(&self, rdr: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(zstd::stream::read::Decoder::new(rdr)?))
+    }
+}
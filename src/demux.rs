@@ -0,0 +1,175 @@
+/*! Routing packets to several outputs in a single pass
+
+A [`Demux`] holds a list of outputs, each with a predicate.  Every packet
+is written to each output whose predicate it satisfies, so splitting an
+archive N ways only requires reading it once.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::demux::Demux;
+use pcarp::legacy::{PcapWriter, TsPrecision};
+use pcarp::Capture;
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 0, &[0; 60], &[])
+#     .enhanced_packet(0, 0, &[0; 1500], &[]);
+let mut demux = Demux::new()
+    .output(|pkt, _| pkt.data.len() < 100, PcapWriter::new(vec![], TsPrecision::Micro))
+    .output(|_, _| true, PcapWriter::new(vec![], TsPrecision::Micro));
+demux.run(&mut Capture::new(pcap.build())).unwrap();
+let outputs = demux.into_inner();
+assert_eq!(outputs[0].get_ref().len(), 24 + 16 + 60);
+assert_eq!(outputs[1].get_ref().len(), 24 + 16 + 60 + 16 + 1500);
+```
+*/
+
+use crate::iface::{InterfaceInfo, LinkType};
+use crate::legacy::{ExportError, PcapWriter, SplitPcapWriter};
+use crate::writer::{PcapngWriter, SplitPcapngWriter};
+use crate::{Capture, Error, Packet};
+use std::convert::Infallible;
+use std::fmt;
+use std::io::{self, Read, Write};
+use tracing::*;
+
+/// Something which packets can be written to
+pub trait PacketSink {
+    type Error;
+
+    /// Write a packet, which was captured on the given interface
+    fn write(&mut self, pkt: &Packet, iface: Option<&InterfaceInfo>) -> Result<(), Self::Error>;
+}
+
+impl<W: Write> PacketSink for PcapWriter<W> {
+    type Error = ExportError;
+    fn write(&mut self, pkt: &Packet, iface: Option<&InterfaceInfo>) -> Result<(), ExportError> {
+        PcapWriter::write(self, pkt, iface)
+    }
+}
+
+impl<W: Write, F: FnMut(LinkType) -> io::Result<W>> PacketSink for SplitPcapWriter<W, F> {
+    type Error = ExportError;
+    fn write(&mut self, pkt: &Packet, iface: Option<&InterfaceInfo>) -> Result<(), ExportError> {
+        SplitPcapWriter::write(self, pkt, iface)
+    }
+}
+
+impl<W: Write> PacketSink for PcapngWriter<W> {
+    type Error = ExportError;
+    fn write(&mut self, pkt: &Packet, iface: Option<&InterfaceInfo>) -> Result<(), ExportError> {
+        PcapngWriter::write(self, pkt, iface)
+    }
+}
+
+impl<W: Write, F: FnMut(&InterfaceInfo) -> io::Result<W>> PacketSink for SplitPcapngWriter<W, F> {
+    type Error = ExportError;
+    fn write(&mut self, pkt: &Packet, iface: Option<&InterfaceInfo>) -> Result<(), ExportError> {
+        SplitPcapngWriter::write(self, pkt, iface)
+    }
+}
+
+/// Collects the packets in memory
+impl PacketSink for Vec<Packet> {
+    type Error = Infallible;
+    fn write(&mut self, pkt: &Packet, _: Option<&InterfaceInfo>) -> Result<(), Infallible> {
+        self.push(pkt.clone());
+        Ok(())
+    }
+}
+
+/// An error which occurred while demultiplexing a capture
+#[derive(Debug, thiserror::Error)]
+pub enum DemuxError<E: fmt::Debug + fmt::Display> {
+    #[error("Error while reading the capture")]
+    Capture(#[source] Error),
+    #[error("Error while writing to output {0}: {1}")]
+    Output(usize, E),
+}
+
+type Predicate = Box<dyn FnMut(&Packet, Option<&InterfaceInfo>) -> bool + Send>;
+
+/// Routes packets to every output whose predicate they satisfy
+pub struct Demux<W> {
+    outputs: Vec<(Predicate, W)>,
+}
+
+impl<W> Default for Demux<W> {
+    fn default() -> Self {
+        Demux::new()
+    }
+}
+
+impl<W> Demux<W> {
+    /// A `Demux` with no outputs
+    pub fn new() -> Demux<W> {
+        Demux { outputs: vec![] }
+    }
+
+    /// Add an output, which receives the packets satisfying the predicate
+    ///
+    /// The predicate is given the packet and the interface it was captured
+    /// on (if known).
+    pub fn output(
+        mut self,
+        pred: impl FnMut(&Packet, Option<&InterfaceInfo>) -> bool + Send + 'static,
+        wtr: W,
+    ) -> Demux<W> {
+        self.outputs.push((Box::new(pred), wtr));
+        self
+    }
+
+    /// The outputs, in the order they were added
+    pub fn outputs(&self) -> impl Iterator<Item = &W> {
+        self.outputs.iter().map(|(_, wtr)| wtr)
+    }
+
+    /// Unwrap the `Demux`, returning the outputs in the order they were added
+    pub fn into_inner(self) -> Vec<W> {
+        self.outputs.into_iter().map(|(_, wtr)| wtr).collect()
+    }
+}
+
+impl<W: PacketSink> Demux<W>
+where
+    W::Error: fmt::Debug + fmt::Display,
+{
+    /// Write a packet to every matching output
+    ///
+    /// Returns the number of outputs it was written to.
+    pub fn write(
+        &mut self,
+        pkt: &Packet,
+        iface: Option<&InterfaceInfo>,
+    ) -> Result<usize, DemuxError<W::Error>> {
+        let mut n = 0;
+        for (i, (pred, wtr)) in self.outputs.iter_mut().enumerate() {
+            if pred(pkt, iface) {
+                wtr.write(pkt, iface)
+                    .map_err(|e| DemuxError::Output(i, e))?;
+                n += 1;
+            }
+        }
+        Ok(n)
+    }
+
+    /// Route all the remaining packets in the capture
+    ///
+    /// Corrupt blocks are skipped (you can see how many with
+    /// [`Capture::recovery_stats()`]).  Any other error stops the run.
+    pub fn run<R: Read>(&mut self, capture: &mut Capture<R>) -> Result<(), DemuxError<W::Error>> {
+        while let Some(x) = capture.next_with_iface() {
+            let (pkt, iface) = match x {
+                Ok(x) => x,
+                Err(Error::Block(block_type, e)) => {
+                    warn!("Skipping a corrupt {block_type:?} block: {e}");
+                    continue;
+                }
+                Err(e) => return Err(DemuxError::Capture(e)),
+            };
+            self.write(&pkt, iface.as_deref())?;
+        }
+        Ok(())
+    }
+}
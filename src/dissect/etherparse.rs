@@ -0,0 +1,47 @@
+/*! Parsing packet headers with etherparse */
+
+use super::Dissected;
+use crate::iface::LinkType;
+use etherparse::SlicedPacket;
+use thiserror::Error;
+
+/// The headers of a packet couldn't be sliced
+#[derive(Debug, Error)]
+pub enum SliceError {
+    #[error("Packet has an unknown link type")]
+    UnknownLinkType,
+    #[error("etherparse doesn't support link type {0:?}")]
+    UnsupportedLinkType(LinkType),
+    #[error("Couldn't slice the packet headers")]
+    Etherparse(#[from] etherparse::err::packet::SliceError),
+}
+
+impl Dissected {
+    /// Slice the packet's headers with etherparse
+    ///
+    /// Ethernet, Linux cooked (SLL), and raw IP link types are supported.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # let ipv4 = [0x45, 0, 0, 20, 0, 0, 0x40, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2];
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::RAW, 0, &[])
+    /// #     .enhanced_packet(0, 0, &ipv4, &[]);
+    /// for x in Capture::new(pcap.build()).dissect() {
+    ///     let x = x.unwrap();
+    ///     let sliced = x.etherparse().unwrap();
+    ///     assert!(sliced.net.is_some());
+    /// }
+    /// ```
+    pub fn etherparse(&self) -> Result<SlicedPacket<'_>, SliceError> {
+        let data = &self.packet.data[..];
+        let sliced = match self.link_type.ok_or(SliceError::UnknownLinkType)? {
+            LinkType::ETHERNET => SlicedPacket::from_ethernet(data)?,
+            LinkType::LINUX_SLL => SlicedPacket::from_linux_sll(data)?,
+            LinkType::RAW => SlicedPacket::from_ip(data)?,
+            x => return Err(SliceError::UnsupportedLinkType(x)),
+        };
+        Ok(sliced)
+    }
+}
@@ -0,0 +1,69 @@
+/*! Adapters for protocol-parsing crates
+
+Parsing a packet's headers requires knowing the link type of the interface
+it was captured on.  [`Capture::dissect()`] pairs each packet with its link
+type, and the feature-gated submodules add methods to [`Dissected`] which
+hand the packet data to the appropriate parser:
+
+* `etherparse`: [`Dissected::etherparse()`] gives an
+  [`etherparse::SlicedPacket`](https://docs.rs/etherparse)
+* `pnet`: [`Dissected::pnet()`] gives a [`PnetPacket`](pnet::PnetPacket)
+
+Neither of these copies the packet data.
+*/
+
+#[cfg(feature = "etherparse")]
+pub mod etherparse;
+#[cfg(feature = "pnet")]
+pub mod pnet;
+
+use crate::iface::LinkType;
+use crate::{Capture, Packet, Result};
+use std::io::Read;
+
+/// A packet, along with the link type of the interface it was captured on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dissected {
+    pub packet: Packet,
+    /// `None` if the packet's interface is unknown
+    pub link_type: Option<LinkType>,
+}
+
+/// An iterator of [`Dissected`] packets
+///
+/// See [`Capture::dissect()`].
+pub struct Dissect<R> {
+    capture: Capture<R>,
+}
+
+impl<R: Read> Iterator for Dissect<R> {
+    type Item = Result<Dissected>;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.capture.next_with_iface()?.map(|(packet, iface)| {
+            let link_type = iface.map(|x| x.link_type());
+            Dissected { packet, link_type }
+        }))
+    }
+}
+
+impl<R> Dissect<R> {
+    /// The underlying `Capture`
+    pub fn capture(&self) -> &Capture<R> {
+        &self.capture
+    }
+
+    /// Unwrap the iterator, returning the underlying `Capture`
+    pub fn into_inner(self) -> Capture<R> {
+        self.capture
+    }
+}
+
+impl<R: Read> Capture<R> {
+    /// Pair each packet with its link type, ready to be handed to a
+    /// protocol parser
+    ///
+    /// Only available with the `etherparse` or `pnet` features.
+    pub fn dissect(self) -> Dissect<R> {
+        Dissect { capture: self }
+    }
+}
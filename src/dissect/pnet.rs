@@ -0,0 +1,53 @@
+/*! Parsing packet headers with pnet */
+
+use super::Dissected;
+use crate::iface::LinkType;
+use pnet_packet::ethernet::EthernetPacket;
+use pnet_packet::ipv4::Ipv4Packet;
+use pnet_packet::ipv6::Ipv6Packet;
+use pnet_packet::sll::SLLPacket;
+
+/// The outermost layer of a packet, as parsed by pnet
+#[derive(Debug)]
+pub enum PnetPacket<'a> {
+    Ethernet(EthernetPacket<'a>),
+    LinuxSll(SLLPacket<'a>),
+    Ipv4(Ipv4Packet<'a>),
+    Ipv6(Ipv6Packet<'a>),
+}
+
+impl Dissected {
+    /// Parse the packet's outermost layer with pnet
+    ///
+    /// Ethernet, Linux cooked (SLL), and raw IP link types are supported.
+    /// Returns `None` if the link type is unsupported or unknown, or if the
+    /// packet is too short.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// use pcarp::dissect::pnet::PnetPacket;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # let ipv4 = [0x45, 0, 0, 20, 0, 0, 0x40, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2];
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::RAW, 0, &[])
+    /// #     .enhanced_packet(0, 0, &ipv4, &[]);
+    /// for x in Capture::new(pcap.build()).dissect() {
+    ///     let x = x.unwrap();
+    ///     assert!(matches!(x.pnet(), Some(PnetPacket::Ipv4(_))));
+    /// }
+    /// ```
+    pub fn pnet(&self) -> Option<PnetPacket<'_>> {
+        let data = &self.packet.data[..];
+        match self.link_type? {
+            LinkType::ETHERNET => EthernetPacket::new(data).map(PnetPacket::Ethernet),
+            LinkType::LINUX_SLL => SLLPacket::new(data).map(PnetPacket::LinuxSll),
+            LinkType::RAW => match data.first()? >> 4 {
+                4 => Ipv4Packet::new(data).map(PnetPacket::Ipv4),
+                6 => Ipv6Packet::new(data).map(PnetPacket::Ipv6),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,265 @@
+/*! A machine-readable description of the blocks in a capture
+
+This module is only available with the `json` feature.  [`structure()`]
+describes every block in a capture as a line of JSON: its type, where it
+is, how long it is, and its options (with their names decoded).  Packet
+data isn't included.  It's meant for debugging odd files, and for
+comparing how different versions of pcarp parse the same file: the output
+is stable, so two dumps can be diffed line by line.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::{dump::structure, Capture};
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+#     .enhanced_packet(0, 0, b"hello", &[(2, &[0, 0])]); // epb_flags should be 4 bytes
+let mut out = vec![];
+structure(Capture::new(pcap.build()), &mut out)?;
+let lines: Vec<serde_json::Value> = out
+    .split(|x| *x == b'\n')
+    .filter(|x| !x.is_empty())
+    .map(|x| serde_json::from_slice(x).unwrap())
+    .collect();
+assert_eq!(lines.len(), 3);
+assert_eq!(lines[1]["type"], "InterfaceDescription");
+assert_eq!(lines[1]["offset"], 28);
+assert_eq!(lines[1]["options"][0]["name"], "IfName");
+assert_eq!(lines[1]["options"][0]["value"], "65746830");
+assert_eq!(lines[2]["type"], "EnhancedPacket");
+assert_eq!(lines[2]["options"][0]["name"], "EpbFlags");
+assert_eq!(lines[2]["malformations"].as_array().unwrap().len(), 1);
+# Ok::<(), std::io::Error>(())
+```
+
+## Format
+
+Each line is an object with the following fields:
+
+* `offset`: the offset of the block within the file
+* `length`: the length of the block, including its framing
+* `type`: the name of the block's type (see [`BlockType`]), eg.
+  `"EnhancedPacket"`, or `"Unknown"`
+* `code`: the block's numeric type, as it appears in the file
+* `byte_order`: `"big"` or `"little"`
+* `options`: the block's options, in the order they appear.  Each has a
+  `code`, a `name` (see [`OptionType`]), eg. `"IfName"` or `"Unknown"`,
+  and a `value` in hex.
+  The end-of-options option is included, if present.  An option which
+  overruns the block is included with `"truncated": true`, and ends the
+  list.
+* `malformations`: descriptions of the problems which pcarp found in the
+  block, and worked around
+* `error`: why pcarp couldn't parse the block, or `null`
+
+If the file can't be split into blocks (eg. because a length field is
+corrupt), a final line with just the `offset` and an `error` is written.
+*/
+
+use crate::block::{BlockType, Endianness, OptionType};
+use crate::{Capture, Error};
+use serde_json::json;
+use std::io::{self, Read, Write};
+
+/// Describe each block in the capture, as a line of JSON
+///
+/// See the [module-level documentation](self) for the format.  The
+/// capture's settings (eg. its [`PaddingPolicy`](crate::block::PaddingPolicy))
+/// are used when parsing the blocks.  Returns an error if reading the
+/// capture or writing the output fails.
+pub fn structure<R: Read>(capture: Capture<R>, mut out: impl Write) -> io::Result<()> {
+    let mut blocks = capture.inner;
+    blocks.set_collect_malformations(true);
+    loop {
+        let offset = blocks.position();
+        let (block_type, frame) = match blocks.try_next_raw() {
+            Ok(Some(x)) => x,
+            Ok(None) => break,
+            Err(Error::IO(e)) => return Err(e),
+            Err(e) => {
+                // Framing errors are unrecoverable
+                let line = json!({ "offset": offset, "error": e.to_string() });
+                writeln!(out, "{line}")?;
+                break;
+            }
+        };
+        let endianness = blocks.endianness();
+        let parsed = blocks.parse_raw(block_type, frame.clone());
+        let malformations: Vec<_> = blocks
+            .take_malformations()
+            .into_iter()
+            .map(|(_, x)| x.to_string())
+            .collect();
+        let line = json!({
+            "offset": offset,
+            "length": frame.len(),
+            "type": block_name(block_type),
+            "code": read_u32(&frame[..4], endianness),
+            "byte_order": match endianness {
+                Endianness::Big => "big",
+                Endianness::Little => "little",
+            },
+            "options": options(block_type, &frame[8..frame.len() - 4], endianness),
+            "malformations": malformations,
+            "error": parsed.err().map(|e| e.to_string()),
+        });
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
+
+/// Describe the options in the body of a block
+fn options(block_type: BlockType, body: &[u8], endianness: Endianness) -> Vec<serde_json::Value> {
+    let mut i = match options_start(block_type, body, endianness) {
+        Some(x) => x,
+        None => return vec![],
+    };
+    let mut options = vec![];
+    while i + 4 <= body.len() {
+        let code = read_u16(&body[i..], endianness);
+        let len = usize::from(read_u16(&body[i + 2..], endianness));
+        let name = option_name(OptionType::new(block_type, code));
+        let value = match body.get(i + 4..i + 4 + len) {
+            Some(x) => x,
+            None => {
+                let value = hex(&body[i + 4..]);
+                options
+                    .push(json!({ "code": code, "name": name, "value": value, "truncated": true }));
+                break;
+            }
+        };
+        options.push(json!({ "code": code, "name": name, "value": hex(value) }));
+        if code == 0 {
+            break;
+        }
+        i += 4 + padded(len);
+    }
+    options
+}
+
+/// Where the options start in the body of a block, if it has any
+fn options_start(block_type: BlockType, body: &[u8], endianness: Endianness) -> Option<usize> {
+    let at = |i: usize| Some(read_u32(body.get(i..i + 4)?, endianness) as usize);
+    let start = match block_type {
+        BlockType::SectionHeader => 16,
+        BlockType::InterfaceDescription => 8,
+        BlockType::InterfaceStatistics => 12,
+        BlockType::EnhancedPacket | BlockType::ObsoletePacket => 20 + padded(at(12)?),
+        BlockType::DecryptionSecrets => 8 + padded(at(4)?),
+        BlockType::NameResolution => {
+            // Skip the records, up to and including the end-of-records one
+            let mut i = 0;
+            loop {
+                let header = body.get(i..i + 4)?;
+                let record_type = read_u16(header, endianness);
+                i += 4 + padded(usize::from(read_u16(&header[2..], endianness)));
+                if record_type == 0 {
+                    break i;
+                }
+            }
+        }
+        _ => return None,
+    };
+    (start <= body.len()).then_some(start)
+}
+
+/// The name of a block type, as it appears in the output
+///
+/// These are spelled out, rather than taken from the `Debug` impl, so that
+/// the output doesn't change if the variants are renamed.
+fn block_name(block_type: BlockType) -> &'static str {
+    match block_type {
+        BlockType::SectionHeader => "SectionHeader",
+        BlockType::InterfaceDescription => "InterfaceDescription",
+        BlockType::ObsoletePacket => "ObsoletePacket",
+        BlockType::SimplePacket => "SimplePacket",
+        BlockType::NameResolution => "NameResolution",
+        BlockType::InterfaceStatistics => "InterfaceStatistics",
+        BlockType::EnhancedPacket => "EnhancedPacket",
+        BlockType::IRIGTimestamp => "IRIGTimestamp",
+        BlockType::Arinc429 => "Arinc429",
+        BlockType::SystemdJournalExport => "SystemdJournalExport",
+        BlockType::DecryptionSecrets => "DecryptionSecrets",
+        BlockType::Custom => "Custom",
+        BlockType::Hone => "Hone",
+        BlockType::Sysdig => "Sysdig",
+        BlockType::Unknown(_) => "Unknown",
+    }
+}
+
+/// The name of an option type, as it appears in the output
+fn option_name(option_type: OptionType) -> &'static str {
+    match option_type {
+        OptionType::EndOfOpt => "EndOfOpt",
+        OptionType::Comment => "Comment",
+        OptionType::CustomStrCopy => "CustomStrCopy",
+        OptionType::CustomBinCopy => "CustomBinCopy",
+        OptionType::CustomStrNoCopy => "CustomStrNoCopy",
+        OptionType::CustomBinNoCopy => "CustomBinNoCopy",
+        OptionType::ShbHardware => "ShbHardware",
+        OptionType::ShbOs => "ShbOs",
+        OptionType::ShbUserAppl => "ShbUserAppl",
+        OptionType::IfName => "IfName",
+        OptionType::IfDescription => "IfDescription",
+        OptionType::IfIpv4Addr => "IfIpv4Addr",
+        OptionType::IfIpv6Addr => "IfIpv6Addr",
+        OptionType::IfMacAddr => "IfMacAddr",
+        OptionType::IfEuiAddr => "IfEuiAddr",
+        OptionType::IfSpeed => "IfSpeed",
+        OptionType::IfTsresol => "IfTsresol",
+        OptionType::IfTzone => "IfTzone",
+        OptionType::IfFilter => "IfFilter",
+        OptionType::IfOs => "IfOs",
+        OptionType::IfFcslen => "IfFcslen",
+        OptionType::IfTsoffset => "IfTsoffset",
+        OptionType::IfHardware => "IfHardware",
+        OptionType::IfTxspeed => "IfTxspeed",
+        OptionType::IfRxspeed => "IfRxspeed",
+        OptionType::IfIanaTzname => "IfIanaTzname",
+        OptionType::EpbFlags => "EpbFlags",
+        OptionType::EpbHash => "EpbHash",
+        OptionType::EpbDropcount => "EpbDropcount",
+        OptionType::EpbPacketid => "EpbPacketid",
+        OptionType::EpbQueue => "EpbQueue",
+        OptionType::EpbVerdict => "EpbVerdict",
+        OptionType::EpbProcessidThreadid => "EpbProcessidThreadid",
+        OptionType::PackFlags => "PackFlags",
+        OptionType::PackHash => "PackHash",
+        OptionType::NsDnsName => "NsDnsName",
+        OptionType::NsDnsIp4Addr => "NsDnsIp4Addr",
+        OptionType::NsDnsIp6Addr => "NsDnsIp6Addr",
+        OptionType::IsbStarttime => "IsbStarttime",
+        OptionType::IsbEndtime => "IsbEndtime",
+        OptionType::IsbIfrecv => "IsbIfrecv",
+        OptionType::IsbIfdrop => "IsbIfdrop",
+        OptionType::IsbFilterAccept => "IsbFilterAccept",
+        OptionType::IsbOsdrop => "IsbOsdrop",
+        OptionType::IsbUsrdeliv => "IsbUsrdeliv",
+        OptionType::Unknown(_) => "Unknown",
+    }
+}
+
+fn padded(len: usize) -> usize {
+    len.saturating_add(3) & !3
+}
+
+fn read_u16(bytes: &[u8], endianness: Endianness) -> u16 {
+    let x = [bytes[0], bytes[1]];
+    match endianness {
+        Endianness::Big => u16::from_be_bytes(x),
+        Endianness::Little => u16::from_le_bytes(x),
+    }
+}
+
+fn read_u32(bytes: &[u8], endianness: Endianness) -> u32 {
+    let x = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    match endianness {
+        Endianness::Big => u32::from_be_bytes(x),
+        Endianness::Little => u32::from_le_bytes(x),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
+}
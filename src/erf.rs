@@ -0,0 +1,282 @@
+/*! Reading packets from ERF files
+
+ERF (Extensible Record Format) is the format written by Endace's capture
+cards.  An ERF file is simply a sequence of records, each with a 16-byte
+header giving the record type, lengths, and a timestamp, followed by the
+packet.  An [`ErfReader`] turns these records into [`Packet`]s, just like a
+[`Capture`](crate::Capture) does with the blocks of a pcapng.
+
+Records of the common types are mapped onto the corresponding link types
+(eg. the Ethernet record types become [`LinkType::ETHERNET`]).  Records of
+other types are passed through whole, with a link type of `ERF` (197), so
+that they can be handed to a dissector which understands ERF.  Padding
+records are skipped.
+
+```
+use pcarp::erf::ErfReader;
+use pcarp::iface::LinkType;
+use pcarp::source::PacketSource;
+use std::time::{Duration, UNIX_EPOCH};
+
+// An Ethernet record, as written by an Endace card
+let mut rec = vec![0, 0, 0, 0x80, 10, 0, 0, 0]; // timestamp: 10.5s
+rec.extend_from_slice(&[2, 0, 0, 23, 0, 0, 0, 5]); // type, flags, rlen, lctr, wlen
+rec.extend_from_slice(&[0, 0]); // offset and padding
+rec.extend_from_slice(b"hello");
+
+let mut rdr = ErfReader::new(&rec[..]);
+let (pkt, iface) = rdr.next_packet().unwrap().unwrap();
+assert_eq!(&pkt.data[..], b"hello");
+assert_eq!(pkt.timestamp, Some(UNIX_EPOCH + Duration::from_millis(10_500)));
+assert_eq!(iface.unwrap().link_type(), LinkType::ETHERNET);
+assert!(rdr.next_packet().is_none());
+```
+*/
+
+use crate::block::{InterfaceDescription, Timestamp};
+use crate::iface::{InterfaceId, InterfaceInfo, LinkType};
+use crate::source::PacketSource;
+use crate::Packet;
+use bytes::{Buf, Bytes};
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::time::SystemTime;
+use thiserror::Error;
+use tracing::*;
+
+/// The length of the header at the start of each record
+const RECORD_HEADER_LEN: usize = 16;
+/// The length of each extension header
+const EXT_HEADER_LEN: usize = 8;
+
+const TYPE_HDLC_POS: u8 = 1;
+const TYPE_ETH: u8 = 2;
+const TYPE_COLOR_HDLC_POS: u8 = 10;
+const TYPE_COLOR_ETH: u8 = 11;
+const TYPE_DSM_COLOR_HDLC_POS: u8 = 15;
+const TYPE_DSM_COLOR_ETH: u8 = 16;
+const TYPE_COLOR_HASH_POS: u8 = 19;
+const TYPE_COLOR_HASH_ETH: u8 = 20;
+const TYPE_INFINIBAND: u8 = 21;
+const TYPE_IPV4: u8 = 22;
+const TYPE_IPV6: u8 = 23;
+const TYPE_PAD: u8 = 48;
+
+/// The link type for records which are passed through whole
+const LINKTYPE_ERF: u16 = 197;
+const LINKTYPE_IPV4: u16 = 228;
+const LINKTYPE_IPV6: u16 = 229;
+const LINKTYPE_INFINIBAND: u16 = 247;
+
+/// An error which occurred while reading an ERF file
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ErfError {
+    /// A record was too short to contain its headers.  This is non-fatal.
+    #[error("The record is {0} bytes, which is too short")]
+    TruncatedRecord(usize),
+    /// The underlying reader returned an error.  Whether this is fatal
+    /// depends on the reader.
+    #[error("IO error")]
+    IO(#[from] io::Error),
+}
+
+/// Reads packets from an ERF file
+///
+/// ERF timestamps have a resolution of 2^-32 seconds.  Each combination of
+/// capture port and link type is presented as a separate interface, with
+/// an ID of `InterfaceId(0, n)`.
+pub struct ErfReader<R> {
+    rdr: R,
+    /// The interfaces we've seen, along with the port and link type which
+    /// identify them
+    interfaces: Vec<(u8, LinkType, Arc<InterfaceInfo>)>,
+    /// The timestamp of the first packet
+    start: Option<SystemTime>,
+    /// The total of the loss counters
+    lost: u64,
+}
+
+impl<R: Read> ErfReader<R> {
+    /// Read ERF records from the given reader
+    pub fn new(rdr: R) -> ErfReader<R> {
+        ErfReader {
+            rdr,
+            interfaces: vec![],
+            start: None,
+            lost: 0,
+        }
+    }
+
+    /// Get some info about a certain network interface
+    pub fn lookup_interface(&self, interface_id: InterfaceId) -> Option<&InterfaceInfo> {
+        match interface_id {
+            InterfaceId(0, n) => self.interfaces.get(n as usize).map(|x| &*x.2),
+            _ => None,
+        }
+    }
+
+    /// The number of packets which the card reported as lost, according
+    /// to the loss counters of the records read so far
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    /// Unwrap the reader, returning the underlying stream
+    pub fn into_inner(self) -> R {
+        self.rdr
+    }
+
+    /// Read a record.  Returns `None` if the stream ended cleanly.
+    fn read_record(&mut self) -> Result<Option<Bytes>, ErfError> {
+        let mut hdr = [0; RECORD_HEADER_LEN];
+        let mut n = 0;
+        while n < hdr.len() {
+            match self.rdr.read(&mut hdr[n..]) {
+                Ok(0) if n == 0 => return Ok(None),
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(x) => n += x,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let rlen = usize::from(u16::from_be_bytes([hdr[10], hdr[11]]));
+        if rlen < RECORD_HEADER_LEN {
+            // We can't even skip the rest of the record, so the stream is
+            // no longer in sync
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("ERF record length {rlen} is shorter than its header"),
+            )
+            .into());
+        }
+        let mut record = vec![0; rlen];
+        record[..RECORD_HEADER_LEN].copy_from_slice(&hdr);
+        self.rdr.read_exact(&mut record[RECORD_HEADER_LEN..])?;
+        Ok(Some(Bytes::from(record)))
+    }
+
+    fn try_next(&mut self) -> Result<Option<(Packet, Arc<InterfaceInfo>)>, ErfError> {
+        loop {
+            let record = match self.read_record()? {
+                Some(x) => x,
+                None => return Ok(None),
+            };
+            if record[8] & 0x7F == TYPE_PAD {
+                trace!("Skipping a padding record");
+                continue;
+            }
+            return self.parse_record(record).map(Some);
+        }
+    }
+
+    fn parse_record(&mut self, record: Bytes) -> Result<(Packet, Arc<InterfaceInfo>), ErfError> {
+        let rlen = record.len();
+        let mut buf = record.clone();
+        let raw_timestamp = Timestamp(buf.get_u64_le());
+        let record_type = buf.get_u8();
+        let flags = buf.get_u8();
+        let _rlen = buf.get_u16();
+        let lctr = buf.get_u16();
+        let wlen = buf.get_u16();
+        if lctr != 0 {
+            warn!("The card reported {lctr} lost packets");
+            self.lost += u64::from(lctr);
+        }
+
+        // Skip the extension headers.  The top bit of the type (and of
+        // each extension header) indicates that another one follows.
+        let mut more = record_type & 0x80 != 0;
+        while more {
+            if buf.remaining() < EXT_HEADER_LEN {
+                return Err(ErfError::TruncatedRecord(rlen));
+            }
+            more = buf[0] & 0x80 != 0;
+            buf.advance(EXT_HEADER_LEN);
+        }
+        let record_type = record_type & 0x7F;
+
+        // The data may be followed by padding
+        let wlen = usize::from(wlen);
+        let trim = |buf: Bytes| buf.slice(..buf.len().min(wlen));
+        let (link_type, data) = match record_type {
+            TYPE_ETH | TYPE_COLOR_ETH | TYPE_DSM_COLOR_ETH | TYPE_COLOR_HASH_ETH => {
+                // Ethernet records have two bytes of offset and padding
+                if buf.remaining() < 2 {
+                    return Err(ErfError::TruncatedRecord(rlen));
+                }
+                buf.advance(2);
+                (LinkType::ETHERNET, trim(buf))
+            }
+            TYPE_HDLC_POS | TYPE_COLOR_HDLC_POS | TYPE_DSM_COLOR_HDLC_POS | TYPE_COLOR_HASH_POS => {
+                // PPP frames start with the all-stations address and UI
+                // control field; anything else is Cisco HDLC
+                let link_type = if buf.starts_with(&[0xFF, 0x03]) {
+                    LinkType::PPP_HDLC
+                } else {
+                    LinkType::C_HDLC
+                };
+                (link_type, trim(buf))
+            }
+            TYPE_IPV4 => (LinkType::from_u16(LINKTYPE_IPV4), trim(buf)),
+            TYPE_IPV6 => (LinkType::from_u16(LINKTYPE_IPV6), trim(buf)),
+            TYPE_INFINIBAND => (LinkType::from_u16(LINKTYPE_INFINIBAND), trim(buf)),
+            _ => {
+                trace!("Passing through an ERF record of type {record_type}");
+                (LinkType::from_u16(LINKTYPE_ERF), record)
+            }
+        };
+        let orig_len = if link_type == LinkType::from_u16(LINKTYPE_ERF) {
+            data.len() as u32
+        } else {
+            wlen as u32
+        };
+
+        let port = flags & 0x03;
+        let (idx, iface) = self.interface(port, link_type);
+        let timestamp = iface.resolve_ts(raw_timestamp);
+        let start = *self.start.get_or_insert(timestamp);
+        let mut pkt = Packet::new(data);
+        pkt.timestamp = Some(timestamp);
+        pkt.raw_timestamp = Some(raw_timestamp);
+        pkt.interface = Some(InterfaceId(0, idx));
+        pkt.orig_len = orig_len;
+        pkt.section_start = Some(start);
+        Ok((pkt, iface))
+    }
+
+    /// Find the interface for the given port and link type, creating it if
+    /// necessary
+    fn interface(&mut self, port: u8, link_type: LinkType) -> (u32, Arc<InterfaceInfo>) {
+        let existing = self
+            .interfaces
+            .iter()
+            .position(|(p, l, _)| *p == port && *l == link_type);
+        let idx = match existing {
+            Some(idx) => idx,
+            None => {
+                debug!("Saw a new interface: port {port}, {link_type:?}");
+                let mut descr = InterfaceDescription::new(link_type);
+                descr.if_name = format!("port{port}").into();
+                descr.if_tsresol = 1 << 32;
+                let iface = InterfaceInfo {
+                    descr,
+                    stats: None,
+                    clock: None,
+                    correction: None,
+                };
+                self.interfaces.push((port, link_type, Arc::new(iface)));
+                self.interfaces.len() - 1
+            }
+        };
+        (idx as u32, self.interfaces[idx].2.clone())
+    }
+}
+
+impl<R: Read> PacketSource for ErfReader<R> {
+    type Error = ErfError;
+    fn next_packet(&mut self) -> Option<Result<(Packet, Option<Arc<InterfaceInfo>>), ErfError>> {
+        let x = self.try_next().transpose()?;
+        Some(x.map(|(pkt, iface)| (pkt, Some(iface))))
+    }
+}
@@ -0,0 +1,186 @@
+/*! Reading a multi-section pcapng as if it had a single section
+
+A pcapng made by concatenating several files (eg. with `cat a.pcapng
+b.pcapng`) has one section per file.  Each section defines its own
+interfaces, so the same physical interface gets a different
+[`InterfaceId`] in each section, and the sections may even differ in
+endianness.
+
+A [`FlatCapture`] hides all this.  Interfaces with identical descriptions
+are treated as the same interface, no matter which section they appear in,
+and each is given a stable global ID.  The packets it returns have
+interface IDs of the form `InterfaceId(0, n)`, where `n` is the global ID.
+
+```
+# use pcarp::{block::Endianness, iface::{InterfaceId, LinkType}, testgen::PcapngBuilder};
+use pcarp::flatten::FlatCapture;
+use pcarp::Capture;
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+#     .enhanced_packet(0, 0, b"a", &[])
+#     .section(Endianness::Big)
+#     .interface(LinkType::ETHERNET, 0, &[(2, b"wlan0")])
+#     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+#     .enhanced_packet(0, 0, b"b", &[])
+#     .enhanced_packet(1, 0, b"c", &[]);
+let mut capture = FlatCapture::new(Capture::new(pcap.build()));
+let ids = capture
+    .by_ref()
+    .map(|pkt| pkt.unwrap().interface.unwrap())
+    .collect::<Vec<_>>();
+assert_eq!(ids, [InterfaceId(0, 0), InterfaceId(0, 1), InterfaceId(0, 0)]);
+assert_eq!(capture.sections(), 2);
+assert_eq!(capture.lookup_interface(InterfaceId(0, 1)).unwrap().name(), "wlan0");
+```
+*/
+
+use crate::iface::{InterfaceId, InterfaceInfo};
+use crate::source::PacketSource;
+use crate::{Capture, Error, Packet, Result};
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tracing::*;
+
+/// A [`Capture`] whose sections have been merged into one
+///
+/// Interfaces are considered identical if their descriptions (link type,
+/// name, options, etc.) are equal.  The interface info returned for a
+/// global ID is the one which was seen first: statistics from later
+/// sections aren't merged into it.
+///
+/// Each packet's `section_start` is the timestamp of the first timestamped
+/// packet in the whole capture.
+pub struct FlatCapture<R> {
+    inner: Capture<R>,
+    /// The interfaces, indexed by global ID.  `None` means the interface
+    /// description was missing or mangled, so it can't be deduplicated.
+    interfaces: Vec<Option<Arc<InterfaceInfo>>>,
+    /// Maps per-section interface IDs to global IDs
+    ids: HashMap<InterfaceId, u32>,
+    /// The distinct sections we've seen packets from
+    sections: Vec<u32>,
+    start: Option<SystemTime>,
+}
+
+impl<R> FlatCapture<R> {
+    /// Flatten the given capture
+    pub fn new(inner: Capture<R>) -> FlatCapture<R> {
+        FlatCapture {
+            inner,
+            interfaces: vec![],
+            ids: HashMap::new(),
+            sections: vec![],
+            start: None,
+        }
+    }
+
+    /// Get some info about a global interface ID
+    pub fn lookup_interface(&self, interface_id: InterfaceId) -> Option<&InterfaceInfo> {
+        match interface_id {
+            InterfaceId(0, n) => self.interfaces.get(n as usize)?.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The global ID of an interface, given its per-section ID
+    ///
+    /// Returns `None` if no packets have been read from that interface yet.
+    pub fn global_id(&self, interface_id: InterfaceId) -> Option<InterfaceId> {
+        self.ids.get(&interface_id).map(|&n| InterfaceId(0, n))
+    }
+
+    /// The number of sections which packets have been read from so far
+    ///
+    /// If this is greater than one, the capture was probably made by
+    /// concatenating several files.
+    pub fn sections(&self) -> usize {
+        self.sections.len()
+    }
+
+    /// Get a reference to the underlying `Capture`
+    pub fn get_ref(&self) -> &Capture<R> {
+        &self.inner
+    }
+
+    /// Unwrap the `FlatCapture`, returning the underlying `Capture`
+    pub fn into_inner(self) -> Capture<R> {
+        self.inner
+    }
+
+    /// Rewrite a packet's interface ID and section start
+    fn flatten(&mut self, mut pkt: Packet, iface: Option<Arc<InterfaceInfo>>) -> Packet {
+        if let Some(id) = pkt.interface {
+            if !self.sections.contains(&id.0) {
+                debug!("Reading packets from a new section (#{})", id.0);
+                self.sections.push(id.0);
+            }
+            let global = match self.ids.get(&id) {
+                Some(&n) => n,
+                None => {
+                    let n = self.assign_id(iface);
+                    debug!("Interface {id:?} has global ID {n}");
+                    self.ids.insert(id, n);
+                    n
+                }
+            };
+            pkt.interface = Some(InterfaceId(0, global));
+        }
+        if self.start.is_none() {
+            self.start = pkt.timestamp;
+        }
+        pkt.section_start = self.start;
+        pkt
+    }
+
+    /// Find the global ID of an interface which hasn't been seen before in
+    /// its section, allocating a new one if necessary
+    fn assign_id(&mut self, iface: Option<Arc<InterfaceInfo>>) -> u32 {
+        let existing = iface.as_ref().and_then(|iface| {
+            self.interfaces
+                .iter()
+                .position(|x| matches!(x, Some(x) if x.descr == iface.descr))
+        });
+        match existing {
+            Some(n) => n as u32,
+            None => {
+                self.interfaces.push(iface);
+                self.interfaces.len() as u32 - 1
+            }
+        }
+    }
+}
+
+impl<R: Read> FlatCapture<R> {
+    /// Get the next packet, along with info about the interface it was
+    /// captured on
+    pub fn next_with_iface(&mut self) -> Option<Result<(Packet, Option<Arc<InterfaceInfo>>)>> {
+        let (pkt, iface) = match self.inner.next_with_iface()? {
+            Ok(x) => x,
+            Err(e) => return Some(Err(e)),
+        };
+        let pkt = self.flatten(pkt, iface);
+        let iface = match pkt.interface {
+            Some(InterfaceId(_, n)) => self.interfaces[n as usize].clone(),
+            None => None,
+        };
+        Some(Ok((pkt, iface)))
+    }
+}
+
+impl<R: Read> Iterator for FlatCapture<R> {
+    type Item = Result<Packet>;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_with_iface()?.map(|(pkt, _)| pkt))
+    }
+}
+
+impl<R: Read> PacketSource for FlatCapture<R> {
+    type Error = Error;
+    fn next_packet(&mut self) -> Option<Result<(Packet, Option<Arc<InterfaceInfo>>)>> {
+        self.next_with_iface()
+    }
+}
@@ -0,0 +1,332 @@
+/*! Grouping packets into flows
+
+This module is only available with the `flows` feature.  [`Capture::flows()`]
+tags each packet with the flow it belongs to, where a flow is the set of
+packets with the same 5-tuple (addresses, ports, and protocol).  Flows are
+bidirectional: a packet and its reply belong to the same flow.  Flow IDs
+are handed out in the order the flows are first seen, and some basic
+per-flow stats are kept alongside them.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::Capture;
+
+# fn udp(src: [u8; 4], dst: [u8; 4], sport: u16, dport: u16) -> Vec<u8> {
+#     let mut x = vec![0x45, 0, 0, 28, 0, 0, 0, 0, 64, 17, 0, 0];
+#     x.extend_from_slice(&src);
+#     x.extend_from_slice(&dst);
+#     x.extend_from_slice(&sport.to_be_bytes());
+#     x.extend_from_slice(&dport.to_be_bytes());
+#     x.extend_from_slice(&[0, 8, 0, 0]);
+#     x
+# }
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::RAW, 0, &[])
+#     .enhanced_packet(0, 0, &udp([10, 0, 0, 1], [10, 0, 0, 2], 5353, 53), &[])
+#     .enhanced_packet(0, 1, &udp([10, 0, 0, 3], [10, 0, 0, 2], 5353, 53), &[])
+#     .enhanced_packet(0, 2, &udp([10, 0, 0, 2], [10, 0, 0, 1], 53, 5353), &[])
+#     .enhanced_packet(0, 3, b"not ip", &[]);
+let mut flows = Capture::new(pcap.build()).flows();
+let ids: Vec<_> = flows.by_ref().map(|x| x.unwrap().flow.map(|x| x.0)).collect();
+assert_eq!(ids, [Some(0), Some(1), Some(0), None]);
+
+let flow = &flows.flows()[0];
+assert_eq!(flow.key.src_port, 5353);
+assert_eq!(flow.packets, 2);
+```
+*/
+
+use crate::iface::LinkType;
+use crate::{Capture, Packet, Result};
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ops::Range;
+use std::time::SystemTime;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88A8;
+
+pub(crate) const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+const IPPROTO_SCTP: u8 = 132;
+
+/// The addresses, ports, and protocol of an IP packet
+///
+/// For protocols without ports (or for IP fragments other than the first),
+/// the ports are zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FiveTuple {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    /// The IP protocol number (eg. 6 for TCP)
+    pub protocol: u8,
+}
+
+impl FiveTuple {
+    /// Read the 5-tuple from the headers of a packet
+    ///
+    /// Returns `None` if the link type isn't supported (only Ethernet, raw
+    /// IP, and Linux "cooked" captures are), or if the packet isn't IP.
+    pub fn parse(link_type: LinkType, data: &[u8]) -> Option<FiveTuple> {
+        parse_with_transport(link_type, data).map(|(x, _)| x)
+    }
+
+    /// The same tuple, seen from the other end
+    pub fn reversed(self) -> FiveTuple {
+        FiveTuple {
+            src: self.dst,
+            dst: self.src,
+            src_port: self.dst_port,
+            dst_port: self.src_port,
+            protocol: self.protocol,
+        }
+    }
+
+    /// The same tuple for both directions of a flow
+    fn canonical(self) -> FiveTuple {
+        let rev = self.reversed();
+        if (rev.src, rev.src_port) < (self.src, self.src_port) {
+            rev
+        } else {
+            self
+        }
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let x = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([x[0], x[1]]))
+}
+
+/// Read the 5-tuple from the headers of a packet, along with the range of
+/// `data` which holds the transport header and payload (if it's there)
+///
+/// The range doesn't include any link-layer padding after the IP packet.
+pub(crate) fn parse_with_transport(
+    link_type: LinkType,
+    data: &[u8],
+) -> Option<(FiveTuple, Option<Range<usize>>)> {
+    let (ethertype, start) = match link_type {
+        LinkType::ETHERNET => {
+            let mut ethertype = read_u16(data, 12)?;
+            let mut offset = 14;
+            while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+                ethertype = read_u16(data, offset + 2)?;
+                offset += 4;
+            }
+            (ethertype, offset)
+        }
+        LinkType::LINUX_SLL => (read_u16(data, 14)?, 16),
+        LinkType::RAW => match data.first()? >> 4 {
+            4 => (ETHERTYPE_IPV4, 0),
+            6 => (ETHERTYPE_IPV6, 0),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match ethertype {
+        ETHERTYPE_IPV4 => parse_ipv4(data, start),
+        ETHERTYPE_IPV6 => parse_ipv6(data, start),
+        _ => None,
+    }
+}
+
+/// Parse the IPv4 packet which starts at `start`
+fn parse_ipv4(data: &[u8], start: usize) -> Option<(FiveTuple, Option<Range<usize>>)> {
+    let ip = data.get(start..)?;
+    let ihl = usize::from(ip.first()? & 0x0F) * 4;
+    let total_len = usize::from(read_u16(ip, 2)?);
+    let protocol = *ip.get(9)?;
+    let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+    let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+    // Only the first fragment has the transport header
+    let frag_offset = read_u16(ip, 6)? & 0x1FFF;
+    let transport = if frag_offset == 0 && ihl >= 20 {
+        transport_range(data, start + ihl, start + total_len)
+    } else {
+        None
+    };
+    let (src_port, dst_port) = ports(protocol, transport.clone().map(|x| &data[x]));
+    let key = FiveTuple {
+        src: IpAddr::V4(Ipv4Addr::from(src)),
+        dst: IpAddr::V4(Ipv4Addr::from(dst)),
+        src_port,
+        dst_port,
+        protocol,
+    };
+    Some((key, transport))
+}
+
+/// Parse the IPv6 packet which starts at `start`
+fn parse_ipv6(data: &[u8], start: usize) -> Option<(FiveTuple, Option<Range<usize>>)> {
+    let ip = data.get(start..)?;
+    let payload_len = usize::from(read_u16(ip, 4)?);
+    let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+    let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+    let mut protocol = *ip.get(6)?;
+    let mut offset = 40;
+    let mut transport = None;
+    loop {
+        match protocol {
+            // Hop-by-hop options, routing, destination options
+            0 | 43 | 60 => {
+                let len = (usize::from(*ip.get(offset + 1)?) + 1) * 8;
+                protocol = *ip.get(offset)?;
+                offset += len;
+            }
+            // Fragment
+            44 => {
+                let frag_offset = read_u16(ip, offset + 2)? >> 3;
+                protocol = *ip.get(offset)?;
+                offset += 8;
+                if frag_offset != 0 {
+                    break;
+                }
+            }
+            _ => {
+                transport = transport_range(data, start + offset, start + 40 + payload_len);
+                break;
+            }
+        }
+    }
+    let (src_port, dst_port) = ports(protocol, transport.clone().map(|x| &data[x]));
+    let key = FiveTuple {
+        src: IpAddr::V6(Ipv6Addr::from(src)),
+        dst: IpAddr::V6(Ipv6Addr::from(dst)),
+        src_port,
+        dst_port,
+        protocol,
+    };
+    Some((key, transport))
+}
+
+/// The range from `start` to the end of the IP packet.  If the packet was
+/// truncated by the snap length, the range ends at the end of the data.
+fn transport_range(data: &[u8], start: usize, ip_end: usize) -> Option<Range<usize>> {
+    let end = ip_end.min(data.len());
+    if start <= end {
+        Some(start..end)
+    } else {
+        None
+    }
+}
+
+fn ports(protocol: u8, transport: Option<&[u8]>) -> (u16, u16) {
+    let transport = match transport {
+        Some(x) if matches!(protocol, IPPROTO_TCP | IPPROTO_UDP | IPPROTO_SCTP) => x,
+        _ => return (0, 0),
+    };
+    match (read_u16(transport, 0), read_u16(transport, 2)) {
+        (Some(src), Some(dst)) => (src, dst),
+        _ => (0, 0),
+    }
+}
+
+/// Identifies a flow within a [`Flows`]
+///
+/// IDs are handed out sequentially, starting from zero, so they can be
+/// used to index [`Flows::flows()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FlowId(pub u64);
+
+/// A flow, and some stats about the packets seen in it so far
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flow {
+    /// The 5-tuple of the first packet in the flow
+    pub key: FiveTuple,
+    /// The number of packets in the flow
+    pub packets: u64,
+    /// The total length of the packets in the flow, as captured
+    pub bytes: u64,
+    /// The timestamp of the first timestamped packet in the flow
+    pub first_seen: Option<SystemTime>,
+    /// The timestamp of the last timestamped packet in the flow
+    pub last_seen: Option<SystemTime>,
+}
+
+/// A packet, tagged with the flow it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowPacket {
+    pub packet: Packet,
+    /// `None` if the packet isn't IP, or its link type isn't supported
+    pub flow: Option<FlowId>,
+}
+
+/// An iterator of packets tagged with their flows
+///
+/// See [`Capture::flows()`].
+pub struct Flows<R> {
+    capture: Capture<R>,
+    ids: HashMap<FiveTuple, FlowId>,
+    flows: Vec<Flow>,
+}
+
+impl<R: Read> Iterator for Flows<R> {
+    type Item = Result<FlowPacket>;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.capture.next_with_iface()?.map(|(packet, iface)| {
+            let key = iface.and_then(|x| FiveTuple::parse(x.link_type(), &packet.data));
+            let flow = key.map(|key| self.record(key, &packet));
+            FlowPacket { packet, flow }
+        }))
+    }
+}
+
+impl<R> Flows<R> {
+    fn record(&mut self, key: FiveTuple, packet: &Packet) -> FlowId {
+        let next_id = FlowId(self.flows.len() as u64);
+        let id = *self.ids.entry(key.canonical()).or_insert(next_id);
+        if id == next_id {
+            self.flows.push(Flow {
+                key,
+                packets: 0,
+                bytes: 0,
+                first_seen: None,
+                last_seen: None,
+            });
+        }
+        let flow = &mut self.flows[id.0 as usize];
+        flow.packets += 1;
+        flow.bytes += packet.data.len() as u64;
+        if let Some(ts) = packet.timestamp {
+            flow.first_seen = Some(flow.first_seen.map_or(ts, |x| x.min(ts)));
+            flow.last_seen = Some(flow.last_seen.map_or(ts, |x| x.max(ts)));
+        }
+        id
+    }
+
+    /// The flows seen so far, indexed by their IDs
+    pub fn flows(&self) -> &[Flow] {
+        &self.flows
+    }
+
+    /// The underlying `Capture`
+    pub fn capture(&self) -> &Capture<R> {
+        &self.capture
+    }
+
+    /// Unwrap the iterator, returning the underlying `Capture`
+    pub fn into_inner(self) -> Capture<R> {
+        self.capture
+    }
+}
+
+impl<R: Read> Capture<R> {
+    /// Tag each packet with the flow it belongs to
+    ///
+    /// Only available with the `flows` feature.  See the [`flows`](crate::flows)
+    /// module.
+    pub fn flows(self) -> Flows<R> {
+        Flows {
+            capture: self,
+            ids: HashMap::new(),
+            flows: vec![],
+        }
+    }
+}
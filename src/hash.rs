@@ -0,0 +1,421 @@
+/*! Hashing packet data
+
+These hashes are computed over the captured bytes only: the timestamp,
+interface, and original length aren't included.  This makes them useful
+for spotting the same packet in two different captures, or the same
+packet captured twice.
+
+Some header fields change as a packet travels through the network (eg. the
+IP TTL and checksum), so the same packet captured at two different points
+won't have identical bytes.  You can mask out such fields with
+[`Packet::hash_masked()`].
+
+```
+# use pcarp::Packet;
+use pcarp::hash::{HashAlgorithm, PacketHash};
+
+# let pkt = |data: &'static [u8]| Packet::new(data);
+let (a, b) = (pkt(b"hello world"), pkt(b"hello WORLD"));
+assert_eq!(a.hash(HashAlgorithm::Crc32), PacketHash::Crc32(0x0d4a_1185));
+assert_ne!(a.hash(HashAlgorithm::Sha256), b.hash(HashAlgorithm::Sha256));
+assert_eq!(
+    a.hash_masked(HashAlgorithm::XxHash64, &[6..11]),
+    b.hash_masked(HashAlgorithm::XxHash64, &[6..11]),
+);
+```
+
+## Tamper-evidence
+
+For forensic work it can be important to show exactly which bytes of the
+original file a reported packet came from.  [`Capture::evidence()`] records
+the region of the file which holds each packet's data, and builds a
+[`MerkleTree`] over those regions as it goes.  Publish the tree's root when
+the capture is first analysed; later, anyone with the file can check a
+packet's region against the root using an [`InclusionProof`], without
+needing the rest of the tree.
+
+The tree is built as described in RFC 9162 (Certificate Transparency), using
+SHA-256.  Each leaf is the hash of a zero byte (RFC 9162's leaf prefix),
+followed by the region's offset within the file (big-endian, 8 bytes),
+followed by the bytes in that region.
+
+The offsets are positions in the stream which the [`Capture`] read.  If the
+capture was compressed and you decompressed it on the fly (eg. with the
+[`decompress`](crate::decompress) module), they're offsets into the
+decompressed data, so that's what the regions have to be checked against.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::Capture;
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little).interface(LinkType::ETHERNET, 0, &[]);
+# for x in [b"one", b"two", b"six"] {
+#     pcap.enhanced_packet(0, 0, x, &[]);
+# }
+let file = pcap.as_bytes();
+let mut evidence = Capture::new(file).evidence();
+let reported: Vec<_> = evidence.by_ref().map(|x| x.unwrap()).collect();
+let root = evidence.tree().root();
+
+// Later...
+let (pkt, region) = &reported[1];
+let proof = evidence.tree().proof(1).unwrap();
+assert!(proof.verify(region, &root));
+assert!(region.check(file));
+assert_eq!(&file[region.offset as usize..][..region.len as usize], &pkt.data[..]);
+```
+*/
+
+use crate::{Capture, Packet, Result};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::ops::Range;
+
+/// A hash function
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum HashAlgorithm {
+    /// CRC-32 (as used by ethernet, zlib, etc.).  Very fast, but only
+    /// suitable for detecting accidental differences.
+    Crc32,
+    /// 64-bit xxHash, with a seed of zero.  Very fast, and has few enough
+    /// collisions to be used as a deduplication key.
+    XxHash64,
+    /// SHA-256.  Slow, but collision-resistant.
+    Sha256,
+}
+
+/// The hash of a packet's data
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum PacketHash {
+    Crc32(u32),
+    XxHash64(u64),
+    Sha256([u8; 32]),
+}
+
+impl PacketHash {
+    /// The algorithm which produced this hash
+    pub fn algorithm(&self) -> HashAlgorithm {
+        match self {
+            PacketHash::Crc32(_) => HashAlgorithm::Crc32,
+            PacketHash::XxHash64(_) => HashAlgorithm::XxHash64,
+            PacketHash::Sha256(_) => HashAlgorithm::Sha256,
+        }
+    }
+}
+
+/// A hasher for any of the supported algorithms
+enum Hasher {
+    Crc32(crc32fast::Hasher),
+    XxHash64(xxhash_rust::xxh64::Xxh64),
+    Sha256(sha2::Sha256),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Hasher {
+        match algorithm {
+            HashAlgorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            HashAlgorithm::XxHash64 => Hasher::XxHash64(xxhash_rust::xxh64::Xxh64::new(0)),
+            HashAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Crc32(x) => x.update(data),
+            Hasher::XxHash64(x) => x.update(data),
+            Hasher::Sha256(x) => x.update(data),
+        }
+    }
+
+    fn finish(self) -> PacketHash {
+        match self {
+            Hasher::Crc32(x) => PacketHash::Crc32(x.finalize()),
+            Hasher::XxHash64(x) => PacketHash::XxHash64(x.digest()),
+            Hasher::Sha256(x) => PacketHash::Sha256(x.finalize().into()),
+        }
+    }
+}
+
+impl Packet {
+    /// Hash the packet's data
+    pub fn hash(&self, algorithm: HashAlgorithm) -> PacketHash {
+        self.hash_masked(algorithm, &[])
+    }
+
+    /// Hash the packet's data, treating the bytes in the given ranges as if
+    /// they were zero
+    ///
+    /// The ranges are byte offsets into the packet's data.  They may
+    /// overlap, and any parts which lie beyond the end of the data are
+    /// ignored.
+    pub fn hash_masked(&self, algorithm: HashAlgorithm, mask: &[Range<usize>]) -> PacketHash {
+        let mut hasher = Hasher::new(algorithm);
+        let len = self.data.len();
+        let mut mask = mask
+            .iter()
+            .map(|r| r.start.min(len)..r.end.min(len))
+            .filter(|r| !r.is_empty())
+            .collect::<Vec<_>>();
+        mask.sort_by_key(|r| r.start);
+        let mut pos = 0;
+        for r in mask {
+            if r.start > pos {
+                hasher.update(&self.data[pos..r.start]);
+                pos = r.start;
+            }
+            if r.end > pos {
+                const ZEROS: [u8; 64] = [0; 64];
+                let mut n = r.end - pos;
+                while n > 0 {
+                    let m = n.min(ZEROS.len());
+                    hasher.update(&ZEROS[..m]);
+                    n -= m;
+                }
+                pos = r.end;
+            }
+        }
+        hasher.update(&self.data[pos..]);
+        hasher.finish()
+    }
+}
+
+/// The region of a file which a packet's data came from
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FileRegion {
+    /// The offset of the region within the file (or the decompressed
+    /// stream; see the [module-level documentation](self))
+    pub offset: u64,
+    /// The length of the region
+    pub len: u64,
+    /// The leaf hash of the region, as used in a [`MerkleTree`]
+    pub hash: [u8; 32],
+}
+
+impl FileRegion {
+    /// Describe the region at `offset` which contains `data`
+    pub fn new(offset: u64, data: &[u8]) -> FileRegion {
+        let mut hasher = Sha256::new();
+        hasher.update([0]);
+        hasher.update(offset.to_be_bytes());
+        hasher.update(data);
+        FileRegion {
+            offset,
+            len: data.len() as u64,
+            hash: hasher.finalize().into(),
+        }
+    }
+
+    /// Whether `file` contains the expected bytes in this region
+    pub fn check(&self, file: &[u8]) -> bool {
+        let range = usize::try_from(self.offset)
+            .ok()
+            .and_then(|start| Some(start..start.checked_add(usize::try_from(self.len).ok()?)?));
+        match range.and_then(|x| file.get(x)) {
+            Some(data) => FileRegion::new(self.offset, data) == *self,
+            None => false,
+        }
+    }
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([1]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// The largest power of two which is less than `n`.  `n` must be at
+/// least 2.
+fn split_point(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// The root of the tree over the given leaves
+fn subtree_root(leaves: &[FileRegion]) -> [u8; 32] {
+    match leaves {
+        [] => Sha256::digest([]).into(),
+        [leaf] => leaf.hash,
+        _ => {
+            let (left, right) = leaves.split_at(split_point(leaves.len()));
+            node_hash(&subtree_root(left), &subtree_root(right))
+        }
+    }
+}
+
+/// A Merkle tree over the regions of a file which packets came from
+///
+/// See the [module-level documentation](self) for an example.
+#[derive(Clone, Debug, Default)]
+pub struct MerkleTree {
+    leaves: Vec<FileRegion>,
+}
+
+impl MerkleTree {
+    /// Create an empty tree
+    pub fn new() -> MerkleTree {
+        MerkleTree::default()
+    }
+
+    /// Add a region to the tree
+    pub fn push(&mut self, region: FileRegion) {
+        self.leaves.push(region);
+    }
+
+    /// The regions in the tree, in the order they were added
+    pub fn regions(&self) -> &[FileRegion] {
+        &self.leaves
+    }
+
+    /// The number of regions in the tree
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Whether the tree is empty
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// The root hash of the tree
+    ///
+    /// This takes time proportional to the number of regions.
+    pub fn root(&self) -> [u8; 32] {
+        subtree_root(&self.leaves)
+    }
+
+    /// A proof that the `index`-th region is in the tree
+    ///
+    /// Returns `None` if there's no such region.
+    pub fn proof(&self, index: usize) -> Option<InclusionProof> {
+        if index >= self.leaves.len() {
+            return None;
+        }
+        let mut path = vec![];
+        let (mut leaves, mut m) = (&self.leaves[..], index);
+        while leaves.len() > 1 {
+            let (left, right) = leaves.split_at(split_point(leaves.len()));
+            if m < left.len() {
+                path.push(subtree_root(right));
+                leaves = left;
+            } else {
+                path.push(subtree_root(left));
+                leaves = right;
+                m -= left.len();
+            }
+        }
+        path.reverse();
+        Some(InclusionProof {
+            index: index as u64,
+            tree_size: self.leaves.len() as u64,
+            path,
+        })
+    }
+}
+
+/// A proof that a region is included in a [`MerkleTree`] with a certain
+/// root
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct InclusionProof {
+    /// The index of the region in the tree
+    pub index: u64,
+    /// The number of regions in the tree
+    pub tree_size: u64,
+    /// The hashes of the sibling subtrees, from the leaf up
+    pub path: Vec<[u8; 32]>,
+}
+
+impl InclusionProof {
+    /// Whether this proves that `region` is in the tree with the given root
+    pub fn verify(&self, region: &FileRegion, root: &[u8; 32]) -> bool {
+        if self.index >= self.tree_size {
+            return false;
+        }
+        let (mut fnode, mut snode) = (self.index, self.tree_size - 1);
+        let mut r = region.hash;
+        for p in &self.path {
+            if snode == 0 {
+                return false;
+            }
+            if fnode & 1 == 1 || fnode == snode {
+                r = node_hash(p, &r);
+                while fnode & 1 == 0 && fnode != 0 {
+                    fnode >>= 1;
+                    snode >>= 1;
+                }
+            } else {
+                r = node_hash(&r, p);
+            }
+            fnode >>= 1;
+            snode >>= 1;
+        }
+        snode == 0 && r == *root
+    }
+}
+
+/// An iterator of packets, along with the regions of the file they came
+/// from
+///
+/// See [`Capture::evidence()`].
+pub struct Evidence<R> {
+    capture: Capture<R>,
+    tree: MerkleTree,
+}
+
+impl<R: Read> Iterator for Evidence<R> {
+    type Item = Result<(Packet, FileRegion)>;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(
+            self.capture
+                .try_next_with_meta()
+                .transpose()?
+                .map(|(pkt, meta)| {
+                    let region = FileRegion::new(meta.data_offset, &pkt.data);
+                    self.tree.push(region);
+                    (pkt, region)
+                }),
+        )
+    }
+}
+
+impl<R> Evidence<R> {
+    /// The tree over the regions of the packets returned so far
+    ///
+    /// The `n`-th packet's region is the `n`-th leaf.
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    /// The underlying `Capture`
+    pub fn capture(&self) -> &Capture<R> {
+        &self.capture
+    }
+
+    /// Unwrap the iterator, returning the tree and the underlying `Capture`
+    pub fn into_inner(self) -> (MerkleTree, Capture<R>) {
+        (self.tree, self.capture)
+    }
+}
+
+impl<R: Read> Capture<R> {
+    /// Record where in the file each packet's data came from, and build a
+    /// Merkle tree over those regions
+    ///
+    /// Only available with the `hash` feature.  See the
+    /// [`hash`](crate::hash) module.
+    ///
+    /// Panics if the capture was built with
+    /// [`metadata_only()`](crate::CaptureBuilder::metadata_only), since
+    /// then there's no data to hash.
+    pub fn evidence(self) -> Evidence<R> {
+        assert!(
+            !self.config.metadata_only,
+            "Evidence can't be gathered from a metadata-only capture"
+        );
+        Evidence {
+            capture: self,
+            tree: MerkleTree::new(),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+/*! Formatting metadata for humans
+
+These wrappers implement `Display`, and are what pcarp uses to format
+speeds, times, and durations (eg. in the `Display` impls of
+[`InterfaceInfo`](crate::iface::InterfaceInfo) and
+[`Packet`](crate::Packet)).  Using them in your own tools keeps their output
+consistent with pcarp's.
+
+```
+use pcarp::human::{Bps, Elapsed, Rfc3339, Ticks};
+use std::time::{Duration, UNIX_EPOCH};
+
+assert_eq!(Bps(10_000_000_000).to_string(), "10 Gbps");
+assert_eq!(Bps(1_544_000).to_string(), "1.544 Mbps");
+assert_eq!(
+    Rfc3339(UNIX_EPOCH + Duration::from_millis(1_700_000_000_500)).to_string(),
+    "2023-11-14T22:13:20.500000000Z",
+);
+assert_eq!(Elapsed(Duration::from_micros(1500)).to_string(), "1.5 ms");
+assert_eq!(Elapsed(Duration::from_secs(3723)).to_string(), "1h 2m 3s");
+assert_eq!(Ticks(1500, 1_000_000).to_string(), "1.5 ms");
+```
+*/
+
+use crate::block::Timestamp;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Displays a speed in bits per second, with an SI prefix
+///
+/// The number is rounded to three decimal places.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bps(pub u64);
+
+impl fmt::Display for Bps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [(u64, &str); 4] = [
+            (1_000_000_000_000, "Tbps"),
+            (1_000_000_000, "Gbps"),
+            (1_000_000, "Mbps"),
+            (1_000, "kbps"),
+        ];
+        for (scale, unit) in UNITS {
+            if self.0 >= scale {
+                // Rounded to three decimal places
+                let x = (self.0 as f64 / scale as f64 * 1000.0).round() / 1000.0;
+                return write!(f, "{x} {unit}");
+            }
+        }
+        write!(f, "{} bps", self.0)
+    }
+}
+
+/// Displays a timestamp in RFC 3339 format, in UTC, with nanoseconds
+///
+/// Times before the epoch are shown in their `Debug` form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rfc3339(pub SystemTime);
+
+impl fmt::Display for Rfc3339 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = match self.0.duration_since(UNIX_EPOCH) {
+            Ok(x) => x,
+            Err(_) => return write!(f, "{:?}", self.0),
+        };
+        let secs = since_epoch.as_secs();
+        let (days, secs_of_day) = ((secs / 86_400) as i64, secs % 86_400);
+        // Howard Hinnant's `civil_from_days`
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:09}Z",
+            secs_of_day / 3600,
+            secs_of_day / 60 % 60,
+            secs_of_day % 60,
+            since_epoch.subsec_nanos(),
+        )
+    }
+}
+
+/// Displays a duration in the most appropriate unit
+///
+/// Durations of under a minute are shown in a single unit (from ns to s),
+/// rounded to three decimal places.  Longer ones are broken down into
+/// hours, minutes, and seconds, to the millisecond.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(pub Duration);
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [(u128, &str); 3] = [(1_000_000_000, "s"), (1_000_000, "ms"), (1_000, "µs")];
+        let nanos = self.0.as_nanos();
+        if self.0 < Duration::from_secs(60) {
+            for (scale, unit) in UNITS {
+                if nanos >= scale {
+                    // Rounded to three decimal places
+                    let x = (nanos as f64 / scale as f64 * 1000.0).round() / 1000.0;
+                    return write!(f, "{x} {unit}");
+                }
+            }
+            return write!(f, "{nanos} ns");
+        }
+        let secs = self.0.as_secs();
+        let (hours, mins) = (secs / 3600, secs / 60 % 60);
+        let secs = (secs % 60) as f64 + f64::from(self.0.subsec_millis()) / 1000.0;
+        if hours > 0 {
+            write!(f, "{hours}h ")?;
+        }
+        write!(f, "{mins}m {secs}s")
+    }
+}
+
+/// Displays a number of clock ticks as a duration, given the number of
+/// ticks per second
+///
+/// This is handy for raw timestamps, which count in units of their
+/// interface's [timestamp resolution](crate::iface::InterfaceInfo::tsresol).
+/// If the number of ticks per second is zero, just the ticks are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ticks(pub u64, pub u64);
+
+impl fmt::Display for Ticks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Ticks(ticks, per_sec) = *self;
+        if per_sec == 0 {
+            return write!(f, "{ticks} ticks");
+        }
+        Elapsed(Timestamp(ticks).to_duration(per_sec)).fmt(f)
+    }
+}
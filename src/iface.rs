@@ -1,260 +1,1101 @@
-/*! Info and stats about the network interfaces used to capture packets */
+/*! Info and stats about the network interfaces used to capture packets
 
-use crate::block::{InterfaceDescription, InterfaceStatistics, Timestamp};
+## Link types
+
+By default, [`LinkType`] is an enum with a variant for every link type
+which pcarp knows about, plus `Unknown(u16)` for the rest.  This means that
+whenever pcarp learns a new link type, it moves out of `Unknown`, and code
+which matches on it exhaustively stops compiling.
+
+With the `raw-link-type` feature, `LinkType` is a newtype around the `u16`
+instead, with an associated constant for each known link type.  Code which
+only uses the constants, `from_u16()`, `to_u16()`, and `family()` works the
+same way either way, and the `Debug` output is the same too.
+
+```
+# use pcarp::iface::LinkType;
+let link_type = LinkType::from_u16(1);
+match link_type {
+    LinkType::ETHERNET => println!("ethernet"),
+    x => println!("link type {}", x.to_u16()),
+}
+```
+
+## DLT values
+
+libpcap refers to link types by their "DLT" values.  These are mostly the
+same as the link type numbers, but a few of them differ, and some differ
+between platforms.  [`LinkType::from_dlt()`] and [`LinkType::to_dlt()`]
+convert between the two, and [`DLT_ALIASES`] lists the differences.
+*/
+
+use crate::block::{Endianness, InterfaceDescription, InterfaceStatistics, StrBytes, Timestamp};
+use crate::human::{Bps, Rfc3339};
+use bytes::Bytes;
 use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
-/// The type of physical link backing a network interface
-///
-/// You can find the lastest list [here][reference].
-///
-/// [reference]: https://github.com/IETF-OPSAWG-WG/draft-ietf-opsawg-pcap/blob/master/linktypes.csv
-#[allow(non_camel_case_types)]
-#[derive(Debug, Clone, PartialEq, Eq, Copy)]
-pub enum LinkType {
+/// Defines `LinkType`, either as an enum or as a newtype, from a table of
+/// names and numbers
+macro_rules! link_types {
+    ($($(#[$attr:meta])* $name:ident = $value:literal,)*) => {
+        /// The type of physical link backing a network interface
+        ///
+        /// You can find the lastest list [here][reference].  With the
+        /// `raw-link-type` feature, this is a newtype around the `u16`
+        /// instead; see the [module docs](self).
+        ///
+        /// [reference]: https://github.com/IETF-OPSAWG-WG/draft-ietf-opsawg-pcap/blob/master/linktypes.csv
+        #[cfg(not(feature = "raw-link-type"))]
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub enum LinkType {
+            $($(#[$attr])* $name,)*
+            /// A link type we didn't recognise.
+            Unknown(u16),
+        }
+
+        #[cfg(not(feature = "raw-link-type"))]
+        impl LinkType {
+            fn from_registry(i: u16) -> LinkType {
+                match i {
+                    $($value => LinkType::$name,)*
+                    x => LinkType::Unknown(x),
+                }
+            }
+
+            /// Encode LinkType as u16
+            pub fn to_u16(self) -> u16 {
+                match self {
+                    $(LinkType::$name => $value,)*
+                    LinkType::Unknown(x) => x,
+                }
+            }
+        }
+
+        /// The type of physical link backing a network interface
+        ///
+        /// This is the number from the pcapng file.  The link types which
+        /// pcarp knows about have associated constants; you can find the
+        /// lastest list [here][reference].
+        ///
+        /// [reference]: https://github.com/IETF-OPSAWG-WG/draft-ietf-opsawg-pcap/blob/master/linktypes.csv
+        #[cfg(feature = "raw-link-type")]
+        #[derive(Clone, PartialEq, Eq, Hash, Copy)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct LinkType(u16);
+
+        #[cfg(feature = "raw-link-type")]
+        impl LinkType {
+            $($(#[$attr])* pub const $name: LinkType = LinkType($value);)*
+
+            fn from_registry(i: u16) -> LinkType {
+                LinkType(i)
+            }
+
+            /// Encode LinkType as u16
+            pub fn to_u16(self) -> u16 {
+                self.0
+            }
+
+            /// The name of the constant for this link type, if there is one
+            fn name(self) -> Option<&'static str> {
+                match self.0 {
+                    $($value => Some(stringify!($name)),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+link_types! {
     /// No link layer information. A packet saved with this link layer contains a raw L3 packet
     /// preceded by a 32-bit host-byte-order AF_ value indicating the specific L3 type.
-    NULL,
+    NULL = 0,
     /// D/I/X and 802.3 Ethernet
-    ETHERNET,
+    ETHERNET = 1,
     /// Experimental Ethernet (3Mb)
-    EXP_ETHERNET,
+    EXP_ETHERNET = 2,
     /// Amateur Radio AX.25
-    AX24,
+    AX24 = 3,
     /// Proteon ProNET Token Ring
-    PRONET,
+    PRONET = 4,
     /// Chaos
-    CHAOS,
+    CHAOS = 5,
     /// IEEE 802 Networks
-    TOKEN_RING,
+    TOKEN_RING = 6,
     /// ARCNET, with BSD-style header
-    ARCNET,
+    ARCNET = 7,
     /// Serial Line IP
-    SLIP,
+    SLIP = 8,
     /// Point-to-point Protocol
-    PPP,
+    PPP = 9,
     /// FDDI
-    FDDI,
+    FDDI = 10,
     /// PPP in HDLC-like framing
-    PPP_HDLC,
+    PPP_HDLC = 50,
     /// NetBSD PPP-over-Ethernet
-    PPP_ETHER,
+    PPP_ETHER = 51,
     /// Symantec Enterprise Firewall
-    SYMANTEC_FIREWALL,
+    SYMANTEC_FIREWALL = 99,
     /// LLC/SNAP-encapsulated ATM
-    ATM_RFC1483,
+    ATM_RFC1483 = 100,
     /// Raw IP
-    RAW,
+    RAW = 101,
     /// BSD/OS SLIP BPF header
-    SLIP_BSDOS,
+    SLIP_BSDOS = 102,
     /// BSD/OS PPP BPF header
-    PPP_BSDOS,
+    PPP_BSDOS = 103,
     /// Cisco HDLC
-    C_HDLC,
+    C_HDLC = 104,
     /// IEEE 802.11 (wireless)
-    IEEE802_11,
+    IEEE802_11 = 105,
     /// Linux Classical IP over ATM
-    ATM_CLIP,
+    ATM_CLIP = 106,
     /// Frame Relay
-    FRELAY,
+    FRELAY = 107,
     /// OpenBSD loopback
-    LOOP,
+    LOOP = 108,
     /// OpenBSD IPSEC enc
-    ENC,
+    ENC = 109,
     /// ATM LANE + 802.3 (Reserved for future use)
-    LANE8023,
+    LANE8023 = 110,
     /// NetBSD HIPPI (Reserved for future use)
-    HIPPI,
+    HIPPI = 111,
     /// NetBSD HDLC framing (Reserved for future use)
-    HDLC,
+    HDLC = 112,
     /// Linux cooked socket capture
-    LINUX_SLL,
+    LINUX_SLL = 113,
     /// Apple LocalTalk hardware
-    LTALK,
+    LTALK = 114,
     /// Acorn Econet
-    ECONET,
+    ECONET = 115,
     /// Reserved for use with OpenBSD ipfilter
-    IPFILTER,
+    IPFILTER = 116,
     /// OpenBSD DLT_PFLOG
-    PFLOG,
+    PFLOG = 117,
     /// For Cisco-internal use
-    CISCO_IOS,
+    CISCO_IOS = 118,
     /// 802.11+Prism II monitor mode
-    PRISM_HEADER,
+    PRISM_HEADER = 119,
     /// FreeBSD Aironet driver stuff
-    AIRONET_HEADER,
+    AIRONET_HEADER = 120,
     /// Reserved for Siemens HiPath HDLC
-    HHDLC,
+    HHDLC = 121,
     /// RFC 2625 IP-over-Fibre Channel
-    IP_OVER_FC,
+    IP_OVER_FC = 122,
     /// Solaris+SunATM
-    SUNATM,
+    SUNATM = 123,
     /// RapidIO - Reserved as per request from Kent Dahlgren <kent@praesum.com> for private use.
-    RIO,
+    RIO = 124,
     /// PCI Express - Reserved as per request from Kent Dahlgren <kent@praesum.com> for private
     /// use.
-    PCI_EXP,
+    PCI_EXP = 125,
     /// Xilinx Aurora link layer - Reserved as per request from Kent Dahlgren <kent@praesum.com>
     /// for private use.
-    AURORA,
+    AURORA = 126,
     /// 802.11 plus BSD radio header
-    IEEE802_11_RADIO,
+    IEEE802_11_RADIO = 127,
     /// Tazmen Sniffer Protocol - Reserved for the TZSP encapsulation, as per request from Chris
     /// Waters <chris.waters@networkchemistry.com> TZSP is a generic encapsulation for any other
     /// link type, which includes a means to include meta-information with the packet, e.g. signal
     /// strength and channel for 802.11 packets.
-    TZSP,
+    TZSP = 128,
     /// Linux-style headers
-    ARCNET_LINUX,
+    ARCNET_LINUX = 129,
     /// Juniper-private data link type, as per request from Hannes Gredler <hannes@juniper.net>.
     /// The corresponding DLT_s are used for passing on chassis-internal metainformation such as
     /// QOS profiles, etc..
-    JUNIPER_MLPPP,
+    JUNIPER_MLPPP = 130,
     /// Juniper-private data link type, as per request from Hannes Gredler <hannes@juniper.net>.
     /// The corresponding DLT_s are used for passing on chassis-internal metainformation such as
     /// QOS profiles, etc..
-    JUNIPER_MLFR,
+    JUNIPER_MLFR = 131,
     /// Juniper-private data link type, as per request from Hannes Gredler <hannes@juniper.net>.
     /// The corresponding DLT_s are used for passing on chassis-internal metainformation such as
     /// QOS profiles, etc..
-    JUNIPER_ES,
+    JUNIPER_ES = 132,
     /// Juniper-private data link type, as per request from Hannes Gredler <hannes@juniper.net>.
     /// The corresponding DLT_s are used for passing on chassis-internal metainformation such as
     /// QOS profiles, etc..
-    JUNIPER_GGSN,
+    JUNIPER_GGSN = 133,
     /// Juniper-private data link type, as per request from Hannes Gredler <hannes@juniper.net>.
     /// The corresponding DLT_s are used for passing on chassis-internal metainformation such as
     /// QOS profiles, etc..
-    JUNIPER_MFR,
+    JUNIPER_MFR = 134,
     /// Juniper-private data link type, as per request from Hannes Gredler <hannes@juniper.net>.
     /// The corresponding DLT_s are used for passing on chassis-internal metainformation such as
     /// QOS profiles, etc..
-    JUNIPER_ATM2,
+    JUNIPER_ATM2 = 135,
     /// Juniper-private data link type, as per request from Hannes Gredler <hannes@juniper.net>.
     /// The corresponding DLT_s are used for passing on chassis-internal metainformation such as
     /// QOS profiles, etc..
-    JUNIPER_SERVICES,
+    JUNIPER_SERVICES = 136,
     /// Juniper-private data link type, as per request from Hannes Gredler <hannes@juniper.net>.
     /// The corresponding DLT_s are used for passing on chassis-internal metainformation such as
     /// QOS profiles, etc..
-    JUNIPER_ATM1,
+    JUNIPER_ATM1 = 137,
     /// Apple IP-over-IEEE 1394 cooked header
-    APPLE_IP_OVER_IEEE1394,
+    APPLE_IP_OVER_IEEE1394 = 138,
     /// ???
-    MTP2_WITH_PHDR,
+    MTP2_WITH_PHDR = 139,
     /// ???
-    MTP2,
+    MTP2 = 140,
     /// ???
-    MTP3,
+    MTP3 = 141,
     /// ???
-    SCCP,
+    SCCP = 142,
     /// DOCSIS MAC frames
-    DOCSIS,
+    DOCSIS = 143,
     /// Linux-IrDA
-    LINUX_IRDA,
+    LINUX_IRDA = 144,
     /// Reserved for IBM SP switch and IBM Next Federation switch.
-    IBM_SP,
+    IBM_SP = 145,
     /// Reserved for IBM SP switch and IBM Next Federation switch.
-    IBM_SN,
-    /// A link type we didn't recognise.
-    Unknown(u16),
+    IBM_SN = 146,
 }
 
 impl LinkType {
     /// Decode LinkType from u16
     pub fn from_u16(i: u16) -> LinkType {
         match i {
-            0 => LinkType::NULL,
-            1 => LinkType::ETHERNET,
-            2 => LinkType::EXP_ETHERNET,
-            3 => LinkType::AX24,
-            4 => LinkType::PRONET,
-            5 => LinkType::CHAOS,
-            6 => LinkType::TOKEN_RING,
-            7 => LinkType::ARCNET,
-            8 => LinkType::SLIP,
-            9 => LinkType::PPP,
-            10 => LinkType::FDDI,
-            50 => LinkType::PPP_HDLC,
-            51 => LinkType::PPP_ETHER,
-            99 => LinkType::SYMANTEC_FIREWALL,
-            100 => LinkType::ATM_RFC1483,
-            101 => LinkType::RAW,
-            102 => LinkType::SLIP_BSDOS,
-            103 => LinkType::PPP_BSDOS,
-            104 => LinkType::C_HDLC,
-            105 => LinkType::IEEE802_11,
-            106 => LinkType::ATM_CLIP,
-            107 => LinkType::FRELAY,
-            108 => LinkType::LOOP,
-            109 => LinkType::ENC,
-            110 => LinkType::LANE8023,
-            111 => LinkType::HIPPI,
-            112 => LinkType::HDLC,
-            113 => LinkType::LINUX_SLL,
-            114 => LinkType::LTALK,
-            115 => LinkType::ECONET,
-            116 => LinkType::IPFILTER,
-            117 => LinkType::PFLOG,
-            118 => LinkType::CISCO_IOS,
-            119 => LinkType::PRISM_HEADER,
-            120 => LinkType::AIRONET_HEADER,
-            121 => LinkType::HHDLC,
-            122 => LinkType::IP_OVER_FC,
-            123 => LinkType::SUNATM,
-            124 => LinkType::RIO,
-            125 => LinkType::PCI_EXP,
-            126 => LinkType::AURORA,
-            127 => LinkType::IEEE802_11_RADIO,
-            128 => LinkType::TZSP,
-            129 => LinkType::ARCNET_LINUX,
-            130 => LinkType::JUNIPER_MLPPP,
-            131 => LinkType::JUNIPER_MLFR,
-            132 => LinkType::JUNIPER_ES,
-            133 => LinkType::JUNIPER_GGSN,
-            134 => LinkType::JUNIPER_MFR,
-            135 => LinkType::JUNIPER_ATM2,
-            136 => LinkType::JUNIPER_SERVICES,
-            137 => LinkType::JUNIPER_ATM1,
-            138 => LinkType::APPLE_IP_OVER_IEEE1394,
-            139 => LinkType::MTP2_WITH_PHDR,
-            140 => LinkType::MTP2,
-            141 => LinkType::MTP3,
-            142 => LinkType::SCCP,
-            143 => LinkType::DOCSIS,
-            144 => LinkType::LINUX_IRDA,
-            145 => LinkType::IBM_SP,
-            146 => LinkType::IBM_SN,
             // LINKTYPE_RAW is defined as 101 in the registry but for some reason libpcap uses DLT_RAW
             // defined as 14 on OpenBSD and as 12 for other platforms for the link type. So in order to
-            // reliably decode link types we need to remap those numbers as LinkType::RAW here.
-            12 => LinkType::RAW,
-            14 => LinkType::RAW,
-            x => LinkType::Unknown(x),
+            // reliably decode link types we need to remap those numbers as LinkType::RAW here.  The
+            // other DLT aliases are ambiguous without knowing the platform; see `from_dlt()`.
+            12 | 14 => LinkType::RAW,
+            x => LinkType::from_registry(x),
+        }
+    }
+
+    /// What sort of header the packets start with, broadly speaking
+    ///
+    /// ```
+    /// # use pcarp::iface::{LinkFamily, LinkType};
+    /// assert_eq!(LinkType::LINUX_SLL.family(), LinkFamily::LinuxCooked);
+    /// assert_eq!(LinkType::from_u16(12).family(), LinkFamily::Ip);
+    /// assert_eq!(LinkType::from_u16(1234).family(), LinkFamily::Other);
+    /// ```
+    pub fn family(self) -> LinkFamily {
+        match self {
+            LinkType::ETHERNET => LinkFamily::Ethernet,
+            LinkType::RAW => LinkFamily::Ip,
+            LinkType::NULL | LinkType::LOOP => LinkFamily::Loopback,
+            LinkType::LINUX_SLL => LinkFamily::LinuxCooked,
+            LinkType::IEEE802_11
+            | LinkType::IEEE802_11_RADIO
+            | LinkType::PRISM_HEADER
+            | LinkType::AIRONET_HEADER => LinkFamily::Ieee802_11,
+            LinkType::PPP | LinkType::PPP_HDLC | LinkType::PPP_ETHER | LinkType::PPP_BSDOS => {
+                LinkFamily::Ppp
+            }
+            _ => LinkFamily::Other,
+        }
+    }
+}
+
+/// A platform whose libpcap uses its own numbering for some DLT values;
+/// see [`LinkType::from_dlt()`]
+///
+/// More platforms may be added in future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum DltPlatform {
+    /// Linux, Windows, and everything else which uses libpcap's default
+    /// numbering
+    #[default]
+    Generic,
+    /// macOS, iOS, etc.
+    Darwin,
+    FreeBsd,
+    NetBsd,
+    OpenBsd,
+}
+
+/// A DLT value which doesn't have the same number as the corresponding
+/// link type, on some platforms; see [`DLT_ALIASES`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DltAlias {
+    /// The DLT value, as used by libpcap on `platforms`
+    pub dlt: u32,
+    /// The number of the link type it corresponds to
+    pub link_type: u16,
+    /// The platforms which use this DLT value for this link type
+    pub platforms: &'static [DltPlatform],
+}
+
+const ALL_PLATFORMS: &[DltPlatform] = &[
+    DltPlatform::Generic,
+    DltPlatform::Darwin,
+    DltPlatform::FreeBsd,
+    DltPlatform::NetBsd,
+    DltPlatform::OpenBsd,
+];
+
+/// The DLT values which differ from their link type numbers, taken from
+/// libpcap's `dlt.h`
+///
+/// Any DLT value which isn't listed here for a given platform has the same
+/// number as its link type.
+pub const DLT_ALIASES: &[DltAlias] = &[
+    DltAlias {
+        dlt: 11,
+        link_type: 100, // ATM_RFC1483
+        platforms: ALL_PLATFORMS,
+    },
+    DltAlias {
+        dlt: 12,
+        link_type: 101, // RAW
+        platforms: &[
+            DltPlatform::Generic,
+            DltPlatform::Darwin,
+            DltPlatform::FreeBsd,
+            DltPlatform::NetBsd,
+        ],
+    },
+    DltAlias {
+        dlt: 14,
+        link_type: 101, // RAW
+        platforms: &[DltPlatform::OpenBsd],
+    },
+    DltAlias {
+        dlt: 12,
+        link_type: 108, // LOOP
+        platforms: &[DltPlatform::OpenBsd],
+    },
+    DltAlias {
+        dlt: 13,
+        link_type: 109, // ENC
+        platforms: &[DltPlatform::OpenBsd],
+    },
+    DltAlias {
+        dlt: 13,
+        link_type: 102, // SLIP_BSDOS
+        platforms: &[DltPlatform::FreeBsd, DltPlatform::NetBsd],
+    },
+    DltAlias {
+        dlt: 14,
+        link_type: 103, // PPP_BSDOS
+        platforms: &[DltPlatform::FreeBsd, DltPlatform::NetBsd],
+    },
+    DltAlias {
+        dlt: 15,
+        link_type: 102, // SLIP_BSDOS
+        platforms: &[
+            DltPlatform::Generic,
+            DltPlatform::Darwin,
+            DltPlatform::OpenBsd,
+        ],
+    },
+    DltAlias {
+        dlt: 16,
+        link_type: 103, // PPP_BSDOS
+        platforms: &[
+            DltPlatform::Generic,
+            DltPlatform::Darwin,
+            DltPlatform::OpenBsd,
+        ],
+    },
+    DltAlias {
+        dlt: 18,
+        link_type: 246, // PFSYNC
+        platforms: &[
+            DltPlatform::Darwin,
+            DltPlatform::NetBsd,
+            DltPlatform::OpenBsd,
+        ],
+    },
+    DltAlias {
+        dlt: 121,
+        link_type: 246, // PFSYNC
+        platforms: &[DltPlatform::FreeBsd],
+    },
+    DltAlias {
+        dlt: 19,
+        link_type: 106, // ATM_CLIP
+        platforms: ALL_PLATFORMS,
+    },
+    DltAlias {
+        dlt: 149,
+        link_type: 258, // PKTAP
+        platforms: &[DltPlatform::Darwin],
+    },
+];
+
+impl LinkType {
+    /// Decode a DLT value, as used by libpcap on `platform`
+    ///
+    /// pcap files are meant to contain link type numbers, but some writers
+    /// store the platform's DLT value instead.  Most DLT values are the
+    /// same as the link type numbers, but a few differ between platforms;
+    /// these are listed in [`DLT_ALIASES`].
+    ///
+    /// ```
+    /// # use pcarp::iface::{DltPlatform, LinkType};
+    /// assert_eq!(LinkType::from_dlt(12, DltPlatform::Generic), LinkType::RAW);
+    /// assert_eq!(LinkType::from_dlt(14, DltPlatform::OpenBsd), LinkType::RAW);
+    /// assert_eq!(LinkType::from_dlt(12, DltPlatform::OpenBsd), LinkType::LOOP);
+    /// assert_eq!(LinkType::from_dlt(14, DltPlatform::FreeBsd), LinkType::PPP_BSDOS);
+    /// assert_eq!(LinkType::from_dlt(1, DltPlatform::OpenBsd), LinkType::ETHERNET);
+    /// assert_eq!(LinkType::RAW.to_dlt(DltPlatform::OpenBsd), 14);
+    /// ```
+    pub fn from_dlt(dlt: u32, platform: DltPlatform) -> LinkType {
+        let alias = DLT_ALIASES
+            .iter()
+            .find(|x| x.dlt == dlt && x.platforms.contains(&platform));
+        match alias {
+            Some(x) => LinkType::from_registry(x.link_type),
+            None => LinkType::from_registry(u16::try_from(dlt).unwrap_or(u16::MAX)),
         }
     }
+
+    /// The DLT value which libpcap uses for this link type on `platform`
+    pub fn to_dlt(self, platform: DltPlatform) -> u32 {
+        let link_type = self.to_u16();
+        DLT_ALIASES
+            .iter()
+            .find(|x| x.link_type == link_type && x.platforms.contains(&platform))
+            .map_or(u32::from(link_type), |x| x.dlt)
+    }
+}
+
+#[cfg(feature = "raw-link-type")]
+impl fmt::Debug for LinkType {
+    /// The same as the derived `Debug` impl of the enum
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "Unknown({})", self.0),
+        }
+    }
+}
+
+/// A broad classification of link types; see [`LinkType::family()`]
+///
+/// More families may be added in future, and link types may move from
+/// `Other` into a new family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LinkFamily {
+    /// Ethernet frames
+    Ethernet,
+    /// Bare IPv4 or IPv6 packets; the version is in the first nibble
+    Ip,
+    /// BSD loopback: a 4-byte address family, followed by the packet
+    Loopback,
+    /// Linux "cooked" capture, as produced by capturing on the "any" device
+    LinuxCooked,
+    /// 802.11 frames, possibly preceded by a radio header
+    Ieee802_11,
+    /// PPP frames, possibly HDLC-framed
+    Ppp,
+    /// Anything else
+    Other,
 }
 
 /// The ID a network interface.
 ///
 /// Note: Packets from different sections will have different interface IDs,
 /// even if they were actually captured from the same interface.
-#[derive(Clone, PartialEq, Eq, Debug, Copy)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InterfaceId(pub u32, pub u32);
 
+impl InterfaceId {
+    /// The number of the section which the interface belongs to
+    pub fn section(self) -> u32 {
+        self.0
+    }
+
+    /// The position of the interface in its section's list of interfaces
+    ///
+    /// To number interfaces across all sections, use
+    /// [`Capture::interface_ordinal()`](crate::Capture::interface_ordinal).
+    /// This is the same as [`RawInterfaceId::index()`].
+    pub fn index(self) -> usize {
+        self.raw().index()
+    }
+
+    /// The interface ID as it appears in the blocks of its section
+    pub fn raw(self) -> RawInterfaceId {
+        RawInterfaceId(self.1)
+    }
+}
+
+/// An interface ID as it appears in a block: the index of an interface
+/// within the current section
+///
+/// Most blocks have a 32-bit interface ID, but the obsolete Packet Block
+/// only has room for 16 bits.  Both are widened to a `RawInterfaceId`, so
+/// that IDs from different kinds of block can be compared.
+///
+/// ```
+/// # use pcarp::{block::{Block, Endianness}, iface::{InterfaceId, LinkType, RawInterfaceId}, testgen::PcapngBuilder};
+/// use pcarp::Capture;
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Big)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .interface(LinkType::RAW, 0, &[])
+/// #     .obsolete_packet(1, 0, b"old", &[])
+/// #     .enhanced_packet(1, 0, b"new", &[]);
+/// let mut capture = Capture::new(pcap.build());
+/// for _ in 0..2 {
+///     let pkt = capture.next().unwrap().unwrap();
+///     let id = pkt.interface.unwrap();
+///     assert_eq!(id.index(), 1);
+///     assert_eq!(id.raw(), RawInterfaceId(1));
+///     let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+///     assert_eq!(iface.link_type(), LinkType::RAW);
+/// }
+///
+/// assert_eq!(RawInterfaceId::from(1_u16), RawInterfaceId::from(1_u32));
+/// assert_eq!(u16::try_from(RawInterfaceId(0x1_0000)).ok(), None);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct RawInterfaceId(pub u32);
+
+impl RawInterfaceId {
+    /// The position of the interface in the section's list of interfaces
+    ///
+    /// On platforms where a `usize` is narrower than 32 bits, IDs which
+    /// don't fit are mapped to `usize::MAX`, which no interface can have.
+    pub fn index(self) -> usize {
+        usize::try_from(self.0).unwrap_or(usize::MAX)
+    }
+
+    /// The full ID of this interface, if it belongs to the given section
+    pub fn in_section(self, section: u32) -> InterfaceId {
+        InterfaceId(section, self.0)
+    }
+}
+
+impl From<u16> for RawInterfaceId {
+    fn from(x: u16) -> RawInterfaceId {
+        RawInterfaceId(u32::from(x))
+    }
+}
+
+impl From<u32> for RawInterfaceId {
+    fn from(x: u32) -> RawInterfaceId {
+        RawInterfaceId(x)
+    }
+}
+
+impl From<RawInterfaceId> for u32 {
+    fn from(x: RawInterfaceId) -> u32 {
+        x.0
+    }
+}
+
+/// Fails if the ID doesn't fit in an obsolete Packet Block
+impl TryFrom<RawInterfaceId> for u16 {
+    type Error = std::num::TryFromIntError;
+    fn try_from(x: RawInterfaceId) -> Result<u16, Self::Error> {
+        u16::try_from(x.0)
+    }
+}
+
+impl fmt::Display for RawInterfaceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A network interface, as identified across sections
+///
+/// Each section of a pcapng file re-describes its interfaces from scratch,
+/// so the same physical interface gets a different [`InterfaceId`] in each
+/// section.  Interfaces with the same link type, name, and MAC address get
+/// the same `LogicalInterface`, which makes it usable as a key for
+/// aggregating stats over a whole capture.  See
+/// [`Capture::logical_interface()`](crate::Capture::logical_interface).
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+/// # use std::collections::HashMap;
+/// let mut pcap = PcapngBuilder::new();
+/// pcap.section(Endianness::Little)
+///     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+///     .enhanced_packet(0, 0, b"hello", &[]);
+/// pcap.section(Endianness::Little)
+///     .interface(LinkType::ETHERNET, 0, &[(2, b"wlan0")])
+///     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+///     .enhanced_packet(1, 0, b"world", &[])
+///     .enhanced_packet(0, 0, b"hi", &[]);
+/// let mut capture = Capture::new(pcap.build());
+/// let mut bytes_per_iface = HashMap::new();
+/// while let Some(pkt) = capture.next() {
+///     let pkt = pkt.unwrap();
+///     let iface = capture.logical_interface(pkt.interface.unwrap()).unwrap();
+///     *bytes_per_iface.entry(iface).or_insert(0) += pkt.data.len();
+/// }
+/// assert_eq!(bytes_per_iface.len(), 2);
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum LogicalInterface {
+    /// An interface with a name or a MAC address (or both)
+    Named {
+        link_type: LinkType,
+        name: StrBytes,
+        mac_addr: Option<MacAddr>,
+    },
+    /// An interface with neither a name nor a MAC address.  There's no way
+    /// to tell whether it's the same as an interface in another section, so
+    /// it's identified by its ID.
+    Anonymous(InterfaceId),
+}
+
 /// A network interface.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InterfaceInfo {
     pub(crate) descr: InterfaceDescription,
     pub(crate) stats: Option<InterfaceStatistics>,
+    /// Overrides the timestamp resolution given in the description
+    pub(crate) clock: Option<TimestampClock>,
+    /// Applied to the timestamps after they've been resolved
+    pub(crate) correction: Option<ClockCorrection>,
+}
+
+/// How to interpret the raw timestamps of an interface
+///
+/// Normally a timestamp counts units of the interface's `if_tsresol` since
+/// the unix epoch.  Capture cards sometimes use a different epoch, or
+/// report the raw value of a free-running counter; in that case you can
+/// override the interpretation using
+/// [`CaptureBuilder::timestamp_clock()`](crate::CaptureBuilder::timestamp_clock).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "UncheckedTimestampClock"))]
+pub struct TimestampClock {
+    epoch: SystemTime,
+    ticks_per_sec: u64,
+}
+
+/// A `TimestampClock` as it was deserialized, before `ticks_per_sec` has
+/// been checked
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct UncheckedTimestampClock {
+    epoch: SystemTime,
+    ticks_per_sec: u64,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<UncheckedTimestampClock> for TimestampClock {
+    type Error = &'static str;
+    fn try_from(x: UncheckedTimestampClock) -> Result<Self, Self::Error> {
+        if x.ticks_per_sec == 0 {
+            return Err("A clock must tick at least once per second");
+        }
+        Ok(TimestampClock::new(x.epoch, x.ticks_per_sec))
+    }
+}
+
+impl TimestampClock {
+    /// A clock which counts `ticks_per_sec` ticks per second, starting from
+    /// `epoch`
+    ///
+    /// Panics if `ticks_per_sec` is zero.
+    pub fn new(epoch: SystemTime, ticks_per_sec: u64) -> TimestampClock {
+        assert!(
+            ticks_per_sec > 0,
+            "A clock must tick at least once per second"
+        );
+        TimestampClock {
+            epoch,
+            ticks_per_sec,
+        }
+    }
+
+    /// The time which a timestamp of zero corresponds to
+    pub fn epoch(&self) -> SystemTime {
+        self.epoch
+    }
+
+    /// The number of ticks per second
+    pub fn ticks_per_sec(&self) -> u64 {
+        self.ticks_per_sec
+    }
+}
+
+/// A predicate which decides which interfaces' packets to keep
+#[derive(Clone)]
+pub(crate) struct InterfaceFilter(Arc<dyn Fn(&InterfaceInfo) -> bool + Send + Sync>);
+
+impl InterfaceFilter {
+    pub(crate) fn new(f: impl Fn(&InterfaceInfo) -> bool + Send + Sync + 'static) -> Self {
+        InterfaceFilter(Arc::new(f))
+    }
+
+    /// Match interfaces whose name or description matches a glob
+    pub(crate) fn glob(pattern: &str) -> Self {
+        let pattern = pattern.as_bytes().to_vec();
+        InterfaceFilter::new(move |iface| {
+            glob_match(&pattern, iface.name().as_bytes())
+                || glob_match(&pattern, iface.description().as_bytes())
+        })
+    }
+
+    pub(crate) fn matches(&self, iface: &InterfaceInfo) -> bool {
+        (self.0)(iface)
+    }
+}
+
+impl fmt::Debug for InterfaceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("InterfaceFilter(..)")
+    }
+}
+
+/// Reads a timestamp from the value of a packet option; see
+/// [`CaptureBuilder::timestamp_option()`](crate::CaptureBuilder::timestamp_option)
+#[derive(Clone)]
+pub(crate) struct TimestampExtractor(Arc<ExtractFn>);
+
+type ExtractFn = dyn Fn(&[u8], Endianness) -> Option<SystemTime> + Send + Sync;
+
+impl TimestampExtractor {
+    pub(crate) fn new(
+        f: impl Fn(&[u8], Endianness) -> Option<SystemTime> + Send + Sync + 'static,
+    ) -> Self {
+        TimestampExtractor(Arc::new(f))
+    }
+
+    pub(crate) fn extract(&self, value: &[u8], endianness: Endianness) -> Option<SystemTime> {
+        (self.0)(value, endianness)
+    }
+}
+
+impl fmt::Debug for TimestampExtractor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TimestampExtractor(..)")
+    }
+}
+
+/// Shell-style matching, where `*` matches any string and `?` matches any
+/// single byte
+fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    // The position to backtrack to when a literal fails to match: just after
+    // the last `*`, and the next position in `s` which it could absorb
+    let mut backtrack = None;
+    let (mut p, mut i) = (0, 0);
+    while i < s.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, i + 1));
+            }
+            Some(&c) if c == b'?' || c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bi)) => {
+                    p = bp;
+                    i = bi;
+                    backtrack = Some((bp, bi + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// A correction for the offset and drift of an interface's clock
+///
+/// When packets are captured on several NICs, each NIC's clock is usually
+/// a little off, and runs a little fast or slow.  A `ClockCorrection` maps
+/// the timestamps of one interface onto the timeline of another, so that
+/// latencies measured across interfaces are meaningful.  Corrections are
+/// applied with
+/// [`CaptureBuilder::clock_correction()`](crate::CaptureBuilder::clock_correction).
+///
+/// A timestamp `t` is corrected to `t + offset + (t - anchor) * skew`.  The
+/// skew is given in parts per billion.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClockCorrection {
+    anchor: SystemTime,
+    offset_nanos: i64,
+    skew_ppb: i64,
+}
+
+impl ClockCorrection {
+    /// A correction which adds a fixed offset to each timestamp
+    pub fn offset(offset_nanos: i64) -> ClockCorrection {
+        ClockCorrection::new(SystemTime::UNIX_EPOCH, offset_nanos, 0)
+    }
+
+    /// A correction which adds a fixed offset to each timestamp, and
+    /// stretches the timeline around `anchor` by `skew_ppb` parts per billion
+    pub fn new(anchor: SystemTime, offset_nanos: i64, skew_ppb: i64) -> ClockCorrection {
+        ClockCorrection {
+            anchor,
+            offset_nanos,
+            skew_ppb,
+        }
+    }
+
+    /// Estimate the correction for `iface` from the interface statistics
+    ///
+    /// This assumes that both interfaces started and stopped capturing at
+    /// the same moment, as recorded in their `isb_starttime` and
+    /// `isb_endtime`.  The correction maps the start and end times of
+    /// `iface` onto those of `reference`.  Any correction which has already
+    /// been applied to `iface` is ignored.
+    ///
+    /// Returns `None` if either interface is missing its start or end time,
+    /// or if `iface`'s end time isn't after its start time.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::{InterfaceId, LinkType}, testgen::PcapngBuilder};
+    /// use pcarp::{iface::ClockCorrection, Capture, CaptureBuilder};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// # let ts = |secs: u64| {
+    /// #     let us = secs * 1_000_000;
+    /// #     let mut x = ((us >> 32) as u32).to_le_bytes().to_vec();
+    /// #     x.extend_from_slice(&(us as u32).to_le_bytes());
+    /// #     x
+    /// # };
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"nic0")])
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"nic1")])
+    /// #     .enhanced_packet(1, 1_550_000_000, b"", &[])
+    /// #     .interface_statistics(0, 0, &[(2, &ts(1000)), (3, &ts(2001))])
+    /// #     .interface_statistics(1, 0, &[(2, &ts(1050)), (3, &ts(2050))]);
+    /// // nic1 is 50 seconds ahead of nic0, and runs 0.1% slow
+    /// let mut capture = Capture::new(pcap.build());
+    /// let InterfaceId(section, _) = capture.next().unwrap().unwrap().interface.unwrap();
+    /// capture.by_ref().for_each(drop);
+    /// let nic0 = capture.lookup_interface(InterfaceId(section, 0)).unwrap();
+    /// let nic1 = capture.lookup_interface(InterfaceId(section, 1)).unwrap();
+    /// let correction = ClockCorrection::from_stats(nic0, nic1).unwrap();
+    /// assert_eq!(correction.skew_ppb(), 1_000_000);
+    ///
+    /// let mut capture = CaptureBuilder::new()
+    ///     .clock_correction("nic1", correction)
+    ///     .build(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// let expected = UNIX_EPOCH + Duration::from_secs(1500) + Duration::from_millis(500);
+    /// assert_eq!(pkt.timestamp, Some(expected));
+    /// ```
+    pub fn from_stats(reference: &InterfaceInfo, iface: &InterfaceInfo) -> Option<ClockCorrection> {
+        let ref_start = nanos_since_epoch(reference.starttime()?);
+        let ref_end = nanos_since_epoch(reference.endtime()?);
+        let iface = InterfaceInfo {
+            correction: None,
+            ..iface.clone()
+        };
+        let start = iface.starttime()?;
+        let (start_nanos, end_nanos) = (
+            nanos_since_epoch(start),
+            nanos_since_epoch(iface.endtime()?),
+        );
+        let duration = end_nanos - start_nanos;
+        if duration <= 0 {
+            return None;
+        }
+        let skew_ppb = ((ref_end - ref_start) - duration) * 1_000_000_000 / duration;
+        Some(ClockCorrection {
+            anchor: start,
+            offset_nanos: i64::try_from(ref_start - start_nanos).ok()?,
+            skew_ppb: i64::try_from(skew_ppb).ok()?,
+        })
+    }
+
+    /// The point around which the skew is applied
+    pub fn anchor(&self) -> SystemTime {
+        self.anchor
+    }
+
+    /// The offset, in nanoseconds
+    pub fn offset_nanos(&self) -> i64 {
+        self.offset_nanos
+    }
+
+    /// The skew, in parts per billion
+    pub fn skew_ppb(&self) -> i64 {
+        self.skew_ppb
+    }
+
+    /// Correct a timestamp.  Returns `None` if the result can't be
+    /// represented.
+    pub fn apply(&self, ts: SystemTime) -> Option<SystemTime> {
+        let nanos = nanos_since_epoch(ts);
+        let elapsed = nanos - nanos_since_epoch(self.anchor);
+        let drift = elapsed * i128::from(self.skew_ppb) / 1_000_000_000;
+        let nanos = nanos + i128::from(self.offset_nanos) + drift;
+        let since_epoch = Duration::from_nanos(u64::try_from(nanos.unsigned_abs()).ok()?);
+        if nanos >= 0 {
+            SystemTime::UNIX_EPOCH.checked_add(since_epoch)
+        } else {
+            SystemTime::UNIX_EPOCH.checked_sub(since_epoch)
+        }
+    }
+}
+
+/// Move a timestamp forward (or back) by a number of seconds
+fn shift_secs(ts: SystemTime, secs: i64) -> Option<SystemTime> {
+    let shift = Duration::from_secs(secs.unsigned_abs());
+    if secs >= 0 {
+        ts.checked_add(shift)
+    } else {
+        ts.checked_sub(shift)
+    }
+}
+
+/// The (signed) number of nanoseconds since the unix epoch
+fn nanos_since_epoch(ts: SystemTime) -> i128 {
+    match ts.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(x) => x.as_nanos() as i128,
+        Err(e) => -(e.duration().as_nanos() as i128),
+    }
+}
+
+/// What to do with timestamps which are too large to represent
+///
+/// pcarp represents timestamps as a number of nanoseconds since the unix
+/// epoch, which must fit into a `u64`; so the latest representable time
+/// is in the year 2554.  Later timestamps can only arise from a corrupt
+/// pcap, or from an interface with a strange timestamp resolution.
+///
+/// Whatever the policy, the original timestamp is always available as
+/// [`Packet::raw_timestamp`](crate::Packet::raw_timestamp).
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::{iface::TimestampPolicy, CaptureBuilder, Error};
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .enhanced_packet(0, u64::MAX, b"far future", &[])
+/// #     .enhanced_packet(0, 0, b"epoch", &[]);
+/// let mut capture = CaptureBuilder::new()
+///     .timestamp_policy(TimestampPolicy::Error)
+///     .build(pcap.build());
+/// assert!(matches!(capture.next(), Some(Err(Error::Block(..)))));
+/// let pkt = capture.next().unwrap().unwrap();
+/// assert_eq!(&pkt.data[..], b"epoch");
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TimestampPolicy {
+    /// Clamp the timestamp to the latest representable time.  This is the
+    /// default.
+    #[default]
+    Saturate,
+    /// Return a (non-fatal) `BlockError::TimestampOverflow` instead of the
+    /// packet.
+    Error,
+    /// Wrap the timestamp around, modulo 2^64 nanoseconds.
+    Wrap,
+}
+
+/// How to timestamp simple packets, which don't carry a timestamp of their
+/// own
+///
+/// Any timestamp given to a simple packet this way is flagged with
+/// [`Packet::synthetic_timestamp`](crate::Packet::synthetic_timestamp).
+/// Simple packets never have a `raw_timestamp`.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::{iface::SimpleTimestampPolicy, CaptureBuilder};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .enhanced_packet(0, 5_000_000, b"enhanced", &[])
+/// #     .simple_packet(b"simple");
+/// let mut capture = CaptureBuilder::new()
+///     .simple_timestamps(SimpleTimestampPolicy::Previous)
+///     .build(pcap.build());
+/// let epb = capture.next().unwrap().unwrap();
+/// let spb = capture.next().unwrap().unwrap();
+/// assert_eq!(spb.timestamp, epb.timestamp);
+/// assert!(spb.synthetic_timestamp && !epb.synthetic_timestamp);
+///
+/// let policy = SimpleTimestampPolicy::FixedRate {
+///     start: UNIX_EPOCH,
+///     interval: Duration::from_millis(10),
+/// };
+/// let mut capture = CaptureBuilder::new().simple_timestamps(policy).build(pcap.build());
+/// capture.next().unwrap().unwrap();
+/// let spb = capture.next().unwrap().unwrap();
+/// assert_eq!(spb.timestamp, Some(UNIX_EPOCH));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SimpleTimestampPolicy {
+    /// Leave them without a timestamp.  This is the default.
+    #[default]
+    None,
+    /// Give each one the timestamp of the last timestamped packet which
+    /// came before it.  Simple packets which come before any timestamped
+    /// packet are left without a timestamp.
+    Previous,
+    /// Space them out evenly: the `n`-th simple packet (counting from zero)
+    /// gets the timestamp `start + n * interval`.
+    FixedRate {
+        start: SystemTime,
+        interval: Duration,
+    },
 }
 
 impl InterfaceInfo {
     pub(crate) fn resolve_ts(&self, ts: Timestamp) -> SystemTime {
-        let units_per_sec = u64::from(self.descr.if_tsresol);
-        let secs = ts.0 / units_per_sec;
-        let nanos = ((ts.0 % units_per_sec) * 1_000_000_000 / units_per_sec) as u32;
-        SystemTime::UNIX_EPOCH + Duration::new(secs, nanos)
+        match self.resolve_ts_with(ts, TimestampPolicy::Saturate) {
+            Some(x) => x,
+            None => unreachable!("Saturating timestamps can't fail"),
+        }
+    }
+
+    /// Returns `None` if the timestamp overflows under
+    /// `TimestampPolicy::Error`
+    pub(crate) fn resolve_ts_with(
+        &self,
+        ts: Timestamp,
+        policy: TimestampPolicy,
+    ) -> Option<SystemTime> {
+        let (epoch, units_per_sec) = match self.clock {
+            Some(clock) => (clock.epoch, clock.ticks_per_sec),
+            None => (SystemTime::UNIX_EPOCH, self.descr.if_tsresol),
+        };
+        let nanos = u128::from(ts.0) * 1_000_000_000 / u128::from(units_per_sec);
+        let nanos = match u64::try_from(nanos) {
+            Ok(x) => x,
+            Err(_) => match policy {
+                TimestampPolicy::Saturate => u64::MAX,
+                TimestampPolicy::Error => return None,
+                TimestampPolicy::Wrap => nanos as u64,
+            },
+        };
+        let ts = match epoch.checked_add(Duration::from_nanos(nanos)) {
+            Some(x) => x,
+            None if policy == TimestampPolicy::Error => return None,
+            // Only possible with an absurdly late epoch
+            None => epoch,
+        };
+        match self.correction {
+            Some(correction) => match correction.apply(ts) {
+                Some(x) => Some(x),
+                None if policy == TimestampPolicy::Error => None,
+                None => Some(ts),
+            },
+            None => Some(ts),
+        }
     }
 }
 
@@ -263,6 +1104,83 @@ impl InterfaceInfo {
         self.descr.link_type
     }
 
+    /// The timestamp resolution, in units per second
+    ///
+    /// Timestamps are converted using exact integer arithmetic, so there's
+    /// no loss of precision beyond truncation to whole nanoseconds.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// let mut pcap = PcapngBuilder::new();
+    /// pcap.section(Endianness::Little)
+    ///     .interface(LinkType::ETHERNET, 0, &[(9, &[0x80 | 10])]) // 2^-10
+    ///     .interface(LinkType::ETHERNET, 0, &[(9, &[9])]) // 10^-9
+    ///     .interface(LinkType::ETHERNET, 0, &[(9, &[0x80 | 32])]) // 2^-32
+    ///     .enhanced_packet(0, 1_500_000 * 1024 + 1, b"", &[])
+    ///     .enhanced_packet(1, 1_500_000_000_000_001, b"", &[])
+    ///     .enhanced_packet(2, (1_500_000 << 32) + (1 << 31) + 1, b"", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let secs = Duration::from_secs(1_500_000);
+    /// let expected = [
+    ///     (1024, secs + Duration::from_nanos(976_562)), // 1/1024 s = 976562.5 ns
+    ///     (1_000_000_000, secs + Duration::from_nanos(1)),
+    ///     (1 << 32, secs + Duration::from_millis(500)), // the extra tick is < 1 ns
+    /// ];
+    /// for (tsresol, since_epoch) in expected {
+    ///     let pkt = capture.next().unwrap().unwrap();
+    ///     let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+    ///     assert_eq!(iface.tsresol(), tsresol);
+    ///     assert_eq!(pkt.timestamp, Some(UNIX_EPOCH + since_epoch));
+    /// }
+    /// ```
+    pub fn tsresol(&self) -> u64 {
+        self.descr.if_tsresol
+    }
+
+    /// The time between the interface's epoch and a timestamp
+    ///
+    /// This is a way to get at packets' timestamps without involving
+    /// `SystemTime`, for targets where it isn't meaningful (eg. wasm32
+    /// without WASI).  The epoch is the unix epoch, unless a
+    /// [clock](Self::clock) has been configured.  [Corrections](Self::correction)
+    /// aren't applied.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # use std::time::Duration;
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 1_500_000_000_123_456, b"hello", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+    /// let since_epoch = iface.since_epoch(pkt.raw_timestamp.unwrap());
+    /// assert_eq!(since_epoch, Duration::from_micros(1_500_000_000_123_456));
+    /// ```
+    pub fn since_epoch(&self, ts: Timestamp) -> Duration {
+        let units_per_sec = match self.clock {
+            Some(clock) => clock.ticks_per_sec,
+            None => self.descr.if_tsresol,
+        };
+        ts.to_duration(units_per_sec)
+    }
+
+    /// The clock which was configured for this interface with
+    /// [`CaptureBuilder::timestamp_clock()`](crate::CaptureBuilder::timestamp_clock),
+    /// if any.  It takes precedence over [`tsresol()`](Self::tsresol).
+    pub fn clock(&self) -> Option<TimestampClock> {
+        self.clock
+    }
+
+    /// The clock correction which was configured for this interface with
+    /// [`CaptureBuilder::clock_correction()`](crate::CaptureBuilder::clock_correction),
+    /// if any
+    pub fn correction(&self) -> Option<ClockCorrection> {
+        self.correction
+    }
+
     pub fn snap_len(&self) -> Option<u32> {
         self.descr.snap_len
     }
@@ -275,35 +1193,82 @@ impl InterfaceInfo {
         &self.descr.if_description
     }
 
-    // TODO: Fix type
-    pub fn ipv4_addrs(&self) -> &[[u8; 8]] {
-        &self.descr.if_ipv4_addr
+    /// The IPv4 addresses of the interface, along with their netmasks
+    pub fn ipv4_addrs(&self) -> impl ExactSizeIterator<Item = Ipv4Network> + '_ {
+        self.descr.if_ipv4_addr.iter().map(|x| Ipv4Network {
+            addr: Ipv4Addr::new(x[0], x[1], x[2], x[3]),
+            netmask: Ipv4Addr::new(x[4], x[5], x[6], x[7]),
+        })
+    }
+
+    /// The IPv6 addresses of the interface, along with their prefix lengths
+    pub fn ipv6_addrs(&self) -> impl ExactSizeIterator<Item = Ipv6Network> + '_ {
+        self.descr.if_ipv6_addr.iter().map(|x| {
+            let mut addr = [0; 16];
+            addr.copy_from_slice(&x[..16]);
+            Ipv6Network {
+                addr: Ipv6Addr::from(addr),
+                prefix_len: x[16],
+            }
+        })
     }
 
-    // TODO: Fix type
-    pub fn ipv6_addrs(&self) -> &[[u8; 17]] {
-        &self.descr.if_ipv6_addr
+    /// This interface's identity across sections, given its ID
+    pub(crate) fn logical(&self, id: InterfaceId) -> LogicalInterface {
+        let mac_addr = self.mac_addr();
+        if self.descr.if_name.is_empty() && mac_addr.is_none() {
+            LogicalInterface::Anonymous(id)
+        } else {
+            LogicalInterface::Named {
+                link_type: self.descr.link_type,
+                name: self.descr.if_name.clone(),
+                mac_addr,
+            }
+        }
     }
 
-    // TODO: Fix type
-    pub fn mac_addr(&self) -> Option<[u8; 6]> {
-        self.descr.if_mac_addr
+    pub fn mac_addr(&self) -> Option<MacAddr> {
+        self.descr.if_mac_addr.map(MacAddr)
     }
 
-    // TODO: Fix type
-    pub fn eui_addr(&self) -> Option<[u8; 8]> {
-        self.descr.if_eui_addr
+    pub fn eui_addr(&self) -> Option<EuiAddr> {
+        self.descr.if_eui_addr.map(EuiAddr)
     }
 
     pub fn speed(&self) -> Option<u64> {
         self.descr.if_speed
     }
 
-    // TODO: Fix type
-    pub fn tzone(&self) -> Option<[u8; 4]> {
+    /// The number of seconds to add to this interface's timestamps to get
+    /// UTC, if the timestamps are in local time
+    ///
+    /// See [`InterfaceDescription::if_tzone`].  This isn't applied unless
+    /// [`CaptureBuilder::apply_tzone()`](crate::CaptureBuilder::apply_tzone)
+    /// is enabled.
+    pub fn tzone(&self) -> Option<i32> {
         self.descr.if_tzone
     }
 
+    /// Convert a local timestamp from this interface to UTC, using its
+    /// [`tzone()`](Self::tzone)
+    ///
+    /// If the interface has no time zone, the timestamp is returned as-is.
+    /// Returns `None` if the result can't be represented.
+    pub fn local_to_utc(&self, ts: SystemTime) -> Option<SystemTime> {
+        shift_secs(ts, i64::from(self.tzone().unwrap_or(0)))
+    }
+
+    /// Convert a UTC timestamp to the local time of this interface, using
+    /// its [`tzone()`](Self::tzone)
+    ///
+    /// The result is the `SystemTime` whose UTC reading matches the local
+    /// wall-clock time.  If the interface has no time zone, the timestamp
+    /// is returned as-is.  Returns `None` if the result can't be
+    /// represented.
+    pub fn utc_to_local(&self, ts: SystemTime) -> Option<SystemTime> {
+        shift_secs(ts, -i64::from(self.tzone().unwrap_or(0)))
+    }
+
     pub fn filter(&self) -> &str {
         &self.descr.if_filter
     }
@@ -312,13 +1277,30 @@ impl InterfaceInfo {
         &self.descr.if_os
     }
 
-    // TODO: Fix type
-    pub fn fcslen(&self) -> Option<[u8; 1]> {
-        self.descr.if_fcslen
+    /// The length of the frame check sequence, in bits
+    pub fn fcslen(&self) -> Option<u8> {
+        self.descr.if_fcslen.map(|[x]| x)
     }
 
-    // TODO: Fix type
-    pub fn tsoffset(&self) -> Option<[u8; 8]> {
+    /// The number of seconds to add to this interface's timestamps to get
+    /// absolute timestamps
+    ///
+    /// See [`InterfaceDescription::if_tsoffset`].  pcarp doesn't apply this
+    /// itself.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// let mut pcap = PcapngBuilder::new();
+    /// pcap.section(Endianness::Big)
+    ///     .interface(LinkType::ETHERNET, 0, &[(14, &(-90_i64).to_be_bytes())])
+    ///     .enhanced_packet(0, 0, b"", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+    /// assert_eq!(iface.tsoffset(), Some(-90));
+    /// assert!(iface.to_string().contains("tsoffset: -90 s"));
+    /// ```
+    pub fn tsoffset(&self) -> Option<i64> {
         self.descr.if_tsoffset
     }
 
@@ -326,16 +1308,20 @@ impl InterfaceInfo {
         &self.descr.if_hardware
     }
 
-    // TODO: Fix type
-    pub fn txspeed(&self) -> Option<[u8; 8]> {
+    pub fn txspeed(&self) -> Option<u64> {
         self.descr.if_txspeed
     }
 
-    // TODO: Fix type
-    pub fn rxspeed(&self) -> Option<[u8; 8]> {
+    pub fn rxspeed(&self) -> Option<u64> {
         self.descr.if_rxspeed
     }
 
+    /// IDB options which pcarp doesn't understand, as `(option_type, value)`
+    /// pairs
+    pub fn options(&self) -> &[(u16, Bytes)] {
+        &self.descr.options
+    }
+
     pub fn stats_timestamp(&self) -> Option<SystemTime> {
         self.stats
             .as_ref()
@@ -380,8 +1366,41 @@ impl InterfaceInfo {
 }
 
 impl fmt::Display for InterfaceInfo {
+    /// A multi-line, human-readable summary of the interface
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[
+    /// #         (2, b"eth0"),
+    /// #         (4, &[192, 168, 1, 2, 255, 255, 255, 0]),
+    /// #         (6, &[0x00, 0x1b, 0x21, 0x0a, 0xbc, 0xde]),
+    /// #         (8, &1_000_000_000_u64.to_le_bytes()),
+    /// #     ])
+    /// #     .enhanced_packet(0, 0, b"", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+    /// assert_eq!(
+    ///     iface.to_string(),
+    ///     "eth0\n\
+    ///      link type: ETHERNET\n\
+    ///      addrs: 192.168.1.2/24\n\
+    ///      MAC addr: 00:1b:21:0a:bc:de\n\
+    ///      speed: 1 Gbps\n",
+    /// );
+    /// ```
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{} ({})", self.name(), self.description())?;
+        if self.description().is_empty() {
+            writeln!(f, "{}", self.name())?;
+        } else {
+            writeln!(f, "{} ({})", self.name(), self.description())?;
+        }
+        writeln!(f, "link type: {:?}", self.link_type())?;
+        if let Some(x) = self.snap_len() {
+            writeln!(f, "snap len: {x}")?;
+        }
         if !self.filter().is_empty() {
             writeln!(f, "filter: {}", self.filter())?;
         }
@@ -391,62 +1410,199 @@ impl fmt::Display for InterfaceInfo {
         if !self.hardware().is_empty() {
             writeln!(f, "hardware: {}", self.hardware())?;
         }
-        if self.ipv4_addrs().len() + self.ipv6_addrs().len() > 0 {
-            writeln!(
-                f,
-                "ip addrs: {:?} {:?}",
-                self.ipv4_addrs(),
-                self.ipv6_addrs(),
-            )?;
+        let addrs = self
+            .ipv4_addrs()
+            .map(|x| x.to_string())
+            .chain(self.ipv6_addrs().map(|x| x.to_string()))
+            .collect::<Vec<_>>();
+        if !addrs.is_empty() {
+            writeln!(f, "addrs: {}", addrs.join(", "))?;
         }
         if let Some(x) = self.mac_addr() {
-            writeln!(f, "MAC addr: {x:?}")?;
+            writeln!(f, "MAC addr: {x}")?;
         }
         if let Some(x) = self.eui_addr() {
-            writeln!(f, "EUI addr: {x:?}")?;
+            writeln!(f, "EUI addr: {x}")?;
         }
         if let Some(x) = self.speed() {
-            writeln!(f, "speed: {x}")?;
+            writeln!(f, "speed: {}", Bps(x))?;
+        }
+        if let Some(x) = self.txspeed() {
+            writeln!(f, "tx speed: {}", Bps(x))?;
+        }
+        if let Some(x) = self.rxspeed() {
+            writeln!(f, "rx speed: {}", Bps(x))?;
         }
         if let Some(x) = self.tzone() {
-            writeln!(f, "tzone: {x:?}")?;
+            writeln!(f, "tzone: {x:+} s")?;
         }
         if let Some(x) = self.fcslen() {
-            writeln!(f, "fcslen: {x:?}")?;
+            writeln!(f, "FCS length: {x} bits")?;
         }
         if let Some(x) = self.tsoffset() {
-            writeln!(f, "tsoffset: {x:?}")?;
-        }
-        if let Some(x) = self.txspeed() {
-            writeln!(f, "txspeed: {x:?}")?;
-        }
-        if let Some(x) = self.rxspeed() {
-            writeln!(f, "rxspeed: {x:?}")?;
+            writeln!(f, "tsoffset: {x:+} s")?;
         }
         if let Some(x) = self.stats_timestamp() {
-            writeln!(f, "stats_timestamp: {x:?}")?; // humantime::Timestamp::from(x)
+            writeln!(f, "stats timestamp: {}", Rfc3339(x))?;
         }
         if let Some(x) = self.starttime() {
-            writeln!(f, "starttime: {x:?}")?; // humantime::Timestamp::from(x)
+            writeln!(f, "start time: {}", Rfc3339(x))?;
         }
         if let Some(x) = self.endtime() {
-            writeln!(f, "endtime: {x:?}")?; // humantime::Timestamp::from(x)
+            writeln!(f, "end time: {}", Rfc3339(x))?;
         }
         if let Some(x) = self.ifrecv() {
-            writeln!(f, "ifrecv: {x}")?;
+            writeln!(f, "received: {x}")?;
         }
         if let Some(x) = self.ifdrop() {
-            writeln!(f, "ifdrop: {x}")?;
+            writeln!(f, "dropped by interface: {x}")?;
         }
         if let Some(x) = self.filter_accept() {
-            writeln!(f, "filter_accept: {x}")?;
+            writeln!(f, "accepted by filter: {x}")?;
         }
         if let Some(x) = self.osdrop() {
-            writeln!(f, "osdrop: {x}")?;
+            writeln!(f, "dropped by OS: {x}")?;
         }
         if let Some(x) = self.usrdeliv() {
-            writeln!(f, "usrdeliv: {x}")?;
+            writeln!(f, "delivered to user: {x}")?;
         }
         Ok(())
     }
 }
+
+#[cfg(feature = "json")]
+impl InterfaceInfo {
+    /// A machine-readable summary of the interface
+    ///
+    /// This is only available with the `json` feature.  Addresses are
+    /// rendered as strings (in the same format as `Display`), timestamps
+    /// as RFC 3339 strings, and speeds in bits per second.  Missing values
+    /// are `null`.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0"), (6, &[0, 0x1b, 0x21, 0x0a, 0xbc, 0xde])])
+    /// #     .enhanced_packet(0, 0, b"", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+    /// let json = iface.to_json();
+    /// assert_eq!(json["name"], "eth0");
+    /// assert_eq!(json["mac_addr"], "00:1b:21:0a:bc:de");
+    /// assert_eq!(json["tsresol"], 1_000_000);
+    /// assert!(json["speed"].is_null());
+    /// ```
+    pub fn to_json(&self) -> serde_json::Value {
+        let non_empty = |x: &str| (!x.is_empty()).then(|| x.to_owned());
+        let ts = |x: Option<SystemTime>| x.map(|x| Rfc3339(x).to_string());
+        serde_json::json!({
+            "name": non_empty(self.name()),
+            "description": non_empty(self.description()),
+            "link_type": self.link_type().to_u16(),
+            "link_type_name": format!("{:?}", self.link_type()),
+            "snap_len": self.snap_len(),
+            "tsresol": self.tsresol(),
+            "filter": non_empty(self.filter()),
+            "os": non_empty(self.os()),
+            "hardware": non_empty(self.hardware()),
+            "ipv4_addrs": self.ipv4_addrs().map(|x| x.to_string()).collect::<Vec<_>>(),
+            "ipv6_addrs": self.ipv6_addrs().map(|x| x.to_string()).collect::<Vec<_>>(),
+            "mac_addr": self.mac_addr().map(|x| x.to_string()),
+            "eui_addr": self.eui_addr().map(|x| x.to_string()),
+            "speed": self.speed(),
+            "txspeed": self.txspeed(),
+            "rxspeed": self.rxspeed(),
+            "fcslen": self.fcslen(),
+            "stats": self.stats.as_ref().map(|_| serde_json::json!({
+                "timestamp": ts(self.stats_timestamp()),
+                "starttime": ts(self.starttime()),
+                "endtime": ts(self.endtime()),
+                "ifrecv": self.ifrecv(),
+                "ifdrop": self.ifdrop(),
+                "filter_accept": self.filter_accept(),
+                "osdrop": self.osdrop(),
+                "usrdeliv": self.usrdeliv(),
+            })),
+        })
+    }
+}
+
+/// An IPv4 address of an interface, along with the netmask of its network
+///
+/// Displayed in CIDR notation (eg. `192.168.1.2/24`), unless the netmask
+/// isn't contiguous.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ipv4Network {
+    pub addr: Ipv4Addr,
+    pub netmask: Ipv4Addr,
+}
+
+impl Ipv4Network {
+    /// The length of the network prefix, if the netmask is contiguous
+    pub fn prefix_len(&self) -> Option<u8> {
+        let mask = u32::from(self.netmask);
+        let ones = mask.leading_ones();
+        (ones + mask.trailing_zeros() >= 32).then_some(ones as u8)
+    }
+}
+
+impl fmt::Display for Ipv4Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.prefix_len() {
+            Some(x) => write!(f, "{}/{x}", self.addr),
+            None => write!(f, "{}/{}", self.addr, self.netmask),
+        }
+    }
+}
+
+/// An IPv6 address of an interface, along with the length of its network
+/// prefix
+///
+/// Displayed in CIDR notation (eg. `fe80::1/64`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ipv6Network {
+    pub addr: Ipv6Addr,
+    pub prefix_len: u8,
+}
+
+impl fmt::Display for Ipv6Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// A 48-bit MAC address
+///
+/// Displayed as colon-separated hex (eg. `00:1b:21:0a:bc:de`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_hex_colons(f, &self.0)
+    }
+}
+
+/// A 64-bit EUI address
+///
+/// Displayed as colon-separated hex, like a [`MacAddr`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EuiAddr(pub [u8; 8]);
+
+impl fmt::Display for EuiAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_hex_colons(f, &self.0)
+    }
+}
+
+fn fmt_hex_colons(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    for (i, x) in bytes.iter().enumerate() {
+        if i > 0 {
+            f.write_str(":")?;
+        }
+        write!(f, "{x:02x}")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,379 @@
+/*! Writing classic (libpcap-format) pcap files
+
+pcarp doesn't read the classic pcap format, but many tools still can't read
+anything else.  A [`PcapWriter`] down-converts packets into classic pcap
+records.
+
+Classic pcap files only have a single link type, so all the packets written
+to a `PcapWriter` must come from interfaces of the same type.  If your
+pcapng has interfaces of several types, you can use a [`SplitPcapWriter`],
+which writes a separate file for each link type.
+
+If you'd rather not write a file at all, a [`PcapStream`] converts a
+pcapng stream into a classic pcap stream on the fly, so you can pipe it into
+a tool which only accepts classic pcap on stdin.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::legacy::{PcapWriter, TsPrecision};
+use pcarp::Capture;
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 1_500_000_000_000_000, b"hello", &[]);
+# let file = pcap.build();
+let mut capture = Capture::new(file);
+let mut wtr = PcapWriter::new(vec![], TsPrecision::Micro);
+while let Some(x) = capture.next_with_iface() {
+    let (pkt, iface) = x.unwrap();
+    wtr.write(&pkt, iface.as_deref()).unwrap();
+}
+let out = wtr.into_inner();
+assert_eq!(out.len(), 24 + 16 + 5); // header + record header + data
+```
+*/
+
+use crate::iface::{InterfaceInfo, LinkType};
+use crate::writer::OptionError;
+use crate::{Capture, Packet};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::time::{Duration, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::*;
+
+/// The snap length used when the interface doesn't specify one.  This is
+/// the same as libpcap's `MAXIMUM_SNAPLEN`.
+const DEFAULT_SNAP_LEN: u32 = 262_144;
+
+/// An error which occurred while writing packets to a file
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("Packet has link type {found:?}, but the file has link type {expected:?}")]
+    LinkTypeMismatch { expected: LinkType, found: LinkType },
+    #[error("Packet has no interface, so its link type is unknown")]
+    MissingInterface,
+    #[error("Packet timestamp can't be represented in a classic pcap")]
+    TimestampOutOfRange,
+    #[error("Invalid options")]
+    InvalidOption(#[from] OptionError),
+    #[error("Couldn't read the capture")]
+    Capture(#[from] crate::Error),
+    #[error("IO error")]
+    IO(#[from] io::Error),
+}
+
+/// The resolution of the timestamps in a classic pcap
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TsPrecision {
+    /// Microsecond timestamps.  This is the original format, and is
+    /// understood by everything.
+    #[default]
+    Micro,
+    /// Nanosecond timestamps.  Not all tools understand these.
+    Nano,
+}
+
+impl TsPrecision {
+    fn magic(self) -> u32 {
+        match self {
+            TsPrecision::Micro => 0xA1B2_C3D4,
+            TsPrecision::Nano => 0xA1B2_3C4D,
+        }
+    }
+
+    fn subsec(self, ts: Duration) -> u32 {
+        match self {
+            TsPrecision::Micro => ts.subsec_micros(),
+            TsPrecision::Nano => ts.subsec_nanos(),
+        }
+    }
+}
+
+/// Writes packets to a classic pcap file
+///
+/// The file header is written along with the first packet, and its link
+/// type and snap length are taken from that packet's interface.
+#[derive(Debug)]
+pub struct PcapWriter<W> {
+    wtr: W,
+    precision: TsPrecision,
+    /// `None` until the file header has been written
+    link_type: Option<LinkType>,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Create a writer which writes to the given `Write`r
+    pub fn new(wtr: W, precision: TsPrecision) -> PcapWriter<W> {
+        PcapWriter {
+            wtr,
+            precision,
+            link_type: None,
+        }
+    }
+
+    /// The link type of the file, if any packets have been written yet
+    pub fn link_type(&self) -> Option<LinkType> {
+        self.link_type
+    }
+
+    /// Write a packet, which was captured on the given interface
+    ///
+    /// The interface info is needed for the link type; see
+    /// [`Capture::next_with_iface()`](crate::Capture::next_with_iface).
+    /// Packets without a timestamp are written with a timestamp of zero.
+    ///
+    /// If the packet's link type doesn't match the file's, nothing is
+    /// written and `ExportError::LinkTypeMismatch` is returned.
+    pub fn write(
+        &mut self,
+        pkt: &Packet,
+        iface: Option<&InterfaceInfo>,
+    ) -> Result<(), ExportError> {
+        let iface = iface.ok_or(ExportError::MissingInterface)?;
+        let ts = match pkt.timestamp {
+            Some(ts) => ts,
+            None => {
+                debug!("Packet has no timestamp; writing it with a timestamp of zero");
+                UNIX_EPOCH
+            }
+        };
+        let since_epoch = ts
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| ExportError::TimestampOutOfRange)?;
+        let secs =
+            u32::try_from(since_epoch.as_secs()).map_err(|_| ExportError::TimestampOutOfRange)?;
+        match self.link_type {
+            Some(expected) if expected != iface.link_type() => {
+                return Err(ExportError::LinkTypeMismatch {
+                    expected,
+                    found: iface.link_type(),
+                })
+            }
+            Some(_) => (),
+            None => self.write_header(iface)?,
+        }
+        let mut hdr = [0; 16];
+        hdr[0..4].copy_from_slice(&secs.to_le_bytes());
+        hdr[4..8].copy_from_slice(&self.precision.subsec(since_epoch).to_le_bytes());
+        hdr[8..12].copy_from_slice(&(pkt.data.len() as u32).to_le_bytes());
+        hdr[12..16].copy_from_slice(&pkt.orig_len.max(pkt.data.len() as u32).to_le_bytes());
+        self.wtr.write_all(&hdr)?;
+        self.wtr.write_all(&pkt.data)?;
+        Ok(())
+    }
+
+    fn write_header(&mut self, iface: &InterfaceInfo) -> io::Result<()> {
+        let snap_len = match iface.snap_len() {
+            Some(0) | None => DEFAULT_SNAP_LEN,
+            Some(x) => x,
+        };
+        let mut hdr = [0; 24];
+        hdr[0..4].copy_from_slice(&self.precision.magic().to_le_bytes());
+        hdr[4..6].copy_from_slice(&2_u16.to_le_bytes());
+        hdr[6..8].copy_from_slice(&4_u16.to_le_bytes());
+        // thiszone and sigfigs are always zero
+        hdr[16..20].copy_from_slice(&snap_len.to_le_bytes());
+        let link_type = u32::from(iface.link_type().to_u16());
+        hdr[20..24].copy_from_slice(&link_type.to_le_bytes());
+        self.wtr.write_all(&hdr)?;
+        self.link_type = Some(iface.link_type());
+        Ok(())
+    }
+
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.wtr.flush()
+    }
+
+    /// Get a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.wtr
+    }
+
+    /// Unwrap the `PcapWriter`, returning the underlying writer
+    pub fn into_inner(self) -> W {
+        self.wtr
+    }
+}
+
+/// Writes packets to a set of classic pcap files, one per link type
+///
+/// A new file is opened (using the given function) whenever a packet with
+/// a previously-unseen link type is written.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::legacy::{SplitPcapWriter, TsPrecision};
+/// use pcarp::Capture;
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .interface(LinkType::RAW, 0, &[])
+/// #     .enhanced_packet(0, 0, b"hello", &[])
+/// #     .enhanced_packet(1, 0, b"world", &[]);
+/// # let file = pcap.build();
+/// let mut capture = Capture::new(file);
+/// let mut wtr = SplitPcapWriter::new(TsPrecision::Micro, |_| Ok(vec![]));
+/// while let Some(x) = capture.next_with_iface() {
+///     let (pkt, iface) = x.unwrap();
+///     wtr.write(&pkt, iface.as_deref()).unwrap();
+/// }
+/// assert_eq!(wtr.into_inner().len(), 2);
+/// ```
+pub struct SplitPcapWriter<W, F> {
+    open: F,
+    precision: TsPrecision,
+    writers: HashMap<LinkType, PcapWriter<W>>,
+}
+
+impl<W: Write, F: FnMut(LinkType) -> io::Result<W>> SplitPcapWriter<W, F> {
+    /// Create a writer which opens new files using the given function
+    pub fn new(precision: TsPrecision, open: F) -> SplitPcapWriter<W, F> {
+        SplitPcapWriter {
+            open,
+            precision,
+            writers: HashMap::new(),
+        }
+    }
+
+    /// Write a packet to the file for its link type
+    pub fn write(
+        &mut self,
+        pkt: &Packet,
+        iface: Option<&InterfaceInfo>,
+    ) -> Result<(), ExportError> {
+        let link_type = iface.ok_or(ExportError::MissingInterface)?.link_type();
+        let wtr = match self.writers.get_mut(&link_type) {
+            Some(wtr) => wtr,
+            None => {
+                debug!("Opening a new pcap for {link_type:?}");
+                let wtr = PcapWriter::new((self.open)(link_type)?, self.precision);
+                self.writers.entry(link_type).or_insert(wtr)
+            }
+        };
+        wtr.write(pkt, iface)
+    }
+
+    /// Flush all the underlying writers
+    pub fn flush(&mut self) -> io::Result<()> {
+        for wtr in self.writers.values_mut() {
+            wtr.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Unwrap the `SplitPcapWriter`, returning the underlying writers
+    pub fn into_inner(self) -> HashMap<LinkType, W> {
+        self.writers
+            .into_iter()
+            .map(|(link_type, wtr)| (link_type, wtr.into_inner()))
+            .collect()
+    }
+}
+
+/// Converts a pcapng stream into a classic pcap stream, on the fly
+///
+/// This is an iterator of chunks of classic pcap: the first one contains
+/// the file header, and each one contains a single packet.  It also
+/// implements `Read`, so it can be passed to `io::copy()`.
+///
+/// As with a [`PcapWriter`], the file's link type is taken from the first
+/// packet, and the file header isn't produced until that packet has been
+/// read.  When iterating, packets of other link types are reported as
+/// `ExportError::LinkTypeMismatch`, and errors in the pcapng as
+/// `ExportError::Capture`; the stream carries on afterwards.  When reading,
+/// these packets are skipped (with a warning), and only IO errors and fatal
+/// errors in the pcapng are returned.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::legacy::{PcapStream, TsPrecision};
+/// use pcarp::Capture;
+/// use std::io;
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .interface(LinkType::RAW, 0, &[])
+/// #     .enhanced_packet(0, 0, b"hello", &[])
+/// #     .enhanced_packet(1, 0, b"not ethernet", &[])
+/// #     .enhanced_packet(0, 0, b"world", &[]);
+/// # let stdin = pcap.build();
+/// let capture = Capture::new(stdin);
+/// let mut stream = PcapStream::new(capture, TsPrecision::Micro);
+/// let mut stdout = vec![];
+/// io::copy(&mut stream, &mut stdout).unwrap();
+/// assert_eq!(stdout.len(), 24 + 2 * (16 + 5));
+/// ```
+pub struct PcapStream<R> {
+    capture: Capture<R>,
+    wtr: PcapWriter<Vec<u8>>,
+    /// A chunk which has been partially read
+    pending: Bytes,
+}
+
+impl<R> PcapStream<R> {
+    /// Convert the packets of the given capture
+    pub fn new(capture: Capture<R>, precision: TsPrecision) -> PcapStream<R> {
+        PcapStream {
+            capture,
+            wtr: PcapWriter::new(vec![], precision),
+            pending: Bytes::new(),
+        }
+    }
+
+    /// The link type of the output, if any packets have been read yet
+    pub fn link_type(&self) -> Option<LinkType> {
+        self.wtr.link_type()
+    }
+
+    /// Unwrap the `PcapStream`, returning the underlying `Capture`
+    pub fn into_inner(self) -> Capture<R> {
+        self.capture
+    }
+}
+
+impl<R: Read> Iterator for PcapStream<R> {
+    type Item = Result<Bytes, ExportError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pkt = match self.capture.next()? {
+            Ok(pkt) => pkt,
+            Err(e) => return Some(Err(e.into())),
+        };
+        let iface = pkt
+            .interface
+            .and_then(|id| self.capture.lookup_interface(id));
+        // The writer only ever writes to its buffer if it succeeds
+        let x = self.wtr.write(&pkt, iface);
+        let chunk = Bytes::from(std::mem::take(&mut self.wtr.wtr));
+        Some(x.map(|()| chunk))
+    }
+}
+
+impl<R: Read> Read for PcapStream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() {
+            self.pending = match self.next() {
+                None => return Ok(0),
+                Some(Ok(x)) => x,
+                Some(Err(ExportError::IO(e))) => return Err(e),
+                Some(Err(ExportError::Capture(crate::Error::IO(e)))) => return Err(e),
+                Some(Err(ExportError::Capture(e @ crate::Error::Frame(_)))) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, e))
+                }
+                Some(Err(e)) => {
+                    warn!("Skipping a packet: {e}");
+                    continue;
+                }
+            };
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending.split_to(n));
+        Ok(n)
+    }
+}
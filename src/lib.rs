@@ -35,15 +35,79 @@ for pkt in Capture::new(file) {
 ```
 */
 
+mod annotations;
 pub mod block;
+#[cfg(feature = "tokio-codec")]
+pub mod codec;
+#[cfg(feature = "corpus")]
+pub mod corpus;
+pub mod decompress;
+pub mod demux;
+#[cfg(any(feature = "etherparse", feature = "pnet"))]
+pub mod dissect;
+#[cfg(feature = "json")]
+pub mod dump;
+pub mod erf;
+pub mod flatten;
+#[cfg(feature = "flows")]
+pub mod flows;
+#[cfg(feature = "hash")]
+pub mod hash;
+pub mod human;
 pub mod iface;
+pub mod legacy;
+mod meta;
+#[cfg(feature = "netmon")]
+pub mod netmon;
+mod poll;
+pub mod read_at;
+#[cfg(feature = "reassembly")]
+pub mod reassembly;
+pub mod replay;
+pub mod rpcap;
+#[cfg(feature = "search")]
+pub mod search;
+pub mod section;
+#[cfg(feature = "sidecar")]
+pub mod sidecar;
+pub mod sniff;
+pub mod source;
+pub mod stats;
+#[cfg(feature = "testgen")]
+pub mod testgen;
+mod truncate;
+#[cfg(feature = "tshark")]
+pub mod tshark;
+mod window;
+pub mod writer;
+#[cfg(feature = "zstd-seekable")]
+pub mod zstd_seekable;
 
-use crate::block::{Block, BlockError, BlockReader, BlockType, FrameError, NameResolution};
-use crate::iface::{InterfaceId, InterfaceInfo};
+pub use crate::annotations::Annotations;
+pub use crate::meta::{MetadataIter, PacketMeta};
+pub use crate::poll::PollUntil;
+pub use crate::truncate::{truncate, TruncateLimit, Truncated};
+pub use crate::window::{extract_window, Extracted};
+
+use crate::block::{
+    Block, BlockError, BlockReader, BlockType, Diagnostic, DiagnosticSink, Endianness, FrameError,
+    InterfaceDescription, Malformation, MalformationPolicy, NameResolution, PaddingPolicy,
+    ParseContext, ParserRegistry, Quirks, Timestamp, VersionPolicy,
+};
+use crate::human::Rfc3339;
+use crate::iface::{
+    ClockCorrection, InterfaceFilter, InterfaceId, InterfaceInfo, LogicalInterface, RawInterfaceId,
+    SimpleTimestampPolicy, TimestampClock, TimestampExtractor, TimestampPolicy,
+};
+use crate::section::SectionSummary;
 use bytes::Bytes;
 use std::{
+    collections::HashMap,
+    fmt,
     io::{Read, Seek},
-    time::SystemTime,
+    ops::{Bound, RangeBounds},
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
 use thiserror::Error;
 use tracing::*;
@@ -76,6 +140,154 @@ pub enum Error {
     IO(#[from] std::io::Error),
 }
 
+/// How much damage a [`Capture`] has had to work around
+///
+/// See [`Capture::recovery_stats()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryStats {
+    /// The number of corrupt blocks which were skipped.  Each of these was
+    /// reported as an `Error::Block`.
+    pub blocks_skipped: u64,
+    /// The total length of the corrupt blocks, in bytes
+    pub bytes_skipped: u64,
+    /// The number of blocks which were skipped because they were truncated
+    pub truncated_blocks: u64,
+    /// The number of packets which were skipped because their timestamps
+    /// overflowed (with [`TimestampPolicy::Error`])
+    pub timestamp_overflows: u64,
+    /// The number of blocks of an unsupported type, which were ignored
+    pub blocks_ignored: u64,
+    /// The number of fatal framing errors (zero or one)
+    pub frame_errors: u64,
+}
+
+impl RecoveryStats {
+    fn record(&mut self, err: &Error, len: u64) {
+        match err {
+            Error::Block(_, e) => {
+                self.blocks_skipped += 1;
+                self.bytes_skipped += len;
+                match e {
+                    BlockError::TruncatedBlock
+                    | BlockError::Malformed(Malformation::FieldOverrun { .. }) => {
+                        self.truncated_blocks += 1
+                    }
+                    BlockError::TimestampOverflow => self.timestamp_overflows += 1,
+                    BlockError::Malformed(_) => (),
+                }
+            }
+            Error::Frame(_) => self.frame_errors += 1,
+            Error::IO(_) => (),
+        }
+    }
+}
+
+/// How much work a [`Capture`] has done
+///
+/// See [`Capture::counters()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Counters {
+    /// The number of blocks read, by type.  This includes corrupt blocks,
+    /// and blocks which were skipped by the interface filter.
+    pub blocks: HashMap<BlockType, u64>,
+    /// The number of bytes consumed
+    pub bytes: u64,
+    /// The number of packets returned
+    pub packets: u64,
+    /// The number of errors returned
+    pub errors: u64,
+}
+
+impl Counters {
+    /// The number of blocks of the given type which have been read
+    pub fn blocks_of(&self, block_type: BlockType) -> u64 {
+        self.blocks.get(&block_type).copied().unwrap_or(0)
+    }
+
+    /// The total number of blocks which have been read
+    pub fn total_blocks(&self) -> u64 {
+        self.blocks.values().sum()
+    }
+
+    fn record_block(&mut self, block_type: BlockType, len: u64) {
+        *self.blocks.entry(block_type).or_insert(0) += 1;
+        self.bytes += len;
+    }
+}
+
+/// Roughly how much memory a [`Capture`] is holding on to
+///
+/// See [`Capture::memory_usage()`].  The figures are estimates, in bytes:
+/// they count the data which pcarp keeps, but not the allocator's
+/// overhead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// The size of the read buffer
+    pub buffer: usize,
+    /// The interface descriptions retained for the current and earlier
+    /// sections
+    pub interfaces: usize,
+    /// The name resolution blocks retained for the current section
+    pub name_resolutions: usize,
+}
+
+impl MemoryUsage {
+    /// The sum of all the figures
+    pub fn total(&self) -> usize {
+        self.buffer + self.interfaces + self.name_resolutions
+    }
+}
+
+fn options_size(options: &[(u16, Bytes)]) -> usize {
+    options
+        .iter()
+        .map(|(_, x)| std::mem::size_of::<(u16, Bytes)>() + x.len())
+        .sum()
+}
+
+fn interface_size(iface: &InterfaceInfo) -> usize {
+    let descr = &iface.descr;
+    std::mem::size_of::<InterfaceInfo>()
+        + descr.if_name.len()
+        + descr.if_description.len()
+        + descr.if_filter.len()
+        + descr.if_os.len()
+        + descr.if_ipv4_addr.len() * 8
+        + descr.if_ipv6_addr.len() * 17
+        + options_size(&descr.options)
+        + iface.stats.as_ref().map_or(0, |x| options_size(&x.options))
+}
+
+fn name_resolution_size(nrb: &NameResolution) -> usize {
+    std::mem::size_of::<NameResolution>()
+        + nrb.record_values.len()
+        + nrb.ns_dnsname.len()
+        + options_size(&nrb.options)
+}
+
+/// How far a [`Capture`] has got
+///
+/// See [`CaptureBuilder::on_progress()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of bytes consumed so far
+    pub bytes_read: u64,
+    /// The number of packets returned so far
+    pub packets: u64,
+    /// The timestamp of the packet which was just read
+    pub timestamp: Option<SystemTime>,
+}
+
+/// A callback registered with `on_progress()`
+#[derive(Clone)]
+struct ProgressFn(Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl fmt::Debug for ProgressFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressFn(..)")
+    }
+}
+
 /// A captured packet
 ///
 /// The pcapng spec defines three kinds of packets
@@ -83,62 +295,1881 @@ pub enum Error {
 /// [`EnhancedPacket`][crate::block::EnhancedPacket], and
 /// [`ObsoletePacket`][crate::block::ObsoletePacket]).  This type provides
 /// a unified view which can represent any of these three.
+///
+/// A `Packet` doesn't borrow from the [`Capture`] which read it: the data
+/// is reference-counted.  This means it can outlive the `Capture`, and be
+/// sent to (and shared between) other threads.
+///
+/// New fields may be added in future, so packets should be constructed with
+/// [`Packet::new()`].
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct Packet {
     /// The time at which the packet was captured.  The resolution depends on the interface.
+    ///
+    /// pcarp never reads the system clock: this is computed from
+    /// `raw_timestamp`.  To avoid `SystemTime` altogether, use
+    /// [`InterfaceInfo::since_epoch()`](crate::iface::InterfaceInfo::since_epoch).
     pub timestamp: Option<SystemTime>,
+    /// The timestamp exactly as it appeared in the pcap, in units of the
+    /// interface's [timestamp resolution](crate::iface::InterfaceInfo::tsresol).
+    pub raw_timestamp: Option<Timestamp>,
     /// The interface used to capture this packet.
     pub interface: Option<InterfaceId>,
     /// The raw packet data.
     pub data: Bytes,
+    /// The length of the packet as it appeared on the wire.  This may be
+    /// greater than `data.len()`, if the packet was truncated when it was
+    /// captured.
+    pub orig_len: u32,
+    /// The timestamp of the first timestamped packet in this packet's
+    /// section.
+    pub section_start: Option<SystemTime>,
+    /// Whether `timestamp` was made up by pcarp, rather than read from the
+    /// pcap.  See [`SimpleTimestampPolicy`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub synthetic_timestamp: bool,
+    /// Notes attached to the packet by your own code.  This is always
+    /// empty when the packet is read.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub annotations: Annotations,
+}
+
+impl Packet {
+    /// A packet with the given data and nothing else
+    ///
+    /// `orig_len` is set to the length of the data (saturating at
+    /// `u32::MAX`); the other fields are empty.  Set them afterwards as
+    /// needed:
+    ///
+    /// ```
+    /// # use pcarp::{iface::InterfaceId, Packet};
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// let mut pkt = Packet::new(&b"hello"[..]);
+    /// pkt.timestamp = Some(UNIX_EPOCH + Duration::from_secs(1));
+    /// pkt.interface = Some(InterfaceId(0, 0));
+    /// assert_eq!(pkt.orig_len, 5);
+    /// ```
+    pub fn new(data: impl Into<Bytes>) -> Packet {
+        let data = data.into();
+        Packet {
+            timestamp: None,
+            raw_timestamp: None,
+            interface: None,
+            orig_len: u32::try_from(data.len()).unwrap_or(u32::MAX),
+            data,
+            section_start: None,
+            synthetic_timestamp: false,
+            annotations: Annotations::new(),
+        }
+    }
+
+    /// The time elapsed since the first packet of the section
+    ///
+    /// This is exact: no floating-point conversions are involved.
+    ///
+    /// Returns `None` if either packet lacks a timestamp, or if this packet
+    /// is timestamped earlier than the first one (which can happen in
+    /// captures taken from multiple interfaces).
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # use std::time::Duration;
+    /// let mut pcap = PcapngBuilder::new();
+    /// pcap.section(Endianness::Little)
+    ///     .interface(LinkType::ETHERNET, 0, &[])
+    ///     .enhanced_packet(0, 1_000_000, b"first", &[])
+    ///     .enhanced_packet(0, 3_500_000, b"second", &[]);
+    /// let elapsed = Capture::new(pcap.build())
+    ///     .map(|pkt| pkt.unwrap().elapsed().unwrap())
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(elapsed, [Duration::ZERO, Duration::from_millis(2500)]);
+    /// ```
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.timestamp?.duration_since(self.section_start?).ok()
+    }
+
+    /// A sub-range of the packet's data
+    ///
+    /// The data isn't copied: the returned `Bytes` shares the packet's
+    /// buffer (which, for a [`Capture::from_bytes()`], is the capture
+    /// itself).  This makes it cheap to hand the headers of a packet to one
+    /// decoder and the rest to another.
+    ///
+    /// Returns `None` if the range extends beyond the end of the captured
+    /// data.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"headerpayload", &[]);
+    /// let pkt = Capture::new(pcap.build()).next().unwrap().unwrap();
+    /// assert_eq!(&pkt.slice(..6).unwrap()[..], b"header");
+    /// assert_eq!(&pkt.payload_from(6).unwrap()[..], b"payload");
+    /// assert_eq!(pkt.payload_from(6).unwrap().as_ptr(), pkt.data[6..].as_ptr());
+    /// assert!(pkt.slice(6..20).is_none());
+    /// ```
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Option<Bytes> {
+        let start = match range.start_bound() {
+            Bound::Included(&x) => x,
+            Bound::Excluded(&x) => x.checked_add(1)?,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&x) => x.checked_add(1)?,
+            Bound::Excluded(&x) => x,
+            Bound::Unbounded => self.data.len(),
+        };
+        if start > end || end > self.data.len() {
+            return None;
+        }
+        Some(self.data.slice(start..end))
+    }
+
+    /// The packet's data, starting at the given offset
+    ///
+    /// Like [`slice()`](Self::slice), this doesn't copy the data.  Returns
+    /// `None` if the offset is beyond the end of the captured data.
+    pub fn payload_from(&self, offset: usize) -> Option<Bytes> {
+        self.slice(offset..)
+    }
+
+    /// The packet's data, formatted as a hex dump
+    ///
+    /// Each line shows the offset, 16 bytes in hex, and the same bytes as
+    /// ASCII (with non-printable bytes replaced by `.`), like `tshark -x`.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"GET /index.html\r\n", &[]);
+    /// let pkt = Capture::new(pcap.build()).next().unwrap().unwrap();
+    /// assert_eq!(
+    ///     pkt.hexdump().to_string(),
+    ///     "0000  47 45 54 20 2f 69 6e 64 65 78 2e 68 74 6d 6c 0d   GET /index.html.\n\
+    ///      0010  0a                                                .\n",
+    /// );
+    /// ```
+    pub fn hexdump(&self) -> HexDump<'_> {
+        HexDump(&self.data)
+    }
+
+    /// A summary of the packet followed by a hex dump, using the name of
+    /// the interface it was captured on
+    ///
+    /// The `Display` impl of `Packet` is the same, but shows the interface
+    /// ID instead, since a `Packet` doesn't know its interface's name.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+    /// #     .enhanced_packet(0, 1_700_000_000_000_000, b"GET", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// let iface = capture.lookup_interface(pkt.interface.unwrap());
+    /// assert_eq!(
+    ///     pkt.display_with(iface).to_string(),
+    ///     "2023-11-14T22:13:20.000000000Z eth0 3 bytes\n\
+    ///      0000  47 45 54                                          GET\n",
+    /// );
+    /// assert_eq!(
+    ///     pkt.to_string().lines().next().unwrap(),
+    ///     "2023-11-14T22:13:20.000000000Z interface 1/0 3 bytes",
+    /// );
+    /// ```
+    pub fn display_with<'a>(&'a self, iface: Option<&'a InterfaceInfo>) -> PacketDisplay<'a> {
+        PacketDisplay {
+            packet: self,
+            iface,
+        }
+    }
+}
+
+impl fmt::Display for Packet {
+    /// A one-line summary (timestamp, interface, and lengths) followed by a
+    /// hex dump of the data; see [`Packet::display_with()`]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display_with(None).fmt(f)
+    }
+}
+
+/// A packet's data formatted as a hex dump; see [`Packet::hexdump()`]
+#[derive(Debug, Clone, Copy)]
+pub struct HexDump<'a>(&'a [u8]);
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, line) in self.0.chunks(16).enumerate() {
+            write!(f, "{:04x} ", i * 16)?;
+            for x in line {
+                write!(f, " {x:02x}")?;
+            }
+            write!(f, "{:1$}   ", "", 3 * (16 - line.len()))?;
+            for &x in line {
+                let c = if x.is_ascii_graphic() || x == b' ' {
+                    x as char
+                } else {
+                    '.'
+                };
+                write!(f, "{c}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A packet formatted for humans; see [`Packet::display_with()`]
+#[derive(Debug, Clone, Copy)]
+pub struct PacketDisplay<'a> {
+    packet: &'a Packet,
+    iface: Option<&'a InterfaceInfo>,
+}
+
+impl fmt::Display for PacketDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pkt = self.packet;
+        match pkt.timestamp {
+            Some(x) => write!(f, "{}", Rfc3339(x))?,
+            None => write!(f, "-")?,
+        }
+        match (self.iface.map(|x| x.name()), pkt.interface) {
+            (Some(name), _) if !name.is_empty() => write!(f, " {name}")?,
+            (_, Some(InterfaceId(section, idx))) => write!(f, " interface {section}/{idx}")?,
+            (_, None) => (),
+        }
+        write!(f, " {} bytes", pkt.data.len())?;
+        if u64::from(pkt.orig_len) != pkt.data.len() as u64 {
+            write!(f, " ({} on the wire)", pkt.orig_len)?;
+        }
+        writeln!(f)?;
+        pkt.hexdump().fmt(f)
+    }
+}
+
+/// Configures a [`Capture`] with non-default settings
+///
+/// ```
+/// # use pcarp::{block::InterfaceDescription, iface::LinkType, CaptureBuilder};
+/// let mut descr = InterfaceDescription::new(LinkType::ETHERNET);
+/// descr.snap_len = Some(1514);
+/// let pcap = CaptureBuilder::new()
+///     .interface(descr)
+///     .build(std::io::empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CaptureBuilder {
+    config: Config,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Config {
+    /// Interfaces to fall back on when a section doesn't define its own
+    interfaces: Vec<Arc<InterfaceInfo>>,
+    /// The byte order of blocks before the first section header
+    initial_endianness: Option<Endianness>,
+    version_policy: VersionPolicy,
+    padding_policy: PaddingPolicy,
+    malformation_policy: MalformationPolicy,
+    quirks: Quirks,
+    diagnostics: Option<DiagnosticSink>,
+    low_latency: bool,
+    metadata_only: bool,
+    /// Merge consecutive sections with the same interfaces
+    merge_sections: bool,
+    collect_malformations: bool,
+    /// Stop after returning this many packets
+    packet_limit: Option<u64>,
+    /// Stop after consuming this many bytes
+    byte_limit: Option<u64>,
+    timestamp_policy: TimestampPolicy,
+    simple_timestamps: SimpleTimestampPolicy,
+    parsers: ParserRegistry,
+    interface_filter: Option<InterfaceFilter>,
+    /// Timestamp clocks, keyed by interface name
+    clocks: HashMap<String, TimestampClock>,
+    /// Clock corrections, keyed by interface name
+    corrections: HashMap<String, ClockCorrection>,
+    /// Timestamp resolutions which replace those in the IDBs, keyed by
+    /// interface name
+    tsresols: HashMap<String, u64>,
+    /// Convert local timestamps to UTC, according to `if_tzone`
+    apply_tzone: bool,
+    /// Packet options to take timestamps from, in order of preference
+    timestamp_options: Vec<(u16, TimestampExtractor)>,
+    /// Called every time this many bytes have been consumed
+    progress: Option<(u64, ProgressFn)>,
+}
+
+impl CaptureBuilder {
+    /// Create a builder with the default settings
+    pub fn new() -> CaptureBuilder {
+        CaptureBuilder::default()
+    }
+
+    /// Pre-register an interface description
+    ///
+    /// Some devices emit streams of packets without the IDBs which describe
+    /// the interfaces they were captured on, perhaps because the interface
+    /// is described out-of-band.  This is illegal, but you can work around
+    /// it by registering the missing descriptions here.
+    ///
+    /// The n-th registered description is used in place of interface `n`
+    /// in any section which doesn't define an interface `n` itself.
+    pub fn interface(mut self, descr: InterfaceDescription) -> CaptureBuilder {
+        let iface = InterfaceInfo {
+            descr,
+            stats: None,
+            clock: None,
+            correction: None,
+        };
+        self.config.interfaces.push(Arc::new(iface));
+        self
+    }
+
+    /// Accept blocks which appear before the first section header, with the
+    /// given byte order
+    ///
+    /// Some appliances stream packet blocks without ever sending a section
+    /// header (for example, after reconnecting).  Such blocks are read as
+    /// if they belonged to a section with the given byte order, whose
+    /// interfaces are the ones registered with
+    /// [`interface()`](Self::interface).  This section is numbered 0.
+    ///
+    /// If a real section header arrives, pcarp switches to it as usual: the
+    /// byte order is taken from the header, and the new section's IDBs take
+    /// precedence over the registered interfaces.  Without this setting,
+    /// blocks before the first section header are assumed to be
+    /// little-endian.
+    ///
+    /// ```
+    /// # use pcarp::testgen::PcapngBuilder;
+    /// use pcarp::block::{Endianness, InterfaceDescription};
+    /// use pcarp::iface::{InterfaceId, LinkType};
+    /// use pcarp::CaptureBuilder;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Big)
+    /// #     .enhanced_packet(0, 0, b"before", &[])
+    /// #     .section(Endianness::Little)
+    /// #     .interface(LinkType::RAW, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"after", &[]);
+    /// # let stream = &pcap.as_bytes()[28..]; // skip the first SHB
+    /// let mut capture = CaptureBuilder::new()
+    ///     .headerless(Endianness::Big)
+    ///     .interface(InterfaceDescription::new(LinkType::ETHERNET))
+    ///     .build(stream);
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!((&pkt.data[..], pkt.interface), (&b"before"[..], Some(InterfaceId(0, 0))));
+    /// let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+    /// assert_eq!(iface.link_type(), LinkType::ETHERNET);
+    ///
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!((&pkt.data[..], pkt.interface), (&b"after"[..], Some(InterfaceId(1, 0))));
+    /// let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+    /// assert_eq!(iface.link_type(), LinkType::RAW);
+    /// ```
+    pub fn headerless(mut self, endianness: Endianness) -> CaptureBuilder {
+        self.config.initial_endianness = Some(endianness);
+        self
+    }
+
+    /// Treat consecutive sections with the same interfaces as one section
+    ///
+    /// Rotated captures are often concatenated back together (eg. with
+    /// `cat`), which gives a file with many sections, each describing the
+    /// same interfaces.  Normally every section gets a new number, so the
+    /// [`InterfaceId`]s of the packets change at each boundary, even
+    /// though they were captured on the same interfaces.
+    ///
+    /// With this enabled, a section whose interfaces are the same as those
+    /// of the section before it (in the same order) is merged into it: its
+    /// packets get the previous section's interface IDs, and the interface
+    /// statistics, name resolutions, and [`rewind_section()`] carry on
+    /// across the boundary.  Interfaces are compared by their parsed
+    /// descriptions, so differences in byte order or in comments are
+    /// ignored.  As soon as a section's interfaces turn out to differ, it
+    /// starts a new section as usual.
+    ///
+    /// [`InterfaceId`]: crate::iface::InterfaceId
+    /// [`rewind_section()`]: Capture::rewind_section
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::iface::InterfaceId;
+    /// use pcarp::CaptureBuilder;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+    /// #     .enhanced_packet(0, 0, b"first", &[])
+    /// #     .section(Endianness::Big)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+    /// #     .enhanced_packet(0, 1, b"second", &[])
+    /// #     .section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth1")])
+    /// #     .enhanced_packet(0, 2, b"third", &[]);
+    /// let ifaces = |capture: pcarp::Capture<_>| -> Vec<InterfaceId> {
+    ///     capture.map(|pkt| pkt.unwrap().interface.unwrap()).collect()
+    /// };
+    /// let capture = CaptureBuilder::new().build(pcap.build());
+    /// assert_eq!(ifaces(capture), [InterfaceId(1, 0), InterfaceId(2, 0), InterfaceId(3, 0)]);
+    ///
+    /// let capture = CaptureBuilder::new()
+    ///     .merge_sections(true)
+    ///     .build(pcap.build());
+    /// assert_eq!(ifaces(capture), [InterfaceId(1, 0), InterfaceId(1, 0), InterfaceId(2, 0)]);
+    /// ```
+    pub fn merge_sections(mut self, merge_sections: bool) -> CaptureBuilder {
+        self.config.merge_sections = merge_sections;
+        self
+    }
+
+    /// Only read packets from interfaces which satisfy the given predicate
+    ///
+    /// The predicate is evaluated once for each interface, when its
+    /// description block is read.  Packets from interfaces which don't
+    /// satisfy it are skipped.  This replaces any previous filter.
+    pub fn interface_filter(
+        mut self,
+        f: impl Fn(&InterfaceInfo) -> bool + Send + Sync + 'static,
+    ) -> CaptureBuilder {
+        self.config.interface_filter = Some(InterfaceFilter::new(f));
+        self
+    }
+
+    /// Only read packets from interfaces whose name or description matches
+    /// the given glob
+    ///
+    /// In the glob, `*` matches any string and `?` matches any single
+    /// character.  For more complex matching (eg. regexes), use
+    /// [`interface_filter()`](Self::interface_filter).  This replaces any
+    /// previous filter.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// # use pcarp::CaptureBuilder;
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+    /// #     .interface(LinkType::IEEE802_11, 0, &[(2, b"wlan0")])
+    /// #     .enhanced_packet(0, 0, b"wired", &[])
+    /// #     .enhanced_packet(1, 0, b"wireless", &[]);
+    /// let capture = CaptureBuilder::new()
+    ///     .interface_glob("eth*")
+    ///     .build(pcap.build());
+    /// let pkts = capture.collect::<Result<Vec<_>, _>>().unwrap();
+    /// assert_eq!(pkts.len(), 1);
+    /// assert_eq!(&pkts[0].data[..], b"wired");
+    /// ```
+    pub fn interface_glob(mut self, pattern: &str) -> CaptureBuilder {
+        self.config.interface_filter = Some(InterfaceFilter::glob(pattern));
+        self
+    }
+
+    /// Interpret the timestamps of the interface with the given name using
+    /// the given clock
+    ///
+    /// This is for capture cards whose timestamps don't count from the
+    /// unix epoch, or which report the raw value of a hardware counter.
+    /// The clock replaces the interface's `if_tsresol`, and applies to
+    /// every interface with this name, in every section.  It also applies
+    /// to interfaces registered with [`interface()`](Self::interface).
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::{iface::TimestampClock, CaptureBuilder};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"card0")])
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+    /// #     .enhanced_packet(0, 250_000_000, b"", &[])
+    /// #     .enhanced_packet(1, 1_000_000, b"", &[]);
+    /// // card0 counts at 125 MHz from when it was powered on
+    /// let power_on = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    /// let mut capture = CaptureBuilder::new()
+    ///     .timestamp_clock("card0", TimestampClock::new(power_on, 125_000_000))
+    ///     .build(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(pkt.timestamp, Some(power_on + Duration::from_secs(2)));
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(pkt.timestamp, Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1)));
+    /// ```
+    pub fn timestamp_clock(mut self, if_name: &str, clock: TimestampClock) -> CaptureBuilder {
+        self.config.clocks.insert(if_name.to_owned(), clock);
+        self
+    }
+
+    /// Override the timestamp resolution of the interface with the given
+    /// name
+    ///
+    /// Some capture stacks write the wrong `if_tsresol` (or leave it out,
+    /// when their timestamps aren't in microseconds).  This replaces the
+    /// resolution when the interface's description is read, so its
+    /// timestamps come out right, and [`InterfaceInfo::tsresol()`] reports
+    /// the corrected value.  Like [`timestamp_clock()`](Self::timestamp_clock),
+    /// this applies to every interface with this name, including those
+    /// registered with [`interface()`](Self::interface).
+    ///
+    /// Panics if `units_per_sec` is zero.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::CaptureBuilder;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// // eth0 claims to use microseconds, but its timestamps are nanoseconds
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+    /// #     .enhanced_packet(0, 1_500_000_000_000_000_000, b"", &[]);
+    /// let mut capture = CaptureBuilder::new()
+    ///     .tsresol_override("eth0", 1_000_000_000)
+    ///     .build(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(pkt.timestamp, Some(UNIX_EPOCH + Duration::from_secs(1_500_000_000)));
+    /// let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+    /// assert_eq!(iface.tsresol(), 1_000_000_000);
+    /// ```
+    pub fn tsresol_override(mut self, if_name: &str, units_per_sec: u64) -> CaptureBuilder {
+        assert!(
+            units_per_sec > 0,
+            "A timestamp resolution must be at least one unit per second"
+        );
+        self.config
+            .tsresols
+            .insert(if_name.to_owned(), units_per_sec);
+        self
+    }
+
+    /// Correct the timestamps of the interface with the given name
+    ///
+    /// Like [`timestamp_clock()`](Self::timestamp_clock), this applies to
+    /// every interface with this name.  See [`ClockCorrection`] for
+    /// details, and an example.
+    pub fn clock_correction(
+        mut self,
+        if_name: &str,
+        correction: ClockCorrection,
+    ) -> CaptureBuilder {
+        self.config
+            .corrections
+            .insert(if_name.to_owned(), correction);
+        self
+    }
+
+    /// Convert timestamps to UTC, for interfaces which say they're in
+    /// local time
+    ///
+    /// Timestamps in pcapng are supposed to be UTC, but some old exporters
+    /// wrote local times, and recorded the time zone in the interface's
+    /// `if_tzone` option.  pcarp ignores `if_tzone` by default; with this
+    /// enabled, it's used to convert such interfaces' timestamps to UTC.
+    /// To get the capture-local times back, use
+    /// [`InterfaceInfo::utc_to_local()`].  `Packet::raw_timestamp` is
+    /// unaffected.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::CaptureBuilder;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// // The timestamps are in UTC+1
+    /// # let tzone = (-3600_i32).to_le_bytes();
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(10, &tzone)])
+    /// #     .enhanced_packet(0, 7_200_000_000, b"", &[]);
+    /// let mut capture = CaptureBuilder::new()
+    ///     .apply_tzone(true)
+    ///     .build(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// let utc = pkt.timestamp.unwrap();
+    /// assert_eq!(utc, UNIX_EPOCH + Duration::from_secs(3600));
+    ///
+    /// let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+    /// let local = iface.utc_to_local(utc).unwrap();
+    /// assert_eq!(local, UNIX_EPOCH + Duration::from_secs(7200));
+    /// ```
+    pub fn apply_tzone(mut self, apply_tzone: bool) -> CaptureBuilder {
+        self.config.apply_tzone = apply_tzone;
+        self
+    }
+
+    /// Take packets' timestamps from an option, when it's present
+    ///
+    /// Some capture systems record a more precise (eg. hardware) timestamp
+    /// in a vendor-specific option of each packet block.  When a packet has
+    /// an option with the given code, `f` is called with its value and the
+    /// byte order of the section; if it returns a timestamp, that's used
+    /// for `Packet::timestamp` instead of the one in the block header.
+    /// `Packet::raw_timestamp` is unaffected.
+    ///
+    /// This works for any option which pcarp doesn't parse itself, such as
+    /// custom options (whose values start with the vendor's Private
+    /// Enterprise Number).  It applies to enhanced and obsolete packet
+    /// blocks.  If it's called several times, the options are tried in the
+    /// order they were registered.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::OPT_CUSTOM_BIN_COPY;
+    /// use pcarp::CaptureBuilder;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// const PEN: u32 = 32473; // reserved for documentation
+    /// // The vendor's option: the PEN, followed by nanoseconds since the epoch
+    /// let mut value = PEN.to_le_bytes().to_vec();
+    /// value.extend_from_slice(&1_500_000_000_123_456_789_u64.to_le_bytes());
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 1_500_000_000_123_456, b"hello", &[(OPT_CUSTOM_BIN_COPY, &value)]);
+    ///
+    /// let mut capture = CaptureBuilder::new()
+    ///     .timestamp_option(OPT_CUSTOM_BIN_COPY, |value, _endianness| {
+    ///         let (pen, nanos) = value.split_at(4);
+    ///         if pen != PEN.to_le_bytes() {
+    ///             return None;
+    ///         }
+    ///         let nanos = u64::from_le_bytes(nanos.try_into().ok()?);
+    ///         Some(UNIX_EPOCH + Duration::from_nanos(nanos))
+    ///     })
+    ///     .build(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// let expected = UNIX_EPOCH + Duration::from_nanos(1_500_000_000_123_456_789);
+    /// assert_eq!(pkt.timestamp, Some(expected));
+    /// ```
+    pub fn timestamp_option(
+        mut self,
+        code: u16,
+        f: impl Fn(&[u8], Endianness) -> Option<SystemTime> + Send + Sync + 'static,
+    ) -> CaptureBuilder {
+        let extractor = TimestampExtractor::new(f);
+        self.config.timestamp_options.push((code, extractor));
+        self
+    }
+
+    /// Set what to do with timestamps which are too large to represent
+    ///
+    /// See [`TimestampPolicy`] for details.
+    pub fn timestamp_policy(mut self, policy: TimestampPolicy) -> CaptureBuilder {
+        self.config.timestamp_policy = policy;
+        self
+    }
+
+    /// Set how to timestamp simple packets, which don't have timestamps of
+    /// their own
+    ///
+    /// See [`SimpleTimestampPolicy`] for details.
+    pub fn simple_timestamps(mut self, policy: SimpleTimestampPolicy) -> CaptureBuilder {
+        self.config.simple_timestamps = policy;
+        self
+    }
+
+    /// Parse blocks of the given type using the given function
+    ///
+    /// This can be used to add support for block types which pcarp doesn't
+    /// know about, or to replace the built-in parsers.  Blocks of new types
+    /// can be returned as a [`Block::Extension`], and will be otherwise
+    /// ignored by the `Capture`.  Returning a built-in type of block (eg. an
+    /// `InterfaceDescription`) has the same effect as if the block had been
+    /// parsed by pcarp.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::{Block, BlockType, Extension};
+    /// use pcarp::CaptureBuilder;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .block(0x0000_0007, &[0; 8])
+    /// #     .enhanced_packet(0, 0, b"hello", &[]);
+    /// let capture = CaptureBuilder::new()
+    ///     .block_parser(BlockType::IRIGTimestamp, |data, _| {
+    ///         Ok(Block::Extension(BlockType::IRIGTimestamp, Extension::new(data.len())))
+    ///     })
+    ///     .build(pcap.build());
+    /// assert_eq!(capture.count(), 1);
+    /// ```
+    pub fn block_parser(
+        mut self,
+        block_type: BlockType,
+        parser: impl Fn(Bytes, &ParseContext) -> Result<Block, BlockError> + Send + Sync + 'static,
+    ) -> CaptureBuilder {
+        self.config.parsers.register(block_type, Arc::new(parser));
+        self
+    }
+
+    /// Set what to do with sections of an unknown format version
+    ///
+    /// See [`VersionPolicy`] for details.
+    pub fn version_policy(mut self, policy: VersionPolicy) -> CaptureBuilder {
+        self.config.version_policy = policy;
+        self
+    }
+
+    /// Set what to do when a block is missing some of its padding
+    ///
+    /// See [`PaddingPolicy`] for details.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::PaddingPolicy;
+    /// use pcarp::CaptureBuilder;
+    ///
+    /// // An EPB whose 5 bytes of data aren't padded
+    /// let mut epb = vec![];
+    /// for x in [0_u32, 0, 0, 5, 5] {
+    ///     epb.extend_from_slice(&x.to_le_bytes());
+    /// }
+    /// epb.extend_from_slice(b"hello");
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .raw(&[6, 0, 0, 0, 37, 0, 0, 0])
+    /// #     .raw(&epb)
+    /// #     .raw(&[37, 0, 0, 0]);
+    /// let mut capture = CaptureBuilder::new().build(pcap.build());
+    /// assert!(capture.next().unwrap().is_err());
+    ///
+    /// let mut capture = CaptureBuilder::new()
+    ///     .padding_policy(PaddingPolicy::Tolerant)
+    ///     .build(pcap.build());
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(&pkt.data[..], b"hello");
+    /// ```
+    pub fn padding_policy(mut self, policy: PaddingPolicy) -> CaptureBuilder {
+        self.config.padding_policy = policy;
+        self
+    }
+
+    /// Set what to do about problems which can be worked around
+    ///
+    /// By default, pcarp logs a warning and carries on.  See
+    /// [`MalformationPolicy`] for details, and an example.
+    ///
+    /// A rejected block is handled like any other corrupt one: it's
+    /// returned as an error, counted in the recovery stats, and reported
+    /// to the diagnostic sink.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::{BlockType, Diagnostic, MalformationPolicy};
+    /// use pcarp::CaptureBuilder;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(3, 0, b"hello", &[]); // there's no interface 3
+    /// let found = Arc::new(Mutex::new(vec![]));
+    /// let sink = found.clone();
+    /// let mut capture = CaptureBuilder::new()
+    ///     .malformation_policy(MalformationPolicy::Reject)
+    ///     .diagnostic_sink(move |x| sink.lock().unwrap().push(x))
+    ///     .build(pcap.build());
+    /// assert!(capture.next().unwrap().is_err());
+    /// assert!(capture.next().is_none());
+    /// assert_eq!(capture.recovery_stats().blocks_skipped, 1);
+    /// assert!(matches!(
+    ///     found.lock().unwrap()[..],
+    ///     [Diagnostic::CorruptBlock { block_type: BlockType::EnhancedPacket, .. }],
+    /// ));
+    /// ```
+    pub fn malformation_policy(mut self, policy: MalformationPolicy) -> CaptureBuilder {
+        self.config.malformation_policy = policy;
+        self
+    }
+
+    /// Accept some of Wireshark's departures from the spec without
+    /// complaint
+    ///
+    /// By default, pcarp treats them like any other problem.  See
+    /// [`Quirks`] for details, and an example.
+    pub fn quirks(mut self, quirks: Quirks) -> CaptureBuilder {
+        self.config.quirks = quirks;
+        self
+    }
+
+    /// Call a function with each problem which pcarp works around
+    ///
+    /// pcarp logs these with `tracing`, which is fine for debugging, but
+    /// they're lost if no subscriber is installed.  The sink is called as
+    /// each one is found, so an application can count them, or show them
+    /// to its users.  They're still logged as well.  See [`Diagnostic`]
+    /// for the kinds of problem which are reported.
+    ///
+    /// This is the canonical way to find out what went wrong: the other
+    /// channels (`collect_malformations()`, [`MalformationPolicy::Custom`],
+    /// [`Capture::recovery_stats()`], and [`Capture::counters()`]) only see
+    /// part of it.  See [`Diagnostic`] for how they relate.
+    ///
+    /// Malformations which the [`MalformationPolicy`] rejects aren't
+    /// passed to the sink as such: the block is returned as an error, and
+    /// reported as a [`Diagnostic::CorruptBlock`].
+    ///
+    /// ```
+    /// # use pcarp::{iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::{BlockType, Diagnostic, Endianness, Malformation};
+    /// use pcarp::CaptureBuilder;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[(2, &[0, 0])]) // epb_flags should be 4 bytes
+    /// #     .block(6, &[0; 4]) // too short to be an EPB
+    /// #     .block(0x0BAD, &[0; 8]);
+    /// let found = Arc::new(Mutex::new(vec![]));
+    /// let sink = found.clone();
+    /// let capture = CaptureBuilder::new()
+    ///     .diagnostic_sink(move |x| sink.lock().unwrap().push(x))
+    ///     .build(pcap.build());
+    /// assert_eq!(capture.filter(|x| x.is_ok()).count(), 1);
+    /// let found = found.lock().unwrap();
+    /// assert!(matches!(
+    ///     found[0],
+    ///     Diagnostic::Malformation(BlockType::EnhancedPacket, Malformation::WrongOptionLength { .. }),
+    /// ));
+    /// assert!(matches!(
+    ///     found[1],
+    ///     Diagnostic::CorruptBlock { block_type: BlockType::EnhancedPacket, len: 16, .. },
+    /// ));
+    /// assert_eq!(found[2], Diagnostic::IgnoredBlock(BlockType::Custom));
+    /// assert_eq!(found[2].to_string(), "Custom blocks are ignored");
+    /// ```
+    pub fn diagnostic_sink(
+        mut self,
+        f: impl Fn(Diagnostic) + Send + Sync + 'static,
+    ) -> CaptureBuilder {
+        self.config.diagnostics = Some(DiagnosticSink::new(f));
+        self
+    }
+
+    /// Return each packet as soon as its block is complete
+    ///
+    /// Normally pcarp asks its reader for up to 8 KiB at a time.  Most
+    /// readers return whatever they have, but some (eg. decompressors, or
+    /// TLS streams) wait until they can fill the buffer.  When packets
+    /// trickle in over a socket, this can hold up a packet until several
+    /// more have arrived.
+    ///
+    /// In low-latency mode, pcarp never asks for more bytes than are needed
+    /// to complete the current block.  This costs more calls to `read()`, so
+    /// it's only worth enabling for near-real-time streams.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// # use std::io::{self, Read};
+    /// use pcarp::CaptureBuilder;
+    ///
+    /// /// A reader which waits until it can fill the whole buffer
+    /// struct Greedy(io::Cursor<Vec<u8>>);
+    ///
+    /// impl Read for Greedy {
+    ///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    ///         let remaining = self.0.get_ref().len() - self.0.position() as usize;
+    ///         if buf.len() > remaining {
+    ///             // The rest of the stream hasn't arrived yet
+    ///             return Err(io::ErrorKind::WouldBlock.into());
+    ///         }
+    ///         self.0.read(buf)
+    ///     }
+    /// }
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[]);
+    /// let mut capture = CaptureBuilder::new().build(Greedy(pcap.build()));
+    /// assert!(capture.next().unwrap().is_err());
+    ///
+    /// let mut capture = CaptureBuilder::new()
+    ///     .low_latency(true)
+    ///     .build(Greedy(pcap.build()));
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(&pkt.data[..], b"hello");
+    /// ```
+    pub fn low_latency(mut self, low_latency: bool) -> CaptureBuilder {
+        self.config.low_latency = low_latency;
+        self
+    }
+
+    /// Don't read the packets' data
+    ///
+    /// In metadata-only mode, each packet's timestamp, interface, and
+    /// original length are decoded as usual, but its `data` is left empty.
+    /// Packet blocks which don't fit in the read buffer are skipped over
+    /// rather than being buffered, so large packets are never copied.
+    /// This makes it cheaper to compute things like packet rates or
+    /// per-interface byte counts over large captures.
+    ///
+    /// Packet options aren't read either, so timestamps can't be taken from
+    /// them (see [`timestamp_option()`](Self::timestamp_option)).  The
+    /// trailing length field of a skipped block isn't checked, so some
+    /// framing errors may go unnoticed.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::CaptureBuilder;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[])
+    /// #     .enhanced_packet(0, 1, &[0; 100_000], &[]);
+    /// let capture = CaptureBuilder::new()
+    ///     .metadata_only(true)
+    ///     .build(pcap.build());
+    /// let lens: Vec<_> = capture
+    ///     .map(|pkt| pkt.unwrap())
+    ///     .map(|pkt| (pkt.data.len(), pkt.orig_len))
+    ///     .collect();
+    /// assert_eq!(lens, [(0, 5), (0, 100_000)]);
+    /// ```
+    pub fn metadata_only(mut self, metadata_only: bool) -> CaptureBuilder {
+        self.config.metadata_only = metadata_only;
+        self
+    }
+
+    /// Keep a list of the problems found in the capture's blocks
+    ///
+    /// pcarp works around most kinds of malformed input, logging a warning
+    /// each time.  With this enabled, it also keeps a list of them, which
+    /// you can get with [`Capture::take_malformations()`].  Each one gives
+    /// the offset of the problem within its block, so it can be tracked
+    /// down precisely.  The list grows until it's taken, so take it
+    /// regularly when reading long captures.
+    ///
+    /// Problems which cause a block to be skipped are returned as errors
+    /// instead.  To see everything which pcarp works around, use
+    /// [`diagnostic_sink()`](Self::diagnostic_sink).
+    ///
+    /// ```
+    /// # use pcarp::{iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::{BlockType, Endianness, Malformation};
+    /// use pcarp::CaptureBuilder;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[(2, &[0, 0])]) // epb_flags should be 4 bytes
+    /// #     .enhanced_packet(3, 0, b"world", &[]);
+    /// let mut capture = CaptureBuilder::new()
+    ///     .collect_malformations(true)
+    ///     .build(pcap.build());
+    /// assert_eq!(capture.by_ref().filter(|x| x.is_ok()).count(), 2);
+    /// let expected = [
+    ///     Malformation::WrongOptionLength { offset: 28, option_type: 2, expected: 4, actual: 2 },
+    ///     Malformation::UndefinedInterface { offset: 0, interface_id: 3 },
+    /// ];
+    /// let found = capture.take_malformations();
+    /// assert_eq!(found, expected.map(|x| (BlockType::EnhancedPacket, x)));
+    /// ```
+    pub fn collect_malformations(mut self, collect: bool) -> CaptureBuilder {
+        self.config.collect_malformations = collect;
+        self
+    }
+
+    /// End the capture after `n` packets have been returned
+    ///
+    /// Once the limit is reached, the `Capture` reports a clean end of
+    /// file, even if there are more packets to come.  This is handy for
+    /// previewing the start of a huge file, or for bounding the work done
+    /// on untrusted input.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::CaptureBuilder;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little).interface(LinkType::ETHERNET, 0, &[]);
+    /// # for _ in 0..10 {
+    /// #     pcap.enhanced_packet(0, 0, b"hello", &[]);
+    /// # }
+    /// let capture = CaptureBuilder::new().limit_packets(3).build(pcap.build());
+    /// assert_eq!(capture.count(), 3);
+    /// ```
+    pub fn limit_packets(mut self, n: u64) -> CaptureBuilder {
+        self.config.packet_limit = Some(n);
+        self
+    }
+
+    /// End the capture after `n` bytes have been consumed
+    ///
+    /// Once the limit is reached, the `Capture` reports a clean end of
+    /// file.  No block which starts beyond the limit is read, but the block
+    /// which crosses it is read in full, so slightly more than `n` bytes may
+    /// be consumed.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::CaptureBuilder;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little).interface(LinkType::ETHERNET, 0, &[]);
+    /// # for _ in 0..10 {
+    /// #     pcap.enhanced_packet(0, 0, b"hello", &[]); // 40 bytes each
+    /// # }
+    /// // The SHB and IDB take 48 bytes, so this reads three packets
+    /// let mut capture = CaptureBuilder::new().limit_bytes(150).build(pcap.build());
+    /// assert_eq!(capture.by_ref().count(), 3);
+    /// assert_eq!(capture.counters().bytes, 168);
+    /// ```
+    pub fn limit_bytes(mut self, n: u64) -> CaptureBuilder {
+        self.config.byte_limit = Some(n);
+        self
+    }
+
+    /// Call `f` periodically while reading, to report how far the `Capture`
+    /// has got
+    ///
+    /// This is intended for driving progress bars.  `f` is called after a
+    /// packet is read, if at least `every` bytes have been consumed since
+    /// the last call.  It's called on the thread which is reading the
+    /// packets, so it should be quick.  This replaces any previous callback.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::CaptureBuilder;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little).interface(LinkType::ETHERNET, 0, &[]);
+    /// # for i in 0..10 {
+    /// #     pcap.enhanced_packet(0, i * 1_000_000, b"hello", &[]); // 40 bytes each
+    /// # }
+    /// let reports = Arc::new(Mutex::new(vec![]));
+    /// let reports2 = reports.clone();
+    /// let capture = CaptureBuilder::new()
+    ///     .on_progress(100, move |x| reports2.lock().unwrap().push(x.packets))
+    ///     .build(pcap.build());
+    /// assert_eq!(capture.count(), 10);
+    /// // The SHB and IDB take 48 bytes
+    /// assert_eq!(*reports.lock().unwrap(), [2, 5, 8]);
+    /// ```
+    pub fn on_progress(
+        mut self,
+        every: u64,
+        f: impl Fn(Progress) + Send + Sync + 'static,
+    ) -> CaptureBuilder {
+        self.config.progress = Some((every, ProgressFn(Arc::new(f))));
+        self
+    }
+
+    /// Create a `Capture` which picks up where a previous one left off
+    ///
+    /// The reader must contain the same pcap which the checkpoint was taken
+    /// from.  See [`Capture::checkpoint()`] for details.  The builder's
+    /// settings should match those of the original `Capture` too.
+    ///
+    /// Simple packets are given the same timestamps as they would have been
+    /// without the interruption:
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::iface::SimpleTimestampPolicy;
+    /// use pcarp::CaptureBuilder;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 5_000_000, b"enhanced", &[]);
+    /// # for _ in 0..4 {
+    /// #     pcap.simple_packet(b"simple");
+    /// # }
+    /// let policies = [
+    ///     SimpleTimestampPolicy::Previous,
+    ///     SimpleTimestampPolicy::FixedRate {
+    ///         start: UNIX_EPOCH,
+    ///         interval: Duration::from_millis(10),
+    ///     },
+    /// ];
+    /// for policy in policies {
+    ///     let builder = CaptureBuilder::new().simple_timestamps(policy);
+    ///     let expected: Vec<_> = builder
+    ///         .clone()
+    ///         .build(pcap.build())
+    ///         .map(|pkt| pkt.unwrap().timestamp)
+    ///         .collect();
+    ///
+    ///     let mut capture = builder.clone().build(pcap.build());
+    ///     let mut found: Vec<_> = capture
+    ///         .by_ref()
+    ///         .take(3)
+    ///         .map(|pkt| pkt.unwrap().timestamp)
+    ///         .collect();
+    ///     let checkpoint = capture.checkpoint();
+    ///     let capture = builder.resume(pcap.build(), checkpoint).unwrap();
+    ///     found.extend(capture.map(|pkt| pkt.unwrap().timestamp));
+    ///     assert_eq!(found, expected);
+    /// }
+    /// ```
+    pub fn resume<R: Seek>(self, rdr: R, checkpoint: Checkpoint) -> Result<Capture<R>> {
+        let mut capture = self.build(rdr);
+        capture
+            .inner
+            .seek_to(checkpoint.offset, checkpoint.endianness)?;
+        capture.current_section = checkpoint.current_section;
+        capture.interface_matches = checkpoint
+            .interfaces
+            .iter()
+            .map(|x| capture.filter_matches(x.as_deref()))
+            .collect();
+        capture.interfaces = checkpoint.interfaces;
+        capture.archived_interfaces = checkpoint.archived_interfaces;
+        capture.resolved_names = checkpoint.resolved_names;
+        capture.section_start = checkpoint.section_start;
+        capture.section_offset = checkpoint.section_offset;
+        capture.pending_merge = checkpoint.pending_merge;
+        capture.last_timestamp = checkpoint.last_timestamp;
+        capture.simple_packets = checkpoint.simple_packets;
+        Ok(capture)
+    }
+
+    /// Create a `Capture` which reads from the given reader
+    pub fn build<R>(self, rdr: R) -> Capture<R> {
+        self.build_inner(BlockReader::new(rdr))
+    }
+
+    /// Create a `Capture` which reads from an in-memory pcap
+    ///
+    /// See [`Capture::from_bytes()`].
+    pub fn build_from_bytes(self, data: Bytes) -> Capture<InMemory> {
+        self.build_inner(BlockReader::from_bytes(InMemory(()), data))
+    }
+
+    /// Create a `Capture` from a boxed reader
+    ///
+    /// See [`Capture::new_boxed()`].
+    pub fn build_boxed(self, rdr: Box<dyn Read + Send>) -> BoxedCapture {
+        self.build(rdr)
+    }
+
+    /// Create a decoder which reads packets from a network stream
+    ///
+    /// See [`codec::PacketDecoder`].  The decoder honours all of the
+    /// builder's settings, except for `low_latency()`: the decoder never
+    /// reads anything itself, so it has no effect.  With `metadata_only()`,
+    /// the packet data is dropped, but the blocks still have to arrive in
+    /// full before they're decoded.
+    #[cfg(feature = "tokio-codec")]
+    pub fn build_decoder(self) -> codec::PacketDecoder {
+        codec::PacketDecoder::from_capture(self.build_from_bytes(Bytes::new()))
+    }
+
+    fn build_inner<R>(mut self, mut inner: BlockReader<R>) -> Capture<R> {
+        for iface in &mut self.config.interfaces {
+            if let Some(tsresol) = self.config.tsresols.get(iface.name()) {
+                Arc::make_mut(iface).descr.if_tsresol = *tsresol;
+            }
+            if let Some(clock) = self.config.clocks.get(iface.name()) {
+                Arc::make_mut(iface).clock = Some(*clock);
+            }
+            if let Some(correction) = self.config.corrections.get(iface.name()) {
+                Arc::make_mut(iface).correction = Some(*correction);
+            }
+        }
+        if let Some(endianness) = self.config.initial_endianness {
+            inner.set_initial_endianness(endianness);
+        }
+        inner.set_version_policy(self.config.version_policy);
+        inner.set_padding_policy(self.config.padding_policy);
+        inner.set_malformation_policy(self.config.malformation_policy.clone());
+        inner.set_quirks(self.config.quirks);
+        inner.set_diagnostics(self.config.diagnostics.clone());
+        inner.set_low_latency(self.config.low_latency);
+        inner.set_metadata_only(self.config.metadata_only);
+        inner.set_collect_malformations(self.config.collect_malformations);
+        inner.set_parsers(self.config.parsers.clone());
+        Capture {
+            inner,
+            config: self.config,
+            current_section: 0,
+            interfaces: Vec::new(),
+            interface_matches: Vec::new(),
+            archived_interfaces: Vec::new(),
+            resolved_names: Vec::new(),
+            section_start: None,
+            section_offset: None,
+            pending_merge: None,
+            recovery_stats: RecoveryStats::default(),
+            counters: Counters::default(),
+            last_progress: 0,
+            last_timestamp: None,
+            simple_packets: 0,
+        }
+    }
+}
+
+/// A `Capture` whose reader is chosen at runtime
+///
+/// See [`Capture::new_boxed()`].
+pub type BoxedCapture = Capture<Box<dyn Read + Send>>;
+
+/// The "reader" of a `Capture` which reads from memory
+///
+/// See [`Capture::from_bytes()`].  This never produces any bytes: the
+/// data is held by the `Capture` itself.
+#[derive(Debug, Clone, Copy)]
+pub struct InMemory(());
+
+impl Read for InMemory {
+    fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+        Ok(0)
+    }
+}
+
+impl Seek for InMemory {
+    fn seek(&mut self, _: std::io::SeekFrom) -> std::io::Result<u64> {
+        Ok(0)
+    }
+}
+
+/// A snapshot of the state of a [`Capture`], from which reading can be
+/// resumed later
+///
+/// With the `serde` feature, checkpoints can be serialized, so they can be
+/// persisted across process restarts.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    offset: u64,
+    endianness: Endianness,
+    current_section: u32,
+    interfaces: Vec<Option<Arc<InterfaceInfo>>>,
+    archived_interfaces: Vec<Vec<Option<Arc<InterfaceInfo>>>>,
+    resolved_names: Vec<NameResolution>,
+    section_start: Option<SystemTime>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    section_offset: Option<(u64, Endianness)>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pending_merge: Option<PendingMerge>,
+    /// For giving timestamps to simple packets
+    #[cfg_attr(feature = "serde", serde(default))]
+    last_timestamp: Option<SystemTime>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    simple_packets: u64,
+}
+
+/// The state of a section whose header has been read, but which may yet be
+/// merged into the current one
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct PendingMerge {
+    /// The number of its IDBs which have matched the current section's
+    /// interfaces so far
+    matched: usize,
+    /// The offset and endianness of its header block
+    offset: (u64, Endianness),
+}
+
+impl Checkpoint {
+    /// The offset within the pcap of the next block to be read
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
 }
 
 /// An iterator that reads packets from a pcap
+///
+/// A `Capture<R>` is `Send` if `R` is, and `Sync` if `R` is, so it can be
+/// handed off to another thread.  The packets it returns own their data,
+/// so they can be sent on to other threads in turn.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::Capture;
+/// use std::sync::mpsc;
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .enhanced_packet(0, 0, b"hello", &[]);
+/// let capture = Capture::new(pcap.build());
+/// let (tx, rx) = mpsc::channel();
+/// let reader = std::thread::spawn(move || {
+///     for pkt in capture {
+///         tx.send(pkt.unwrap()).unwrap();
+///     }
+/// });
+/// assert_eq!(&rx.recv().unwrap().data[..], b"hello");
+/// reader.join().unwrap();
+/// ```
 pub struct Capture<R> {
     inner: BlockReader<R>,
+    config: Config,
     current_section: u32,
     /// The interface map for the current section.  A `None` entry indicates
     /// that the interface definition block was mangled.
-    interfaces: Vec<Option<InterfaceInfo>>,
+    interfaces: Vec<Option<Arc<InterfaceInfo>>>,
+    /// Whether each interface in the current section passes the interface
+    /// filter
+    interface_matches: Vec<bool>,
+    /// The interface maps for completed sections, indexed by section number.
+    archived_interfaces: Vec<Vec<Option<Arc<InterfaceInfo>>>>,
     /// The resolved names for the current section.
     resolved_names: Vec<NameResolution>,
+    /// The timestamp of the first timestamped packet in the current section.
+    section_start: Option<SystemTime>,
+    /// The offset and endianness of the current section's header block.
+    /// `None` if no section header has been read.
+    section_offset: Option<(u64, Endianness)>,
+    /// A section which may yet be merged into the current one.  See
+    /// [`CaptureBuilder::merge_sections()`].
+    pending_merge: Option<PendingMerge>,
+    recovery_stats: RecoveryStats,
+    counters: Counters,
+    /// The number of bytes consumed when progress was last reported
+    last_progress: u64,
+    /// The timestamp of the last packet which had a real one
+    last_timestamp: Option<SystemTime>,
+    /// The number of simple packets returned so far
+    simple_packets: u64,
 }
 
 impl<R> Capture<R> {
     /// Create a new `Capture`
     pub fn new(rdr: R) -> Capture<R> {
-        Capture {
-            inner: BlockReader::new(rdr),
-            current_section: 0,
-            interfaces: Vec::new(),
-            resolved_names: Vec::new(),
-        }
+        CaptureBuilder::new().build(rdr)
+    }
+
+    /// Create a `Capture` with non-default settings
+    pub fn builder() -> CaptureBuilder {
+        CaptureBuilder::new()
+    }
+
+    /// Create a `Capture` which picks up from a checkpoint
+    ///
+    /// See [`checkpoint()`](Self::checkpoint).
+    pub fn resume(rdr: R, checkpoint: Checkpoint) -> Result<Capture<R>>
+    where
+        R: Seek,
+    {
+        CaptureBuilder::new().resume(rdr, checkpoint)
     }
 
     /// Rewind to the beginning of the pcapng file
+    ///
+    /// Sections are re-numbered from the start, so the interface IDs seen
+    /// after rewinding will match those seen on the previous pass.
     pub fn rewind(&mut self) -> Result<()>
     where
         R: Seek,
     {
         self.inner.rewind()?;
+        self.current_section = 0;
         self.interfaces.clear();
+        self.interface_matches.clear();
+        self.archived_interfaces.clear();
+        self.resolved_names.clear();
+        self.section_start = None;
+        self.section_offset = None;
+        self.pending_merge = None;
+        self.recovery_stats = RecoveryStats::default();
+        self.counters = Counters::default();
+        self.last_progress = 0;
+        self.last_timestamp = None;
+        self.simple_packets = 0;
+        Ok(())
+    }
+
+    /// Rewind to the beginning of the current section
+    ///
+    /// This is cheaper than [`rewind()`](Self::rewind) for algorithms which
+    /// make several passes over each section, since the earlier sections
+    /// aren't re-read.  The section keeps its number, so the interface IDs
+    /// seen after rewinding will match those seen on the previous pass.
+    /// Unlike `rewind()`, the [counters](Self::counters) and
+    /// [recovery stats](Self::recovery_stats) aren't reset.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # use std::io::Cursor;
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"first", &[])
+    /// #     .section(Endianness::Big)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"second", &[])
+    /// #     .enhanced_packet(0, 0, b"third", &[]);
+    /// let mut capture = Capture::new(Cursor::new(pcap.as_bytes()));
+    /// capture.next().unwrap().unwrap();
+    /// let second = capture.next().unwrap().unwrap();
+    /// capture.next().unwrap().unwrap();
+    ///
+    /// capture.rewind_section().unwrap();
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(&pkt.data[..], b"second");
+    /// assert_eq!(pkt.interface, second.interface);
+    /// ```
+    pub fn rewind_section(&mut self) -> Result<()>
+    where
+        R: Seek,
+    {
+        match self.section_offset {
+            Some((offset, endianness)) => {
+                self.inner.seek_to(offset, endianness)?;
+                // Go back to the state from before the section header was
+                // read.  Reading it again will put everything back.
+                self.interfaces = self.archived_interfaces.pop().unwrap_or_default();
+                self.current_section -= 1;
+            }
+            // Either no section has started yet, or we're in the headerless
+            // section at the start of the file
+            None => {
+                self.inner.rewind()?;
+                self.interfaces.clear();
+            }
+        }
+        self.interface_matches = self
+            .interfaces
+            .iter()
+            .map(|x| self.filter_matches(x.as_deref()))
+            .collect();
         self.resolved_names.clear();
+        self.section_start = None;
+        self.pending_merge = None;
         Ok(())
     }
 
+    /// Skim through the whole pcap, and summarise each of its sections
+    ///
+    /// Only the block headers are read, so this is much faster than reading
+    /// all the packets.  Afterwards, the `Capture` carries on from where it
+    /// was before.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # use std::io::Cursor;
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 1_000_000, b"hello", &[])
+    /// #     .enhanced_packet(0, 3_000_000, b"world", &[])
+    /// #     .section(Endianness::Big)
+    /// #     .interface(LinkType::RAW, 0, &[])
+    /// #     .interface(LinkType::RAW, 0, &[])
+    /// #     .enhanced_packet(1, 0, b"!", &[]);
+    /// let mut capture = Capture::new(Cursor::new(pcap.as_bytes()));
+    /// let sections = capture.sections().unwrap();
+    /// assert_eq!(sections.len(), 2);
+    /// assert_eq!(sections[0].packets, 2);
+    /// let span = sections[0].end.unwrap().duration_since(sections[0].start.unwrap());
+    /// assert_eq!(span.unwrap().as_secs(), 2);
+    /// assert_eq!(sections[1].offset, sections[0].len);
+    /// assert_eq!(sections[1].interfaces.len(), 2);
+    /// assert_eq!(capture.count(), 3);
+    /// ```
+    pub fn sections(&mut self) -> Result<Vec<SectionSummary>>
+    where
+        R: Read + Seek,
+    {
+        let (pos, endianness) = (self.inner.position(), self.inner.endianness());
+        let sink = self.config.diagnostics.as_ref();
+        let sections = match self.inner.source() {
+            Some(source) => section::scan(std::io::Cursor::new(source.clone()), sink),
+            None => section::scan(self.inner.get_mut(), sink),
+        };
+        self.inner.seek_to(pos, endianness)?;
+        sections
+    }
+
+    /// Read the block at the given offset
+    ///
+    /// This is for when the locations of interesting blocks are already
+    /// known (eg. from an index).  The block is parsed on its own, without
+    /// affecting the `Capture`: it carries on from where it was before, and
+    /// the block's contents (eg. any interfaces it defines) aren't taken
+    /// into account.  The offset must be the start of a block.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # use std::io::Cursor;
+    /// use pcarp::block::Block;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[])
+    /// #     .section(Endianness::Big)
+    /// #     .interface(LinkType::RAW, 0, &[]);
+    /// let mut capture = Capture::new(Cursor::new(pcap.as_bytes()));
+    /// let offset = capture.sections().unwrap()[1].offset;
+    /// match capture.read_block_at(offset).unwrap() {
+    ///     Block::SectionHeader(shb) => assert_eq!(shb.endianness, Endianness::Big),
+    ///     x => panic!("{x:?}"),
+    /// }
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(&pkt.data[..], b"hello");
+    /// ```
+    pub fn read_block_at(&mut self, offset: u64) -> Result<Block>
+    where
+        R: Read + Seek,
+    {
+        self.inner.read_block_at(offset)
+    }
+
+    /// Take a snapshot of the reader's state
+    ///
+    /// This records where the next block starts, along with everything
+    /// learned from the blocks read so far (interfaces, resolved names,
+    /// etc.).  Pass it to [`Capture::resume()`] to carry on from the same
+    /// point, with the same interface IDs, without re-reading the pcap from
+    /// the start.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// # use pcarp::Capture;
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+    /// #     .enhanced_packet(0, 0, b"first", &[])
+    /// #     .enhanced_packet(0, 0, b"second", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// capture.next().unwrap().unwrap();
+    /// let checkpoint = capture.checkpoint();
+    ///
+    /// let mut capture = Capture::resume(pcap.build(), checkpoint).unwrap();
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(&pkt.data[..], b"second");
+    /// let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+    /// assert_eq!(iface.name(), "eth0");
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            offset: self.inner.position(),
+            endianness: self.inner.endianness(),
+            current_section: self.current_section,
+            interfaces: self.interfaces.clone(),
+            archived_interfaces: self.archived_interfaces.clone(),
+            resolved_names: self.resolved_names.clone(),
+            section_start: self.section_start,
+            section_offset: self.section_offset,
+            pending_merge: self.pending_merge,
+            last_timestamp: self.last_timestamp,
+            simple_packets: self.simple_packets,
+        }
+    }
+
+    /// Counts of the errors which have been encountered so far
+    ///
+    /// This is useful for flagging badly damaged pcaps in automated
+    /// pipelines, without having to inspect every error.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .block(6, &[0; 4]) // too short to be an EPB
+    /// #     .enhanced_packet(0, 0, b"hello", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let n_ok = capture.by_ref().filter(|x| x.is_ok()).count();
+    /// assert_eq!(n_ok, 1);
+    /// assert_eq!(capture.recovery_stats().blocks_skipped, 1);
+    /// assert_eq!(capture.recovery_stats().bytes_skipped, 16);
+    /// ```
+    pub fn recovery_stats(&self) -> &RecoveryStats {
+        &self.recovery_stats
+    }
+
+    /// Counts of the blocks, bytes, packets, and errors seen so far
+    ///
+    /// These are cheap to maintain, so they're always on.  They're handy
+    /// for monitoring long-running readers.
+    ///
+    /// ```
+    /// # use pcarp::{block::{BlockType, Endianness}, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[])
+    /// #     .block(6, &[0; 4]) // too short to be an EPB
+    /// #     .enhanced_packet(0, 0, b"world", &[]);
+    /// let file = pcap.as_bytes();
+    /// let mut capture = Capture::new(file);
+    /// capture.by_ref().for_each(drop);
+    /// let counters = capture.counters();
+    /// assert_eq!(counters.blocks_of(BlockType::EnhancedPacket), 3);
+    /// assert_eq!(counters.total_blocks(), 5);
+    /// assert_eq!(counters.bytes, file.len() as u64);
+    /// assert_eq!((counters.packets, counters.errors), (2, 1));
+    /// ```
+    pub fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
+    /// Roughly how much memory the `Capture` is using
+    ///
+    /// Interface descriptions are kept for the whole capture (so that
+    /// [`lookup_interface()`](Self::lookup_interface) works for packets from
+    /// earlier sections), which means
+    /// that a capture with many sections will slowly grow.  Long-running
+    /// readers can keep an eye on this, and start afresh if it gets too big.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+    /// #     .enhanced_packet(0, 0, b"hello", &[]);
+    /// let mut capture = Capture::new(pcap.as_bytes());
+    /// assert_eq!(capture.memory_usage().total(), 0);
+    /// capture.next().unwrap().unwrap();
+    /// let usage = capture.memory_usage();
+    /// assert!(usage.buffer > 0);
+    /// assert!(usage.interfaces > 4);
+    /// assert_eq!(usage.name_resolutions, 0);
+    /// ```
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let interfaces = self
+            .interfaces
+            .iter()
+            .chain(self.archived_interfaces.iter().flatten())
+            .flatten()
+            .map(|x| interface_size(x))
+            .sum();
+        MemoryUsage {
+            buffer: self.inner.buffer_capacity(),
+            interfaces,
+            name_resolutions: self.resolved_names.iter().map(name_resolution_size).sum(),
+        }
+    }
+
+    /// Take the problems found in the blocks read since the last call,
+    /// along with the types of the blocks they were found in
+    ///
+    /// This is always empty unless
+    /// [`CaptureBuilder::collect_malformations()`] was enabled.
+    pub fn take_malformations(&mut self) -> Vec<(BlockType, Malformation)> {
+        self.inner.take_malformations()
+    }
+
+    /// The name resolution blocks seen so far in the current section
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// # use std::net::Ipv4Addr;
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .name_resolution_with_options(&[0, 0, 0, 0], &[(2, b"ns1"), (3, &[10, 0, 0, 53])])
+    /// #     .enhanced_packet(0, 0, b"hello", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// capture.next().unwrap().unwrap();
+    /// let nrb = &capture.name_resolutions()[0];
+    /// assert_eq!(nrb.ns_dnsname, "ns1");
+    /// assert_eq!(nrb.ns_dns_ip4_addr, Some(Ipv4Addr::new(10, 0, 0, 53)));
+    /// ```
+    pub fn name_resolutions(&self) -> &[NameResolution] {
+        &self.resolved_names
+    }
+
+    /// The byte order of the current section
+    ///
+    /// This is handy for preserving the byte order when re-writing a
+    /// capture: see [`PcapngWriter::set_endianness()`](writer::PcapngWriter::set_endianness).
+    pub fn endianness(&self) -> Endianness {
+        self.inner.endianness()
+    }
+
     /// Get some info about a certain network interface.
     ///
-    /// Note: Only shows info for the interfaces in the current section of
-    /// the pcap.
+    /// This works for any interface which has been seen so far, including
+    /// those from earlier sections of the pcap.
     pub fn lookup_interface(&self, interface_id: InterfaceId) -> Option<&InterfaceInfo> {
-        if interface_id.0 != self.current_section {
-            None
+        self.lookup_interface_arc(interface_id).map(|x| &**x)
+    }
+
+    /// Number an interface across all the sections of the pcap
+    ///
+    /// Interfaces are numbered from zero in the order their description
+    /// blocks appear in the file, so the first interface of each section
+    /// follows the last interface of the previous one.  The numbers don't
+    /// depend on how far through the file you are, so they're suitable as
+    /// keys in your own tables.  Returns `None` for interfaces which
+    /// haven't been described (including those registered with
+    /// [`CaptureBuilder::interface()`]).
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::Capture;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(1, 0, b"first", &[])
+    /// #     .section(Endianness::Little)
+    /// #     .interface(LinkType::RAW, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"second", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let mut ordinals = vec![];
+    /// while let Some(pkt) = capture.next() {
+    ///     let id = pkt.unwrap().interface.unwrap();
+    ///     ordinals.push((id.section(), id.index(), capture.interface_ordinal(id)));
+    /// }
+    /// assert_eq!(ordinals, [(1, 1, Some(1)), (2, 0, Some(2))]);
+    /// ```
+    pub fn interface_ordinal(&self, interface_id: InterfaceId) -> Option<u32> {
+        let section = interface_id.section() as usize;
+        let earlier = self.archived_interfaces.get(..section)?;
+        let interfaces = if interface_id.section() == self.current_section {
+            &self.interfaces
         } else {
-            self.interfaces.get(interface_id.1 as usize)?.as_ref()
+            self.archived_interfaces.get(section)?
+        };
+        if interface_id.index() >= interfaces.len() {
+            return None;
+        }
+        let earlier: usize = earlier.iter().map(|x| x.len()).sum();
+        u32::try_from(earlier + interface_id.index()).ok()
+    }
+
+    /// Identify an interface across sections.
+    ///
+    /// The same interface gets a different [`InterfaceId`] in each section
+    /// of the pcap; this maps them to a common [`LogicalInterface`], based
+    /// on their link type, name, and MAC address.  Returns `None` for the
+    /// same interfaces as [`lookup_interface()`](Self::lookup_interface).
+    pub fn logical_interface(&self, interface_id: InterfaceId) -> Option<LogicalInterface> {
+        self.lookup_interface(interface_id)
+            .map(|x| x.logical(interface_id))
+    }
+
+    fn lookup_interface_arc(&self, interface_id: InterfaceId) -> Option<&Arc<InterfaceInfo>> {
+        let InterfaceId(section, idx) = interface_id;
+        let interfaces = if section == self.current_section {
+            &self.interfaces
+        } else {
+            self.archived_interfaces.get(section as usize)?
+        };
+        self.resolve_interface(interfaces, RawInterfaceId(idx))
+    }
+
+    /// Whether packets from the given interface should be returned.  Packets
+    /// whose interface is unknown are always returned.
+    fn filter_matches(&self, iface: Option<&InterfaceInfo>) -> bool {
+        match (&self.config.interface_filter, iface) {
+            (Some(filter), Some(iface)) => filter.matches(iface),
+            _ => true,
+        }
+    }
+
+    /// Whether packets from interface `idx` of the current section should
+    /// be returned
+    fn interface_matches(&self, idx: RawInterfaceId) -> bool {
+        match self.interface_matches.get(idx.index()) {
+            Some(x) => *x,
+            None => self.filter_matches(self.config.interfaces.get(idx.index()).map(|x| &**x)),
+        }
+    }
+
+    /// Look up an interface in the given section's interface map, falling
+    /// back to the pre-registered interfaces if the section doesn't define it
+    fn resolve_interface<'a>(
+        &'a self,
+        interfaces: &'a [Option<Arc<InterfaceInfo>>],
+        idx: RawInterfaceId,
+    ) -> Option<&'a Arc<InterfaceInfo>> {
+        match interfaces.get(idx.index()) {
+            Some(iface) => iface.as_ref(),
+            None => self.config.interfaces.get(idx.index()),
+        }
+    }
+
+    /// Convert a packet's timestamp, as configured.  Returns `None` if it
+    /// overflows under `TimestampPolicy::Error`.
+    fn resolve_ts(&self, iface: &InterfaceInfo, ts: Timestamp) -> Option<SystemTime> {
+        let policy = self.config.timestamp_policy;
+        let ts = iface.resolve_ts_with(ts, policy)?;
+        if !self.config.apply_tzone {
+            return Some(ts);
+        }
+        match iface.local_to_utc(ts) {
+            Some(x) => Some(x),
+            None if policy == TimestampPolicy::Error => None,
+            None => Some(ts),
         }
     }
 }
 
+impl Capture<Box<dyn Read + Send>> {
+    /// Create a `Capture` from a boxed reader
+    ///
+    /// This is for when the type of the reader isn't known until runtime:
+    /// eg. when a decompressor is chosen based on the file's extension.
+    /// Everything which doesn't need to seek works just the same as with
+    /// any other reader.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::{BoxedCapture, Capture};
+    /// use std::io::{BufReader, Cursor};
+    ///
+    /// fn open(data: Vec<u8>, buffered: bool) -> BoxedCapture {
+    ///     let rdr = Cursor::new(data);
+    ///     if buffered {
+    ///         Capture::new_boxed(Box::new(BufReader::new(rdr)))
+    ///     } else {
+    ///         Capture::new_boxed(Box::new(rdr))
+    ///     }
+    /// }
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[]);
+    /// let mut capture = open(pcap.as_bytes().to_vec(), true);
+    /// let (pkt, iface) = capture.next_with_iface().unwrap().unwrap();
+    /// assert_eq!(&pkt.data[..], b"hello");
+    /// assert_eq!(iface.unwrap().link_type(), LinkType::ETHERNET);
+    /// ```
+    pub fn new_boxed(rdr: Box<dyn Read + Send>) -> BoxedCapture {
+        Capture::new(rdr)
+    }
+}
+
+impl Capture<InMemory> {
+    /// Create a `Capture` which reads from an in-memory pcap
+    ///
+    /// The data isn't copied: the `data` field of each packet is a slice of
+    /// the given buffer.  This is faster than wrapping the buffer in a
+    /// `Cursor`, and means that cloning the packets is free.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder, Capture};
+    /// use bytes::Bytes;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[]);
+    /// let data = Bytes::from(pcap.as_bytes().to_vec());
+    /// let pkt = Capture::from_bytes(data.clone()).next().unwrap().unwrap();
+    /// assert_eq!(&pkt.data[..], b"hello");
+    /// let data_range = data.as_ptr_range();
+    /// assert!(data_range.contains(&pkt.data.as_ptr()));
+    /// ```
+    pub fn from_bytes(data: Bytes) -> Capture<InMemory> {
+        CaptureBuilder::new().build_from_bytes(data)
+    }
+}
+
 impl<R: Read> Iterator for Capture<R> {
     type Item = Result<Packet>;
     fn next(&mut self) -> Option<Self::Item> {
@@ -147,63 +2178,478 @@ impl<R: Read> Iterator for Capture<R> {
 }
 
 impl<R: Read> Capture<R> {
+    /// Get the next packet, along with info about the interface it was
+    /// captured on
+    ///
+    /// The interface info is a snapshot: it's cheap to clone, and it won't be
+    /// affected by blocks which the `Capture` reads later on (such as
+    /// interface statistics).  Unlike [`lookup_interface()`](Self::lookup_interface),
+    /// it doesn't borrow the `Capture`, so you can send it off along with the
+    /// packet.
+    pub fn next_with_iface(&mut self) -> Option<Result<(Packet, Option<Arc<InterfaceInfo>>)>> {
+        let pkt = match self.try_next() {
+            Ok(pkt) => pkt?,
+            Err(e) => return Some(Err(e)),
+        };
+        let iface = pkt
+            .interface
+            .and_then(|id| self.lookup_interface_arc(id))
+            .cloned();
+        Some(Ok((pkt, iface)))
+    }
+
+    /// Get the timestamp of the next packet, without reading the packet
+    ///
+    /// Only the header fields of the packet block are decoded: the packet
+    /// data isn't copied, and the options aren't parsed.  This makes it
+    /// cheap to pick the earliest of several captures when merging them.
+    /// The packet itself is still returned by the next call to `next()`.
+    ///
+    /// Returns `None` if there are no more packets, and `Some(Ok(None))` if
+    /// the next packet has no timestamp.  Any non-packet blocks before the
+    /// next packet are read as usual.  If one of them is corrupt, the error
+    /// is returned here (rather than by `next()`), and the block is skipped.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::Capture;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 1_000_000, b"hello", &[]);
+    /// let mut capture = Capture::new(pcap.as_bytes());
+    /// let ts = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+    /// assert_eq!(capture.peek_timestamp().unwrap().unwrap(), Some(ts));
+    /// let pkt = capture.next().unwrap().unwrap();
+    /// assert_eq!(pkt.timestamp, Some(ts));
+    /// assert!(capture.peek_timestamp().is_none());
+    /// ```
+    pub fn peek_timestamp(&mut self) -> Option<Result<Option<SystemTime>>> {
+        loop {
+            if self.limit_reached() {
+                return None;
+            }
+            let (block_type, frame) = match self.inner.peek_raw() {
+                Ok(x) => x?,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Some(x) = self.peek_packet_ts(block_type, &frame) {
+                let extracted = self.peek_extracted_ts(block_type, frame);
+                return Some(Ok(extracted.or(x)));
+            }
+            // Not a packet we can peek at, so handle it in the usual way
+            let start = self.inner.position();
+            let block = self.inner.try_next();
+            let len = self.inner.position() - start;
+            match block {
+                Ok(Some(block)) => {
+                    if let Err(e) = self.handle_block(block, len, self.inner.endianness()) {
+                        return Some(Err(e));
+                    }
+                }
+                Ok(None) => return None,
+                Err(e) => {
+                    self.handle_error(&e, len);
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+
+    /// Decode the interface and timestamp from the header of a packet
+    /// block, and resolve the timestamp
+    ///
+    /// Returns `None` if the block isn't a packet block, if the packet
+    /// would be filtered out, or if the header can't be decoded.
+    fn peek_packet_ts(&self, block_type: BlockType, frame: &[u8]) -> Option<Option<SystemTime>> {
+        let endianness = self.inner.endianness();
+        let read_u32 = |i: usize| {
+            let x = frame.get(i..i + 4)?.try_into().unwrap();
+            Some(match endianness {
+                Endianness::Big => u32::from_be_bytes(x),
+                Endianness::Little => u32::from_le_bytes(x),
+            })
+        };
+        // The body of the block starts 8 bytes into the frame, and is
+        // followed by the 4-byte trailer
+        let body_len = frame.len().checked_sub(12)?;
+        let (iface, ts) = match block_type {
+            BlockType::EnhancedPacket if body_len >= 20 => {
+                (RawInterfaceId::from(read_u32(8)?), true)
+            }
+            BlockType::ObsoletePacket if body_len >= 20 => {
+                let x = frame[8..10].try_into().unwrap();
+                let iface = match endianness {
+                    Endianness::Big => u16::from_be_bytes(x),
+                    Endianness::Little => u16::from_le_bytes(x),
+                };
+                (RawInterfaceId::from(iface), true)
+            }
+            BlockType::SimplePacket if body_len >= 4 => (RawInterfaceId(0), false),
+            _ => return None,
+        };
+        if !self.interface_matches(iface) {
+            return None;
+        }
+        if !ts {
+            return Some(self.synthetic_ts());
+        }
+        let ts = Timestamp((u64::from(read_u32(12)?) << 32) + u64::from(read_u32(16)?));
+        match self.resolve_interface(&self.interfaces, iface) {
+            // On overflow, fall back to reading the block in the usual way,
+            // which reports the error
+            Some(iface) => Some(Some(self.resolve_ts(iface, ts)?)),
+            None => Some(None),
+        }
+    }
+
+    /// Parse a packet block, and look for a timestamp in the options
+    /// registered with `timestamp_option()`
+    fn peek_extracted_ts(&mut self, block_type: BlockType, frame: Bytes) -> Option<SystemTime> {
+        if self.config.timestamp_options.is_empty() {
+            return None;
+        }
+        // If the block is corrupt, the error will be reported when it's read
+        let block = self.inner.parse_raw(block_type, frame).ok()?;
+        self.extract_timestamp(&block, self.inner.endianness())
+    }
+
     /// Get the next packet
     fn try_next(&mut self) -> Result<Option<Packet>> {
+        Ok(self.try_next_with_meta()?.map(|(pkt, _)| pkt))
+    }
+
+    /// Get the next packet, along with some details of the block it came
+    /// from
+    pub(crate) fn try_next_with_meta(&mut self) -> Result<Option<(Packet, PacketMeta)>> {
         loop {
-            let block = match self.inner.try_next() {
+            if self.limit_reached() {
+                return Ok(None);
+            }
+            let start = self.inner.position();
+            let block = self.inner.try_next();
+            let len = self.inner.position() - start;
+            let block = match block {
                 Ok(Some(block)) => block,
                 Ok(None) => return Ok(None),
                 Err(e) => {
-                    if let Error::Block(block_type, _) = e {
-                        // This error is non-fatal, so let's try to handle
-                        // it as best we can
-                        self.handle_corrupt_block(block_type);
-                    }
+                    self.handle_error(&e, len);
                     return Err(e);
                 }
             };
-            self.handle_block(&block);
-            let Some((meta, data)) = block.into_pkt() else { continue };
+            let mut meta = PacketMeta::new(&block, start, len, self.inner.endianness());
+            if let Some(pkt) = self.handle_block(block, len, self.inner.endianness())? {
+                meta.timestamp = pkt.timestamp;
+                meta.interface = pkt.interface;
+                meta.orig_len = pkt.orig_len;
+                return Ok(Some((pkt, meta)));
+            }
+        }
+    }
+}
+
+impl<R> Capture<R> {
+    /// Whether the limits set by `limit_packets()` or `limit_bytes()` have
+    /// been reached
+    fn limit_reached(&self) -> bool {
+        let over = |limit: Option<u64>, x: u64| matches!(limit, Some(limit) if x >= limit);
+        let reached = over(self.config.packet_limit, self.counters.packets)
+            || over(self.config.byte_limit, self.counters.bytes);
+        if reached {
+            debug!("Reached the read limit");
+        }
+        reached
+    }
 
-            let interface = meta.map(|(_, iface)| InterfaceId(self.current_section, iface));
-            let timestamp = meta.and_then(|(ts, iface)| {
-                let iface = self.interfaces.get(iface as usize)?.as_ref()?;
-                Some(iface.resolve_ts(ts))
+    /// Update the state after a block failed to parse
+    fn handle_error(&mut self, e: &Error, len: u64) {
+        self.recovery_stats.record(e, len);
+        self.counters.errors += 1;
+        if let Error::Block(block_type, reason) = e {
+            self.diagnose(Diagnostic::CorruptBlock {
+                block_type: *block_type,
+                len,
+                reason: reason.to_string(),
             });
+            self.counters.record_block(*block_type, len);
+            // This error is non-fatal, so let's try to handle it as best
+            // we can
+            self.handle_corrupt_block(*block_type);
+            if *block_type == BlockType::SectionHeader {
+                self.saw_section_header(len, self.inner.endianness());
+            }
+        }
+    }
+
+    /// Remember where the section header which was just read started, for
+    /// `rewind_section()`
+    fn saw_section_header(&mut self, len: u64, endianness: Endianness) {
+        let offset = self.inner.position().saturating_sub(len);
+        self.section_offset = Some((offset, endianness));
+    }
+
+    /// Update the state after a block was parsed, and extract the packet
+    /// (if it contains one)
+    ///
+    /// `len` is the length of the block in bytes, for the recovery stats.
+    /// If the block turns out to be corrupt, it's handled like any other
+    /// corrupt block, and the error is returned.
+    fn handle_block(
+        &mut self,
+        block: Block,
+        len: u64,
+        endianness: Endianness,
+    ) -> Result<Option<Packet>> {
+        let block_type = block.block_type();
+        match self.process_block(block, len, endianness) {
+            Ok(pkt) => {
+                self.counters.record_block(block_type, len);
+                if let Some(pkt) = &pkt {
+                    self.counters.packets += 1;
+                    self.report_progress(pkt.timestamp);
+                }
+                Ok(pkt)
+            }
+            Err(e) => {
+                self.handle_error(&e, len);
+                Err(e)
+            }
+        }
+    }
+
+    /// The part of `handle_block()` which may find that the block is
+    /// corrupt
+    fn process_block(
+        &mut self,
+        block: Block,
+        len: u64,
+        endianness: Endianness,
+    ) -> Result<Option<Packet>> {
+        let block_type = block.block_type();
+        self.update_state(&block, len)?;
+        let extracted_ts = self.extract_timestamp(&block, endianness);
+        let (iface, ts, data, orig_len) = match block.into_pkt() {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        if !self.interface_matches(iface) {
+            trace!("Skipping a packet from interface {iface}");
+            return Ok(None);
+        }
 
-            return Ok(Some(Packet {
-                timestamp,
-                interface,
-                data,
-            }));
+        // Simple packets don't name their interface, so they're exempt
+        if block_type != BlockType::SimplePacket
+            && self.resolve_interface(&self.interfaces, iface).is_none()
+        {
+            self.report_undefined_interface(block_type, iface)?;
         }
+
+        let interface = Some(iface.in_section(self.current_section));
+        let mut timestamp = match (ts, self.resolve_interface(&self.interfaces, iface)) {
+            _ if extracted_ts.is_some() => extracted_ts,
+            (Some(ts), Some(iface)) => match self.resolve_ts(iface, ts) {
+                Some(x) => Some(x),
+                None => return Err(Error::Block(block_type, BlockError::TimestampOverflow)),
+            },
+            _ => None,
+        };
+
+        if self.section_start.is_none() {
+            self.section_start = timestamp;
+        }
+        let mut synthetic_timestamp = false;
+        if block_type == BlockType::SimplePacket {
+            timestamp = self.synthetic_ts();
+            synthetic_timestamp = timestamp.is_some();
+            self.simple_packets += 1;
+        } else if timestamp.is_some() {
+            self.last_timestamp = timestamp;
+        }
+
+        let mut pkt = Packet::new(data);
+        pkt.timestamp = timestamp;
+        pkt.raw_timestamp = ts;
+        pkt.interface = interface;
+        pkt.orig_len = orig_len;
+        pkt.section_start = self.section_start;
+        pkt.synthetic_timestamp = synthetic_timestamp;
+        Ok(Some(pkt))
+    }
+
+    /// The timestamp to give the next simple packet
+    fn synthetic_ts(&self) -> Option<SystemTime> {
+        match self.config.simple_timestamps {
+            SimpleTimestampPolicy::None => None,
+            SimpleTimestampPolicy::Previous => self.last_timestamp,
+            SimpleTimestampPolicy::FixedRate { start, interval } => {
+                let nanos = interval.as_nanos() * u128::from(self.simple_packets);
+                let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+                start.checked_add(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+            }
+        }
+    }
+
+    /// Call the progress callback, if it's due
+    fn report_progress(&mut self, timestamp: Option<SystemTime>) {
+        let (every, f) = match &self.config.progress {
+            Some(x) => x,
+            None => return,
+        };
+        if self.counters.bytes.saturating_sub(self.last_progress) < *every {
+            return;
+        }
+        self.last_progress = self.counters.bytes;
+        (f.0)(Progress {
+            bytes_read: self.counters.bytes,
+            packets: self.counters.packets,
+            timestamp,
+        });
+    }
+
+    /// Note a reference to an interface which hasn't been defined.  The
+    /// interface ID is the first field of every block which has one.
+    ///
+    /// If the malformation policy rejects the block, the error is returned.
+    fn report_undefined_interface(
+        &mut self,
+        block_type: BlockType,
+        interface_id: RawInterfaceId,
+    ) -> Result<()> {
+        let x = Malformation::UndefinedInterface {
+            offset: 0,
+            interface_id: interface_id.0,
+        };
+        self.inner
+            .report_malformation(block_type, x)
+            .map_err(|e| Error::Block(block_type, e))
+    }
+
+    /// Look for a timestamp in the options registered with
+    /// `timestamp_option()`
+    fn extract_timestamp(&self, block: &Block, endianness: Endianness) -> Option<SystemTime> {
+        let options = match block {
+            Block::EnhancedPacket(x) => &x.options,
+            Block::ObsoletePacket(x) => &x.options,
+            _ => return None,
+        };
+        self.config.timestamp_options.iter().find_map(|(code, f)| {
+            options
+                .iter()
+                .filter(|(ty, _)| ty == code)
+                .find_map(|(_, value)| f.extract(value, endianness))
+        })
     }
 
     fn start_new_section(&mut self) {
-        self.interfaces.clear();
+        // Keep hold of the old interfaces so that IDs handed out for the
+        // previous section remain resolvable
+        let interfaces = std::mem::take(&mut self.interfaces);
+        self.archived_interfaces.push(interfaces);
+        self.interface_matches.clear();
         self.resolved_names.clear();
+        self.section_start = None;
         self.current_section += 1;
         debug!("Starting new section (#{})", self.current_section);
     }
 
+    /// Start the pending section for real, rather than merging it into the
+    /// current one.  The interfaces which it's been found to share with the
+    /// current section are carried over.
+    fn split_pending_section(&mut self) {
+        let merge = match self.pending_merge.take() {
+            Some(x) => x,
+            None => return,
+        };
+        let shared: Vec<_> = self.interfaces[..merge.matched]
+            .iter()
+            .map(|x| {
+                x.as_ref().map(|x| {
+                    let mut iface = InterfaceInfo::clone(x);
+                    iface.stats = None;
+                    Arc::new(iface)
+                })
+            })
+            .collect();
+        self.section_offset = Some(merge.offset);
+        self.start_new_section();
+        for iface in shared {
+            self.interface_matches
+                .push(self.filter_matches(iface.as_deref()));
+            self.interfaces.push(iface);
+        }
+    }
+
+    /// Called when a block other than an IDB is read: if there's a pending
+    /// section, we now know whether it has the same interfaces as the
+    /// current one
+    fn settle_pending_section(&mut self) {
+        match &self.pending_merge {
+            Some(merge) if merge.matched == self.interfaces.len() => {
+                debug!("Merged the new section into #{}", self.current_section);
+                self.pending_merge = None;
+            }
+            Some(_) => self.split_pending_section(),
+            None => (),
+        }
+    }
+
     /// Update the interface description map etc. if necessary
-    fn handle_block(&mut self, block: &Block) {
+    fn update_state(&mut self, block: &Block, len: u64) -> Result<()> {
+        if !matches!(block, Block::InterfaceDescription(_)) {
+            self.settle_pending_section();
+        }
         match block {
-            Block::SectionHeader(_) => self.start_new_section(),
+            Block::SectionHeader(shb) => {
+                let offset = self.inner.position().saturating_sub(len);
+                if self.config.merge_sections && self.current_section > 0 {
+                    // Hold off until we've seen the new section's IDBs
+                    debug!("Checking whether the new section can be merged");
+                    self.pending_merge = Some(PendingMerge {
+                        matched: 0,
+                        offset: (offset, shb.endianness),
+                    });
+                } else {
+                    self.section_offset = Some((offset, shb.endianness));
+                    self.start_new_section();
+                }
+            }
             Block::InterfaceDescription(descr) => {
                 debug!("Defined a new interface: {:?}", descr);
-                if descr.snap_len.unwrap_or(0) > BlockReader::<R>::BUF_CAPACITY as u32 {
-                    warn!(
-                        "The max packet length for this interface is greater \
-                        than the length of our buffer."
-                    );
+                match descr.snap_len {
+                    Some(snap_len) if snap_len > BlockReader::<R>::BUF_CAPACITY as u32 => {
+                        self.diagnose(Diagnostic::LargeSnapLen { snap_len });
+                    }
+                    _ => (),
                 }
-                let iface = InterfaceInfo {
+                let mut iface = InterfaceInfo {
                     descr: descr.clone(),
                     stats: None,
+                    clock: self.config.clocks.get(descr.if_name.as_str()).copied(),
+                    correction: self.config.corrections.get(descr.if_name.as_str()).copied(),
                 };
+                if let Some(tsresol) = self.config.tsresols.get(descr.if_name.as_str()) {
+                    debug!("Overriding the timestamp resolution of {}", descr.if_name);
+                    iface.descr.if_tsresol = *tsresol;
+                }
                 debug!("Parsed: {iface:?}");
-                self.interfaces.push(Some(iface));
+                if let Some(merge) = &mut self.pending_merge {
+                    let same = self
+                        .interfaces
+                        .get(merge.matched)
+                        .and_then(|x| x.as_deref())
+                        .map_or(false, |x| x.descr == iface.descr);
+                    if same {
+                        merge.matched += 1;
+                        return Ok(());
+                    }
+                    self.split_pending_section();
+                }
+                let matches = self.filter_matches(Some(&iface));
+                self.interface_matches.push(matches);
+                self.interfaces.push(Some(Arc::new(iface)));
             }
             Block::NameResolution(x) => {
                 debug!("Defined a new resolved name: {x:?}");
@@ -213,30 +2659,90 @@ impl<R: Read> Capture<R> {
                 debug!("Got some interface statistics: {stats:?}");
                 match self
                     .interfaces
-                    .get_mut(stats.interface_id as usize)
+                    .get_mut(stats.interface_id.index())
                     .and_then(|x| x.as_mut())
                 {
-                    Some(x) => x.stats = Some(stats.clone()),
-                    None => warn!("Saw statistics for an undefined interface"),
+                    Some(x) => Arc::make_mut(x).stats = Some(stats.clone()),
+                    None => self.report_undefined_interface(
+                        BlockType::InterfaceStatistics,
+                        stats.interface_id,
+                    )?,
                 }
             }
             Block::EnhancedPacket(pkt) => trace!("Got a packet: {pkt:?}"),
             Block::SimplePacket(pkt) => trace!("Got a packet: {pkt:?}"),
             Block::ObsoletePacket(pkt) => trace!("Got a packet: {pkt:?}"),
-            Block::Unparsed(block_type) => {
-                warn!("{block_type:?} blocks are ignored")
+            Block::Extension(block_type, _) => trace!("Got a {block_type:?} extension block"),
+            Block::Unparsed {
+                ty: BlockType::Sysdig,
+                ..
+            } if self.config.quirks.sysdig_blocks => {
+                trace!("Skipping a sysdig block");
+                self.recovery_stats.blocks_ignored += 1;
+            }
+            Block::Unparsed { ty: block_type, .. } => {
+                self.diagnose(Diagnostic::IgnoredBlock(*block_type));
+                self.recovery_stats.blocks_ignored += 1;
             }
         }
+        Ok(())
+    }
+
+    /// Log something which was worked around, and pass it on to the
+    /// diagnostic sink
+    ///
+    /// The sink is shared with the block reader.
+    fn diagnose(&self, x: Diagnostic) {
+        self.inner.diagnose(x);
     }
 
     fn handle_corrupt_block(&mut self, block_type: BlockType) {
         use crate::block::BlockType as BT;
+        if block_type == BT::InterfaceDescription {
+            // We can't tell whether it matches, so don't merge
+            self.split_pending_section();
+        } else {
+            self.settle_pending_section();
+        }
         match block_type {
             BT::SectionHeader => self.start_new_section(),
-            BT::InterfaceDescription => self.interfaces.push(None),
+            BT::InterfaceDescription => {
+                self.interfaces.push(None);
+                self.interface_matches.push(self.filter_matches(None));
+            }
             BT::NameResolution | BT::InterfaceStatistics => (),
             BT::ObsoletePacket | BT::SimplePacket | BT::EnhancedPacket => (),
             _ => (),
         }
     }
 }
+
+// The guarantees documented on `Capture` and `Packet`.  These fail to
+// compile if a non-thread-safe type creeps into one of them.
+const _: () = {
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+    #[allow(dead_code)]
+    fn capture_is_send<R: Send>() {
+        assert_send::<Capture<R>>();
+    }
+    #[allow(dead_code)]
+    fn capture_is_sync<R: Sync>() {
+        assert_sync::<Capture<R>>();
+    }
+    #[allow(dead_code)]
+    fn others_are_thread_safe() {
+        assert_send::<CaptureBuilder>();
+        assert_sync::<CaptureBuilder>();
+        assert_send::<Packet>();
+        assert_sync::<Packet>();
+        assert_send::<Block>();
+        assert_sync::<Block>();
+        assert_send::<InterfaceInfo>();
+        assert_sync::<InterfaceInfo>();
+        assert_send::<Checkpoint>();
+        assert_sync::<Checkpoint>();
+        assert_send::<Error>();
+        assert_sync::<Error>();
+    }
+};
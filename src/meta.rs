@@ -0,0 +1,133 @@
+use crate::block::{self, Block, Endianness};
+use crate::iface::InterfaceId;
+use crate::{Capture, Result};
+use std::io::Read;
+use std::time::SystemTime;
+
+/// A packet's metadata, without its data
+///
+/// See [`Capture::metadata_iter()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct PacketMeta {
+    /// The time at which the packet was captured, as in
+    /// [`Packet::timestamp`](crate::Packet::timestamp)
+    pub timestamp: Option<SystemTime>,
+    /// The interface used to capture this packet
+    pub interface: Option<InterfaceId>,
+    /// The number of bytes of the packet which were captured.  This is
+    /// what `data.len()` would be, even if the data wasn't read.
+    pub captured_len: u32,
+    /// The length of the packet as it appeared on the wire
+    pub orig_len: u32,
+    /// The `epb_flags` or `pack_flags` option, or zero if it was absent
+    pub flags: u32,
+    /// The offset of the packet's block
+    pub block_offset: u64,
+    /// The offset of the packet's data
+    pub data_offset: u64,
+}
+
+impl PacketMeta {
+    /// The metadata which can be read from the block itself.  `block_len`
+    /// is the length of the whole block, framing and all.
+    pub(crate) fn new(
+        block: &Block,
+        block_offset: u64,
+        block_len: u64,
+        endianness: Endianness,
+    ) -> PacketMeta {
+        let (header_len, captured_len, flags) = match block {
+            Block::EnhancedPacket(x) => (28, x.captured_len, x.epb_flags),
+            Block::ObsoletePacket(x) => {
+                let flags = x.options.iter().find(|(ty, _)| *ty == block::PACK_FLAGS);
+                let flags = match flags {
+                    Some((_, x)) if x.len() == 4 => {
+                        let x = x[..].try_into().unwrap();
+                        match endianness {
+                            Endianness::Big => u32::from_be_bytes(x),
+                            Endianness::Little => u32::from_le_bytes(x),
+                        }
+                    }
+                    _ => 0,
+                };
+                (28, x.captured_len, flags)
+            }
+            Block::SimplePacket(x) => {
+                // The data fills the rest of the block, up to the padding
+                let available = u32::try_from(block_len.saturating_sub(16)).unwrap_or(u32::MAX);
+                (12, x.packet_len.min(available), 0)
+            }
+            _ => (0, 0, 0),
+        };
+        PacketMeta {
+            timestamp: None,
+            interface: None,
+            captured_len,
+            orig_len: 0,
+            flags,
+            block_offset,
+            data_offset: block_offset + header_len,
+        }
+    }
+}
+
+/// An iterator of the metadata of the packets in a capture
+///
+/// See [`Capture::metadata_iter()`].
+pub struct MetadataIter<'a, R> {
+    capture: &'a mut Capture<R>,
+}
+
+impl<R: Read> Iterator for MetadataIter<'_, R> {
+    type Item = Result<PacketMeta>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.capture.try_next_with_meta().transpose()?;
+        Some(x.map(|(_, meta)| meta))
+    }
+}
+
+impl<R: Read> Capture<R> {
+    /// Read the metadata of the remaining packets, dropping their data
+    ///
+    /// Each packet is read just as [`next()`](Iterator::next) would read
+    /// it, but only its timestamp, interface, lengths, flags, and location
+    /// in the file are returned.  This is handy for counting passes, which
+    /// don't need to hang on to the data.
+    ///
+    /// To avoid reading the data at all, build the `Capture` with
+    /// [`metadata_only()`](crate::CaptureBuilder::metadata_only).  Large
+    /// packets are then skipped over without being buffered, which makes
+    /// a pass over a big archive much cheaper.  The packets' options aren't
+    /// read in that mode, so `flags` is always zero.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::{Capture, CaptureBuilder};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[(2, &1u32.to_le_bytes())]) // inbound
+    /// #     .enhanced_packet(0, 1, &[0; 100_000], &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let metas: Vec<_> = capture.metadata_iter().map(|x| x.unwrap()).collect();
+    /// assert_eq!(metas[0].captured_len, 5);
+    /// assert_eq!(metas[0].flags, 1);
+    /// assert_eq!(metas[0].data_offset, metas[0].block_offset + 28);
+    /// assert_eq!(metas[1].block_offset, metas[0].block_offset + 52);
+    ///
+    /// let mut capture = CaptureBuilder::new()
+    ///     .metadata_only(true)
+    ///     .build(pcap.build());
+    /// let total: u64 = capture
+    ///     .metadata_iter()
+    ///     .map(|x| u64::from(x.unwrap().orig_len))
+    ///     .sum();
+    /// assert_eq!(total, 100_005);
+    /// ```
+    pub fn metadata_iter(&mut self) -> MetadataIter<'_, R> {
+        MetadataIter { capture: self }
+    }
+}
@@ -0,0 +1,306 @@
+/*! Reading packets from Microsoft Network Monitor captures
+
+This module is only available with the `netmon` feature.  It reads the
+`.cap` files written by versions 2.x of Network Monitor (and by Message
+Analyzer).  Such a file has a header, a table giving the offset of each
+frame, and the frames themselves; so reading one requires `Seek`.
+
+```
+use pcarp::iface::LinkType;
+use pcarp::netmon::NetmonReader;
+use pcarp::source::PacketSource;
+use std::time::{Duration, UNIX_EPOCH};
+
+# let mut file = vec![0; 32];
+# file[..4].copy_from_slice(b"GMBU");
+# file[4] = 0; // minor version
+# file[5] = 2; // major version
+# file[6..8].copy_from_slice(&1_u16.to_le_bytes()); // ethernet
+# for (i, x) in [2020_u16, 1, 3, 1, 0, 0, 0, 0].iter().enumerate() {
+#     file[8 + 2 * i..10 + 2 * i].copy_from_slice(&x.to_le_bytes());
+# }
+# file.extend_from_slice(&1_500_000_u64.to_le_bytes()); // microseconds since start
+# file.extend_from_slice(&5_u32.to_le_bytes());
+# file.extend_from_slice(&5_u32.to_le_bytes());
+# file.extend_from_slice(b"hello");
+# let table_offset = file.len() as u32;
+# file.extend_from_slice(&32_u32.to_le_bytes());
+# file[24..28].copy_from_slice(&table_offset.to_le_bytes());
+# file[28..32].copy_from_slice(&4_u32.to_le_bytes());
+let mut rdr = NetmonReader::new(std::io::Cursor::new(file)).unwrap();
+let (pkt, iface) = rdr.next_packet().unwrap().unwrap();
+assert_eq!(&pkt.data[..], b"hello");
+assert_eq!(iface.unwrap().link_type(), LinkType::ETHERNET);
+let start = UNIX_EPOCH + Duration::from_secs(1_577_836_800); // 2020-01-01
+assert_eq!(pkt.timestamp, Some(start + Duration::from_millis(1500)));
+assert!(rdr.next_packet().is_none());
+```
+*/
+
+use crate::block::{read_vec, InterfaceDescription, Timestamp};
+use crate::iface::{InterfaceId, InterfaceInfo, LinkType};
+use crate::source::PacketSource;
+use crate::Packet;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::*;
+
+const MAGIC: &[u8; 4] = b"GMBU";
+/// The length of the part of the file header which we care about
+const FILE_HEADER_LEN: usize = 32;
+/// The length of the header at the start of each frame
+const FRAME_HEADER_LEN: usize = 16;
+
+const MAC_ETHERNET: u16 = 1;
+const MAC_TOKEN_RING: u16 = 2;
+const MAC_FDDI: u16 = 3;
+/// Media types from here upwards are pcap link types, plus this base
+const MAC_PCAP_BASE: u16 = 0xE000;
+/// Media types from here upwards are special Network Monitor records
+/// (network events, etc.)
+const MAC_PCAP_MAX: u16 = 0xEFFF;
+
+/// The number of seconds between 1601-01-01 (the Windows epoch) and
+/// 1970-01-01
+const FILETIME_EPOCH_OFFSET: u64 = 11_644_473_600;
+
+/// An error which occurred while reading a Network Monitor capture
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum NetmonError {
+    /// The file doesn't start with the Network Monitor 2.x magic bytes
+    #[error("Not a Network Monitor 2.x capture")]
+    NotNetmon,
+    /// The file is from a version of Network Monitor which we don't
+    /// understand
+    #[error("Unsupported Network Monitor version {0}.{1}")]
+    UnsupportedVersion(u8, u8),
+    /// A frame has a media type which doesn't correspond to a link type.
+    /// This is non-fatal.
+    #[error("Frame {0} has unsupported media type {1:#x}")]
+    UnsupportedMediaType(usize, u16),
+    /// A frame extends beyond the end of the file.  This is non-fatal.
+    #[error("Frame {0} is truncated")]
+    TruncatedFrame(usize),
+    /// The underlying reader returned an error.  Whether this is fatal
+    /// depends on the reader.
+    #[error("IO error")]
+    IO(#[from] io::Error),
+}
+
+/// Reads packets from a Network Monitor capture
+///
+/// Timestamps have microsecond resolution.  Files written by Network
+/// Monitor 2.3 and later record the UTC time of each frame; for older
+/// files, the time of each frame is relative to the start time in the file
+/// header, which is assumed to be UTC (even though it's often local time).
+///
+/// Each link type is presented as a separate interface, with an ID of
+/// `InterfaceId(0, n)`.
+pub struct NetmonReader<R> {
+    rdr: R,
+    version: (u8, u8),
+    /// The media type given in the file header
+    media_type: u16,
+    /// The time at which the capture started
+    start: SystemTime,
+    /// The offset of each frame
+    frames: Vec<u32>,
+    /// The index of the next frame to be read
+    next: usize,
+    interfaces: Vec<Arc<InterfaceInfo>>,
+}
+
+impl<R: Read + Seek> NetmonReader<R> {
+    /// Read the file header and the frame table
+    pub fn new(mut rdr: R) -> Result<NetmonReader<R>, NetmonError> {
+        rdr.seek(SeekFrom::Start(0))?;
+        let mut hdr = [0; FILE_HEADER_LEN];
+        rdr.read_exact(&mut hdr).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => NetmonError::NotNetmon,
+            _ => e.into(),
+        })?;
+        if &hdr[..4] != MAGIC {
+            return Err(NetmonError::NotNetmon);
+        }
+        let version = (hdr[5], hdr[4]);
+        if version.0 != 2 {
+            return Err(NetmonError::UnsupportedVersion(version.0, version.1));
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([hdr[i], hdr[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(hdr[i..i + 4].try_into().unwrap());
+        let media_type = u16_at(6);
+        let start = system_time(&hdr[8..24]);
+        let table_offset = u32_at(24);
+        let table_len = u32_at(28) as usize;
+        debug!(
+            "Network Monitor {}.{} capture, started at {start:?}",
+            version.0, version.1
+        );
+
+        rdr.seek(SeekFrom::Start(u64::from(table_offset)))?;
+        let table = read_vec(&mut rdr, (table_len - table_len % 4) as u64)?;
+        let frames = table
+            .chunks_exact(4)
+            .map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        debug!("The capture has {} frames", frames.len());
+        Ok(NetmonReader {
+            rdr,
+            version,
+            media_type,
+            start,
+            frames,
+            next: 0,
+            interfaces: vec![],
+        })
+    }
+
+    /// The version of Network Monitor which wrote the file, as `(major,
+    /// minor)`
+    pub fn version(&self) -> (u8, u8) {
+        self.version
+    }
+
+    /// The number of frames in the file
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether the file contains no frames
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Get some info about a certain network interface
+    pub fn lookup_interface(&self, interface_id: InterfaceId) -> Option<&InterfaceInfo> {
+        match interface_id {
+            InterfaceId(0, n) => self.interfaces.get(n as usize).map(|x| &**x),
+            _ => None,
+        }
+    }
+
+    /// Unwrap the reader, returning the underlying file
+    pub fn into_inner(self) -> R {
+        self.rdr
+    }
+
+    fn read_frame(&mut self, idx: usize) -> Result<(Packet, Arc<InterfaceInfo>), NetmonError> {
+        let truncated = |e: io::Error| match e.kind() {
+            io::ErrorKind::UnexpectedEof => NetmonError::TruncatedFrame(idx),
+            _ => e.into(),
+        };
+        self.rdr
+            .seek(SeekFrom::Start(u64::from(self.frames[idx])))?;
+        let mut hdr = [0; FRAME_HEADER_LEN];
+        self.rdr.read_exact(&mut hdr).map_err(truncated)?;
+        let offset_us = u64::from_le_bytes(hdr[..8].try_into().unwrap());
+        let orig_len = u32::from_le_bytes(hdr[8..12].try_into().unwrap());
+        let len = u32::from_le_bytes(hdr[12..16].try_into().unwrap());
+        let data = read_vec(&mut self.rdr, u64::from(len)).map_err(truncated)?;
+
+        // Later versions follow each frame with a trailer
+        let mut media_type = self.media_type;
+        let mut timestamp = None;
+        if self.version >= (2, 1) {
+            let mut trailer = [0; 2 + 4 + 8 + 1];
+            let trailer_len = match self.version {
+                (2, 1) => 2,
+                (2, 2) => 2 + 4,
+                _ => trailer.len(),
+            };
+            self.rdr
+                .read_exact(&mut trailer[..trailer_len])
+                .map_err(truncated)?;
+            media_type = u16::from_le_bytes([trailer[0], trailer[1]]);
+            if trailer_len == trailer.len() {
+                let filetime = u64::from_le_bytes(trailer[6..14].try_into().unwrap());
+                timestamp = filetime_to_system_time(filetime);
+            }
+        }
+
+        let link_type = match media_type {
+            MAC_ETHERNET => LinkType::ETHERNET,
+            MAC_TOKEN_RING => LinkType::TOKEN_RING,
+            MAC_FDDI => LinkType::FDDI,
+            MAC_PCAP_BASE..=MAC_PCAP_MAX => LinkType::from_u16(media_type - MAC_PCAP_BASE),
+            _ => return Err(NetmonError::UnsupportedMediaType(idx, media_type)),
+        };
+        let (n, iface) = self.interface(link_type);
+        let timestamp = timestamp.unwrap_or(self.start + Duration::from_micros(offset_us));
+        let mut pkt = Packet::new(data);
+        pkt.timestamp = Some(timestamp);
+        pkt.raw_timestamp = Some(Timestamp(offset_us));
+        pkt.interface = Some(InterfaceId(0, n));
+        pkt.orig_len = orig_len;
+        pkt.section_start = Some(self.start);
+        Ok((pkt, iface))
+    }
+
+    /// Find the interface for the given link type, creating it if necessary
+    fn interface(&mut self, link_type: LinkType) -> (u32, Arc<InterfaceInfo>) {
+        let idx = match self
+            .interfaces
+            .iter()
+            .position(|x| x.link_type() == link_type)
+        {
+            Some(idx) => idx,
+            None => {
+                debug!("Saw a new link type: {link_type:?}");
+                let iface = InterfaceInfo {
+                    descr: InterfaceDescription::new(link_type),
+                    stats: None,
+                    clock: None,
+                    correction: None,
+                };
+                self.interfaces.push(Arc::new(iface));
+                self.interfaces.len() - 1
+            }
+        };
+        (idx as u32, self.interfaces[idx].clone())
+    }
+}
+
+impl<R: Read + Seek> PacketSource for NetmonReader<R> {
+    type Error = NetmonError;
+    fn next_packet(&mut self) -> Option<Result<(Packet, Option<Arc<InterfaceInfo>>), NetmonError>> {
+        let idx = self.next;
+        if idx >= self.frames.len() {
+            return None;
+        }
+        self.next += 1;
+        Some(self.read_frame(idx).map(|(pkt, iface)| (pkt, Some(iface))))
+    }
+}
+
+/// Convert a Windows `SYSTEMTIME` (in UTC) to a `SystemTime`
+fn system_time(buf: &[u8]) -> SystemTime {
+    let field = |i: usize| i64::from(u16::from_le_bytes([buf[2 * i], buf[2 * i + 1]]));
+    let (year, month, day) = (field(0), field(1), field(3));
+    let (hour, minute, second, milli) = (field(4), field(5), field(6), field(7));
+    // Days since the unix epoch, from Howard Hinnant's `days_from_civil`
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    let secs = days * 86_400 + hour * 3600 + minute * 60 + second;
+    match u64::try_from(secs) {
+        Ok(secs) => UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(milli as u64),
+        Err(_) => {
+            warn!("The capture claims to have started before 1970");
+            UNIX_EPOCH
+        }
+    }
+}
+
+/// Convert a Windows `FILETIME` (100ns ticks since 1601) to a `SystemTime`
+fn filetime_to_system_time(filetime: u64) -> Option<SystemTime> {
+    let since_1601 = Duration::from_nanos(filetime.checked_mul(100)?);
+    let since_1970 = since_1601.checked_sub(Duration::from_secs(FILETIME_EPOCH_OFFSET))?;
+    Some(UNIX_EPOCH + since_1970)
+}
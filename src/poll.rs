@@ -0,0 +1,94 @@
+use crate::{Capture, Error, Packet, Result};
+use std::io::{ErrorKind, Read};
+use std::time::Instant;
+
+/// An iterator of the packets which can be read before a deadline
+///
+/// See [`Capture::poll_until()`].
+pub struct PollUntil<'a, R> {
+    capture: &'a mut Capture<R>,
+    deadline: Instant,
+}
+
+impl<R: Read> Iterator for PollUntil<'_, R> {
+    type Item = Result<Packet>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if Instant::now() >= self.deadline {
+            return None;
+        }
+        match self.capture.next()? {
+            Err(Error::IO(e)) if e.kind() == ErrorKind::WouldBlock => None,
+            x => Some(x),
+        }
+    }
+}
+
+impl<R> PollUntil<'_, R> {
+    /// When the iterator stops returning packets
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+}
+
+impl<R: Read> Capture<R> {
+    /// Read the packets which are available now, stopping at `deadline`
+    ///
+    /// This is for following a capture which is still being written, from
+    /// an event loop.  The returned iterator stops at the deadline, or as
+    /// soon as the reader runs out of data: that is, when it reaches the
+    /// end of the file, or returns [`WouldBlock`](ErrorKind::WouldBlock).
+    /// A packet which has only been partly written stays buffered.  Once
+    /// more data has arrived, call `poll_until()` again to carry on from
+    /// where it left off.
+    ///
+    /// The deadline is checked before each packet is read, so it may be
+    /// overrun by the time it takes to read one packet.  If the reader
+    /// blocks, so does this: use a non-blocking reader, or a file.  Other
+    /// errors are passed through.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::Capture;
+    /// use std::io::{self, Read};
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::{Duration, Instant};
+    ///
+    /// /// The reading end of a pipe, which doesn't block
+    /// struct Pipe(Arc<Mutex<io::Cursor<Vec<u8>>>>);
+    ///
+    /// impl Read for Pipe {
+    ///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    ///         match self.0.lock().unwrap().read(buf)? {
+    ///             0 => Err(io::ErrorKind::WouldBlock.into()),
+    ///             n => Ok(n),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let pipe = Arc::new(Mutex::new(io::Cursor::new(vec![])));
+    /// let mut capture = Capture::new(Pipe(pipe.clone()));
+    /// let deadline = || Instant::now() + Duration::from_millis(100);
+    ///
+    /// let mut pcap = PcapngBuilder::new();
+    /// pcap.section(Endianness::Little)
+    ///     .interface(LinkType::ETHERNET, 0, &[])
+    ///     .enhanced_packet(0, 0, b"one", &[])
+    ///     .enhanced_packet(0, 1, b"two", &[]);
+    /// let bytes = pcap.as_bytes();
+    ///
+    /// // The second packet has only been partly written so far
+    /// pipe.lock().unwrap().get_mut().extend_from_slice(&bytes[..bytes.len() - 4]);
+    /// let pkts: Vec<_> = capture.poll_until(deadline()).map(|x| x.unwrap().data).collect();
+    /// assert_eq!(pkts, ["one"]);
+    ///
+    /// pipe.lock().unwrap().get_mut().extend_from_slice(&bytes[bytes.len() - 4..]);
+    /// let pkts: Vec<_> = capture.poll_until(deadline()).map(|x| x.unwrap().data).collect();
+    /// assert_eq!(pkts, ["two"]);
+    /// ```
+    pub fn poll_until(&mut self, deadline: Instant) -> PollUntil<'_, R> {
+        PollUntil {
+            capture: self,
+            deadline,
+        }
+    }
+}
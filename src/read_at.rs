@@ -0,0 +1,323 @@
+/*! Positioned reads, for reading one pcap with many cursors
+
+A [`Capture`](crate::Capture) needs exclusive access to its reader, since
+reading moves the reader's cursor.  Sources which implement [`ReadAt`] don't
+have a cursor: each read says where it should start.  This means that one
+source can be shared between many [`ReadAtCursor`]s, each of which tracks
+its own position, and each of which can drive its own `Capture`.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::{read_at::ReadAtCursor, Capture};
+use std::sync::Arc;
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 0, b"hello", &[]);
+# let bytes = pcap.as_bytes().to_vec();
+// This would typically be an `Arc<File>`
+let source = Arc::new(bytes);
+let threads = (0..4).map(|_| {
+    let source = source.clone();
+    std::thread::spawn(move || Capture::new(ReadAtCursor::new(source)).count())
+});
+for t in threads {
+    assert_eq!(t.join().unwrap(), 1);
+}
+```
+
+## Fetching packet data on demand
+
+Filters which look at each packet's metadata, and throw most packets
+away, don't need to read most of the data.  [`LazyPackets`] reads a
+capture in two phases: the blocks are read with a small buffer, skipping
+over the data of any packets which don't fit in it, and the data is only
+fetched (with a positioned read) if you ask for it.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::CaptureBuilder;
+use std::sync::Arc;
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 0, &[0; 100_000], &[])
+#     .enhanced_packet(0, 1, b"hello", &[]);
+# let bytes = pcap.as_bytes().to_vec();
+// This would typically be an `Arc<File>`
+let source = Arc::new(bytes);
+for pkt in CaptureBuilder::new().build_lazy(source) {
+    let pkt = pkt.unwrap();
+    if pkt.meta.orig_len < 1000 {
+        assert_eq!(&pkt.data().unwrap()[..], b"hello");
+    }
+}
+```
+*/
+
+use crate::{Capture, CaptureBuilder, PacketMeta, Result};
+use bytes::Bytes;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+/// How much of a packet's data `LazyPacket::data()` reads at first.  The
+/// reads get bigger from there.
+const FIRST_CHUNK: usize = 64 * 1024;
+
+/// A source of bytes which can be read from any offset, without a cursor
+///
+/// This is implemented for `File` on unix and windows, and for in-memory
+/// buffers.  Since `read_at()` takes `&self`, a source can be shared
+/// between many readers (eg. by wrapping it in an `Arc`).
+pub trait ReadAt {
+    /// Read some bytes starting at the given offset
+    ///
+    /// Returns the number of bytes read.  As with `Read::read()`, this may
+    /// be fewer than `buf.len()`; and zero means that the offset is at (or
+    /// beyond) the end of the source.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+
+    /// The total length of the source, in bytes
+    fn size(&self) -> io::Result<u64>;
+}
+
+#[cfg(unix)]
+impl ReadAt for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(self, buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+#[cfg(windows)]
+impl ReadAt for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        // This moves the file's cursor, but we never use that anyway
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+}
+
+impl ReadAt for [u8] {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let start = usize::try_from(offset)
+            .unwrap_or(usize::MAX)
+            .min(self.len());
+        let src = &self[start..];
+        let n = src.len().min(buf.len());
+        buf[..n].copy_from_slice(&src[..n]);
+        Ok(n)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        Ok(self.len() as u64)
+    }
+}
+
+impl ReadAt for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.as_slice().read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        self.as_slice().size()
+    }
+}
+
+impl ReadAt for Bytes {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.as_ref().read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        self.as_ref().size()
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for &T {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for Box<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+}
+
+impl<T: ReadAt + ?Sized> ReadAt for Arc<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+
+    fn size(&self) -> io::Result<u64> {
+        (**self).size()
+    }
+}
+
+/// A cursor over a [`ReadAt`] source
+///
+/// This implements `Read` and `Seek`, so it can be passed to
+/// [`Capture::new()`](crate::Capture::new).  Each cursor has its own
+/// position; moving one doesn't affect any others over the same source.
+#[derive(Debug, Clone)]
+pub struct ReadAtCursor<T> {
+    inner: T,
+    pos: u64,
+}
+
+impl<T> ReadAtCursor<T> {
+    /// A cursor at the start of the source
+    pub fn new(inner: T) -> ReadAtCursor<T> {
+        ReadAtCursor { inner, pos: 0 }
+    }
+
+    /// The current position of the cursor
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// The underlying source
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Unwrap the cursor, returning the underlying source
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ReadAt> Read for ReadAtCursor<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read_at(buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<T: ReadAt> Seek for ReadAtCursor<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(x) => {
+                self.pos = x;
+                return Ok(x);
+            }
+            SeekFrom::End(x) => (self.inner.size()?, x),
+            SeekFrom::Current(x) => (self.pos, x),
+        };
+        let new_pos = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.unsigned_abs())
+        };
+        match new_pos {
+            Some(x) => {
+                self.pos = x;
+                Ok(x)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// Packets whose data is only read when it's asked for
+///
+/// Create one with [`CaptureBuilder::build_lazy()`]; see the
+/// [module-level documentation](self) for an example.
+pub struct LazyPackets<T> {
+    capture: Capture<ReadAtCursor<T>>,
+    source: T,
+}
+
+impl<T: ReadAt + Clone> Iterator for LazyPackets<T> {
+    type Item = Result<LazyPacket<T>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.capture.try_next_with_meta().transpose()?;
+        Some(x.map(|(_, meta)| LazyPacket {
+            meta,
+            source: self.source.clone(),
+        }))
+    }
+}
+
+impl<T> LazyPackets<T> {
+    /// The `Capture` which reads the packets' metadata
+    ///
+    /// It's in [metadata-only mode](CaptureBuilder::metadata_only).
+    pub fn capture(&self) -> &Capture<ReadAtCursor<T>> {
+        &self.capture
+    }
+}
+
+/// A packet whose data hasn't been read yet
+#[derive(Debug, Clone)]
+pub struct LazyPacket<T> {
+    /// Everything about the packet except its data.  Since the packet's
+    /// options aren't read, `flags` is always zero.
+    pub meta: PacketMeta,
+    source: T,
+}
+
+impl<T: ReadAt> LazyPacket<T> {
+    /// Read the packet's data from the source
+    ///
+    /// Each call reads the data afresh.  If the source ends before the
+    /// end of the data, an `UnexpectedEof` error is returned.
+    ///
+    /// The buffer grows as the data arrives, so a bogus length can't make
+    /// us allocate much more than the source actually contains.
+    pub fn data(&self) -> io::Result<Bytes> {
+        let len = self.meta.captured_len as usize;
+        let mut buf = vec![];
+        while buf.len() < len {
+            let filled = buf.len();
+            let chunk = (len - filled).min(filled.max(FIRST_CHUNK));
+            buf.resize(filled + chunk, 0);
+            let offset = self.meta.data_offset + filled as u64;
+            match self.source.read_at(&mut buf[filled..], offset)? {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => buf.truncate(filled + n),
+            }
+        }
+        Ok(buf.into())
+    }
+}
+
+impl CaptureBuilder {
+    /// Create an iterator of packets whose data is read on demand
+    ///
+    /// The `Capture` is put into [metadata-only mode](Self::metadata_only),
+    /// and packet blocks which don't fit in its buffer are skipped by
+    /// seeking.  Each packet's data can then be fetched from `source`
+    /// with [`LazyPacket::data()`].  See the
+    /// [module-level documentation](crate::read_at) for an example.
+    pub fn build_lazy<T: ReadAt + Clone>(self, source: T) -> LazyPackets<T> {
+        let mut capture = self
+            .metadata_only(true)
+            .build(ReadAtCursor::new(source.clone()));
+        capture.inner.set_skip_by_seeking(true);
+        LazyPackets { capture, source }
+    }
+}
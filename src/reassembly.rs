@@ -0,0 +1,446 @@
+/*! Reassembling TCP streams
+
+This module is only available with the `reassembly` feature.  A
+[`Reassembler`] takes packets in capture order, puts each TCP connection's
+segments back into sequence order, and hands out the contiguous runs of
+bytes as they become available.  Retransmitted and overlapping data is
+delivered only once.  The two directions of a connection are separate
+streams, identified by the [`FiveTuple`] of the packets which carry them.
+
+The data isn't copied: each [`StreamData`] shares its storage with the
+packet it came from.
+
+If a segment is lost (eg. because the capture dropped it), the data after
+it is held back in the hope that a retransmission will fill the gap.  Once
+more than [`max_buffered()`](Reassembler::max_buffered) bytes are waiting,
+the reassembler gives up on the gap, and carries on from the next data it
+has; the number of bytes which were lost is reported in
+[`StreamData::skipped`].
+
+A stream is forgotten once it's been closed (by a FIN or RST) and all its
+data has been delivered, or once it's been idle for longer than
+[`idle_timeout()`](Reassembler::idle_timeout).  If more packets turn up for
+it after that, they start a new stream.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::Capture;
+
+# fn tcp(seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+#     let mut x = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 6, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2];
+#     x[3] = 40 + payload.len() as u8;
+#     x.extend_from_slice(&[0x30, 0x39, 0, 80]); // ports 12345 -> 80
+#     x.extend_from_slice(&seq.to_be_bytes());
+#     x.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+#     x.extend_from_slice(payload);
+#     x
+# }
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::RAW, 0, &[])
+#     .enhanced_packet(0, 0, &tcp(1000, 0x02, b""), &[]) // SYN
+#     .enhanced_packet(0, 1, &tcp(1008, 0x18, b"world"), &[])
+#     .enhanced_packet(0, 2, &tcp(1001, 0x18, b"hello, "), &[])
+#     .enhanced_packet(0, 3, &tcp(1001, 0x18, b"hello, "), &[]) // retransmission
+#     .enhanced_packet(0, 4, &tcp(1013, 0x11, b""), &[]); // FIN
+let mut stream = vec![];
+for x in Capture::new(pcap.build()).tcp_streams() {
+    let x = x.unwrap();
+    assert_eq!(x.key.dst_port, 80);
+    stream.extend_from_slice(&x.data);
+}
+assert_eq!(stream, b"hello, world");
+```
+*/
+
+use crate::flows::{parse_with_transport, FiveTuple, IPPROTO_TCP};
+use crate::iface::LinkType;
+use crate::{Capture, Packet, Result};
+use bytes::Bytes;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Read;
+use std::time::{Duration, SystemTime};
+use tracing::*;
+
+const FIN: u8 = 0x01;
+const SYN: u8 = 0x02;
+const RST: u8 = 0x04;
+
+/// By default, give up on a gap once this many bytes are waiting behind it
+const DEFAULT_MAX_BUFFERED: usize = 1 << 20;
+
+/// By default, forget streams which have been idle for this long
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Some contiguous data from a TCP stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamData {
+    /// The 5-tuple of the packets which carried the data
+    pub key: FiveTuple,
+    /// The position of the data within the stream, counting from the first
+    /// byte after the SYN (or from the first data seen, if the capture
+    /// started after the connection did)
+    pub offset: u64,
+    /// The number of bytes which were lost immediately before this data
+    pub skipped: u64,
+    /// The data itself, which shares its storage with the packet it came
+    /// from
+    pub data: Bytes,
+}
+
+/// The state of one direction of a TCP connection
+#[derive(Debug, Default)]
+struct Stream {
+    /// The sequence number of the next byte to be delivered.  `None` until
+    /// the stream's first segment has been seen.
+    next_seq: Option<u32>,
+    /// The number of bytes delivered (or skipped) so far
+    offset: u64,
+    /// Segments which arrived ahead of a gap, keyed by their offset within
+    /// the stream
+    pending: BTreeMap<u64, Bytes>,
+    buffered: usize,
+    /// Whether a FIN has been seen
+    fin: bool,
+    closed: bool,
+    /// The timestamp of the latest timestamped segment
+    last_seen: Option<SystemTime>,
+}
+
+impl Stream {
+    /// Add a segment, and return any data which is now contiguous
+    fn push(
+        &mut self,
+        key: FiveTuple,
+        seq: u32,
+        flags: u8,
+        mut data: Bytes,
+        max: usize,
+    ) -> Vec<StreamData> {
+        if self.closed {
+            return vec![];
+        }
+        let mut seq = seq;
+        if flags & SYN != 0 {
+            // The SYN occupies a sequence number of its own
+            seq = seq.wrapping_add(1);
+            self.next_seq.get_or_insert(seq);
+        }
+        let next_seq = *self.next_seq.get_or_insert(seq);
+        // Sequence numbers wrap, so work out where the segment is relative
+        // to the next expected byte
+        let rel = i64::from(seq.wrapping_sub(next_seq) as i32);
+        let mut start = self.offset as i64 + rel;
+        if start < self.offset as i64 {
+            // Some (or all) of this has already been delivered
+            let overlap = (self.offset as i64 - start) as usize;
+            if overlap >= data.len() {
+                data = Bytes::new();
+            } else {
+                data = data.slice(overlap..);
+            }
+            start = self.offset as i64;
+        }
+        if !data.is_empty() {
+            let start = start as u64;
+            let old = self.pending.insert(start, data.clone());
+            self.buffered += data.len();
+            match old {
+                // Keep the longer of the two copies
+                Some(old) if old.len() > data.len() => {
+                    self.buffered -= data.len();
+                    self.pending.insert(start, old);
+                }
+                Some(old) => self.buffered -= old.len(),
+                None => (),
+            }
+        }
+        let mut out = self.drain(key, false);
+        while self.buffered > max {
+            debug!("Giving up on a gap in {key:?} at offset {}", self.offset);
+            out.extend(self.drain(key, true));
+        }
+        self.fin |= flags & FIN != 0;
+        if flags & RST != 0 || (self.fin && self.pending.is_empty()) {
+            trace!("{key:?} closed");
+            self.closed = true;
+        }
+        out
+    }
+
+    /// Deliver the data at the front of the stream.  If `skip` is set, the
+    /// gap in front of the first pending segment is skipped over.
+    fn drain(&mut self, key: FiveTuple, mut skip: bool) -> Vec<StreamData> {
+        let mut out = vec![];
+        while let Some((&start, data)) = self.pending.iter().next() {
+            let end = start + data.len() as u64;
+            if start > self.offset && !skip {
+                break;
+            }
+            let mut data = self.pending.remove(&start).unwrap();
+            self.buffered -= data.len();
+            if end <= self.offset {
+                continue;
+            }
+            let skipped = start.saturating_sub(self.offset);
+            if start < self.offset {
+                data = data.slice((self.offset - start) as usize..);
+            }
+            let offset = self.offset + skipped;
+            self.offset = end;
+            self.next_seq = self
+                .next_seq
+                .map(|x| x.wrapping_add((skipped + data.len() as u64) as u32));
+            skip = false;
+            out.push(StreamData {
+                key,
+                offset,
+                skipped,
+                data,
+            });
+        }
+        out
+    }
+
+    /// Deliver all the data which is still held back, skipping over any
+    /// gaps
+    fn flush(&mut self, key: FiveTuple) -> Vec<StreamData> {
+        let mut out = self.drain(key, false);
+        while !self.pending.is_empty() {
+            out.extend(self.drain(key, true));
+        }
+        out
+    }
+
+    /// Whether the stream has seen no segments for at least `timeout`
+    fn is_idle(&self, now: SystemTime, timeout: Duration) -> bool {
+        let idle = self.last_seen.and_then(|x| now.duration_since(x).ok());
+        idle.map_or(false, |x| x >= timeout)
+    }
+}
+
+/// Puts TCP segments back in order
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug)]
+pub struct Reassembler {
+    streams: HashMap<FiveTuple, Stream>,
+    max_buffered: usize,
+    idle_timeout: Option<Duration>,
+    /// When the streams were last checked for idleness
+    last_sweep: Option<SystemTime>,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Reassembler::new()
+    }
+}
+
+impl Reassembler {
+    /// Create a reassembler which holds back at most 1 MiB per stream, and
+    /// forgets streams which have been idle for five minutes
+    pub fn new() -> Reassembler {
+        Reassembler {
+            streams: HashMap::new(),
+            max_buffered: DEFAULT_MAX_BUFFERED,
+            idle_timeout: Some(DEFAULT_IDLE_TIMEOUT),
+            last_sweep: None,
+        }
+    }
+
+    /// Set the number of bytes which can wait behind a gap in a stream,
+    /// before the gap is given up on
+    pub fn set_max_buffered(&mut self, n: usize) {
+        self.max_buffered = n;
+    }
+
+    /// The number of bytes which can wait behind a gap in a stream
+    pub fn max_buffered(&self) -> usize {
+        self.max_buffered
+    }
+
+    /// Set how long a stream can go without any segments before it's
+    /// forgotten, or `None` to keep streams until they're closed
+    ///
+    /// Idleness is measured using the packets' timestamps.  When a stream
+    /// is forgotten, any data it was holding back is delivered (skipping
+    /// over the gaps).  Streams are checked at most once per timeout
+    /// period, so a stream may outlive its timeout by up to twice as long.
+    ///
+    /// ```
+    /// # use pcarp::{reassembly::Reassembler, iface::LinkType, Packet};
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// # fn tcp(src_port: u8, secs: u64) -> Packet {
+    /// #     let mut x = vec![0x45, 0, 0, 41, 0, 0, 0, 0, 64, 6, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2];
+    /// #     x.extend_from_slice(&[0, src_port, 0, 80, 0, 0, 0, 1]);
+    /// #     x.extend_from_slice(&[0, 0, 0, 0, 0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0, b'x']);
+    /// #     let mut pkt = Packet::new(x);
+    /// #     pkt.timestamp = Some(UNIX_EPOCH + Duration::from_secs(secs));
+    /// #     pkt
+    /// # }
+    /// let mut reassembler = Reassembler::new();
+    /// reassembler.set_idle_timeout(Some(Duration::from_secs(60)));
+    /// reassembler.push(LinkType::RAW, &tcp(1, 0));
+    /// reassembler.push(LinkType::RAW, &tcp(2, 30));
+    /// assert_eq!(reassembler.open_streams(), 2);
+    /// reassembler.push(LinkType::RAW, &tcp(2, 61)); // the first stream is idle
+    /// assert_eq!(reassembler.open_streams(), 1);
+    /// ```
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// How long a stream can go without any segments before it's forgotten
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    /// The number of streams which are currently being tracked
+    pub fn open_streams(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Add a packet, and return any stream data which is now contiguous
+    ///
+    /// Packets other than TCP are ignored.
+    pub fn push(&mut self, link_type: LinkType, pkt: &Packet) -> Vec<StreamData> {
+        let (key, transport) = match parse_with_transport(link_type, &pkt.data) {
+            Some((key, Some(transport))) if key.protocol == IPPROTO_TCP => (key, transport),
+            _ => return vec![],
+        };
+        let tcp = &pkt.data[transport.clone()];
+        let (seq, data_offset, flags) = match (tcp.get(4..8), tcp.get(12), tcp.get(13)) {
+            (Some(seq), Some(&off), Some(&flags)) => (
+                u32::from_be_bytes([seq[0], seq[1], seq[2], seq[3]]),
+                usize::from(off >> 4) * 4,
+                flags,
+            ),
+            _ => return vec![],
+        };
+        if data_offset < 20 || data_offset > tcp.len() {
+            return vec![];
+        }
+        let payload = pkt.data.slice(transport.start + data_offset..transport.end);
+        let mut out = match pkt.timestamp {
+            Some(ts) => self.expire(ts),
+            None => vec![],
+        };
+        let stream = self.streams.entry(key).or_default();
+        if pkt.timestamp.is_some() {
+            stream.last_seen = pkt.timestamp;
+        }
+        out.extend(stream.push(key, seq, flags, payload, self.max_buffered));
+        if stream.closed {
+            // Anything still pending was cut off by a RST
+            out.extend(stream.flush(key));
+            self.streams.remove(&key);
+        }
+        out
+    }
+
+    /// Forget the streams which have been idle for longer than the
+    /// timeout, returning the data they were holding back
+    fn expire(&mut self, now: SystemTime) -> Vec<StreamData> {
+        let timeout = match self.idle_timeout {
+            Some(x) => x,
+            None => return vec![],
+        };
+        // Checking every stream is expensive, so only do it once per period
+        if let Some(last) = self.last_sweep {
+            if now.duration_since(last).map_or(true, |x| x < timeout) {
+                return vec![];
+            }
+        }
+        self.last_sweep = Some(now);
+        let mut out = vec![];
+        self.streams.retain(|key, stream| {
+            let idle = stream.is_idle(now, timeout);
+            if idle {
+                trace!("{key:?} timed out");
+                out.extend(stream.flush(*key));
+            }
+            !idle
+        });
+        out
+    }
+
+    /// Deliver all the data which is still held back, skipping over any
+    /// gaps.  Call this when there are no more packets.
+    pub fn finish(&mut self) -> Vec<StreamData> {
+        let mut out = vec![];
+        for (key, mut stream) in self.streams.drain() {
+            out.extend(stream.flush(key));
+        }
+        out
+    }
+}
+
+/// An iterator of the data in a capture's TCP streams
+///
+/// See [`Capture::tcp_streams()`].
+pub struct TcpStreams<R> {
+    capture: Capture<R>,
+    reassembler: Reassembler,
+    ready: VecDeque<StreamData>,
+    finished: bool,
+}
+
+impl<R: Read> Iterator for TcpStreams<R> {
+    type Item = Result<StreamData>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(x) = self.ready.pop_front() {
+                return Some(Ok(x));
+            }
+            if self.finished {
+                return None;
+            }
+            match self.capture.next_with_iface() {
+                Some(Ok((pkt, Some(iface)))) => {
+                    let data = self.reassembler.push(iface.link_type(), &pkt);
+                    self.ready.extend(data);
+                }
+                Some(Ok((_, None))) => (),
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.finished = true;
+                    self.ready.extend(self.reassembler.finish());
+                }
+            }
+        }
+    }
+}
+
+impl<R> TcpStreams<R> {
+    /// The underlying `Reassembler`
+    pub fn reassembler_mut(&mut self) -> &mut Reassembler {
+        &mut self.reassembler
+    }
+
+    /// The underlying `Capture`
+    pub fn capture(&self) -> &Capture<R> {
+        &self.capture
+    }
+
+    /// Unwrap the iterator, returning the underlying `Capture`
+    pub fn into_inner(self) -> Capture<R> {
+        self.capture
+    }
+}
+
+impl<R: Read> Capture<R> {
+    /// Reassemble the TCP streams in the capture
+    ///
+    /// The data from all the streams is interleaved, in the order it
+    /// became available.  Only available with the `reassembly` feature.
+    /// See the [`reassembly`](crate::reassembly) module.
+    pub fn tcp_streams(self) -> TcpStreams<R> {
+        TcpStreams {
+            capture: self,
+            reassembler: Reassembler::new(),
+            ready: VecDeque::new(),
+            finished: false,
+        }
+    }
+}
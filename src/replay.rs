@@ -0,0 +1,142 @@
+/*! Replaying packets alongside other timestamped events
+
+Simulators often need to feed a model both the packets from a capture and
+events from somewhere else (eg. timers, or a log of control-plane
+activity), all in the order in which they happened.  A [`Replay`] merges a
+[`PacketSource`] with an iterator of [`Event`]s into a single stream,
+ordered by timestamp.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::replay::{Replay, ReplayItem};
+use pcarp::Capture;
+use std::time::{Duration, UNIX_EPOCH};
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 1_000_000, b"a", &[])
+#     .enhanced_packet(0, 3_000_000, b"b", &[]);
+let secs = |x| UNIX_EPOCH + Duration::from_secs(x);
+let events = vec![(secs(0), "start"), (secs(2), "tick"), (secs(4), "stop")];
+let replay = Replay::new(Capture::new(pcap.build()), events);
+let items: Vec<String> = replay
+    .map(|x| match x.unwrap() {
+        ReplayItem::Packet(pkt, _) => String::from_utf8(pkt.data.to_vec()).unwrap(),
+        ReplayItem::Event((_, name)) => name.to_owned(),
+    })
+    .collect();
+assert_eq!(items, ["start", "a", "tick", "b", "stop"]);
+```
+*/
+
+use crate::iface::InterfaceInfo;
+use crate::source::PacketSource;
+use crate::Packet;
+use std::iter::Peekable;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Something which happened at a particular time
+pub trait Event {
+    /// When it happened
+    fn timestamp(&self) -> SystemTime;
+}
+
+/// A pair of a timestamp and some data is an event
+impl<T> Event for (SystemTime, T) {
+    fn timestamp(&self) -> SystemTime {
+        self.0
+    }
+}
+
+/// An item produced by a [`Replay`]
+#[derive(Debug, Clone)]
+pub enum ReplayItem<E> {
+    /// A packet, along with info about the interface it was captured on
+    Packet(Packet, Option<Arc<InterfaceInfo>>),
+    /// One of the events
+    Event(E),
+}
+
+/// Merges packets and events into a single time-ordered stream
+///
+/// Both the packets and the events are assumed to be in order already:
+/// this doesn't sort them, it just interleaves them.  When a packet and an
+/// event have the same timestamp, the packet comes first.  Packets without
+/// a timestamp are passed through as soon as they're read.
+///
+/// Errors from the packet source are passed through too.  Reading carries
+/// on afterwards, if the source allows it.
+///
+/// See the [module-level documentation](self) for an example.
+pub struct Replay<S, I: Iterator> {
+    source: S,
+    events: Peekable<I>,
+    /// The next packet, which is waiting for earlier events to go first
+    pending: Option<(Packet, Option<Arc<InterfaceInfo>>)>,
+    /// Whether the source has run out of packets
+    exhausted: bool,
+}
+
+impl<S, I> Replay<S, I>
+where
+    S: PacketSource,
+    I: Iterator,
+    I::Item: Event,
+{
+    /// Merge the packets from `source` with `events`
+    pub fn new(source: S, events: impl IntoIterator<IntoIter = I>) -> Replay<S, I> {
+        Replay {
+            source,
+            events: events.into_iter().peekable(),
+            pending: None,
+            exhausted: false,
+        }
+    }
+
+    /// Get a reference to the underlying packet source
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Unwrap the `Replay`, returning the packet source
+    ///
+    /// If a packet had been read from the source but not yet returned, it's
+    /// lost.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S, I> Iterator for Replay<S, I>
+where
+    S: PacketSource,
+    I: Iterator,
+    I::Item: Event,
+{
+    type Item = Result<ReplayItem<I::Item>, S::Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_none() && !self.exhausted {
+            match self.source.next_packet() {
+                Some(Ok(x)) => self.pending = Some(x),
+                Some(Err(e)) => return Some(Err(e)),
+                None => self.exhausted = true,
+            }
+        }
+        let event_first = match (&self.pending, self.events.peek()) {
+            (Some((pkt, _)), Some(event)) => match pkt.timestamp {
+                Some(ts) => event.timestamp() < ts,
+                None => false,
+            },
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        if event_first {
+            self.events.next().map(|x| Ok(ReplayItem::Event(x)))
+        } else {
+            let (pkt, iface) = self.pending.take()?;
+            Some(Ok(ReplayItem::Packet(pkt, iface)))
+        }
+    }
+}
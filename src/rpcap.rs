@@ -0,0 +1,261 @@
+/*! Reading packets from an RPCAP stream
+
+[RPCAP] is the protocol spoken by `rpcapd`, libpcap's remote capture
+daemon.  The daemon sends the captured packets to the client as a stream
+of messages, each of which carries a single packet.  An [`RpcapReader`]
+reads these messages and turns them into [`Packet`]s, just like a
+[`Capture`](crate::Capture) does with the blocks of a pcapng.
+
+Setting up the capture (authenticating, opening the device, starting the
+capture) is up to you.  Once you have the data connection, hand it to an
+`RpcapReader`.  The link type is reported by the daemon when the device is
+opened, which is usually on a different connection, so you need to supply
+it up-front.  If an "open" reply does appear in the stream, its link type
+takes precedence.
+
+```
+use pcarp::iface::LinkType;
+use pcarp::rpcap::RpcapReader;
+use pcarp::source::PacketSource;
+
+// A packet message, as sent by rpcapd
+let mut msg = vec![0, 7, 0, 0, 0, 0, 0, 25]; // version, type, value, length
+msg.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 2]); // timestamp
+msg.extend_from_slice(&[0, 0, 0, 5, 0, 0, 0, 5]); // caplen, len
+msg.extend_from_slice(&[0, 0, 0, 1]); // sequence number
+msg.extend_from_slice(b"hello");
+
+let mut rdr = RpcapReader::new(&msg[..], LinkType::ETHERNET);
+let (pkt, iface) = rdr.next_packet().unwrap().unwrap();
+assert_eq!(&pkt.data[..], b"hello");
+assert_eq!(iface.unwrap().link_type(), LinkType::ETHERNET);
+assert!(rdr.next_packet().is_none());
+```
+
+[RPCAP]: https://www.tcpdump.org/manpages/rpcapd.8.html
+*/
+
+use crate::block::{read_vec, InterfaceDescription, Timestamp};
+use crate::iface::{InterfaceId, InterfaceInfo, LinkType};
+use crate::source::PacketSource;
+use crate::Packet;
+use bytes::{Buf, Bytes};
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::time::SystemTime;
+use thiserror::Error;
+use tracing::*;
+
+/// The only version of the protocol which has been defined
+const RPCAP_VERSION: u8 = 0;
+
+const MSG_ERROR: u8 = 1;
+const MSG_PACKET: u8 = 7;
+const MSG_OPEN_REPLY: u8 = 0x83;
+
+/// The length of the header which precedes each message
+const MSG_HEADER_LEN: usize = 8;
+/// The length of the header which precedes the data of a packet message
+const PKT_HEADER_LEN: usize = 20;
+
+/// An error which occurred while reading an RPCAP stream
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RpcapError {
+    /// The daemon reported an error.  This is non-fatal.
+    #[error("The RPCAP server reported an error: {0}")]
+    Server(String),
+    /// A packet message was too short to contain the packet.  This is
+    /// non-fatal.
+    #[error("The packet message is {0} bytes, which is too short")]
+    TruncatedPacket(usize),
+    /// The underlying reader returned an error.  Whether this is fatal
+    /// depends on the reader.
+    #[error("IO error")]
+    IO(#[from] io::Error),
+}
+
+/// Reads packets from the data connection of an RPCAP session
+///
+/// Packets are timestamped with microsecond resolution.  The interface IDs
+/// are `InterfaceId(n, 0)`, where `n` counts the number of "open" replies
+/// seen so far.
+pub struct RpcapReader<R> {
+    rdr: R,
+    /// The interfaces we've seen: one for the link type supplied by the
+    /// user, and one for each "open" reply
+    interfaces: Vec<Arc<InterfaceInfo>>,
+    /// The timestamp of the first packet since the device was opened
+    start: Option<SystemTime>,
+    /// The sequence number we expect the next packet to have
+    next_seq: Option<u32>,
+    /// The number of packets which were missing from the sequence
+    lost: u64,
+}
+
+impl<R: Read> RpcapReader<R> {
+    /// Read RPCAP messages from the given reader.  `link_type` should be
+    /// the link type which the daemon reported when the device was opened.
+    pub fn new(rdr: R, link_type: LinkType) -> RpcapReader<R> {
+        RpcapReader {
+            rdr,
+            interfaces: vec![new_interface(link_type)],
+            start: None,
+            next_seq: None,
+            lost: 0,
+        }
+    }
+
+    /// Get some info about a certain network interface
+    pub fn lookup_interface(&self, interface_id: InterfaceId) -> Option<&InterfaceInfo> {
+        match interface_id {
+            InterfaceId(n, 0) => self.interfaces.get(n as usize).map(|x| &**x),
+            _ => None,
+        }
+    }
+
+    /// The number of packets which were skipped, according to the
+    /// sequence numbers which the daemon attached to the packets
+    ///
+    /// When the data is sent over UDP, packets may be lost in transit.  Only
+    /// forward jumps in the sequence are counted: a packet which arrives
+    /// out of order is counted as lost when it's skipped over, and is
+    /// ignored when it turns up later.
+    ///
+    /// ```
+    /// # use pcarp::{iface::LinkType, rpcap::RpcapReader, source::PacketSource};
+    /// # let msg = |seq: u32| {
+    /// #     let mut msg = vec![0, 7, 0, 0, 0, 0, 0, 20];
+    /// #     msg.extend_from_slice(&[0; 16]);
+    /// #     msg.extend_from_slice(&seq.to_be_bytes());
+    /// #     msg
+    /// # };
+    /// let msgs: Vec<u8> = [1, 2, 5, 3, 6].into_iter().flat_map(msg).collect();
+    /// let mut rdr = RpcapReader::new(&msgs[..], LinkType::ETHERNET);
+    /// while let Some(x) = rdr.next_packet() {
+    ///     x.unwrap();
+    /// }
+    /// assert_eq!(rdr.lost(), 2); // packets 3 and 4
+    /// ```
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    /// Unwrap the reader, returning the underlying stream
+    pub fn into_inner(self) -> R {
+        self.rdr
+    }
+
+    /// Read a message.  Returns `None` if the stream ended cleanly.
+    fn read_msg(&mut self) -> Result<Option<(u8, Bytes)>, RpcapError> {
+        let mut hdr = [0; MSG_HEADER_LEN];
+        let mut n = 0;
+        while n < hdr.len() {
+            match self.rdr.read(&mut hdr[n..]) {
+                Ok(0) if n == 0 => return Ok(None),
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(x) => n += x,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let mut hdr = &hdr[..];
+        let version = hdr.get_u8();
+        let msg_type = hdr.get_u8();
+        let _value = hdr.get_u16();
+        let len = hdr.get_u32() as usize;
+        if version != RPCAP_VERSION {
+            warn!("Saw a message with unknown RPCAP version {version}; reading it anyway");
+        }
+        let body = read_vec(&mut self.rdr, len as u64)?;
+        Ok(Some((msg_type, Bytes::from(body))))
+    }
+
+    fn try_next(&mut self) -> Result<Option<(Packet, Arc<InterfaceInfo>)>, RpcapError> {
+        loop {
+            let (msg_type, mut body) = match self.read_msg()? {
+                Some(x) => x,
+                None => return Ok(None),
+            };
+            match msg_type {
+                MSG_PACKET => return self.parse_packet(body).map(Some),
+                MSG_OPEN_REPLY if body.len() >= 4 => {
+                    let link_type = LinkType::from_u16(body.get_u32() as u16);
+                    debug!("The device was opened with link type {link_type:?}");
+                    self.interfaces.push(new_interface(link_type));
+                    self.start = None;
+                    self.next_seq = None;
+                }
+                MSG_ERROR => {
+                    let msg = String::from_utf8_lossy(&body).into_owned();
+                    return Err(RpcapError::Server(msg));
+                }
+                _ => debug!("Ignoring an RPCAP message of type {msg_type:#x}"),
+            }
+        }
+    }
+
+    fn parse_packet(
+        &mut self,
+        mut body: Bytes,
+    ) -> Result<(Packet, Arc<InterfaceInfo>), RpcapError> {
+        let msg_len = body.len();
+        if msg_len < PKT_HEADER_LEN {
+            return Err(RpcapError::TruncatedPacket(msg_len));
+        }
+        let secs = body.get_u32();
+        let usecs = body.get_u32();
+        let caplen = body.get_u32() as usize;
+        let orig_len = body.get_u32();
+        let seq = body.get_u32();
+        if caplen > body.len() {
+            return Err(RpcapError::TruncatedPacket(msg_len));
+        }
+        match self
+            .next_seq
+            .map(|expected| (expected, seq.wrapping_sub(expected)))
+        {
+            Some((_, 0)) | None => self.next_seq = Some(seq.wrapping_add(1)),
+            Some((expected, gap)) if gap < 1 << 31 => {
+                warn!("Expected packet {expected}, but got packet {seq}");
+                self.lost += u64::from(gap);
+                self.next_seq = Some(seq.wrapping_add(1));
+            }
+            // A packet from before the one we expected: it arrived out of
+            // order, so it was already counted as lost
+            Some((expected, _)) => {
+                warn!("Expected packet {expected}, but got earlier packet {seq}");
+            }
+        }
+
+        let idx = self.interfaces.len() - 1;
+        let iface = &self.interfaces[idx];
+        let raw_timestamp = Timestamp(u64::from(secs) * 1_000_000 + u64::from(usecs));
+        let timestamp = iface.resolve_ts(raw_timestamp);
+        let start = *self.start.get_or_insert(timestamp);
+        let mut pkt = Packet::new(body.slice(..caplen));
+        pkt.timestamp = Some(timestamp);
+        pkt.raw_timestamp = Some(raw_timestamp);
+        pkt.interface = Some(InterfaceId(idx as u32, 0));
+        pkt.orig_len = orig_len;
+        pkt.section_start = Some(start);
+        Ok((pkt, iface.clone()))
+    }
+}
+
+fn new_interface(link_type: LinkType) -> Arc<InterfaceInfo> {
+    Arc::new(InterfaceInfo {
+        descr: InterfaceDescription::new(link_type),
+        stats: None,
+        clock: None,
+        correction: None,
+    })
+}
+
+impl<R: Read> PacketSource for RpcapReader<R> {
+    type Error = RpcapError;
+    fn next_packet(&mut self) -> Option<Result<(Packet, Option<Arc<InterfaceInfo>>), RpcapError>> {
+        let x = self.try_next().transpose()?;
+        Some(x.map(|(pkt, iface)| (pkt, Some(iface))))
+    }
+}
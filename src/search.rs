@@ -0,0 +1,151 @@
+/*! Searching packet data
+
+This module is only available with the `search` feature.  [`find()`] scans
+the data of every packet in a capture for a byte string or a regex, and
+yields the packets which match, along with where the matches are.  It's
+"grep for pcaps": no attempt is made to understand the protocols involved,
+so a match which is split across two packets won't be found.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::{search::find, Capture};
+use regex::bytes::Regex;
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 0, b"GET /index.html", &[])
+#     .enhanced_packet(0, 1, b"HTTP/1.1 200 OK", &[])
+#     .enhanced_packet(0, 2, b"GET /favicon.ico GET", &[]);
+# let file = pcap.build();
+let matches: Vec<_> = find(Capture::new(file.clone()), &b"GET"[..])
+    .map(|x| x.unwrap().offsets)
+    .collect();
+assert_eq!(matches, [vec![0..3], vec![0..3, 17..20]]);
+
+let re = Regex::new(r"HTTP/\d\.\d (\d+)").unwrap();
+let m = find(Capture::new(file), re).next().unwrap().unwrap();
+assert_eq!(&m.packet.data[m.offsets[0].clone()], b"HTTP/1.1 200");
+```
+*/
+
+use crate::{Capture, Packet, Result};
+use memchr::memmem::Finder;
+use regex::bytes::Regex;
+use std::io::Read;
+use std::ops::Range;
+
+/// Something to search for
+///
+/// Byte strings (`&[u8]`, `Vec<u8>`, and `&str`) and regexes can be
+/// converted into patterns.
+#[derive(Debug, Clone)]
+pub struct Pattern(PatternKind);
+
+#[derive(Debug, Clone)]
+enum PatternKind {
+    /// An exact sequence of bytes
+    Bytes(Box<Finder<'static>>),
+    /// A regex, matched against the raw bytes of the packet
+    Regex(Regex),
+}
+
+impl Pattern {
+    /// The ranges of `data` which match the pattern.  The matches don't
+    /// overlap.
+    pub fn find_all(&self, data: &[u8]) -> Vec<Range<usize>> {
+        match &self.0 {
+            PatternKind::Bytes(finder) => {
+                let len = finder.needle().len();
+                finder.find_iter(data).map(|x| x..x + len).collect()
+            }
+            PatternKind::Regex(re) => re.find_iter(data).map(|x| x.range()).collect(),
+        }
+    }
+}
+
+impl From<&[u8]> for Pattern {
+    fn from(x: &[u8]) -> Pattern {
+        Pattern(PatternKind::Bytes(Box::new(Finder::new(x).into_owned())))
+    }
+}
+
+impl From<Vec<u8>> for Pattern {
+    fn from(x: Vec<u8>) -> Pattern {
+        Pattern::from(&x[..])
+    }
+}
+
+impl From<&str> for Pattern {
+    fn from(x: &str) -> Pattern {
+        Pattern::from(x.as_bytes())
+    }
+}
+
+impl From<Regex> for Pattern {
+    fn from(x: Regex) -> Pattern {
+        Pattern(PatternKind::Regex(x))
+    }
+}
+
+/// A packet which matched a pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub packet: Packet,
+    /// Where the matches are in the packet's data, in order
+    pub offsets: Vec<Range<usize>>,
+}
+
+/// An iterator of the packets which match a pattern
+///
+/// See [`find()`].
+pub struct Find<R> {
+    capture: Capture<R>,
+    pattern: Pattern,
+}
+
+impl<R: Read> Iterator for Find<R> {
+    type Item = Result<Match>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let packet = match self.capture.next()? {
+                Ok(x) => x,
+                Err(e) => return Some(Err(e)),
+            };
+            let offsets = self.pattern.find_all(&packet.data);
+            if !offsets.is_empty() {
+                return Some(Ok(Match { packet, offsets }));
+            }
+        }
+    }
+}
+
+impl<R> Find<R> {
+    /// The pattern being searched for
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    /// The underlying `Capture`
+    pub fn capture(&self) -> &Capture<R> {
+        &self.capture
+    }
+
+    /// Unwrap the iterator, returning the underlying `Capture`
+    pub fn into_inner(self) -> Capture<R> {
+        self.capture
+    }
+}
+
+/// Search the data of each packet for a pattern
+///
+/// The packets which match are returned, along with the positions of the
+/// matches.  Errors from the capture are passed through.  The packet data
+/// isn't copied.  See the [module-level documentation](self) for an
+/// example.
+pub fn find<R: Read>(capture: Capture<R>, pattern: impl Into<Pattern>) -> Find<R> {
+    Find {
+        capture,
+        pattern: pattern.into(),
+    }
+}
@@ -0,0 +1,200 @@
+/*! A quick structural overview of a pcapng
+
+A pcapng can contain several sections (eg. if it was made by concatenating
+several files), each with its own interfaces.  [`Capture::sections()`]
+skims through the file and describes each section, without decoding the
+packets.
+
+[`Capture::sections()`]: crate::Capture::sections
+*/
+
+use crate::block::read_vec;
+use crate::block::{
+    block_endianness, diagnose, Diagnostic, DiagnosticSink, Endianness, FrameError, FromBytes,
+    InterfaceDescription, Malformation, ParseContext, SectionHeader, Timestamp,
+};
+use crate::iface::InterfaceInfo;
+use crate::Result;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::time::SystemTime;
+
+const SHB: u32 = 0x0A0D_0D0A;
+const IDB: u32 = 0x0000_0001;
+const OPB: u32 = 0x0000_0002;
+const SPB: u32 = 0x0000_0003;
+const EPB: u32 = 0x0000_0006;
+
+/// Blocks shorter than this are skipped by reading, rather than seeking
+const SEEK_THRESHOLD: u64 = 8 * 1024;
+
+/// A summary of a section of a pcapng
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SectionSummary {
+    /// The offset of the section header block within the file
+    pub offset: u64,
+    /// The length of the section in bytes, including the section header
+    pub len: u64,
+    /// The section header, or `None` if it was mangled
+    pub header: Option<SectionHeader>,
+    /// The interfaces defined in this section.  A `None` entry indicates
+    /// that the interface description block was mangled.
+    pub interfaces: Vec<Option<InterfaceDescription>>,
+    /// The number of packet blocks in the section
+    pub packets: u64,
+    /// The earliest packet timestamp in the section
+    pub start: Option<SystemTime>,
+    /// The latest packet timestamp in the section
+    pub end: Option<SystemTime>,
+}
+
+impl SectionSummary {
+    fn new(offset: u64) -> SectionSummary {
+        SectionSummary {
+            offset,
+            len: 0,
+            header: None,
+            interfaces: vec![],
+            packets: 0,
+            start: None,
+            end: None,
+        }
+    }
+
+    fn saw_timestamp(&mut self, iface: u32, ts: Timestamp) {
+        let descr = match self.interfaces.get(iface as usize) {
+            Some(Some(x)) => x.clone(),
+            _ => return,
+        };
+        let ts = InterfaceInfo {
+            descr,
+            stats: None,
+            clock: None,
+            correction: None,
+        }
+        .resolve_ts(ts);
+        self.start = Some(self.start.map_or(ts, |x| x.min(ts)));
+        self.end = Some(self.end.map_or(ts, |x| x.max(ts)));
+    }
+}
+
+/// Read as many bytes as possible, up to the length of `buf`
+fn read_full(rdr: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match rdr.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(x) => n += x,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(n)
+}
+
+fn skip(rdr: &mut (impl Read + Seek), n: u64) -> io::Result<()> {
+    if n < SEEK_THRESHOLD {
+        io::copy(&mut rdr.take(n), &mut io::sink())?;
+    } else {
+        rdr.seek(SeekFrom::Current(n as i64))?;
+    }
+    Ok(())
+}
+
+/// Scan a whole pcapng, from the start
+///
+/// Only the headers of the blocks are checked, so this may not notice
+/// some kinds of corruption which would stop a `Capture`.
+pub(crate) fn scan(
+    mut rdr: impl Read + Seek,
+    sink: Option<&DiagnosticSink>,
+) -> Result<Vec<SectionSummary>> {
+    rdr.seek(SeekFrom::Start(0))?;
+    let mut sections: Vec<SectionSummary> = vec![];
+    let mut endianness = Endianness::Little; // arbitrary
+    let mut pos = 0;
+    loop {
+        let mut hdr = [0; 20];
+        if read_full(&mut rdr, &mut hdr[..12])? < 12 {
+            break;
+        }
+        let read_u32 = |buf: &[u8], i: usize, endianness: Endianness| {
+            let x = buf[i..i + 4].try_into().unwrap();
+            match endianness {
+                Endianness::Big => u32::from_be_bytes(x),
+                Endianness::Little => u32::from_le_bytes(x),
+            }
+        };
+        let mut block_type = read_u32(&hdr, 0, endianness);
+        if block_type == SHB {
+            endianness = match &hdr[8..12] {
+                &[0x1A, 0x2B, 0x3C, 0x4D] => Endianness::Big,
+                &[0x4D, 0x3C, 0x2B, 0x1A] => Endianness::Little,
+                x => {
+                    return Err(FrameError::DidntUnderstandMagicBytes(x.try_into().unwrap()).into())
+                }
+            };
+            sections.push(SectionSummary::new(pos));
+        } else if block_type == 0xa1b2c3d4 || block_type == 0xd4c3b2a1 {
+            return Err(FrameError::LegacyPcap.into());
+        } else if block_endianness(&hdr[..12], endianness) != endianness {
+            endianness = block_endianness(&hdr[..12], endianness);
+            block_type = read_u32(&hdr, 0, endianness);
+            let x = Malformation::SwappedByteOrder;
+            diagnose(sink, Diagnostic::Malformation(block_type.into(), x));
+        }
+        let block_len = read_u32(&hdr, 4, endianness) as usize;
+        if block_len < 12 {
+            return Err(FrameError::BlockLengthTooSmall(block_len).into());
+        }
+        let mut remaining = block_len as u64 - 12;
+        let section = match sections.last_mut() {
+            Some(x) => x,
+            None => {
+                diagnose(sink, Diagnostic::NoSectionHeader);
+                sections.push(SectionSummary::new(pos));
+                sections.last_mut().unwrap()
+            }
+        };
+
+        match block_type {
+            SHB | IDB => {
+                let mut block = hdr[..12].to_vec();
+                match read_vec(&mut rdr, remaining) {
+                    Ok(x) => block.extend_from_slice(&x),
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                    Err(e) => return Err(e.into()),
+                }
+                remaining = 0;
+                let body = &block[8..block_len - 4];
+                let ctx = ParseContext::new(endianness);
+                if block_type == SHB {
+                    section.header = SectionHeader::parse(body, &ctx).ok();
+                } else {
+                    let descr = InterfaceDescription::parse(body, &ctx).ok();
+                    section.interfaces.push(descr);
+                }
+            }
+            EPB | OPB if remaining >= 8 => {
+                if read_full(&mut rdr, &mut hdr[12..20])? < 8 {
+                    break;
+                }
+                remaining -= 8;
+                let iface = match (block_type, endianness) {
+                    (EPB, _) => read_u32(&hdr, 8, endianness),
+                    (_, Endianness::Big) => u32::from(u16::from_be_bytes([hdr[8], hdr[9]])),
+                    (_, Endianness::Little) => u32::from(u16::from_le_bytes([hdr[8], hdr[9]])),
+                };
+                let hi = read_u32(&hdr, 12, endianness);
+                let lo = read_u32(&hdr, 16, endianness);
+                section.saw_timestamp(iface, Timestamp((u64::from(hi) << 32) + u64::from(lo)));
+                section.packets += 1;
+            }
+            EPB | OPB | SPB => section.packets += 1,
+            _ => (),
+        }
+        skip(&mut rdr, remaining)?;
+        pos += block_len as u64;
+        section.len += block_len as u64;
+    }
+    Ok(sections)
+}
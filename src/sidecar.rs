@@ -0,0 +1,193 @@
+/*! Exporting packet metadata to Apache Arrow and Parquet
+
+This module is only available with the `sidecar` feature.  It reads a
+[`Capture`] in a single pass, and produces a table with one row per packet:
+
+| column         | type                    | description                                  |
+|----------------|-------------------------|----------------------------------------------|
+| `timestamp`    | timestamp (ns, UTC)     | `null` if the packet has no timestamp        |
+| `section`      | uint32                  | the section number (see [`InterfaceId`])     |
+| `interface`    | uint32                  | the interface number within the section      |
+| `caplen`       | uint32                  | the number of bytes captured                 |
+| `origlen`      | uint32                  | the length of the packet on the wire         |
+| `flags`        | uint32                  | `epb_flags` or `pack_flags`; zero if absent  |
+| `block_offset` | uint64                  | the offset of the packet's block in the file |
+| `data_offset`  | uint64                  | the offset of the packet's data in the file  |
+
+The packet data isn't copied: use the offsets to fetch it from the original
+file.  This lets you query a big capture with a SQL engine, and then go
+back to the pcapng for the packets you're interested in.
+
+Packets from corrupt blocks are skipped, with a warning.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use pcarp::{sidecar, Capture};
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 1_500_000_000_000_000, b"hello", &[])
+#     .enhanced_packet(0, 1_500_000_000_000_001, b"world", &[]);
+let file = pcap.as_bytes();
+let mut capture = Capture::new(file);
+let mut out = vec![];
+assert_eq!(sidecar::write_parquet(&mut capture, &mut out).unwrap(), 2);
+
+let rdr = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(out))
+    .unwrap()
+    .build()
+    .unwrap();
+let batch = rdr.into_iter().next().unwrap().unwrap();
+assert_eq!(batch.num_rows(), 2);
+let offsets = batch
+    .column_by_name("data_offset")
+    .unwrap()
+    .as_any()
+    .downcast_ref::<arrow_array::UInt64Array>()
+    .unwrap();
+let offset = offsets.value(1) as usize;
+assert_eq!(&file[offset..offset + 5], b"world");
+```
+
+[`InterfaceId`]: crate::iface::InterfaceId
+*/
+
+use crate::{Capture, Error};
+use arrow_array::builder::{TimestampNanosecondBuilder, UInt32Builder, UInt64Builder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use tracing::*;
+
+/// The number of rows in each record batch written by [`write_parquet()`]
+const BATCH_SIZE: usize = 8192;
+
+/// An error which occurred while exporting metadata
+#[derive(Debug, thiserror::Error)]
+pub enum SidecarError {
+    /// The capture couldn't be read
+    #[error("Couldn't read the capture")]
+    Capture(#[source] Error),
+    #[error("Arrow error")]
+    Arrow(#[from] ArrowError),
+    #[error("Parquet error")]
+    Parquet(#[from] ParquetError),
+}
+
+/// The schema of the metadata table
+pub fn schema() -> SchemaRef {
+    let ts = DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into()));
+    Arc::new(Schema::new(vec![
+        Field::new("timestamp", ts, true),
+        Field::new("section", DataType::UInt32, true),
+        Field::new("interface", DataType::UInt32, true),
+        Field::new("caplen", DataType::UInt32, false),
+        Field::new("origlen", DataType::UInt32, false),
+        Field::new("flags", DataType::UInt32, false),
+        Field::new("block_offset", DataType::UInt64, false),
+        Field::new("data_offset", DataType::UInt64, false),
+    ]))
+}
+
+/// Read the metadata of the packets in a capture as Arrow record batches
+///
+/// Each batch contains up to `batch_size` rows.
+pub fn batches<R: Read>(capture: &mut Capture<R>, batch_size: usize) -> Batches<'_, R> {
+    Batches {
+        capture,
+        batch_size: batch_size.max(1),
+        schema: schema(),
+    }
+}
+
+/// Write the metadata of the packets in a capture to a Parquet file
+///
+/// Returns the number of rows written.
+pub fn write_parquet<R: Read, W: Write + Send>(
+    capture: &mut Capture<R>,
+    wtr: W,
+) -> Result<u64, SidecarError> {
+    let mut batches = batches(capture, BATCH_SIZE);
+    let mut wtr = ArrowWriter::try_new(wtr, batches.schema.clone(), None)?;
+    let mut n = 0;
+    for batch in &mut batches {
+        let batch = batch?;
+        n += batch.num_rows() as u64;
+        wtr.write(&batch)?;
+    }
+    wtr.close()?;
+    Ok(n)
+}
+
+/// An iterator of record batches; see [`batches()`]
+pub struct Batches<'a, R> {
+    capture: &'a mut Capture<R>,
+    batch_size: usize,
+    schema: SchemaRef,
+}
+
+impl<'a, R: Read> Batches<'a, R> {
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>, SidecarError> {
+        let n = self.batch_size;
+        let mut timestamp = TimestampNanosecondBuilder::with_capacity(n).with_timezone("UTC");
+        let mut section = UInt32Builder::with_capacity(n);
+        let mut interface = UInt32Builder::with_capacity(n);
+        let mut caplen = UInt32Builder::with_capacity(n);
+        let mut origlen = UInt32Builder::with_capacity(n);
+        let mut flags = UInt32Builder::with_capacity(n);
+        let mut block_offset = UInt64Builder::with_capacity(n);
+        let mut data_offset = UInt64Builder::with_capacity(n);
+        let mut rows = 0;
+        while rows < n {
+            let (pkt, meta) = match self.capture.try_next_with_meta() {
+                Ok(Some(x)) => x,
+                Ok(None) => break,
+                Err(Error::Block(block_type, e)) => {
+                    warn!("Skipping a corrupt {block_type:?} block: {e}");
+                    continue;
+                }
+                Err(e) => return Err(SidecarError::Capture(e)),
+            };
+            let ts = pkt
+                .timestamp
+                .and_then(|ts| ts.duration_since(UNIX_EPOCH).ok())
+                .and_then(|x| i64::try_from(x.as_nanos()).ok());
+            timestamp.append_option(ts);
+            section.append_option(pkt.interface.map(|x| x.0));
+            interface.append_option(pkt.interface.map(|x| x.1));
+            caplen.append_value(pkt.data.len() as u32);
+            origlen.append_value(pkt.orig_len);
+            flags.append_value(meta.flags);
+            block_offset.append_value(meta.block_offset);
+            data_offset.append_value(meta.data_offset);
+            rows += 1;
+        }
+        if rows == 0 {
+            return Ok(None);
+        }
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(timestamp.finish()),
+            Arc::new(section.finish()),
+            Arc::new(interface.finish()),
+            Arc::new(caplen.finish()),
+            Arc::new(origlen.finish()),
+            Arc::new(flags.finish()),
+            Arc::new(block_offset.finish()),
+            Arc::new(data_offset.finish()),
+        ];
+        Ok(Some(RecordBatch::try_new(self.schema.clone(), columns)?))
+    }
+}
+
+impl<'a, R: Read> Iterator for Batches<'a, R> {
+    type Item = Result<RecordBatch, SidecarError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_batch().transpose()
+    }
+}
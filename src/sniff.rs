@@ -0,0 +1,126 @@
+/*! Guessing the format of a capture file
+
+pcarp only reads pcapng, but it's easy to hand it something else by
+mistake: a classic pcap, a compressed pcapng, or a capture from another
+tool.  Rather than reporting a confusing framing error (or worse, reading
+no packets at all), a [`Capture`](crate::Capture) checks the start of the
+file, and if it's recognisably something else, fails with
+[`FrameError::UnsupportedFormat`](crate::block::FrameError::UnsupportedFormat).
+Compressed pcapngs can be read with the help of the
+[`decompress`](crate::decompress) module.
+
+```
+use pcarp::sniff::Format;
+
+assert_eq!(Format::detect(b"\x0a\x0d\x0d\x0a\x1c\x00\x00\x00\x4d\x3c\x2b\x1a"), Format::Pcapng);
+assert_eq!(Format::detect(b"\x1f\x8b\x08\x00\x00\x00\x00\x00"), Format::Gzip);
+assert_eq!(Format::detect(b"hello"), Format::Unknown);
+```
+
+```
+use pcarp::{block::FrameError, sniff::Format, Capture, Error};
+
+let gzipped = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\x03\x03\x00\x00\x00\x00\x00";
+let mut capture = Capture::new(&gzipped[..]);
+let err = capture.next().unwrap().unwrap_err();
+assert!(matches!(err, Error::Frame(FrameError::UnsupportedFormat(Format::Gzip))));
+assert!(capture.next().is_none());
+```
+*/
+
+use std::fmt;
+
+/// The number of bytes which [`Format::detect()`] needs to see to make its
+/// best guess
+pub const SNIFF_LEN: usize = 16;
+
+/// A capture file format (or compression format)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Format {
+    /// pcapng, starting with a section header
+    Pcapng,
+    /// Classic (libpcap-format) pcap, with micro- or nanosecond timestamps
+    Pcap,
+    /// Compressed with gzip
+    Gzip,
+    /// Compressed with xz
+    Xz,
+    /// Compressed with zstd
+    Zstd,
+    /// Endace's Extensible Record Format.  ERF files have no magic number,
+    /// so this is only a guess, based on the first record header.  These
+    /// can be read with an [`ErfReader`](crate::erf::ErfReader).
+    Erf,
+    /// Sun's snoop format (RFC 1761)
+    Snoop,
+    /// Microsoft Network Monitor 2.x.  With the `netmon` feature, these can
+    /// be read with a `NetmonReader`.
+    Netmon,
+    /// None of the above.  This includes pcapng streams which don't start
+    /// with a section header.
+    Unknown,
+}
+
+impl Format {
+    /// Guess the format of a file from its first few bytes
+    ///
+    /// At least [`SNIFF_LEN`] bytes should be given, if the file is that
+    /// long.
+    pub fn detect(buf: &[u8]) -> Format {
+        let magic_u32 =
+            |x: u32| buf.len() >= 4 && (buf[..4] == x.to_le_bytes() || buf[..4] == x.to_be_bytes());
+        if buf.starts_with(&[0x0A, 0x0D, 0x0D, 0x0A]) {
+            Format::Pcapng
+        } else if magic_u32(0xA1B2_C3D4) || magic_u32(0xA1B2_3C4D) {
+            Format::Pcap
+        } else if buf.starts_with(&[0x1F, 0x8B]) {
+            Format::Gzip
+        } else if buf.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+            Format::Xz
+        } else if buf.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            Format::Zstd
+        } else if buf.starts_with(b"snoop\0\0\0") {
+            Format::Snoop
+        } else if buf.starts_with(b"GMBU") {
+            Format::Netmon
+        } else if looks_like_erf(buf) {
+            Format::Erf
+        } else {
+            Format::Unknown
+        }
+    }
+}
+
+/// Whether the buffer plausibly starts with an ERF record header
+fn looks_like_erf(buf: &[u8]) -> bool {
+    if buf.len() < SNIFF_LEN {
+        return false;
+    }
+    // A pcapng stream without a section header could start with any block,
+    // so don't mistake one of those for ERF
+    let block_type = u32::from_le_bytes(buf[..4].try_into().unwrap());
+    if block_type.swap_bytes() <= 0x0A || block_type <= 0x0A {
+        return false;
+    }
+    // The top bit of the type indicates an extension header
+    let record_type = buf[8] & 0x7F;
+    let flags = buf[9];
+    let rlen = u16::from_be_bytes([buf[10], buf[11]]);
+    (1..=0x30).contains(&record_type) && flags & 0xC0 == 0 && rlen >= 16
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Pcapng => "pcapng",
+            Format::Pcap => "classic pcap",
+            Format::Gzip => "gzip-compressed",
+            Format::Xz => "xz-compressed",
+            Format::Zstd => "zstd-compressed",
+            Format::Erf => "ERF",
+            Format::Snoop => "snoop",
+            Format::Netmon => "Network Monitor",
+            Format::Unknown => "unknown",
+        })
+    }
+}
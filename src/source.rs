@@ -0,0 +1,33 @@
+/*! A common interface for things which produce packets
+
+[`Capture`] reads packets from pcapng files, but packets can come from
+elsewhere too (eg. an [RPCAP](crate::rpcap) stream).  Code which only
+cares about the packets can be written against the [`PacketSource`] trait,
+so it works with any of them.
+*/
+
+use crate::iface::InterfaceInfo;
+use crate::{Capture, Error, Packet};
+use std::io::Read;
+use std::sync::Arc;
+
+/// Something which packets can be read from
+///
+/// This is the counterpart of [`PacketSink`](crate::demux::PacketSink).
+pub trait PacketSource {
+    type Error;
+
+    /// Get the next packet, along with info about the interface it was
+    /// captured on
+    ///
+    /// Returns `None` when there are no more packets.
+    #[allow(clippy::type_complexity)]
+    fn next_packet(&mut self) -> Option<Result<(Packet, Option<Arc<InterfaceInfo>>), Self::Error>>;
+}
+
+impl<R: Read> PacketSource for Capture<R> {
+    type Error = Error;
+    fn next_packet(&mut self) -> Option<Result<(Packet, Option<Arc<InterfaceInfo>>), Error>> {
+        self.next_with_iface()
+    }
+}
@@ -0,0 +1,1100 @@
+/*! Timing and load statistics, for checking the quality of a capture
+
+A [`TimeSeries`] is fed packets one at a time, and keeps some statistics
+about each interface: how the gaps between packets are distributed, how
+much they jitter, how many packets share a timestamp with their
+predecessor, and how the packet rate varies over time.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::{stats::TimeSeries, Capture};
+use std::time::Duration;
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 1_000_000, b"a", &[])
+#     .enhanced_packet(0, 1_000_000, b"b", &[])
+#     .enhanced_packet(0, 3_000_000, b"c", &[]);
+# let file = pcap.build();
+let mut series = TimeSeries::new(Duration::from_secs(1));
+for pkt in Capture::new(file) {
+    series.push(&pkt.unwrap());
+}
+let (_, iface) = series.interfaces().next().unwrap();
+assert_eq!(iface.packets(), 3);
+assert_eq!(iface.duplicate_timestamps(), 1);
+assert_eq!(iface.rate().count(), 3); // the middle bucket is empty
+```
+
+A [`Utilisation`] measures how busy each link was, using the interface
+speeds recorded in the pcap.
+
+A [`Timeline`] just counts the traffic in each bucket of time, across all
+interfaces.  It's cheap, and good for drawing an overview of a capture.
+
+[`InterfaceStats`] collects the counters from the interface statistics
+blocks, so that two captures of the same interfaces can be compared.
+
+A [`CapabilityReport`] says which of the useful options each interface's
+description has, and whether its timestamps looked sane.  It's meant as a
+quality gate for captures, eg. in CI.
+*/
+
+use crate::iface::{InterfaceId, InterfaceInfo, LinkType, LogicalInterface};
+use crate::{Capture, Packet};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Per-interface timing statistics, computed in a single pass
+#[derive(Debug, Clone)]
+pub struct TimeSeries {
+    bucket_width: Duration,
+    interfaces: BTreeMap<Option<InterfaceId>, InterfaceSeries>,
+}
+
+impl TimeSeries {
+    /// Create an empty `TimeSeries`
+    ///
+    /// The packet rate is measured over buckets of the given width.  The
+    /// buckets are aligned to the unix epoch, so that the buckets of
+    /// different interfaces (and different captures) line up.
+    ///
+    /// Panics if the bucket width is zero.
+    pub fn new(bucket_width: Duration) -> TimeSeries {
+        assert!(!bucket_width.is_zero(), "The bucket width must be non-zero");
+        TimeSeries {
+            bucket_width,
+            interfaces: BTreeMap::new(),
+        }
+    }
+
+    /// Update the statistics with the given packet
+    ///
+    /// Packets without a timestamp are counted, but otherwise ignored.
+    pub fn push(&mut self, pkt: &Packet) {
+        let bucket_width = self.bucket_width;
+        let series = self
+            .interfaces
+            .entry(pkt.interface)
+            .or_insert_with(|| InterfaceSeries::new(bucket_width));
+        series.push(pkt);
+    }
+
+    /// The statistics for each interface seen so far, in order of ID
+    ///
+    /// Packets which don't belong to an interface are grouped under `None`.
+    pub fn interfaces(&self) -> impl Iterator<Item = (Option<InterfaceId>, &InterfaceSeries)> {
+        self.interfaces.iter().map(|(id, x)| (*id, x))
+    }
+
+    /// The statistics for a particular interface
+    pub fn interface(&self, id: Option<InterfaceId>) -> Option<&InterfaceSeries> {
+        self.interfaces.get(&id)
+    }
+}
+
+/// Timing statistics for a single interface
+#[derive(Debug, Clone)]
+pub struct InterfaceSeries {
+    bucket_width: Duration,
+    packets: u64,
+    untimestamped: u64,
+    out_of_range: u64,
+    duplicate_timestamps: u64,
+    out_of_order: u64,
+    first: Option<SystemTime>,
+    last: Option<SystemTime>,
+    /// In nanoseconds
+    last_gap: Option<u64>,
+    /// `gaps[0]` counts zero gaps; `gaps[i]` counts gaps in the range
+    /// [2^(i-1), 2^i) nanoseconds
+    gaps: Vec<u64>,
+    /// In nanoseconds
+    jitter: f64,
+    /// Indexed by the number of bucket widths since the epoch
+    buckets: BTreeMap<u64, Bucket>,
+}
+
+/// The traffic seen in a single bucket of time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Bucket {
+    /// The number of packets whose timestamps fall in this bucket
+    pub packets: u64,
+    /// The total captured length of those packets
+    pub bytes: u64,
+}
+
+impl InterfaceSeries {
+    fn new(bucket_width: Duration) -> InterfaceSeries {
+        InterfaceSeries {
+            bucket_width,
+            packets: 0,
+            untimestamped: 0,
+            out_of_range: 0,
+            duplicate_timestamps: 0,
+            out_of_order: 0,
+            first: None,
+            last: None,
+            last_gap: None,
+            gaps: vec![],
+            jitter: 0.0,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    fn push(&mut self, pkt: &Packet) {
+        self.packets += 1;
+        let ts = match pkt.timestamp {
+            Some(ts) => ts,
+            None => {
+                self.untimestamped += 1;
+                return;
+            }
+        };
+
+        match bucket_idx(self.bucket_width, ts) {
+            Some(idx) => {
+                let bucket = self.buckets.entry(idx).or_default();
+                bucket.packets += 1;
+                bucket.bytes += pkt.data.len() as u64;
+            }
+            None => self.out_of_range += 1,
+        }
+
+        if self.first.map_or(true, |first| ts < first) {
+            self.first = Some(ts);
+        }
+        let last = match self.last {
+            Some(last) => last,
+            None => {
+                self.last = Some(ts);
+                return;
+            }
+        };
+        let gap = match ts.duration_since(last) {
+            Ok(gap) => gap,
+            Err(_) => {
+                // Leave `last` alone, so that one early packet doesn't
+                // distort the following gap
+                self.out_of_order += 1;
+                return;
+            }
+        };
+        self.last = Some(ts);
+        if gap.is_zero() {
+            self.duplicate_timestamps += 1;
+        }
+
+        let gap_nanos = gap.as_nanos().min(u128::from(u64::MAX)) as u64;
+        let idx = (64 - gap_nanos.leading_zeros()) as usize;
+        if self.gaps.len() <= idx {
+            self.gaps.resize(idx + 1, 0);
+        }
+        self.gaps[idx] += 1;
+
+        // This is the interarrival jitter estimator from RFC 3550
+        if let Some(last_gap) = self.last_gap {
+            let d = gap_nanos as f64 - last_gap as f64;
+            self.jitter += (d.abs() - self.jitter) / 16.0;
+        }
+        self.last_gap = Some(gap_nanos);
+    }
+
+    /// The total number of packets seen
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// The number of packets which had no timestamp
+    pub fn untimestamped(&self) -> u64 {
+        self.untimestamped
+    }
+
+    /// The number of packets which were left out of the buckets, because
+    /// their timestamps were before the unix epoch (or too far after it)
+    ///
+    /// They're still included in the gap statistics.
+    pub fn out_of_range(&self) -> u64 {
+        self.out_of_range
+    }
+
+    /// The number of packets with the same timestamp as the previous packet
+    ///
+    /// A high proportion of these suggests that the interface's clock (or
+    /// its timestamp resolution) is too coarse for the traffic.
+    pub fn duplicate_timestamps(&self) -> u64 {
+        self.duplicate_timestamps
+    }
+
+    /// The number of packets timestamped earlier than the previous packet
+    ///
+    /// These aren't included in the gap statistics.
+    pub fn out_of_order(&self) -> u64 {
+        self.out_of_order
+    }
+
+    /// The earliest timestamp seen
+    pub fn first(&self) -> Option<SystemTime> {
+        self.first
+    }
+
+    /// The latest timestamp seen
+    pub fn last(&self) -> Option<SystemTime> {
+        self.last
+    }
+
+    /// The distribution of gaps between consecutive packets
+    ///
+    /// The gaps are grouped into power-of-two bins.  Each item gives the
+    /// (inclusive) lower bound of a bin and the number of gaps in it.  The
+    /// first bin contains only gaps of zero.
+    pub fn gaps(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.gaps.iter().enumerate().map(|(i, n)| {
+            let lower = match i {
+                0 => 0,
+                _ => 1 << (i - 1),
+            };
+            (Duration::from_nanos(lower), *n)
+        })
+    }
+
+    /// The interarrival jitter, as defined in RFC 3550
+    ///
+    /// This is a smoothed estimate of how much consecutive gaps differ,
+    /// weighted towards recent packets.
+    pub fn jitter(&self) -> Duration {
+        Duration::from_nanos(self.jitter as u64)
+    }
+
+    /// The amount of traffic seen in each bucket of time
+    ///
+    /// The buckets between the first and last timestamped packets are
+    /// included even if they're empty, so that the series is continuous,
+    /// except that gaps of more than 1024 empty buckets are skipped.  (A
+    /// single packet with a bogus timestamp would otherwise produce
+    /// billions of them.)
+    pub fn rate(&self) -> impl Iterator<Item = (SystemTime, Bucket)> + '_ {
+        with_gaps(&self.buckets)
+            .map(move |(idx, bucket)| (bucket_start(self.bucket_width, idx), bucket))
+    }
+}
+
+/// Link utilisation per interface, computed in a single pass
+///
+/// The utilisation of a link is the number of bits which crossed it,
+/// divided by the number it could have carried.  The bits are counted using
+/// each packet's original length, so truncated captures give accurate
+/// figures.  Framing overhead which isn't captured (eg. the Ethernet
+/// preamble and inter-frame gap) isn't counted.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::{stats::Utilisation, Capture};
+/// use std::time::Duration;
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # let speed = 8_000_u64.to_le_bytes();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[(8, &speed)])
+/// #     .enhanced_packet(0, 0, &[0; 250], &[])
+/// #     .enhanced_packet(0, 500_000, &[0; 250], &[]);
+/// # let file = pcap.build();
+/// let mut util = Utilisation::new(Duration::from_secs(1));
+/// let mut capture = Capture::new(file);
+/// while let Some(x) = capture.next_with_iface() {
+///     let (pkt, iface) = x.unwrap();
+///     util.push(&pkt, iface.as_deref());
+/// }
+/// let (_, iface) = util.interfaces().next().unwrap();
+/// let (_, load) = iface.buckets().next().unwrap();
+/// assert_eq!(load.bits, 4_000);
+/// assert_eq!(load.utilisation, Some(0.5));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Utilisation {
+    bucket_width: Duration,
+    interfaces: BTreeMap<Option<InterfaceId>, InterfaceUtilisation>,
+}
+
+impl Utilisation {
+    /// Create an empty `Utilisation`
+    ///
+    /// The utilisation is measured over buckets of the given width, aligned
+    /// to the unix epoch.
+    ///
+    /// Panics if the bucket width is zero.
+    pub fn new(bucket_width: Duration) -> Utilisation {
+        assert!(!bucket_width.is_zero(), "The bucket width must be non-zero");
+        Utilisation {
+            bucket_width,
+            interfaces: BTreeMap::new(),
+        }
+    }
+
+    /// Count the given packet, which was captured on the given interface
+    ///
+    /// The interface info provides the link speed; see
+    /// [`Capture::next_with_iface()`](crate::Capture::next_with_iface).
+    /// Packets without a timestamp are ignored.
+    pub fn push(&mut self, pkt: &Packet, iface: Option<&InterfaceInfo>) {
+        let ts = match pkt.timestamp {
+            Some(ts) => ts,
+            None => return,
+        };
+        let bucket_width = self.bucket_width;
+        let series = self
+            .interfaces
+            .entry(pkt.interface)
+            .or_insert_with(|| InterfaceUtilisation {
+                bucket_width,
+                speed: None,
+                out_of_range: 0,
+                bits: BTreeMap::new(),
+            });
+        if let Some(speed) = iface.and_then(link_speed) {
+            series.speed = Some(speed);
+        }
+        match bucket_idx(bucket_width, ts) {
+            Some(idx) => *series.bits.entry(idx).or_default() += u64::from(pkt.orig_len) * 8,
+            None => series.out_of_range += 1,
+        }
+    }
+
+    /// The utilisation of each interface seen so far, in order of ID
+    pub fn interfaces(&self) -> impl Iterator<Item = (Option<InterfaceId>, &InterfaceUtilisation)> {
+        self.interfaces.iter().map(|(id, x)| (*id, x))
+    }
+
+    /// The utilisation of a particular interface
+    pub fn interface(&self, id: Option<InterfaceId>) -> Option<&InterfaceUtilisation> {
+        self.interfaces.get(&id)
+    }
+}
+
+/// The speed of the link, in bits per second
+///
+/// If the interface only specifies separate transmit and receive speeds, we
+/// use the faster of the two, since packets don't say which way they were
+/// going.
+fn link_speed(iface: &InterfaceInfo) -> Option<u64> {
+    let speed = iface
+        .speed()
+        .or_else(|| iface.txspeed().max(iface.rxspeed()))?;
+    if speed == 0 {
+        None
+    } else {
+        Some(speed)
+    }
+}
+
+/// The utilisation of a single interface
+#[derive(Debug, Clone)]
+pub struct InterfaceUtilisation {
+    bucket_width: Duration,
+    speed: Option<u64>,
+    out_of_range: u64,
+    /// Indexed by the number of bucket widths since the epoch
+    bits: BTreeMap<u64, u64>,
+}
+
+/// The load on a link during a single bucket of time
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Load {
+    /// The number of bits which crossed the link
+    pub bits: u64,
+    /// The fraction of the link's capacity which was used.  This is `None`
+    /// if the interface's speed is unknown.
+    pub utilisation: Option<f64>,
+}
+
+impl InterfaceUtilisation {
+    /// The speed of the link, in bits per second
+    ///
+    /// This comes from `if_speed`, or failing that, the faster of
+    /// `if_txspeed` and `if_rxspeed`.
+    pub fn speed(&self) -> Option<u64> {
+        self.speed
+    }
+
+    /// The number of packets which were left out, because their timestamps
+    /// were before the unix epoch (or too far after it)
+    pub fn out_of_range(&self) -> u64 {
+        self.out_of_range
+    }
+
+    /// The load on the link in each bucket of time
+    ///
+    /// The buckets between the first and last timestamped packets are
+    /// included even if they're empty, so that the series is continuous,
+    /// except that gaps of more than 1024 empty buckets are skipped.  (A
+    /// single packet with a bogus timestamp would otherwise produce
+    /// billions of them.)
+    pub fn buckets(&self) -> impl Iterator<Item = (SystemTime, Load)> + '_ {
+        let capacity = self
+            .speed
+            .map(|speed| speed as f64 * self.bucket_width.as_secs_f64());
+        with_gaps(&self.bits).map(move |(idx, bits)| {
+            let load = Load {
+                bits,
+                utilisation: capacity.map(|x| bits as f64 / x),
+            };
+            (bucket_start(self.bucket_width, idx), load)
+        })
+    }
+}
+
+/// The traffic in each bucket of time, across all interfaces
+///
+/// See [`timeline()`].
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    bucket_width: Duration,
+    untimestamped: u64,
+    out_of_range: u64,
+    errors: u64,
+    /// Indexed by the number of bucket widths since the epoch
+    buckets: BTreeMap<u64, Bucket>,
+}
+
+impl Timeline {
+    /// Create an empty `Timeline`
+    ///
+    /// The buckets have the given width, and are aligned to the unix epoch.
+    ///
+    /// Panics if the bucket width is zero.
+    pub fn new(bucket_width: Duration) -> Timeline {
+        assert!(!bucket_width.is_zero(), "The bucket width must be non-zero");
+        Timeline {
+            bucket_width,
+            untimestamped: 0,
+            out_of_range: 0,
+            errors: 0,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /// Count the given packet
+    ///
+    /// Packets without a timestamp are counted, but otherwise ignored.
+    pub fn push(&mut self, pkt: &Packet) {
+        let ts = match pkt.timestamp {
+            Some(ts) => ts,
+            None => {
+                self.untimestamped += 1;
+                return;
+            }
+        };
+        match bucket_idx(self.bucket_width, ts) {
+            Some(idx) => {
+                let bucket = self.buckets.entry(idx).or_default();
+                bucket.packets += 1;
+                bucket.bytes += pkt.data.len() as u64;
+            }
+            None => self.out_of_range += 1,
+        }
+    }
+
+    /// The width of the buckets
+    pub fn bucket_width(&self) -> Duration {
+        self.bucket_width
+    }
+
+    /// The number of packets which had no timestamp
+    pub fn untimestamped(&self) -> u64 {
+        self.untimestamped
+    }
+
+    /// The number of packets which were left out, because their timestamps
+    /// were before the unix epoch (or too far after it)
+    pub fn out_of_range(&self) -> u64 {
+        self.out_of_range
+    }
+
+    /// The number of errors which were skipped over by [`timeline()`]
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// The amount of traffic seen in each bucket of time
+    ///
+    /// The buckets between the first and last timestamped packets are
+    /// included even if they're empty, so that the series is continuous,
+    /// except that gaps of more than 1024 empty buckets are skipped.  (A
+    /// single packet with a bogus timestamp would otherwise produce
+    /// billions of them.)
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::{stats::timeline, Capture};
+    /// use std::time::Duration;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"a", &[]) // a bogus timestamp
+    /// #     .enhanced_packet(0, 3_000_000_000, b"bb", &[])
+    /// #     .enhanced_packet(0, 3_002_000_000, b"ccc", &[]);
+    /// let timeline = timeline(Capture::new(pcap.build()), Duration::from_secs(1));
+    /// let counts: Vec<_> = timeline.buckets().map(|(_, x)| x.packets).collect();
+    /// assert_eq!(counts, [1, 1, 0, 1]);
+    /// ```
+    pub fn buckets(&self) -> impl Iterator<Item = (SystemTime, Bucket)> + '_ {
+        with_gaps(&self.buckets)
+            .map(move |(idx, bucket)| (bucket_start(self.bucket_width, idx), bucket))
+    }
+}
+
+/// Count the packets and bytes in each bucket of time, in one pass over
+/// the capture
+///
+/// Errors are counted and skipped over, so a few corrupt blocks don't stop
+/// the whole capture from being drawn.  Panics if the bucket width is zero.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::{stats::timeline, Capture};
+/// use std::time::Duration;
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[])
+/// #     .enhanced_packet(0, 1_000_000, b"a", &[])
+/// #     .enhanced_packet(0, 1_500_000, b"bb", &[])
+/// #     .enhanced_packet(0, 3_000_000, b"ccc", &[]);
+/// let timeline = timeline(Capture::new(pcap.build()), Duration::from_secs(1));
+/// let counts: Vec<_> = timeline.buckets().map(|(_, x)| (x.packets, x.bytes)).collect();
+/// assert_eq!(counts, [(2, 3), (0, 0), (1, 3)]);
+/// ```
+pub fn timeline<R: Read>(capture: Capture<R>, bucket_width: Duration) -> Timeline {
+    let mut timeline = Timeline::new(bucket_width);
+    for pkt in capture {
+        match pkt {
+            Ok(pkt) => timeline.push(&pkt),
+            Err(_) => timeline.errors += 1,
+        }
+    }
+    timeline
+}
+
+/// The number of bucket widths between the epoch and the given time, or
+/// `None` if it's before the epoch (or the number doesn't fit in a `u64`)
+fn bucket_idx(width: Duration, ts: SystemTime) -> Option<u64> {
+    let since_epoch = ts.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_nanos() / width.as_nanos()).ok()
+}
+
+fn bucket_start(width: Duration, idx: u64) -> SystemTime {
+    let nanos = width.as_nanos() * u128::from(idx);
+    let since_epoch = Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    );
+    UNIX_EPOCH + since_epoch
+}
+
+/// The longest run of empty buckets which [`with_gaps()`] fills in
+const MAX_EMPTY_BUCKETS: u64 = 1024;
+
+/// The populated buckets, with the empty buckets between them filled in,
+/// unless there are more than `MAX_EMPTY_BUCKETS` of them in a row
+fn with_gaps<T: Copy + Default>(buckets: &BTreeMap<u64, T>) -> impl Iterator<Item = (u64, T)> + '_ {
+    let nexts = buckets
+        .keys()
+        .skip(1)
+        .map(Some)
+        .chain(std::iter::once(None));
+    buckets.iter().zip(nexts).flat_map(|((&idx, &x), next)| {
+        let empty = match next {
+            Some(&next) if next - idx - 1 <= MAX_EMPTY_BUCKETS => idx + 1..next,
+            _ => 0..0,
+        };
+        std::iter::once((idx, x)).chain(empty.map(|idx| (idx, T::default())))
+    })
+}
+
+/// The counters from an interface's statistics block
+///
+/// Each counts from the start of the capture.  They're `None` if the
+/// capture didn't record them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InterfaceCounters {
+    /// Packets received from the interface
+    pub ifrecv: Option<u64>,
+    /// Packets dropped by the interface
+    pub ifdrop: Option<u64>,
+    /// Packets accepted by the capture filter
+    pub filter_accept: Option<u64>,
+    /// Packets dropped by the OS
+    pub osdrop: Option<u64>,
+    /// Packets delivered to the capturing program
+    pub usrdeliv: Option<u64>,
+}
+
+impl InterfaceCounters {
+    fn new(iface: &InterfaceInfo) -> InterfaceCounters {
+        InterfaceCounters {
+            ifrecv: iface.ifrecv(),
+            ifdrop: iface.ifdrop(),
+            filter_accept: iface.filter_accept(),
+            osdrop: iface.osdrop(),
+            usrdeliv: iface.usrdeliv(),
+        }
+    }
+}
+
+/// The final statistics of each interface in a capture
+///
+/// This is for comparing two captures of the same interfaces, eg. to check
+/// that nothing was lost in between.  See [`interface_stats()`].
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceStats {
+    /// In the order they were first seen
+    interfaces: Vec<(LogicalInterface, InterfaceCounters)>,
+    errors: u64,
+}
+
+impl InterfaceStats {
+    /// The interfaces with statistics, and their counters
+    pub fn interfaces(&self) -> impl Iterator<Item = &(LogicalInterface, InterfaceCounters)> {
+        self.interfaces.iter()
+    }
+
+    /// The counters of the given interface
+    pub fn get(&self, iface: &LogicalInterface) -> Option<&InterfaceCounters> {
+        self.interfaces
+            .iter()
+            .find(|(x, _)| x == iface)
+            .map(|(_, x)| x)
+    }
+
+    /// The number of errors which were skipped over by [`interface_stats()`]
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    /// Compare these statistics with those of a later capture
+    ///
+    /// Interfaces are matched by their link type, name, and MAC address.
+    /// The result has an entry for every interface which appears in either
+    /// capture: first the ones in this capture, then the ones which only
+    /// appear in `after`.
+    pub fn diff(&self, after: &InterfaceStats) -> Vec<InterfaceStatsDiff> {
+        let mut diffs: Vec<_> = self
+            .interfaces
+            .iter()
+            .map(|(iface, before)| InterfaceStatsDiff {
+                interface: iface.clone(),
+                before: Some(*before),
+                after: after.get(iface).copied(),
+            })
+            .collect();
+        for (iface, x) in &after.interfaces {
+            if self.get(iface).is_none() {
+                diffs.push(InterfaceStatsDiff {
+                    interface: iface.clone(),
+                    before: None,
+                    after: Some(*x),
+                });
+            }
+        }
+        diffs
+    }
+}
+
+/// How an interface's counters changed between two captures
+///
+/// See [`InterfaceStats::diff()`].  Each of the methods returns `None` if
+/// the counter is missing from either capture.  A negative change
+/// suggests that the counters were reset in between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceStatsDiff {
+    pub interface: LogicalInterface,
+    /// `None` if the interface has no statistics in the first capture
+    pub before: Option<InterfaceCounters>,
+    /// `None` if the interface has no statistics in the second capture
+    pub after: Option<InterfaceCounters>,
+}
+
+impl InterfaceStatsDiff {
+    fn delta(&self, f: impl Fn(&InterfaceCounters) -> Option<u64>) -> Option<i64> {
+        let before = f(self.before.as_ref()?)?;
+        let after = f(self.after.as_ref()?)?;
+        Some(after.wrapping_sub(before) as i64)
+    }
+
+    /// The number of packets received from the interface in between
+    pub fn received(&self) -> Option<i64> {
+        self.delta(|x| x.ifrecv)
+    }
+
+    /// The number of packets dropped by the interface in between
+    pub fn dropped(&self) -> Option<i64> {
+        self.delta(|x| x.ifdrop)
+    }
+
+    /// The number of packets accepted by the capture filter in between
+    pub fn accepted(&self) -> Option<i64> {
+        self.delta(|x| x.filter_accept)
+    }
+
+    /// The number of packets dropped by the OS in between
+    pub fn os_dropped(&self) -> Option<i64> {
+        self.delta(|x| x.osdrop)
+    }
+
+    /// The number of packets delivered to the capturing program in between
+    pub fn delivered(&self) -> Option<i64> {
+        self.delta(|x| x.usrdeliv)
+    }
+}
+
+/// Read a capture to the end, and collect the final statistics of each
+/// interface
+///
+/// An interface's counters are taken from the last statistics block which
+/// refers to it.  If the same interface appears in several sections, the
+/// last section in which it has statistics wins.  Interfaces with neither
+/// a name nor a MAC address can't be matched with those of another
+/// capture, so they're left out.
+///
+/// Errors are counted and skipped over.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::{stats::interface_stats, Capture};
+///
+/// # let capture = |recv: u64, drop: u64| {
+/// #     let mut pcap = PcapngBuilder::new();
+/// #     pcap.section(Endianness::Little)
+/// #         .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+/// #         .interface_statistics(0, 0, &[(4, &recv.to_le_bytes()), (5, &drop.to_le_bytes())]);
+/// #     pcap.build()
+/// # };
+/// let before = interface_stats(Capture::new(capture(1000, 3)));
+/// let after = interface_stats(Capture::new(capture(1500, 5)));
+/// let diff = &before.diff(&after)[0];
+/// assert_eq!(diff.received(), Some(500));
+/// assert_eq!(diff.dropped(), Some(2));
+/// assert_eq!(diff.os_dropped(), None);
+/// ```
+pub fn interface_stats<R: Read>(mut capture: Capture<R>) -> InterfaceStats {
+    let mut stats = InterfaceStats::default();
+    for x in capture.by_ref() {
+        if x.is_err() {
+            stats.errors += 1;
+        }
+    }
+    let sections = capture
+        .archived_interfaces
+        .iter()
+        .enumerate()
+        .map(|(section, ifaces)| (section as u32, ifaces))
+        .chain(std::iter::once((
+            capture.current_section,
+            &capture.interfaces,
+        )));
+    for (section, ifaces) in sections {
+        for (idx, iface) in ifaces.iter().enumerate() {
+            let iface = match iface {
+                Some(x) if x.stats.is_some() => x,
+                _ => continue,
+            };
+            let logical = iface.logical(InterfaceId(section, idx as u32));
+            if let LogicalInterface::Anonymous(_) = logical {
+                continue;
+            }
+            let counters = InterfaceCounters::new(iface);
+            match stats.interfaces.iter_mut().find(|(x, _)| *x == logical) {
+                Some((_, x)) => *x = counters,
+                None => stats.interfaces.push((logical, counters)),
+            }
+        }
+    }
+    stats
+}
+
+/// Which options an interface's description has, and whether its
+/// timestamps looked sane
+///
+/// See [`capability_report()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterfaceCapabilities {
+    pub interface: InterfaceId,
+    /// The interface's `if_name`, if it has one
+    pub name: Option<String>,
+    pub link_type: LinkType,
+    /// The timestamp resolution, in units per second, if it isn't the
+    /// default of microseconds.  An `if_tsresol` which says microseconds
+    /// can't be told apart from a missing one.
+    pub tsresol: Option<u64>,
+    /// Whether the description has an `if_MACaddr`
+    pub mac_addr: bool,
+    /// Whether the description has an `if_filter`
+    pub filter: bool,
+    /// Whether the description has an `if_os`
+    pub os: bool,
+    /// Whether the description has an `if_hardware`
+    pub hardware: bool,
+    /// Whether the interface has a statistics block
+    pub statistics: bool,
+    /// The number of packets captured on the interface
+    pub packets: u64,
+    /// The earliest timestamp of a packet on the interface
+    pub first_timestamp: Option<SystemTime>,
+    /// The latest timestamp of a packet on the interface
+    pub last_timestamp: Option<SystemTime>,
+    /// The number of packets timestamped earlier than the packet before
+    /// them on the same interface
+    pub out_of_order: u64,
+    /// The number of packets timestamped at exactly the unix epoch.  This
+    /// usually means that the capturing machine's clock wasn't set, or
+    /// that the timestamps are relative to something other than the epoch.
+    pub at_epoch: u64,
+}
+
+impl InterfaceCapabilities {
+    fn new(interface: InterfaceId, iface: &InterfaceInfo) -> InterfaceCapabilities {
+        InterfaceCapabilities {
+            interface,
+            name: (!iface.name().is_empty()).then(|| iface.name().to_owned()),
+            link_type: iface.link_type(),
+            tsresol: (iface.tsresol() != 1_000_000).then(|| iface.tsresol()),
+            mac_addr: iface.mac_addr().is_some(),
+            filter: !iface.filter().is_empty(),
+            os: !iface.os().is_empty(),
+            hardware: !iface.hardware().is_empty(),
+            statistics: iface.stats.is_some(),
+            packets: 0,
+            first_timestamp: None,
+            last_timestamp: None,
+            out_of_order: 0,
+            at_epoch: 0,
+        }
+    }
+
+    /// Whether the timestamps looked sane: that is, none were out of order
+    /// or at the epoch
+    pub fn timestamps_sane(&self) -> bool {
+        self.out_of_order == 0 && self.at_epoch == 0
+    }
+}
+
+/// A summary of each interface in a capture, for checking its quality
+///
+/// See [`capability_report()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CapabilityReport {
+    /// In the order they were defined
+    pub interfaces: Vec<InterfaceCapabilities>,
+    /// The number of errors which were skipped over
+    pub errors: u64,
+}
+
+impl CapabilityReport {
+    /// Whether every interface's timestamps looked sane
+    ///
+    /// Missing options aren't counted against the capture, since which
+    /// ones matter depends on what it's for.
+    pub fn is_ok(&self) -> bool {
+        self.interfaces.iter().all(|x| x.timestamps_sane())
+    }
+}
+
+#[cfg(feature = "json")]
+impl CapabilityReport {
+    /// A machine-readable version of the report
+    ///
+    /// This is only available with the `json` feature.  Timestamps are
+    /// rendered as RFC 3339 strings, and missing values are `null`.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::{stats::capability_report, Capture};
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+    /// #     .enhanced_packet(0, 1_000_000, b"a", &[]);
+    /// let json = capability_report(Capture::new(pcap.build())).to_json();
+    /// assert_eq!(json["ok"], true);
+    /// assert_eq!(json["interfaces"][0]["name"], "eth0");
+    /// assert_eq!(json["interfaces"][0]["first_timestamp"], "1970-01-01T00:00:01.000000000Z");
+    /// assert!(json["interfaces"][0]["tsresol"].is_null());
+    /// ```
+    pub fn to_json(&self) -> serde_json::Value {
+        use crate::human::Rfc3339;
+        let ts = |x: Option<SystemTime>| x.map(|x| Rfc3339(x).to_string());
+        let interfaces: Vec<_> = self
+            .interfaces
+            .iter()
+            .map(|x| {
+                serde_json::json!({
+                    "section": x.interface.0,
+                    "interface": x.interface.1,
+                    "name": x.name,
+                    "link_type": x.link_type.to_u16(),
+                    "link_type_name": format!("{:?}", x.link_type),
+                    "tsresol": x.tsresol,
+                    "mac_addr": x.mac_addr,
+                    "filter": x.filter,
+                    "os": x.os,
+                    "hardware": x.hardware,
+                    "statistics": x.statistics,
+                    "packets": x.packets,
+                    "first_timestamp": ts(x.first_timestamp),
+                    "last_timestamp": ts(x.last_timestamp),
+                    "out_of_order": x.out_of_order,
+                    "at_epoch": x.at_epoch,
+                    "timestamps_sane": x.timestamps_sane(),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "ok": self.is_ok(),
+            "errors": self.errors,
+            "interfaces": interfaces,
+        })
+    }
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} interfaces ({} errors)",
+            self.interfaces.len(),
+            self.errors
+        )?;
+        for x in &self.interfaces {
+            let InterfaceId(section, idx) = x.interface;
+            write!(f, "interface {section}/{idx}")?;
+            if let Some(name) = &x.name {
+                write!(f, " ({name})")?;
+            }
+            write!(f, ": {:?}, {} packets", x.link_type, x.packets)?;
+            let missing: Vec<_> = [
+                ("tsresol", x.tsresol.is_some()),
+                ("MAC", x.mac_addr),
+                ("filter", x.filter),
+                ("OS", x.os),
+                ("hardware", x.hardware),
+                ("statistics", x.statistics),
+            ]
+            .into_iter()
+            .filter(|(_, present)| !present)
+            .map(|(name, _)| name)
+            .collect();
+            if !missing.is_empty() {
+                write!(f, "; no {}", missing.join(", "))?;
+            }
+            if x.out_of_order > 0 {
+                write!(f, "; {} out of order", x.out_of_order)?;
+            }
+            if x.at_epoch > 0 {
+                write!(f, "; {} at the epoch", x.at_epoch)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read a capture to the end, and summarise each of its interfaces
+///
+/// Every interface which was defined is included, even if no packets were
+/// captured on it.  Errors are counted and skipped over.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::{stats::capability_report, Capture};
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0"), (9, &[9]), (12, b"Linux")])
+/// #     .enhanced_packet(0, 2_000_000_000, b"a", &[])
+/// #     .enhanced_packet(0, 1_000_000_000, b"b", &[]);
+/// let report = capability_report(Capture::new(pcap.build()));
+/// let eth0 = &report.interfaces[0];
+/// assert_eq!(eth0.name.as_deref(), Some("eth0"));
+/// assert_eq!(eth0.tsresol, Some(1_000_000_000));
+/// assert!(eth0.os && !eth0.hardware);
+/// assert_eq!(eth0.out_of_order, 1);
+/// assert!(!report.is_ok());
+/// assert_eq!(
+///     report.to_string(),
+///     "1 interfaces (0 errors)\n\
+///      interface 1/0 (eth0): ETHERNET, 2 packets; no MAC, filter, hardware, statistics; \
+///      1 out of order\n",
+/// );
+/// ```
+pub fn capability_report<R: Read>(mut capture: Capture<R>) -> CapabilityReport {
+    let mut report = CapabilityReport::default();
+    // The interfaces which have packets, and the timestamp of their latest
+    // packet
+    let mut seen = BTreeMap::<InterfaceId, (InterfaceCapabilities, Option<SystemTime>)>::new();
+    while let Some(x) = capture.next_with_iface() {
+        let (pkt, iface) = match x {
+            Ok((pkt, Some(iface))) => (pkt, iface),
+            Ok(_) => continue,
+            Err(_) => {
+                report.errors += 1;
+                continue;
+            }
+        };
+        let id = match pkt.interface {
+            Some(x) => x,
+            None => continue,
+        };
+        let (x, prev) = seen
+            .entry(id)
+            .or_insert_with(|| (InterfaceCapabilities::new(id, &iface), None));
+        x.packets += 1;
+        let ts = match pkt.timestamp {
+            Some(ts) if !pkt.synthetic_timestamp => ts,
+            _ => continue,
+        };
+        if ts == UNIX_EPOCH {
+            x.at_epoch += 1;
+        }
+        if matches!(*prev, Some(prev) if ts < prev) {
+            x.out_of_order += 1;
+        }
+        x.first_timestamp = Some(x.first_timestamp.map_or(ts, |x| x.min(ts)));
+        x.last_timestamp = Some(x.last_timestamp.map_or(ts, |x| x.max(ts)));
+        *prev = Some(ts);
+    }
+    let sections = capture
+        .archived_interfaces
+        .iter()
+        .enumerate()
+        .map(|(section, ifaces)| (section as u32, ifaces))
+        .chain(std::iter::once((
+            capture.current_section,
+            &capture.interfaces,
+        )));
+    for (section, ifaces) in sections {
+        for (idx, iface) in ifaces.iter().enumerate() {
+            let iface = match iface {
+                Some(x) => x,
+                None => continue,
+            };
+            let id = InterfaceId(section, idx as u32);
+            // The statistics may have arrived after the packets
+            let mut x = InterfaceCapabilities::new(id, iface);
+            if let Some((y, _)) = seen.remove(&id) {
+                x.packets = y.packets;
+                x.first_timestamp = y.first_timestamp;
+                x.last_timestamp = y.last_timestamp;
+                x.out_of_order = y.out_of_order;
+                x.at_epoch = y.at_epoch;
+            }
+            report.interfaces.push(x);
+        }
+    }
+    report
+}
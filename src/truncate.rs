@@ -0,0 +1,162 @@
+use crate::block::{Block, BlockReader, BlockType};
+use crate::iface::{InterfaceInfo, RawInterfaceId};
+use crate::{Error, Result};
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::time::SystemTime;
+use tracing::*;
+
+/// Where [`truncate()`] should stop
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TruncateLimit {
+    /// Stop before the first block which would take the output over this
+    /// many bytes.  The interface statistics which are appended afterwards
+    /// aren't counted.
+    Bytes(u64),
+    /// Stop before the first packet after this many packets have been
+    /// written
+    Packets(u64),
+    /// Stop before the first packet whose timestamp is at or after this
+    /// time.  Packets without a timestamp don't trigger the limit.
+    EndTime(SystemTime),
+}
+
+/// What [`truncate()`] wrote
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Truncated {
+    /// The number of packets written
+    pub packets: u64,
+    /// The total number of bytes written
+    pub bytes: u64,
+    /// The number of interface statistics blocks which were appended after
+    /// the limit was reached
+    pub stats_appended: u64,
+    /// Whether the whole capture fit within the limit
+    pub complete: bool,
+}
+
+/// Copy a pcapng, stopping when the given limit is reached
+///
+/// The blocks are copied verbatim, so the output always ends on a block
+/// boundary.  Once the limit has been reached, the rest of the current
+/// section is scanned for interface statistics blocks: the final
+/// statistics for each interface which was written are appended to the
+/// output.
+///
+/// Corrupt blocks are copied like any other.  A framing error stops the
+/// copy, and is returned.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::{truncate, Capture, TruncateLimit};
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little).interface(LinkType::ETHERNET, 0, &[]);
+/// # for i in 0..10_u8 {
+/// #     pcap.enhanced_packet(0, u64::from(i), &[i], &[]);
+/// # }
+/// # pcap.interface_statistics(0, 10, &[]);
+/// let mut out = vec![];
+/// let summary = truncate(pcap.build(), &mut out, TruncateLimit::Packets(3)).unwrap();
+/// assert_eq!(summary.packets, 3);
+/// assert_eq!(summary.stats_appended, 1);
+/// assert!(!summary.complete);
+///
+/// let mut capture = Capture::new(&out[..]);
+/// assert_eq!(capture.by_ref().count(), 3);
+/// ```
+pub fn truncate<R: Read, W: Write>(rdr: R, mut wtr: W, limit: TruncateLimit) -> Result<Truncated> {
+    let mut blocks = BlockReader::new(rdr);
+    // The interfaces of the current section.  `None` means the block was
+    // mangled.
+    let mut interfaces: Vec<Option<InterfaceInfo>> = vec![];
+    let mut out = Truncated::default();
+
+    let (block_type, frame) = loop {
+        let (block_type, frame) = match blocks.try_next_raw()? {
+            Some(x) => x,
+            None => {
+                wtr.flush()?;
+                out.complete = true;
+                return Ok(out);
+            }
+        };
+        let block = blocks.parse_raw(block_type, frame.clone());
+        let is_pkt = matches!(
+            block_type,
+            BlockType::EnhancedPacket | BlockType::SimplePacket | BlockType::ObsoletePacket
+        );
+        let within_limit = match limit {
+            TruncateLimit::Bytes(max) => out.bytes + frame.len() as u64 <= max,
+            TruncateLimit::Packets(max) => !is_pkt || out.packets < max,
+            TruncateLimit::EndTime(end) => match packet_ts(&interfaces, &block) {
+                Some(ts) => ts < end,
+                None => true,
+            },
+        };
+        if !within_limit {
+            break (block_type, frame);
+        }
+        match block {
+            Ok(Block::SectionHeader(_)) => interfaces.clear(),
+            Ok(Block::InterfaceDescription(descr)) => interfaces.push(Some(InterfaceInfo {
+                descr,
+                stats: None,
+                clock: None,
+                correction: None,
+            })),
+            Err(Error::Block(BlockType::SectionHeader, _)) => interfaces.clear(),
+            Err(Error::Block(BlockType::InterfaceDescription, _)) => interfaces.push(None),
+            _ => (),
+        }
+        wtr.write_all(&frame)?;
+        out.bytes += frame.len() as u64;
+        if is_pkt {
+            out.packets += 1;
+        }
+    };
+    debug!("Reached the limit after {} packets", out.packets);
+
+    // Find the final statistics for each of the interfaces we wrote
+    let mut stats = BTreeMap::<RawInterfaceId, Bytes>::new();
+    let mut next = Some((block_type, frame));
+    while let Some((block_type, frame)) = next {
+        match blocks.parse_raw(block_type, frame.clone()) {
+            Ok(Block::SectionHeader(_)) => break,
+            Ok(Block::InterfaceStatistics(isb)) if isb.interface_id.index() < interfaces.len() => {
+                stats.insert(isb.interface_id, frame);
+            }
+            Err(Error::Frame(e)) => {
+                warn!("Stopped looking for interface statistics: {e}");
+                break;
+            }
+            _ => (),
+        }
+        next = match blocks.try_next_raw() {
+            Ok(x) => x,
+            Err(Error::IO(e)) => return Err(e.into()),
+            Err(e) => {
+                warn!("Stopped looking for interface statistics: {e}");
+                None
+            }
+        };
+    }
+    for frame in stats.into_values() {
+        wtr.write_all(&frame)?;
+        out.bytes += frame.len() as u64;
+        out.stats_appended += 1;
+    }
+    wtr.flush()?;
+    Ok(out)
+}
+
+/// The timestamp of the given block, if it's a timestamped packet
+pub(crate) fn packet_ts(
+    interfaces: &[Option<InterfaceInfo>],
+    block: &Result<Block>,
+) -> Option<SystemTime> {
+    let (iface, ts, _, _) = block.as_ref().ok()?.clone().into_pkt()?;
+    let iface = interfaces.get(iface.index())?.as_ref()?;
+    Some(iface.resolve_ts(ts?))
+}
@@ -0,0 +1,237 @@
+/*! Checking pcarp against `tshark`
+
+This module is only available with the `tshark` feature.  pcarp's
+integration tests check that it agrees with `tshark` on a collection of
+captures scraped from the Wireshark wiki.  If you have captures of your own
+which are weird in some way, [`compare_with_tshark()`] lets you run the same
+check on them, using your local copy of Wireshark.
+
+For each packet, the timestamp, captured length, and original length are
+compared.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::tshark::{compare_with_tshark, TsharkError};
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[])
+#     .enhanced_packet(0, 1_500_000_000_000_000, b"hello", &[]);
+# let path = std::env::temp_dir().join("pcarp-tshark-doctest.pcapng");
+# std::fs::write(&path, pcap.as_bytes()).unwrap();
+match compare_with_tshark(&path) {
+    Ok(report) => assert!(report.is_ok(), "{report}"),
+    Err(TsharkError::NotFound) => println!("tshark isn't installed"),
+    Err(e) => panic!("{e}"),
+}
+```
+*/
+
+use crate::{Capture, Error};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::*;
+
+/// An error which prevented the comparison from being made
+#[derive(Debug, thiserror::Error)]
+pub enum TsharkError {
+    /// There's no `tshark` on the `PATH`
+    #[error("tshark isn't installed")]
+    NotFound,
+    /// `tshark` exited unsuccessfully
+    #[error("tshark failed: {0}")]
+    Failed(String),
+    /// `tshark` printed a line which couldn't be parsed
+    #[error("Couldn't parse line {0} of tshark's output: {1:?}")]
+    Parse(u64, String),
+    #[error("IO error")]
+    IO(#[from] io::Error),
+}
+
+/// The fields of a packet which are compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketSummary {
+    pub timestamp: Option<SystemTime>,
+    /// The number of bytes captured
+    pub caplen: u32,
+    /// The length of the packet on the wire
+    pub origlen: u32,
+}
+
+/// A packet on which pcarp and `tshark` disagree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// The frame number, counting from 1, as `tshark` does
+    pub frame: u64,
+    /// The packet as read by pcarp; `None` if pcarp read fewer packets
+    pub pcarp: Option<PacketSummary>,
+    /// The packet as read by `tshark`; `None` if `tshark` read fewer packets
+    pub tshark: Option<PacketSummary>,
+}
+
+/// The result of a comparison
+///
+/// Packets are matched up by their position in the file.  This means that
+/// if pcarp skips a packet which `tshark` reads (or vice versa), all the
+/// following packets will be reported as mismatches.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The number of packets read by pcarp
+    pub pcarp_packets: u64,
+    /// The number of packets read by `tshark`
+    pub tshark_packets: u64,
+    /// The number of errors which pcarp reported
+    pub pcarp_errors: u64,
+    /// The packets on which pcarp and `tshark` disagree
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl Report {
+    /// Whether pcarp and `tshark` agreed on every packet, and pcarp didn't
+    /// report any errors
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty() && self.pcarp_errors == 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "pcarp read {} packets ({} errors), tshark read {}; {} mismatches",
+            self.pcarp_packets,
+            self.pcarp_errors,
+            self.tshark_packets,
+            self.mismatches.len(),
+        )?;
+        for x in &self.mismatches {
+            writeln!(
+                f,
+                "frame {}: pcarp {:?}, tshark {:?}",
+                x.frame, x.pcarp, x.tshark
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Read a capture with both pcarp and `tshark`, and compare the results
+///
+/// `tshark` is looked for on the `PATH`; if it isn't there, this returns
+/// `TsharkError::NotFound`.  Errors reported by pcarp while reading the
+/// capture are counted in the report, rather than being returned, except
+/// for IO errors.
+pub fn compare_with_tshark(path: impl AsRef<Path>) -> Result<Report, TsharkError> {
+    let path = path.as_ref();
+    let tshark = run_tshark(path)?;
+    let mut report = Report {
+        tshark_packets: tshark.len() as u64,
+        ..Report::default()
+    };
+    let mut tshark = tshark.into_iter();
+    let mut frame = 0;
+    for pkt in Capture::new(File::open(path)?) {
+        let pkt = match pkt {
+            Ok(x) => x,
+            Err(Error::IO(e)) => return Err(e.into()),
+            Err(e) => {
+                debug!("pcarp reported an error: {e}");
+                report.pcarp_errors += 1;
+                continue;
+            }
+        };
+        frame += 1;
+        report.pcarp_packets += 1;
+        let ours = PacketSummary {
+            timestamp: pkt.timestamp,
+            caplen: pkt.data.len() as u32,
+            origlen: pkt.orig_len,
+        };
+        let theirs = tshark.next();
+        if theirs != Some(ours) {
+            report.mismatches.push(Mismatch {
+                frame,
+                pcarp: Some(ours),
+                tshark: theirs,
+            });
+        }
+    }
+    for theirs in tshark {
+        frame += 1;
+        report.mismatches.push(Mismatch {
+            frame,
+            pcarp: None,
+            tshark: Some(theirs),
+        });
+    }
+    Ok(report)
+}
+
+fn run_tshark(path: &Path) -> Result<Vec<PacketSummary>, TsharkError> {
+    let output = Command::new("tshark")
+        .arg("-r")
+        .arg(path)
+        .args(["-T", "fields"])
+        .args([
+            "-e",
+            "frame.time_epoch",
+            "-e",
+            "frame.cap_len",
+            "-e",
+            "frame.len",
+        ])
+        .stdin(Stdio::null())
+        .output();
+    let output = match output {
+        Ok(x) => x,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(TsharkError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(TsharkError::Failed(stderr.trim().to_owned()));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            parse_line(line).ok_or_else(|| TsharkError::Parse(i as u64 + 1, line.to_owned()))
+        })
+        .collect()
+}
+
+/// Parse a line of the form `<epoch secs>.<fraction>\t<caplen>\t<origlen>`
+fn parse_line(line: &str) -> Option<PacketSummary> {
+    let mut fields = line.split('\t');
+    let timestamp = fields.next()?;
+    let caplen = fields.next()?.parse().ok()?;
+    let origlen = fields.next()?.parse().ok()?;
+    let timestamp = if timestamp.is_empty() {
+        None
+    } else {
+        Some(parse_epoch(timestamp)?)
+    };
+    Some(PacketSummary {
+        timestamp,
+        caplen,
+        origlen,
+    })
+}
+
+/// Parse a decimal number of seconds, exactly, to nanosecond precision
+fn parse_epoch(x: &str) -> Option<SystemTime> {
+    let (secs, frac) = x.split_once('.').unwrap_or((x, ""));
+    let secs: u64 = secs.parse().ok()?;
+    let mut nanos = 0;
+    for (i, c) in frac.chars().enumerate() {
+        let digit = c.to_digit(10)?;
+        if i < 9 {
+            nanos += digit * 10_u32.pow(8 - i as u32);
+        }
+    }
+    UNIX_EPOCH.checked_add(Duration::new(secs, nanos))
+}
@@ -0,0 +1,142 @@
+use crate::block::{Block, BlockReader, BlockType};
+use crate::iface::InterfaceInfo;
+use crate::section;
+use crate::truncate::packet_ts;
+use crate::{Error, Result};
+use bytes::{Bytes, BytesMut};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::SystemTime;
+use tracing::*;
+
+/// What [`extract_window()`] wrote
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Extracted {
+    /// The number of packets written
+    pub packets: u64,
+    /// The total number of bytes written
+    pub bytes: u64,
+    /// The number of sections which were written
+    pub sections: u64,
+    /// The number of sections which were skipped without being read,
+    /// because none of their packets could fall within the window
+    pub sections_skipped: u64,
+}
+
+/// Copy the packets with timestamps in `start..end` into a new pcapng
+///
+/// The file is first skimmed (as with
+/// [`Capture::sections()`](crate::Capture::sections)) to find the time span
+/// of each section.  Sections which don't overlap the window are skipped
+/// over without being read.  For the rest, the section header, interface
+/// descriptions, name resolution blocks, and decryption secrets are copied
+/// along with the matching packets, so the output is a valid pcapng in its
+/// own right, with the same interface IDs as the original.
+///
+/// Packets without a timestamp, and packets whose blocks are corrupt, are
+/// left out.  Blocks are copied verbatim, except that section headers have
+/// their section length cleared, since it no longer holds.  A framing error
+/// stops the copy, and is returned.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::{extract_window, Capture};
+/// use std::io::Cursor;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little).interface(LinkType::ETHERNET, 0, &[]);
+/// # for i in 0..10_u8 {
+/// #     pcap.enhanced_packet(0, u64::from(i) * 1_000_000, &[i], &[]);
+/// # }
+/// # pcap.section(Endianness::Big).interface(LinkType::RAW, 0, &[]);
+/// # pcap.enhanced_packet(0, 100_000_000, b"later", &[]);
+/// let start = UNIX_EPOCH + Duration::from_secs(3);
+/// let end = UNIX_EPOCH + Duration::from_secs(6);
+/// let mut out = vec![];
+/// let summary = extract_window(Cursor::new(pcap.as_bytes()), &mut out, start, end).unwrap();
+/// assert_eq!(summary.packets, 3);
+/// assert_eq!(summary.sections_skipped, 1);
+///
+/// let data: Vec<_> = Capture::new(&out[..]).map(|pkt| pkt.unwrap().data[0]).collect();
+/// assert_eq!(data, [3, 4, 5]);
+/// ```
+pub fn extract_window<R: Read + Seek, W: Write>(
+    mut rdr: R,
+    mut wtr: W,
+    start: SystemTime,
+    end: SystemTime,
+) -> Result<Extracted> {
+    let sections = section::scan(&mut rdr, None)?;
+    let mut out = Extracted::default();
+    for summary in sections {
+        let overlaps = match (summary.start, summary.end) {
+            (Some(first), Some(last)) => first < end && last >= start,
+            _ => false,
+        };
+        if !overlaps {
+            trace!("Skipping the section at offset {}", summary.offset);
+            out.sections_skipped += 1;
+            continue;
+        }
+        rdr.seek(SeekFrom::Start(summary.offset))?;
+        let mut blocks = BlockReader::new((&mut rdr).take(summary.len));
+        // The interfaces of the current section.  `None` means the block was
+        // mangled.
+        let mut interfaces: Vec<Option<InterfaceInfo>> = vec![];
+        while let Some((block_type, frame)) = blocks.try_next_raw()? {
+            let block = blocks.parse_raw(block_type, frame.clone());
+            let frame = match block_type {
+                BlockType::SectionHeader => {
+                    interfaces.clear();
+                    clear_section_len(frame)
+                }
+                BlockType::InterfaceDescription => {
+                    interfaces.push(match &block {
+                        Ok(Block::InterfaceDescription(descr)) => Some(InterfaceInfo {
+                            descr: descr.clone(),
+                            stats: None,
+                            clock: None,
+                            correction: None,
+                        }),
+                        _ => None,
+                    });
+                    frame
+                }
+                BlockType::NameResolution | BlockType::DecryptionSecrets => frame,
+                BlockType::EnhancedPacket | BlockType::SimplePacket | BlockType::ObsoletePacket => {
+                    match packet_ts(&interfaces, &block) {
+                        Some(ts) if ts >= start && ts < end => {
+                            out.packets += 1;
+                            frame
+                        }
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+            if let Err(Error::Block(block_type, e)) = &block {
+                debug!("Copying a corrupt {block_type:?} block: {e}");
+            }
+            wtr.write_all(&frame)?;
+            out.bytes += frame.len() as u64;
+        }
+        out.sections += 1;
+    }
+    debug!(
+        "Extracted {} packets from {} sections",
+        out.packets, out.sections
+    );
+    wtr.flush()?;
+    Ok(out)
+}
+
+/// Set a section header's length field to -1 ("unspecified")
+fn clear_section_len(frame: Bytes) -> Bytes {
+    if frame.len() < 24 {
+        return frame;
+    }
+    let mut frame = BytesMut::from(&frame[..]);
+    // -1 is all ones, whatever the endianness
+    frame[16..24].fill(0xFF);
+    frame.freeze()
+}
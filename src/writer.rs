@@ -0,0 +1,1144 @@
+/*! Writing pcapng files
+
+A [`PcapngWriter`] writes packets back out as a pcapng.  Unlike a classic
+pcap, a pcapng can carry comments on the sections, interfaces, and packets
+which it contains: Wireshark shows them alongside the packets, and you can
+filter on them with `frame.comment`.  This makes them a handy way to record
+the findings of an analysis pipeline in the capture itself.  Findings
+attached to a packet as [`Annotations`](crate::Annotations) are written out
+along with it (see [`AnnotationFormat`]).
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::writer::PcapngWriter;
+use pcarp::Capture;
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little)
+#     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+#     .enhanced_packet(0, 1_500_000_000_000_000, b"hello", &[])
+#     .enhanced_packet(0, 1_500_000_000_000_001, b"", &[]);
+let mut capture = Capture::new(pcap.build());
+let mut wtr = PcapngWriter::new(vec![]);
+wtr.write_section(&["Checked for empty packets"]).unwrap();
+while let Some(x) = capture.next_with_iface() {
+    let (pkt, iface) = x.unwrap();
+    let comments: &[&str] = if pkt.data.is_empty() { &["empty"] } else { &[] };
+    wtr.write_with_comments(&pkt, iface.as_deref(), comments).unwrap();
+}
+
+let out = wtr.into_inner();
+let contains = |s: &[u8]| out.windows(s.len()).any(|x| x == s);
+assert!(contains(b"Checked for empty packets") && contains(b"empty"));
+let mut capture = Capture::new(&out[..]);
+let pkt = capture.next().unwrap().unwrap();
+assert_eq!(&pkt.data[..], b"hello");
+assert_eq!(capture.lookup_interface(pkt.interface.unwrap()).unwrap().name(), "eth0");
+```
+
+If you're writing packets which didn't come from a `Capture`, describe
+their interfaces with [`write_interface_with()`](PcapngWriter::write_interface_with).
+The options of each block are given with a typed builder ([`ShbOptions`],
+[`IdbOptions`], or [`EpbOptions`]), which takes care of encoding and
+padding, and checks that the result is valid: values which are too long
+for an option, or options which may only appear once but were given twice,
+are reported as an [`OptionError`], and nothing is written.
+
+```
+use pcarp::iface::{InterfaceId, LinkType};
+use pcarp::legacy::ExportError;
+use pcarp::writer::{EpbOptions, IdbOptions, OptionError, PcapngWriter};
+use pcarp::{Capture, Packet};
+use std::time::{Duration, UNIX_EPOCH};
+
+let mut wtr = PcapngWriter::new(vec![]);
+let opts = IdbOptions::new().name("eth0").tsresol_micros();
+wtr.write_interface_with(InterfaceId(0, 0), LinkType::ETHERNET, 0, opts).unwrap();
+let mut pkt = Packet::new(&b"hello"[..]);
+pkt.timestamp = Some(UNIX_EPOCH + Duration::from_micros(1_500_000));
+pkt.interface = Some(InterfaceId(0, 0));
+wtr.write_with_options(&pkt, None, EpbOptions::new().flags(1)).unwrap();
+
+// The same option twice is an error
+let opts = IdbOptions::new().name("eth0").name("eth1");
+let err = wtr.write_interface_with(InterfaceId(0, 1), LinkType::ETHERNET, 0, opts);
+assert!(matches!(err, Err(ExportError::InvalidOption(OptionError::Repeated(2)))));
+
+let out = wtr.into_inner();
+let mut capture = Capture::new(&out[..]);
+let pkt = capture.next().unwrap().unwrap();
+let iface = capture.lookup_interface(pkt.interface.unwrap()).unwrap();
+assert_eq!((iface.name(), iface.tsresol()), ("eth0", 1_000_000));
+assert_eq!(pkt.timestamp, Some(UNIX_EPOCH + Duration::from_micros(1_500_000)));
+```
+
+To split a capture into one file per interface (for tools which only
+handle a single interface), use a [`SplitPcapngWriter`].  To generate a
+capture from scratch in one go (eg. in tests or simulators), use
+[`write_capture()`].
+*/
+
+use crate::block::{
+    Endianness, EPB_DROPCOUNT, EPB_FLAGS, EPB_HASH, EPB_PACKETID, EPB_QUEUE, IF_DESCRIPTION,
+    IF_EUIADDR, IF_FCSLEN, IF_FILTER, IF_HARDWARE, IF_IPV4ADDR, IF_IPV6ADDR, IF_MACADDR, IF_NAME,
+    IF_OS, IF_RXSPEED, IF_SPEED, IF_TSOFFSET, IF_TSRESOL, IF_TXSPEED, OPT_COMMENT,
+    OPT_CUSTOM_STR_COPY, SHB_HARDWARE, SHB_OS, SHB_USERAPPL,
+};
+use crate::iface::{InterfaceId, InterfaceInfo, LinkType, LogicalInterface};
+use crate::legacy::ExportError;
+use crate::Packet;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::*;
+
+const SHB: u32 = 0x0A0D_0D0A;
+const IDB: u32 = 0x0000_0001;
+const EPB: u32 = 0x0000_0006;
+
+/// The resolution of an interface without an `if_tsresol` option
+const DEFAULT_TICKS_PER_SEC: u64 = 1_000_000;
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Writes packets to a pcapng file
+///
+/// The file is written in the machine's byte order, unless you choose
+/// another with [`set_endianness()`](Self::set_endianness).  Each interface is
+/// described the first time a packet from it is written (unless you've
+/// already described it with [`write_interface()`](Self::write_interface)).
+/// Interfaces described this way have nanosecond timestamps, whatever the
+/// resolution of the original interface was.
+///
+/// The packets' options aren't preserved, but comments (or other options)
+/// can be attached to each block as it's written.
+///
+/// Blocks are passed straight on to the underlying writer.  If that's
+/// buffered (eg. a `BufWriter`), recently-written packets can be lost if
+/// the process dies.  To bound the loss, call [`flush()`](Self::flush)
+/// (or [`sync_data()`](Self::sync_data)) periodically, or have it done
+/// automatically with [`set_auto_flush()`](Self::set_auto_flush) (or
+/// [`set_auto_sync()`](Self::set_auto_sync)).
+///
+/// ```
+/// use pcarp::iface::{InterfaceId, LinkType};
+/// use pcarp::writer::{IdbOptions, PcapngWriter};
+/// use std::io::BufWriter;
+///
+/// # let mut pkt = pcarp::Packet::new(&b"hello"[..]);
+/// # pkt.interface = Some(InterfaceId(0, 0));
+/// let mut wtr = PcapngWriter::new(BufWriter::new(vec![]));
+/// wtr.set_auto_flush(Some(4));
+/// let opts = IdbOptions::new().name("eth0");
+/// wtr.write_interface_with(InterfaceId(0, 0), LinkType::ETHERNET, 0, opts).unwrap();
+/// wtr.write(&pkt, None).unwrap();
+/// assert!(wtr.get_ref().get_ref().is_empty()); // 3 blocks written
+/// wtr.write(&pkt, None).unwrap();
+/// let flushed = wtr.get_ref().get_ref().len();
+/// assert!(flushed > 0);
+/// wtr.write(&pkt, None).unwrap();
+/// assert_eq!(wtr.get_ref().get_ref().len(), flushed);
+/// ```
+#[derive(Debug)]
+pub struct PcapngWriter<W> {
+    wtr: W,
+    /// Flush (or sync) the writer after every this many blocks
+    auto_flush: Option<(u64, FlushFn<W>)>,
+    /// The number of blocks written since the last flush
+    unflushed: u64,
+    /// The byte order for new sections
+    endianness: Endianness,
+    /// The byte order of the current section
+    section_endianness: Endianness,
+    annotation_format: AnnotationFormat,
+    /// The interfaces described in the current section, keyed by their ID
+    /// in the capture they came from, along with their ID in the output
+    /// and their timestamp resolution.  `None` until a section header has
+    /// been written.
+    interfaces: Option<HashMap<Option<InterfaceId>, (u32, u64)>>,
+}
+
+/// Either `flush()` or `sync_data()`
+type FlushFn<W> = fn(&mut W) -> io::Result<()>;
+
+impl<W: Write> PcapngWriter<W> {
+    /// Create a writer which writes to the given `Write`r
+    pub fn new(wtr: W) -> PcapngWriter<W> {
+        PcapngWriter {
+            wtr,
+            auto_flush: None,
+            unflushed: 0,
+            endianness: Endianness::native(),
+            section_endianness: Endianness::native(),
+            annotation_format: AnnotationFormat::default(),
+            interfaces: None,
+        }
+    }
+
+    /// Choose how packets' [`Annotations`] are written
+    ///
+    /// By default, they're written as comments.
+    ///
+    /// ```
+    /// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::{Block, BlockReader};
+    /// use pcarp::writer::{AnnotationFormat, PcapngWriter};
+    /// use pcarp::Capture;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Little)
+    /// #     .interface(LinkType::ETHERNET, 0, &[])
+    /// #     .enhanced_packet(0, 0, b"hello", &[]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let mut wtr = PcapngWriter::new(vec![]);
+    /// wtr.set_annotation_format(AnnotationFormat::Custom(32473));
+    /// let (mut pkt, iface) = capture.next_with_iface().unwrap().unwrap();
+    /// pkt.annotations.insert("flow", "17");
+    /// wtr.write(&pkt, iface.as_deref()).unwrap();
+    ///
+    /// let out = wtr.into_inner();
+    /// let epb = BlockReader::new(&out[..])
+    ///     .find_map(|x| match x.unwrap() {
+    ///         Block::EnhancedPacket(x) => Some(x),
+    ///         _ => None,
+    ///     })
+    ///     .unwrap();
+    /// let opt = &epb.custom_options[0];
+    /// assert_eq!((opt.pen, &opt.payload[..]), (32473, &b"flow=17"[..]));
+    /// ```
+    pub fn set_annotation_format(&mut self, format: AnnotationFormat) {
+        self.annotation_format = format;
+    }
+
+    /// Write new sections in the given byte order
+    ///
+    /// By default, sections are written in the byte order of the machine
+    /// you're running on.  The byte order can only change at the start of
+    /// a section, so this takes effect from the next one (which is started
+    /// by [`write_section()`](Self::write_section), or by the first packet
+    /// if no section has been started yet).
+    ///
+    /// To keep the byte order of a capture you're re-writing, use
+    /// [`Capture::endianness()`](crate::Capture::endianness):
+    ///
+    /// ```
+    /// # use pcarp::{iface::LinkType, testgen::PcapngBuilder};
+    /// use pcarp::block::Endianness;
+    /// use pcarp::writer::PcapngWriter;
+    /// use pcarp::Capture;
+    ///
+    /// # let mut pcap = PcapngBuilder::new();
+    /// # pcap.section(Endianness::Big)
+    /// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+    /// #     .enhanced_packet(0, 0, b"hello", &[(2, &1u32.to_be_bytes())]);
+    /// let mut capture = Capture::new(pcap.build());
+    /// let mut wtr = PcapngWriter::new(vec![]);
+    /// while let Some(x) = capture.next_with_iface() {
+    ///     let (pkt, iface) = x.unwrap();
+    ///     wtr.set_endianness(capture.endianness());
+    ///     wtr.write(&pkt, iface.as_deref()).unwrap();
+    /// }
+    ///
+    /// let out = wtr.into_inner();
+    /// assert_eq!(&out[8..12], &[0x1A, 0x2B, 0x3C, 0x4D]); // big-endian
+    /// let mut capture = Capture::new(&out[..]);
+    /// let (pkt, iface) = capture.next_with_iface().unwrap().unwrap();
+    /// assert_eq!(capture.endianness(), Endianness::Big);
+    /// assert_eq!((&pkt.data[..], iface.unwrap().name()), (&b"hello"[..], "eth0"));
+    /// ```
+    pub fn set_endianness(&mut self, endianness: Endianness) {
+        self.endianness = endianness;
+    }
+
+    /// Flush the underlying writer after every `blocks` blocks
+    ///
+    /// Section headers and interface descriptions count as blocks.  `None`
+    /// turns off automatic flushing (which is the default).  This replaces
+    /// any previous [`set_auto_sync()`](Self::set_auto_sync).
+    pub fn set_auto_flush(&mut self, blocks: Option<u64>) {
+        self.auto_flush = blocks.map(|n| (n.max(1), <W as Write>::flush as FlushFn<W>));
+        self.unflushed = 0;
+    }
+
+    /// Flush the underlying writer, and wait for its data to reach the
+    /// disk, after every `blocks` blocks
+    ///
+    /// Syncing is much more expensive than flushing, so `blocks` should
+    /// usually be large.  `None` turns off automatic syncing (which is the
+    /// default).  This replaces any previous
+    /// [`set_auto_flush()`](Self::set_auto_flush).
+    pub fn set_auto_sync(&mut self, blocks: Option<u64>)
+    where
+        W: SyncData,
+    {
+        self.auto_flush = blocks.map(|n| (n.max(1), W::sync_data as FlushFn<W>));
+        self.unflushed = 0;
+    }
+
+    /// Start a new section, with the given comments
+    ///
+    /// Interfaces are local to a section, so they'll be described again
+    /// when their packets are written.  If you don't call this, a section
+    /// with no comments is started when the first packet is written.
+    pub fn write_section(&mut self, comments: &[&str]) -> Result<(), ExportError> {
+        self.write_section_with(ShbOptions::new().comments(comments))
+    }
+
+    /// Start a new section, with the given options
+    pub fn write_section_with(&mut self, opts: ShbOptions) -> Result<(), ExportError> {
+        let e = self.endianness;
+        let mut body = vec![];
+        body.extend_from_slice(&u32_bytes(0x1A2B_3C4D, e));
+        body.extend_from_slice(&u16_bytes(1, e));
+        body.extend_from_slice(&u16_bytes(0, e));
+        body.extend_from_slice(&[0xFF; 8]); // section length: unspecified
+        opts.0.finish(&mut body, e)?;
+        self.section_endianness = e;
+        self.write_block(SHB, &body)?;
+        self.interfaces = Some(HashMap::new());
+        Ok(())
+    }
+
+    /// Describe an interface, with the given comments
+    ///
+    /// `id` is the interface's ID in the capture it came from: packets with
+    /// this ID will be attributed to the new interface.  If an interface
+    /// with the same ID has already been described in this section, it's
+    /// superseded.
+    pub fn write_interface(
+        &mut self,
+        id: InterfaceId,
+        iface: &InterfaceInfo,
+        comments: &[&str],
+    ) -> Result<(), ExportError> {
+        self.write_interface_inner(Some(id), iface, comments)?;
+        Ok(())
+    }
+
+    /// Describe an interface from scratch, with the given options
+    ///
+    /// As with [`write_interface()`](Self::write_interface), packets with
+    /// the given `id` will be attributed to the new interface.  Their
+    /// timestamps are written with the resolution given in `opts`
+    /// (microseconds, if it doesn't specify one).  A `snap_len` of zero
+    /// means there's no limit.
+    pub fn write_interface_with(
+        &mut self,
+        id: InterfaceId,
+        link_type: LinkType,
+        snap_len: u32,
+        opts: IdbOptions,
+    ) -> Result<(), ExportError> {
+        self.write_idb(Some(id), link_type, snap_len, opts)?;
+        Ok(())
+    }
+
+    /// Write a packet, which was captured on the given interface
+    ///
+    /// The interface info is needed to describe the interface; see
+    /// [`Capture::next_with_iface()`](crate::Capture::next_with_iface).
+    /// Packets without a timestamp are written with a timestamp of zero.
+    pub fn write(
+        &mut self,
+        pkt: &Packet,
+        iface: Option<&InterfaceInfo>,
+    ) -> Result<(), ExportError> {
+        self.write_with_options(pkt, iface, EpbOptions::new())
+    }
+
+    /// Write a packet, with the given comments
+    pub fn write_with_comments(
+        &mut self,
+        pkt: &Packet,
+        iface: Option<&InterfaceInfo>,
+        comments: &[&str],
+    ) -> Result<(), ExportError> {
+        self.write_with_options(pkt, iface, EpbOptions::new().comments(comments))
+    }
+
+    /// Write a packet, with the given options
+    pub fn write_with_options(
+        &mut self,
+        pkt: &Packet,
+        iface: Option<&InterfaceInfo>,
+        mut opts: EpbOptions,
+    ) -> Result<(), ExportError> {
+        for (key, value) in pkt.annotations.iter() {
+            match self.annotation_format {
+                AnnotationFormat::Comments => opts
+                    .0
+                    .put(OPT_COMMENT, format!("{key}: {value}").as_bytes()),
+                AnnotationFormat::Custom(pen) => opts.0.put_custom(
+                    OPT_CUSTOM_STR_COPY,
+                    pen,
+                    format!("{key}={value}").as_bytes(),
+                ),
+                AnnotationFormat::Omit => break,
+            }
+        }
+        let ts = match pkt.timestamp {
+            Some(ts) => ts,
+            None => {
+                debug!("Packet has no timestamp; writing it with a timestamp of zero");
+                UNIX_EPOCH
+            }
+        };
+        let known = self
+            .interfaces
+            .as_ref()
+            .and_then(|x| x.get(&pkt.interface))
+            .copied();
+        let (interface_id, ticks_per_sec) = match known {
+            Some(x) => x,
+            None => {
+                let iface = iface.ok_or(ExportError::MissingInterface)?;
+                self.write_interface_inner(pkt.interface, iface, &[])?
+            }
+        };
+        let ticks = ts
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .and_then(|x| {
+                u64::try_from(x.as_nanos() * u128::from(ticks_per_sec) / NANOS_PER_SEC).ok()
+            })
+            .ok_or(ExportError::TimestampOutOfRange)?;
+
+        let e = self.section_endianness;
+        let mut body = vec![];
+        body.extend_from_slice(&u32_bytes(interface_id, e));
+        body.extend_from_slice(&u32_bytes((ticks >> 32) as u32, e));
+        body.extend_from_slice(&u32_bytes(ticks as u32, e));
+        body.extend_from_slice(&u32_bytes(pkt.data.len() as u32, e));
+        let orig_len = pkt.orig_len.max(pkt.data.len() as u32);
+        body.extend_from_slice(&u32_bytes(orig_len, e));
+        put_padded(&mut body, &pkt.data);
+        opts.0.finish(&mut body, e)?;
+        self.write_block(EPB, &body)?;
+        Ok(())
+    }
+
+    /// Describe an interface which came from a capture, returning its ID
+    /// and timestamp resolution in the output
+    fn write_interface_inner(
+        &mut self,
+        id: Option<InterfaceId>,
+        iface: &InterfaceInfo,
+        comments: &[&str],
+    ) -> Result<(u32, u64), ExportError> {
+        let descr = &iface.descr;
+        let mut opts = IdbOptions::new().comments(comments).tsresol_nanos();
+        if !descr.if_name.is_empty() {
+            opts = opts.name(&descr.if_name);
+        }
+        if !descr.if_description.is_empty() {
+            opts = opts.description(&descr.if_description);
+        }
+        for x in &descr.if_ipv4_addr {
+            opts.0.put(IF_IPV4ADDR, x);
+        }
+        for x in &descr.if_ipv6_addr {
+            opts.0.put(IF_IPV6ADDR, x);
+        }
+        if let Some(x) = descr.if_mac_addr {
+            opts = opts.mac_addr(x);
+        }
+        if let Some(x) = descr.if_eui_addr {
+            opts = opts.eui_addr(x);
+        }
+        if let Some(x) = descr.if_speed {
+            opts = opts.speed(x);
+        }
+        if !descr.if_os.is_empty() {
+            opts = opts.os(&descr.if_os);
+        }
+        if let Some([x]) = descr.if_fcslen {
+            opts = opts.fcslen(x);
+        }
+        if !descr.if_hardware.is_empty() {
+            opts = opts.hardware(&descr.if_hardware);
+        }
+        let snap_len = descr.snap_len.unwrap_or(0);
+        self.write_idb(id, descr.link_type, snap_len, opts)
+    }
+
+    /// Write an IDB, returning its ID and timestamp resolution in the output
+    fn write_idb(
+        &mut self,
+        id: Option<InterfaceId>,
+        link_type: LinkType,
+        snap_len: u32,
+        opts: IdbOptions,
+    ) -> Result<(u32, u64), ExportError> {
+        let ticks_per_sec = opts.1.unwrap_or(DEFAULT_TICKS_PER_SEC);
+        let e = match self.interfaces {
+            Some(_) => self.section_endianness,
+            // We'll be starting a section
+            None => self.endianness,
+        };
+        let mut body = vec![];
+        body.extend_from_slice(&u16_bytes(link_type.to_u16(), e));
+        body.extend_from_slice(&u16_bytes(0, e));
+        body.extend_from_slice(&u32_bytes(snap_len, e));
+        opts.0.finish(&mut body, e)?;
+        if self.interfaces.is_none() {
+            self.write_section(&[])?;
+        }
+        self.write_block(IDB, &body)?;
+
+        let interfaces = self.interfaces.get_or_insert_with(HashMap::new);
+        // Superseded interfaces still count towards the numbering
+        let idx = interfaces.values().map(|x| x.0 + 1).max().unwrap_or(0);
+        debug!("Described interface {id:?} as interface {idx}");
+        interfaces.insert(id, (idx, ticks_per_sec));
+        Ok((idx, ticks_per_sec))
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> io::Result<()> {
+        let block_len = (body.len() + padding(body.len()) + 12) as u32;
+        let e = self.section_endianness;
+        let mut buf = Vec::with_capacity(block_len as usize);
+        buf.extend_from_slice(&u32_bytes(block_type, e));
+        buf.extend_from_slice(&u32_bytes(block_len, e));
+        put_padded(&mut buf, body);
+        buf.extend_from_slice(&u32_bytes(block_len, e));
+        self.wtr.write_all(&buf)?;
+        if let Some((n, flush)) = self.auto_flush {
+            self.unflushed += 1;
+            if self.unflushed >= n {
+                trace!("Flushing after {n} blocks");
+                flush(&mut self.wtr)?;
+                self.unflushed = 0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.unflushed = 0;
+        self.wtr.flush()
+    }
+
+    /// Flush the underlying writer, and wait for its data to reach the
+    /// disk
+    ///
+    /// See [`SyncData`].
+    pub fn sync_data(&mut self) -> io::Result<()>
+    where
+        W: SyncData,
+    {
+        self.unflushed = 0;
+        self.wtr.sync_data()
+    }
+
+    /// Get a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.wtr
+    }
+
+    /// Unwrap the `PcapngWriter`, returning the underlying writer
+    pub fn into_inner(self) -> W {
+        self.wtr
+    }
+}
+
+/// Writers whose data can be made durable
+///
+/// This is implemented for `File` (using [`File::sync_data()`]), and for
+/// buffered writers wrapping one, which are flushed first.
+pub trait SyncData: Write {
+    /// Flush any buffered data, and wait for it to reach the disk
+    fn sync_data(&mut self) -> io::Result<()>;
+}
+
+impl SyncData for File {
+    fn sync_data(&mut self) -> io::Result<()> {
+        File::sync_data(self)
+    }
+}
+
+impl<W: SyncData> SyncData for BufWriter<W> {
+    fn sync_data(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.get_mut().sync_data()
+    }
+}
+
+impl<W: SyncData + ?Sized> SyncData for &mut W {
+    fn sync_data(&mut self) -> io::Result<()> {
+        (**self).sync_data()
+    }
+}
+
+/// Write a complete pcapng, containing the given packets
+///
+/// The file has a single section, with a single interface of the given
+/// link type.  The interface has nanosecond timestamps and no snap length,
+/// and the blocks have no options.
+///
+/// ```
+/// use pcarp::iface::LinkType;
+/// use pcarp::writer::write_capture;
+/// use pcarp::Capture;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let t0 = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+/// let packets = (0..3_u64).map(|i| (t0 + Duration::from_millis(i), &b"ping"[..]));
+/// let mut out = vec![];
+/// write_capture(&mut out, LinkType::RAW, packets).unwrap();
+///
+/// let mut capture = Capture::new(&out[..]);
+/// let (pkt, iface) = capture.next_with_iface().unwrap().unwrap();
+/// assert_eq!(iface.unwrap().link_type(), LinkType::RAW);
+/// assert_eq!((pkt.timestamp, &pkt.data[..]), (Some(t0), &b"ping"[..]));
+/// assert_eq!(capture.count(), 2);
+/// ```
+pub fn write_capture<'a, W: Write>(
+    wtr: W,
+    link_type: LinkType,
+    packets: impl IntoIterator<Item = (SystemTime, &'a [u8])>,
+) -> Result<(), ExportError> {
+    let id = InterfaceId(0, 0);
+    let mut wtr = PcapngWriter::new(wtr);
+    wtr.write_section(&[])?;
+    wtr.write_interface_with(id, link_type, 0, IdbOptions::new().tsresol_nanos())?;
+    for (ts, data) in packets {
+        let mut pkt = Packet::new(Bytes::copy_from_slice(data));
+        pkt.timestamp = Some(ts);
+        pkt.interface = Some(id);
+        wtr.write(&pkt, None)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Writes packets to a set of pcapng files, one per interface
+///
+/// A new file is opened (using the given function) whenever a packet from
+/// a previously-unseen interface is written.  Each file describes a single
+/// interface, with ID 0, which suits tools which can only handle one
+/// interface at a time.
+///
+/// Interfaces are matched up across sections by their
+/// [`LogicalInterface`], so packets from the same interface end up in the
+/// same file, even if the capture describes it more than once.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::writer::SplitPcapngWriter;
+/// use pcarp::Capture;
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth0")])
+/// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth1")])
+/// #     .enhanced_packet(0, 0, b"hello", &[])
+/// #     .enhanced_packet(1, 0, b"world", &[])
+/// #     .section(Endianness::Little)
+/// #     .interface(LinkType::ETHERNET, 0, &[(2, b"eth1")])
+/// #     .enhanced_packet(0, 0, b"again", &[]);
+/// let mut capture = Capture::new(pcap.build());
+/// let mut wtr = SplitPcapngWriter::new(|_| Ok(vec![]));
+/// while let Some(x) = capture.next_with_iface() {
+///     let (pkt, iface) = x.unwrap();
+///     wtr.write(&pkt, iface.as_deref()).unwrap();
+/// }
+/// let outputs = wtr.into_inner();
+/// assert_eq!(outputs.len(), 2);
+///
+/// for out in outputs.values() {
+///     let mut capture = Capture::new(&out[..]);
+///     while let Some(x) = capture.next_with_iface() {
+///         let (pkt, iface) = x.unwrap();
+///         assert_eq!(pkt.interface.unwrap().index(), 0);
+///         let expected = if &pkt.data[..] == b"hello" { "eth0" } else { "eth1" };
+///         assert_eq!(iface.unwrap().name(), expected);
+///     }
+/// }
+/// ```
+pub struct SplitPcapngWriter<W, F> {
+    open: F,
+    writers: HashMap<LogicalInterface, PcapngWriter<W>>,
+}
+
+impl<W: Write, F: FnMut(&InterfaceInfo) -> io::Result<W>> SplitPcapngWriter<W, F> {
+    /// Create a writer which opens new files using the given function
+    pub fn new(open: F) -> SplitPcapngWriter<W, F> {
+        SplitPcapngWriter {
+            open,
+            writers: HashMap::new(),
+        }
+    }
+
+    /// Write a packet to the file for its interface
+    pub fn write(
+        &mut self,
+        pkt: &Packet,
+        iface: Option<&InterfaceInfo>,
+    ) -> Result<(), ExportError> {
+        let info = iface.ok_or(ExportError::MissingInterface)?;
+        let id = pkt.interface.ok_or(ExportError::MissingInterface)?;
+        let logical = info.logical(id);
+        let wtr = match self.writers.get_mut(&logical) {
+            Some(wtr) => wtr,
+            None => {
+                debug!("Opening a new pcapng for {logical:?}");
+                let wtr = PcapngWriter::new((self.open)(info)?);
+                self.writers.entry(logical).or_insert(wtr)
+            }
+        };
+        // Every packet in the file is attributed to the same interface, so
+        // that it's only described once
+        let mut pkt = pkt.clone();
+        pkt.interface = None;
+        wtr.write(&pkt, iface)
+    }
+
+    /// Flush all the underlying writers
+    pub fn flush(&mut self) -> io::Result<()> {
+        for wtr in self.writers.values_mut() {
+            wtr.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Unwrap the `SplitPcapngWriter`, returning the underlying writers
+    pub fn into_inner(self) -> HashMap<LogicalInterface, W> {
+        self.writers
+            .into_iter()
+            .map(|(logical, wtr)| (logical, wtr.into_inner()))
+            .collect()
+    }
+}
+
+/// How a [`PcapngWriter`] writes packets' [`Annotations`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum AnnotationFormat {
+    /// As comments of the form `key: value`.  Wireshark shows these
+    /// alongside the packet.  This is the default.
+    #[default]
+    Comments,
+    /// As custom string options with the given Private Enterprise Number,
+    /// of the form `key=value`.  These are easier for other tools to pick
+    /// out, and are marked as safe to copy, so they survive being merged
+    /// or filtered by tools which respect that.
+    Custom(u32),
+    /// Don't write them
+    Omit,
+}
+
+/// A problem with the options given for a block
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum OptionError {
+    /// The value of an option was longer than 65535 bytes
+    #[error("The value of option {code} is {len} bytes, which is too long")]
+    TooLong { code: u16, len: usize },
+    /// An option which may appear at most once in a block was given again
+    #[error("Option {0} was given more than once")]
+    Repeated(u16),
+    /// The value of an option is out of range
+    #[error("The value of option {0} is out of range")]
+    OutOfRange(u16),
+}
+
+/// The options of a section header block
+///
+/// ```
+/// use pcarp::writer::ShbOptions;
+///
+/// let opts = ShbOptions::new()
+///     .user_appl("my-capture-tool 1.0")
+///     .comment("Captured in the lab");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ShbOptions(Options);
+
+impl ShbOptions {
+    /// No options
+    pub fn new() -> ShbOptions {
+        ShbOptions::default()
+    }
+
+    /// Add a comment.  There may be any number of comments.
+    pub fn comment(mut self, comment: &str) -> ShbOptions {
+        self.0.put(OPT_COMMENT, comment.as_bytes());
+        self
+    }
+
+    /// Add several comments
+    pub fn comments(mut self, comments: &[&str]) -> ShbOptions {
+        self.0.comments(comments);
+        self
+    }
+
+    /// The hardware of the machine which wrote the section (`shb_hardware`)
+    pub fn hardware(mut self, hardware: &str) -> ShbOptions {
+        self.0.put_once(SHB_HARDWARE, hardware.as_bytes());
+        self
+    }
+
+    /// The OS of the machine which wrote the section (`shb_os`)
+    pub fn os(mut self, os: &str) -> ShbOptions {
+        self.0.put_once(SHB_OS, os.as_bytes());
+        self
+    }
+
+    /// The application which wrote the section (`shb_userappl`)
+    pub fn user_appl(mut self, user_appl: &str) -> ShbOptions {
+        self.0.put_once(SHB_USERAPPL, user_appl.as_bytes());
+        self
+    }
+}
+
+/// The options of an interface description block
+///
+/// ```
+/// use pcarp::writer::IdbOptions;
+/// use std::net::Ipv4Addr;
+///
+/// let opts = IdbOptions::new()
+///     .name("eth0")
+///     .ipv4_addr(Ipv4Addr::new(192, 168, 1, 2), Ipv4Addr::new(255, 255, 255, 0))
+///     .tsresol_nanos();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct IdbOptions(Options, Option<u64>);
+
+impl IdbOptions {
+    /// No options
+    pub fn new() -> IdbOptions {
+        IdbOptions::default()
+    }
+
+    /// Add a comment.  There may be any number of comments.
+    pub fn comment(mut self, comment: &str) -> IdbOptions {
+        self.0.put(OPT_COMMENT, comment.as_bytes());
+        self
+    }
+
+    /// Add several comments
+    pub fn comments(mut self, comments: &[&str]) -> IdbOptions {
+        self.0.comments(comments);
+        self
+    }
+
+    /// The name of the interface (`if_name`)
+    pub fn name(mut self, name: &str) -> IdbOptions {
+        self.0.put_once(IF_NAME, name.as_bytes());
+        self
+    }
+
+    /// A description of the interface (`if_description`)
+    pub fn description(mut self, description: &str) -> IdbOptions {
+        self.0.put_once(IF_DESCRIPTION, description.as_bytes());
+        self
+    }
+
+    /// Add an IPv4 address and netmask (`if_IPv4addr`).  There may be any
+    /// number of these.
+    pub fn ipv4_addr(mut self, addr: Ipv4Addr, netmask: Ipv4Addr) -> IdbOptions {
+        let mut value = [0; 8];
+        value[..4].copy_from_slice(&addr.octets());
+        value[4..].copy_from_slice(&netmask.octets());
+        self.0.put(IF_IPV4ADDR, &value);
+        self
+    }
+
+    /// Add an IPv6 address and prefix length (`if_IPv6addr`).  There may be
+    /// any number of these.
+    pub fn ipv6_addr(mut self, addr: Ipv6Addr, prefix_len: u8) -> IdbOptions {
+        if prefix_len > 128 {
+            self.0.fail(OptionError::OutOfRange(IF_IPV6ADDR));
+        }
+        let mut value = [0; 17];
+        value[..16].copy_from_slice(&addr.octets());
+        value[16] = prefix_len;
+        self.0.put(IF_IPV6ADDR, &value);
+        self
+    }
+
+    /// The MAC address of the interface (`if_MACaddr`)
+    pub fn mac_addr(mut self, addr: [u8; 6]) -> IdbOptions {
+        self.0.put_once(IF_MACADDR, &addr);
+        self
+    }
+
+    /// The EUI-64 address of the interface (`if_EUIaddr`)
+    pub fn eui_addr(mut self, addr: [u8; 8]) -> IdbOptions {
+        self.0.put_once(IF_EUIADDR, &addr);
+        self
+    }
+
+    /// The speed of the interface, in bits per second (`if_speed`)
+    pub fn speed(mut self, bps: u64) -> IdbOptions {
+        self.0.put_number(IF_SPEED, &bps.to_le_bytes());
+        self
+    }
+
+    /// Timestamps are in units of 10^-`exp` seconds (`if_tsresol`)
+    ///
+    /// `exp` must be at most 19, so that a second fits in a timestamp.
+    pub fn tsresol_decimal(mut self, exp: u8) -> IdbOptions {
+        match 10_u64.checked_pow(u32::from(exp)) {
+            Some(x) => self.set_tsresol(exp, x),
+            None => self.0.fail(OptionError::OutOfRange(IF_TSRESOL)),
+        }
+        self
+    }
+
+    /// Timestamps are in units of 2^-`exp` seconds (`if_tsresol`)
+    ///
+    /// `exp` must be at most 63, so that a second fits in a timestamp.
+    pub fn tsresol_binary(mut self, exp: u8) -> IdbOptions {
+        match 1_u64.checked_shl(u32::from(exp)) {
+            Some(x) => self.set_tsresol(0x80 | exp, x),
+            None => self.0.fail(OptionError::OutOfRange(IF_TSRESOL)),
+        }
+        self
+    }
+
+    /// Timestamps are in microseconds.  This is the default.
+    pub fn tsresol_micros(self) -> IdbOptions {
+        self.tsresol_decimal(6)
+    }
+
+    /// Timestamps are in nanoseconds
+    pub fn tsresol_nanos(self) -> IdbOptions {
+        self.tsresol_decimal(9)
+    }
+
+    fn set_tsresol(&mut self, value: u8, ticks_per_sec: u64) {
+        self.0.put_once(IF_TSRESOL, &[value]);
+        self.1 = Some(ticks_per_sec);
+    }
+
+    /// The filter which was applied when capturing (`if_filter`), as a
+    /// libpcap filter string
+    pub fn filter(mut self, filter: &str) -> IdbOptions {
+        let mut value = vec![0]; // a libpcap filter string
+        value.extend_from_slice(filter.as_bytes());
+        self.0.put_once(IF_FILTER, &value);
+        self
+    }
+
+    /// The OS of the machine which the interface is on (`if_os`)
+    pub fn os(mut self, os: &str) -> IdbOptions {
+        self.0.put_once(IF_OS, os.as_bytes());
+        self
+    }
+
+    /// The length of the frame check sequence, in bits (`if_fcslen`)
+    pub fn fcslen(mut self, bits: u8) -> IdbOptions {
+        self.0.put_once(IF_FCSLEN, &[bits]);
+        self
+    }
+
+    /// An offset, in seconds, to add to the timestamps (`if_tsoffset`)
+    pub fn tsoffset(mut self, secs: i64) -> IdbOptions {
+        self.0.put_number(IF_TSOFFSET, &secs.to_le_bytes());
+        self
+    }
+
+    /// The hardware of the interface (`if_hardware`)
+    pub fn hardware(mut self, hardware: &str) -> IdbOptions {
+        self.0.put_once(IF_HARDWARE, hardware.as_bytes());
+        self
+    }
+
+    /// The transmit speed of the interface, in bits per second
+    /// (`if_txspeed`)
+    pub fn tx_speed(mut self, bps: u64) -> IdbOptions {
+        self.0.put_number(IF_TXSPEED, &bps.to_le_bytes());
+        self
+    }
+
+    /// The receive speed of the interface, in bits per second
+    /// (`if_rxspeed`)
+    pub fn rx_speed(mut self, bps: u64) -> IdbOptions {
+        self.0.put_number(IF_RXSPEED, &bps.to_le_bytes());
+        self
+    }
+}
+
+/// The options of an enhanced packet block
+///
+/// ```
+/// use pcarp::writer::EpbOptions;
+///
+/// let opts = EpbOptions::new().flags(0x1).drop_count(3).comment("retransmission");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EpbOptions(Options);
+
+impl EpbOptions {
+    /// No options
+    pub fn new() -> EpbOptions {
+        EpbOptions::default()
+    }
+
+    /// Add a comment.  There may be any number of comments.
+    pub fn comment(mut self, comment: &str) -> EpbOptions {
+        self.0.put(OPT_COMMENT, comment.as_bytes());
+        self
+    }
+
+    /// Add several comments
+    pub fn comments(mut self, comments: &[&str]) -> EpbOptions {
+        self.0.comments(comments);
+        self
+    }
+
+    /// The link-layer flags (`epb_flags`): direction, reception type, etc.
+    pub fn flags(mut self, flags: u32) -> EpbOptions {
+        self.0.put_number(EPB_FLAGS, &flags.to_le_bytes());
+        self
+    }
+
+    /// Add a hash of the packet (`epb_hash`).  `algorithm` is the code of
+    /// the hash algorithm, as given in the spec.  There may be any number
+    /// of these.
+    pub fn hash(mut self, algorithm: u8, hash: &[u8]) -> EpbOptions {
+        let mut value = Vec::with_capacity(hash.len() + 1);
+        value.push(algorithm);
+        value.extend_from_slice(hash);
+        self.0.put(EPB_HASH, &value);
+        self
+    }
+
+    /// The number of packets lost between this packet and the previous one
+    /// (`epb_dropcount`)
+    pub fn drop_count(mut self, n: u64) -> EpbOptions {
+        self.0.put_number(EPB_DROPCOUNT, &n.to_le_bytes());
+        self
+    }
+
+    /// An identifier for the packet (`epb_packetid`)
+    pub fn packet_id(mut self, id: u64) -> EpbOptions {
+        self.0.put_number(EPB_PACKETID, &id.to_le_bytes());
+        self
+    }
+
+    /// The queue of the interface on which the packet was received
+    /// (`epb_queue`)
+    pub fn queue(mut self, queue: u32) -> EpbOptions {
+        self.0.put_number(EPB_QUEUE, &queue.to_le_bytes());
+        self
+    }
+}
+
+/// An options list which is being built up
+///
+/// The first problem with the options is remembered, and reported when the
+/// list is finished.
+#[derive(Debug, Clone, Default)]
+struct Options {
+    /// The options' codes and values, and the length of the number at the
+    /// start of each value.  Numbers are stored in little-endian order,
+    /// and swapped if the block is big-endian.
+    opts: Vec<(u16, Vec<u8>, usize)>,
+    /// The single-use options which have been added
+    seen: Vec<u16>,
+    error: Option<OptionError>,
+}
+
+impl Options {
+    fn put(&mut self, code: u16, value: &[u8]) {
+        self.put_inner(code, value, 0);
+    }
+
+    /// Add a custom option, with the vendor's Private Enterprise Number
+    fn put_custom(&mut self, code: u16, pen: u32, payload: &[u8]) {
+        let mut value = pen.to_le_bytes().to_vec();
+        value.extend_from_slice(payload);
+        self.put_inner(code, &value, 4);
+    }
+
+    fn put_inner(&mut self, code: u16, value: &[u8], number_len: usize) {
+        if u16::try_from(value.len()).is_err() {
+            self.fail(OptionError::TooLong {
+                code,
+                len: value.len(),
+            });
+            return;
+        }
+        self.opts.push((code, value.to_vec(), number_len));
+    }
+
+    /// Add a numeric option which may only appear once.  `value` is in
+    /// little-endian order.
+    fn put_number(&mut self, code: u16, value: &[u8]) {
+        self.put_unique(code, value, value.len());
+    }
+
+    /// Add an option which may only appear once
+    fn put_once(&mut self, code: u16, value: &[u8]) {
+        self.put_unique(code, value, 0);
+    }
+
+    fn put_unique(&mut self, code: u16, value: &[u8], number_len: usize) {
+        if self.seen.contains(&code) {
+            self.fail(OptionError::Repeated(code));
+        } else {
+            self.seen.push(code);
+            self.put_inner(code, value, number_len);
+        }
+    }
+
+    fn comments(&mut self, comments: &[&str]) {
+        for x in comments {
+            self.put(OPT_COMMENT, x.as_bytes());
+        }
+    }
+
+    fn fail(&mut self, err: OptionError) {
+        self.error.get_or_insert(err);
+    }
+
+    /// Append the options to the given block body, along with the
+    /// end-of-options marker (if there were any options)
+    fn finish(self, body: &mut Vec<u8>, endianness: Endianness) -> Result<(), OptionError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        if self.opts.is_empty() {
+            return Ok(());
+        }
+        for (code, mut value, number_len) in self.opts {
+            if endianness == Endianness::Big {
+                value[..number_len].reverse();
+            }
+            body.extend_from_slice(&u16_bytes(code, endianness));
+            body.extend_from_slice(&u16_bytes(value.len() as u16, endianness));
+            put_padded(body, &value);
+        }
+        body.extend_from_slice(&[0; 4]);
+        Ok(())
+    }
+}
+
+fn u16_bytes(x: u16, endianness: Endianness) -> [u8; 2] {
+    match endianness {
+        Endianness::Big => x.to_be_bytes(),
+        Endianness::Little => x.to_le_bytes(),
+    }
+}
+
+fn u32_bytes(x: u32, endianness: Endianness) -> [u8; 4] {
+    match endianness {
+        Endianness::Big => x.to_be_bytes(),
+        Endianness::Little => x.to_le_bytes(),
+    }
+}
+
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+fn put_padded(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(data);
+    buf.extend(std::iter::repeat(0).take(padding(data.len())));
+}
@@ -0,0 +1,524 @@
+/*! Reading and writing pcaps compressed in zstd's seekable format
+
+An ordinary zstd stream can only be read from the start.  The [seekable
+format][spec] splits the data into independently-compressed frames, and
+appends a table recording where each frame starts.  A
+[`SeekableReader`] uses this table to implement `Seek`: seeking to an
+offset only requires decompressing the frame which contains it.
+
+Combined with [`Capture::checkpoint()`](crate::Capture::checkpoint), this
+gives random access into large compressed archives: record checkpoints (eg.
+every 10,000 packets, or every minute of capture time) in a first pass,
+and later [`resume`](crate::Capture::resume) from whichever one you need.
+A [`FrameIndex`] does this for you, with a checkpoint for each frame, so
+you can seek to a packet number or a time.
+
+```
+# use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+use pcarp::zstd_seekable::{SeekableReader, SeekableWriter};
+use pcarp::Capture;
+use std::io::{Cursor, Write};
+
+# let mut pcap = PcapngBuilder::new();
+# pcap.section(Endianness::Little).interface(LinkType::ETHERNET, 0, &[]);
+# for i in 0..1000_u32 {
+#     pcap.enhanced_packet(0, 0, &i.to_le_bytes(), &[]);
+# }
+# let pcap = pcap.as_bytes();
+let mut wtr = SeekableWriter::new(vec![], 4096);
+wtr.write_all(pcap).unwrap();
+let compressed = wtr.finish().unwrap();
+
+// Read half the packets, and remember where we got to
+let rdr = SeekableReader::new(Cursor::new(&compressed)).unwrap();
+let mut capture = Capture::new(rdr);
+capture.by_ref().take(500).for_each(drop);
+let checkpoint = capture.checkpoint();
+
+// Later, jump straight there
+let rdr = SeekableReader::new(Cursor::new(&compressed)).unwrap();
+let mut capture = Capture::resume(rdr, checkpoint).unwrap();
+let pkt = capture.next().unwrap().unwrap();
+assert_eq!(&pkt.data[..], &500_u32.to_le_bytes());
+```
+
+Frame checksums aren't verified.
+
+[spec]: https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md
+*/
+
+use crate::block::read_vec;
+use crate::{Capture, Checkpoint, Error};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::time::SystemTime;
+use tracing::*;
+
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A5E;
+const SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+const FOOTER_LEN: u64 = 9;
+const CHECKSUM_FLAG: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    compressed_offset: u64,
+    compressed_len: u32,
+    decompressed_offset: u64,
+    decompressed_len: u32,
+}
+
+/// Decompresses a zstd-seekable stream, with random access
+///
+/// This implements `Read` and `Seek`, so it can be passed to
+/// [`Capture::new()`](crate::Capture::new).  The most recently used frame
+/// is kept in memory.
+#[derive(Debug)]
+pub struct SeekableReader<R> {
+    inner: R,
+    frames: Vec<Frame>,
+    len: u64,
+    pos: u64,
+    /// The index and contents of the most recently decompressed frame
+    cache: Option<(usize, Vec<u8>)>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_u32(rdr: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    rdr.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+impl<R: Read + Seek> SeekableReader<R> {
+    /// Read the seek table from the end of the stream
+    ///
+    /// Fails if the stream isn't in the seekable format.
+    pub fn new(mut inner: R) -> io::Result<SeekableReader<R>> {
+        let end = inner.seek(SeekFrom::End(0))?;
+        if end < FOOTER_LEN + 8 {
+            return Err(invalid("Too short to be a zstd-seekable stream"));
+        }
+        inner.seek(SeekFrom::Start(end - FOOTER_LEN))?;
+        let n_frames = read_u32(&mut inner)?;
+        let mut descriptor = [0];
+        inner.read_exact(&mut descriptor)?;
+        if read_u32(&mut inner)? != SEEKABLE_MAGIC {
+            return Err(invalid("Missing zstd-seekable magic number"));
+        }
+        let entry_len = if descriptor[0] & CHECKSUM_FLAG != 0 {
+            12
+        } else {
+            8
+        };
+        let table_len = u64::from(n_frames) * entry_len;
+        let table_start = (end - FOOTER_LEN)
+            .checked_sub(table_len + 8)
+            .ok_or_else(|| invalid("The zstd-seekable table is larger than the stream"))?;
+        inner.seek(SeekFrom::Start(table_start))?;
+        if read_u32(&mut inner)? != SKIPPABLE_MAGIC {
+            return Err(invalid(
+                "The zstd-seekable table isn't in a skippable frame",
+            ));
+        }
+        if u64::from(read_u32(&mut inner)?) != table_len + FOOTER_LEN {
+            return Err(invalid("The zstd-seekable table has the wrong length"));
+        }
+
+        let mut frames = Vec::with_capacity(n_frames as usize);
+        let (mut compressed_offset, mut decompressed_offset) = (0, 0);
+        for _ in 0..n_frames {
+            let compressed_len = read_u32(&mut inner)?;
+            let decompressed_len = read_u32(&mut inner)?;
+            if entry_len == 12 {
+                read_u32(&mut inner)?; // checksum
+            }
+            frames.push(Frame {
+                compressed_offset,
+                compressed_len,
+                decompressed_offset,
+                decompressed_len,
+            });
+            compressed_offset += u64::from(compressed_len);
+            decompressed_offset += u64::from(decompressed_len);
+        }
+        if compressed_offset > table_start {
+            return Err(invalid("The zstd-seekable frames overlap the table"));
+        }
+        debug!("Read a zstd-seekable table with {n_frames} frames");
+        Ok(SeekableReader {
+            inner,
+            frames,
+            len: decompressed_offset,
+            pos: 0,
+            cache: None,
+        })
+    }
+
+    /// The total length of the decompressed data
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the decompressed data is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Unwrap the reader, returning the underlying stream
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn load_frame(&mut self, idx: usize) -> io::Result<&[u8]> {
+        if self.cache.as_ref().map(|(i, _)| *i) != Some(idx) {
+            let frame = self.frames[idx];
+            trace!("Decompressing zstd-seekable frame {idx}: {frame:?}");
+            self.inner.seek(SeekFrom::Start(frame.compressed_offset))?;
+            let compressed = read_vec(&mut self.inner, frame.compressed_len.into())?;
+            // The table's length isn't trusted: the buffer only grows as
+            // the data is actually decompressed
+            let expected = u64::from(frame.decompressed_len);
+            let mut data = vec![];
+            zstd::stream::read::Decoder::with_buffer(&compressed[..])?
+                .single_frame()
+                .take(expected + 1)
+                .read_to_end(&mut data)?;
+            if data.len() as u64 != expected {
+                return Err(invalid("A zstd-seekable frame has the wrong length"));
+            }
+            self.cache = Some((idx, data));
+        }
+        match &self.cache {
+            Some((_, data)) => Ok(data),
+            None => unreachable!(),
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let pos = self.pos;
+        let idx = self
+            .frames
+            .partition_point(|f| f.decompressed_offset + u64::from(f.decompressed_len) <= pos);
+        let start = (pos - self.frames[idx].decompressed_offset) as usize;
+        let data = &self.load_frame(idx)?[start..];
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(x) => {
+                self.pos = x;
+                return Ok(x);
+            }
+            SeekFrom::End(x) => (self.len, x),
+            SeekFrom::Current(x) => (self.pos, x),
+        };
+        let new_pos = if offset >= 0 {
+            base.checked_add(offset as u64)
+        } else {
+            base.checked_sub(offset.unsigned_abs())
+        };
+        match new_pos {
+            Some(x) => {
+                self.pos = x;
+                Ok(x)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+/// Maps packet numbers and timestamps onto the compressed frames which
+/// hold them
+///
+/// This is built by reading the capture once, taking a
+/// [`Checkpoint`] at the first block which starts in each frame.  Seeking
+/// to a packet then resumes from the nearest checkpoint, so only the
+/// frames from there onwards have to be decompressed.
+///
+/// ```
+/// # use pcarp::{block::Endianness, iface::LinkType, testgen::PcapngBuilder};
+/// use pcarp::zstd_seekable::{FrameIndex, SeekableReader, SeekableWriter};
+/// use std::io::{Cursor, Write};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// # let mut pcap = PcapngBuilder::new();
+/// # pcap.section(Endianness::Little).interface(LinkType::ETHERNET, 0, &[]);
+/// # for i in 0..1000_u32 {
+/// #     pcap.enhanced_packet(0, u64::from(i) * 1_000_000, &i.to_le_bytes(), &[]);
+/// # }
+/// # let pcap = pcap.as_bytes();
+/// let mut wtr = SeekableWriter::new(vec![], 4096);
+/// wtr.write_all(pcap).unwrap();
+/// let compressed = wtr.finish().unwrap();
+///
+/// let rdr = SeekableReader::new(Cursor::new(&compressed)).unwrap();
+/// let index = FrameIndex::build(rdr).unwrap();
+/// assert!(index.len() > 1);
+///
+/// let rdr = SeekableReader::new(Cursor::new(&compressed)).unwrap();
+/// let mut capture = index.seek_to_packet(rdr, 700).unwrap();
+/// let pkt = capture.next().unwrap().unwrap();
+/// assert_eq!(&pkt.data[..], &700_u32.to_le_bytes());
+///
+/// // One packet per second (the interface uses microseconds)
+/// let rdr = SeekableReader::new(Cursor::new(&compressed)).unwrap();
+/// let t = UNIX_EPOCH + Duration::from_millis(321_500);
+/// let mut capture = index.seek_to_time(rdr, t).unwrap();
+/// let pkt = capture.next().unwrap().unwrap();
+/// assert_eq!(&pkt.data[..], &322_u32.to_le_bytes());
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameIndex {
+    entries: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct IndexEntry {
+    /// The number of packets which come before the checkpoint
+    packet: u64,
+    /// The timestamp of the first packet after the checkpoint
+    timestamp: Option<SystemTime>,
+    checkpoint: Checkpoint,
+}
+
+impl FrameIndex {
+    /// Read through a whole capture, recording where each frame starts
+    ///
+    /// Corrupt blocks are skipped (they aren't counted as packets); any
+    /// other error stops the build.
+    pub fn build<R: Read + Seek>(rdr: SeekableReader<R>) -> crate::Result<FrameIndex> {
+        let starts: Vec<u64> = rdr.frames.iter().map(|f| f.decompressed_offset).collect();
+        let mut capture = Capture::new(rdr);
+        let mut entries = vec![];
+        let mut current_frame = None;
+        let mut packet = 0;
+        // An entry waiting for the timestamp of its first packet
+        let mut pending: Option<IndexEntry> = None;
+        loop {
+            let pos = capture.inner.position();
+            let frame = starts.partition_point(|&x| x <= pos).checked_sub(1);
+            if frame != current_frame {
+                current_frame = frame;
+                entries.extend(pending.take());
+                pending = Some(IndexEntry {
+                    packet,
+                    timestamp: None,
+                    checkpoint: capture.checkpoint(),
+                });
+            }
+            match capture.next() {
+                Some(Ok(pkt)) => {
+                    if let Some(mut entry) = pending.take() {
+                        entry.timestamp = pkt.timestamp;
+                        entries.push(entry);
+                    }
+                    packet += 1;
+                }
+                Some(Err(Error::Block(..))) => (),
+                Some(Err(e)) => return Err(e),
+                None => break,
+            }
+        }
+        entries.extend(pending);
+        debug!("Indexed {packet} packets in {} frames", entries.len());
+        Ok(FrameIndex { entries })
+    }
+
+    /// The number of checkpoints in the index
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the index is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get a `Capture` whose next packet is the `n`th one (counting from
+    /// zero)
+    ///
+    /// The reader must be reading the same stream which the index was built
+    /// from.  If there are fewer than `n` packets, the capture is left at
+    /// the end of the stream.
+    pub fn seek_to_packet<R: Read + Seek>(
+        &self,
+        rdr: SeekableReader<R>,
+        n: u64,
+    ) -> crate::Result<Capture<SeekableReader<R>>> {
+        let i = self.entries.partition_point(|x| x.packet <= n);
+        let (mut capture, mut packet) = self.resume_at(rdr, i.checked_sub(1))?;
+        while packet < n {
+            match capture.next() {
+                Some(Ok(_)) => packet += 1,
+                Some(Err(Error::Block(..))) => (),
+                Some(Err(e)) => return Err(e),
+                None => break,
+            }
+        }
+        Ok(capture)
+    }
+
+    /// Get a `Capture` whose next packet is the first one at or after `t`
+    ///
+    /// This assumes that the packets are in time order.  The reader must
+    /// be reading the same stream which the index was built from.  If there
+    /// are no packets at or after `t`, the capture is left at the end of the
+    /// stream.
+    pub fn seek_to_time<R: Read + Seek>(
+        &self,
+        rdr: SeekableReader<R>,
+        t: SystemTime,
+    ) -> crate::Result<Capture<SeekableReader<R>>> {
+        let i = self
+            .entries
+            .iter()
+            .rposition(|x| x.timestamp.map_or(false, |ts| ts < t));
+        let (mut capture, _) = self.resume_at(rdr, i)?;
+        loop {
+            let checkpoint = capture.checkpoint();
+            match capture.next() {
+                Some(Ok(pkt)) if pkt.timestamp.map_or(false, |ts| ts >= t) => {
+                    // Go back to just before it
+                    return Capture::resume(capture.inner.into_inner(), checkpoint);
+                }
+                Some(Ok(_)) | Some(Err(Error::Block(..))) => (),
+                Some(Err(e)) => return Err(e),
+                None => return Ok(capture),
+            }
+        }
+    }
+
+    /// Resume from the given entry, or from the start of the stream,
+    /// returning the number of packets before the resumption point
+    fn resume_at<R: Read + Seek>(
+        &self,
+        rdr: SeekableReader<R>,
+        i: Option<usize>,
+    ) -> crate::Result<(Capture<SeekableReader<R>>, u64)> {
+        match i.and_then(|i| self.entries.get(i)) {
+            Some(entry) => {
+                trace!("Resuming from {} bytes in", entry.checkpoint.offset());
+                let capture = Capture::resume(rdr, entry.checkpoint.clone())?;
+                Ok((capture, entry.packet))
+            }
+            None => Ok((Capture::new(rdr), 0)),
+        }
+    }
+}
+
+/// Compresses data into a zstd-seekable stream
+///
+/// The data is split into frames of the given (decompressed) size.
+/// Smaller frames make seeking cheaper, but compress less well.
+///
+/// The seek table is written by [`finish()`](Self::finish).  If the writer
+/// is dropped without calling it, the output will be incomplete.
+#[derive(Debug)]
+pub struct SeekableWriter<W: Write> {
+    inner: W,
+    frame_len: usize,
+    level: i32,
+    buf: Vec<u8>,
+    /// The compressed and decompressed lengths of each frame written so far
+    table: Vec<(u32, u32)>,
+}
+
+impl<W: Write> SeekableWriter<W> {
+    /// Create a writer with the given frame size, using zstd's default
+    /// compression level
+    ///
+    /// Panics if the frame size is zero or doesn't fit into a `u32`.
+    pub fn new(inner: W, frame_len: usize) -> SeekableWriter<W> {
+        assert!(frame_len > 0, "The frame size must be non-zero");
+        assert!(
+            u32::try_from(frame_len).is_ok(),
+            "The frame size is too large"
+        );
+        SeekableWriter {
+            inner,
+            frame_len,
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+            buf: Vec::with_capacity(frame_len),
+            table: vec![],
+        }
+    }
+
+    /// Set the zstd compression level
+    pub fn level(mut self, level: i32) -> SeekableWriter<W> {
+        self.level = level;
+        self
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let compressed = zstd::bulk::compress(&self.buf, self.level)?;
+        let compressed_len = u32::try_from(compressed.len())
+            .map_err(|_| invalid("A compressed frame is too large"))?;
+        self.inner.write_all(&compressed)?;
+        self.table.push((compressed_len, self.buf.len() as u32));
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Compress any buffered data and write the seek table
+    ///
+    /// Returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_frame()?;
+        let n_frames = u32::try_from(self.table.len())
+            .map_err(|_| invalid("Too many frames for a zstd-seekable stream"))?;
+        let table_len = self.table.len() as u64 * 8 + FOOTER_LEN;
+        let table_len = u32::try_from(table_len)
+            .map_err(|_| invalid("Too many frames for a zstd-seekable stream"))?;
+        let mut table = Vec::with_capacity(table_len as usize + 8);
+        table.extend_from_slice(&SKIPPABLE_MAGIC.to_le_bytes());
+        table.extend_from_slice(&table_len.to_le_bytes());
+        for (compressed_len, decompressed_len) in &self.table {
+            table.extend_from_slice(&compressed_len.to_le_bytes());
+            table.extend_from_slice(&decompressed_len.to_le_bytes());
+        }
+        table.extend_from_slice(&n_frames.to_le_bytes());
+        table.push(0); // no checksums
+        table.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+        self.inner.write_all(&table)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for SeekableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.frame_len - self.buf.len());
+        self.buf.extend_from_slice(&buf[..n]);
+        if self.buf.len() == self.frame_len {
+            self.write_frame()?;
+        }
+        Ok(n)
+    }
+
+    /// Flushing doesn't end the current frame: it only flushes the frames
+    /// which have already been compressed
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}